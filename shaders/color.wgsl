@@ -0,0 +1,12 @@
+// Coloring function of the GPU coloring, the part of coloring.wgsl that
+// --shader replaces. `rounds` is the escape iteration of the pixel (0 for
+// the interior) and `pixel` its position in the frame. `palette()` looks
+// an iteration up in the palette, with the cycling offset, and `r_locals`
+// holds the gamma and the seconds since the start (`time`).
+fn color(rounds: u32, pixel: vec2<i32>) -> vec4<f32> {
+    if (rounds == 0u) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    let rgba = palette(rounds);
+    return vec4<f32>(pow(rgba.rgb, vec3<f32>(1.0 / r_locals.gamma)), 1.0);
+}
@@ -0,0 +1,70 @@
+// Coloring pass of the GPU coloring mode.
+//
+// Fractal pixels of the frame carry alpha 0 and the escape iteration in
+// their RGB bytes (0 for the interior); every other pixel is an overlay and
+// is drawn as it is. The fractal pixels are colored by `color()`, which
+// takes the place of the placeholder below: the one of color.wgsl, or the
+// file given with --shader.
+
+struct Locals {
+    // top-left corner of the frame on the surface, in pixels
+    origin: vec2<f32>;
+    // surface pixels per frame pixel
+    scale: f32;
+    // iterations of one palette cycle
+    period: u32;
+    // palette cycling offset, in iterations
+    offset: u32;
+    // texels per row of the palette texture
+    palette_width: u32;
+    gamma: f32;
+    // seconds since the GPU coloring started
+    time: f32;
+};
+
+[[group(0), binding(0)]] var r_frame: texture_2d<f32>;
+[[group(0), binding(1)]] var r_palette: texture_2d<f32>;
+[[group(0), binding(2)]] var<uniform> r_locals: Locals;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> [[builtin(position)]] vec4<f32> {
+    // one triangle covering the whole surface
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index >> 1u) * 4 - 1);
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+// The byte a linear channel was decoded from by the sRGB texture.
+fn srgb_byte(linear: f32) -> u32 {
+    var encoded: f32;
+    if (linear <= 0.0031308) {
+        encoded = linear * 12.92;
+    } else {
+        encoded = 1.055 * pow(linear, 1.0 / 2.4) - 0.055;
+    }
+    return u32(clamp(encoded * 255.0 + 0.5, 0.0, 255.0));
+}
+
+// Palette color of an escape iteration, shifted by the cycling offset.
+fn palette(rounds: u32) -> vec4<f32> {
+    let index = (rounds + r_locals.offset) % r_locals.period;
+    return textureLoad(
+        r_palette,
+        vec2<i32>(i32(index % r_locals.palette_width), i32(index / r_locals.palette_width)),
+        0
+    );
+}
+
+// {color}
+
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let pixel = vec2<i32>(floor((position.xy - r_locals.origin) / r_locals.scale));
+    let texel = textureLoad(r_frame, pixel, 0);
+    if (texel.a > 0.5) {
+        return texel;
+    }
+
+    let rounds = srgb_byte(texel.r) | (srgb_byte(texel.g) << 8u) | (srgb_byte(texel.b) << 16u);
+    return color(rounds, pixel);
+}
@@ -0,0 +1,439 @@
+use serde::Deserialize;
+use winit::event::VirtualKeyCode;
+use winit_input_helper::{TextChar, WinitInputHelper};
+
+// typed, whatever the key and the layout, it opens the help like F1
+const HELP_CHARACTER: char = '?';
+
+/// Operations triggered by the user, independent of the input device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    Reset,
+    UndoView,
+    ZoomIn,
+    ZoomOut,
+    StopAutoZoom,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    ToggleInfo,
+    ToggleLegend,
+    ToggleIterationHistogram,
+    ToggleMinimap,
+    TogglePeriodAnalysis,
+    ToggleExternalRays,
+    CycleHudCorner,
+    CycleHudOpacity,
+    ToggleHudShadow,
+    InvertHudColors,
+    ToggleCostMap,
+    CycleAntialias,
+    CyclePalette,
+    CycleColoring,
+    ToggleAutoExposure,
+    CycleFractal,
+    HalveIterations,
+    DoubleIterations,
+    ToggleAutoIterations,
+    CycleNorm,
+    CycleBands,
+    ShiftBands,
+    IncreaseExponent,
+    DecreaseExponent,
+    ToggleExperiment,
+    CommitExperiment,
+    IncreaseBailout,
+    DecreaseBailout,
+    IncreaseRelaxation,
+    DecreaseRelaxation,
+    NudgeZ0Left,
+    NudgeZ0Right,
+    NudgeZ0Up,
+    NudgeZ0Down,
+    CycleBuddhabrot,
+    IncreaseExposure,
+    DecreaseExposure,
+    ToggleGpuColoring,
+    ToggleColorCycling,
+    IncreaseGamma,
+    DecreaseGamma,
+    Dump,
+    CopyView,
+    ExportAnimation,
+    AddKeyframe,
+    ExportKeyframes,
+    CycleThreads,
+    ExportDistanceField,
+    ExportIterations,
+    ExportTransect,
+    ExportImage,
+    CycleFit,
+    SaveBookmark,
+    JumpBookmark(usize),
+    Tutorial,
+    ToggleInfoCard,
+    CloseOrbit,
+    ToggleRuler,
+    ToggleSettings,
+    ToggleFullscreen,
+    ToggleHelp,
+    // mouse operations, reported by the event loop but not bound to keys
+    Drag,
+    Recenter,
+    Scroll,
+    InspectOrbit,
+    ExplainPoint,
+    ProbePeriod,
+    ZoomRect,
+}
+
+impl Action {
+    /// Name of the action in the configuration file, like `zoom-in` or
+    /// `{ jump-bookmark = 0 }`.
+    pub fn name(&self) -> String {
+        let name = match self {
+            Action::Quit => "quit",
+            Action::Reset => "reset",
+            Action::UndoView => "undo-view",
+            Action::ZoomIn => "zoom-in",
+            Action::ZoomOut => "zoom-out",
+            Action::StopAutoZoom => "stop-auto-zoom",
+            Action::MoveUp => "move-up",
+            Action::MoveDown => "move-down",
+            Action::MoveLeft => "move-left",
+            Action::MoveRight => "move-right",
+            Action::ToggleInfo => "toggle-info",
+            Action::ToggleLegend => "toggle-legend",
+            Action::ToggleIterationHistogram => "toggle-iteration-histogram",
+            Action::ToggleMinimap => "toggle-minimap",
+            Action::TogglePeriodAnalysis => "toggle-period-analysis",
+            Action::ToggleExternalRays => "toggle-external-rays",
+            Action::CycleHudCorner => "cycle-hud-corner",
+            Action::CycleHudOpacity => "cycle-hud-opacity",
+            Action::ToggleHudShadow => "toggle-hud-shadow",
+            Action::InvertHudColors => "invert-hud-colors",
+            Action::ToggleCostMap => "toggle-cost-map",
+            Action::CycleAntialias => "cycle-antialias",
+            Action::CyclePalette => "cycle-palette",
+            Action::CycleColoring => "cycle-coloring",
+            Action::ToggleAutoExposure => "toggle-auto-exposure",
+            Action::CycleFractal => "cycle-fractal",
+            Action::HalveIterations => "halve-iterations",
+            Action::DoubleIterations => "double-iterations",
+            Action::ToggleAutoIterations => "toggle-auto-iterations",
+            Action::CycleNorm => "cycle-norm",
+            Action::CycleBands => "cycle-bands",
+            Action::ShiftBands => "shift-bands",
+            Action::IncreaseExponent => "increase-exponent",
+            Action::DecreaseExponent => "decrease-exponent",
+            Action::ToggleExperiment => "toggle-experiment",
+            Action::CommitExperiment => "commit-experiment",
+            Action::IncreaseBailout => "increase-bailout",
+            Action::DecreaseBailout => "decrease-bailout",
+            Action::IncreaseRelaxation => "increase-relaxation",
+            Action::DecreaseRelaxation => "decrease-relaxation",
+            Action::NudgeZ0Left => "nudge-z0-left",
+            Action::NudgeZ0Right => "nudge-z0-right",
+            Action::NudgeZ0Up => "nudge-z0-up",
+            Action::NudgeZ0Down => "nudge-z0-down",
+            Action::CycleBuddhabrot => "cycle-buddhabrot",
+            Action::IncreaseExposure => "increase-exposure",
+            Action::DecreaseExposure => "decrease-exposure",
+            Action::ToggleGpuColoring => "toggle-gpu-coloring",
+            Action::ToggleColorCycling => "toggle-color-cycling",
+            Action::IncreaseGamma => "increase-gamma",
+            Action::DecreaseGamma => "decrease-gamma",
+            Action::Dump => "dump",
+            Action::CopyView => "copy-view",
+            Action::ExportAnimation => "export-animation",
+            Action::AddKeyframe => "add-keyframe",
+            Action::ExportKeyframes => "export-keyframes",
+            Action::CycleThreads => "cycle-threads",
+            Action::ExportDistanceField => "export-distance-field",
+            Action::ExportIterations => "export-iterations",
+            Action::ExportTransect => "export-transect",
+            Action::ExportImage => "export-image",
+            Action::CycleFit => "cycle-fit",
+            Action::SaveBookmark => "save-bookmark",
+            Action::JumpBookmark(index) => return format!("{{ jump-bookmark = {} }}", index),
+            Action::Tutorial => "tutorial",
+            Action::ToggleInfoCard => "toggle-info-card",
+            Action::CloseOrbit => "close-orbit",
+            Action::ToggleRuler => "toggle-ruler",
+            Action::ToggleSettings => "toggle-settings",
+            Action::ToggleFullscreen => "toggle-fullscreen",
+            Action::ToggleHelp => "toggle-help",
+            Action::Drag => "drag",
+            Action::Recenter => "recenter",
+            Action::Scroll => "scroll",
+            Action::InspectOrbit => "inspect-orbit",
+            Action::ExplainPoint => "explain-point",
+            Action::ProbePeriod => "probe-period",
+            Action::ZoomRect => "zoom-rect",
+        };
+        String::from(name)
+    }
+
+    /// What the action does, for the help.
+    pub fn description(&self) -> String {
+        let description = match self {
+            Action::Quit => "quit",
+            Action::Reset => "reset the view, the looks, everything",
+            Action::UndoView => "previous view (Shift: next)",
+            Action::ZoomIn => "zoom in (Shift: small, Alt: auto)",
+            Action::ZoomOut => "zoom out (Shift: small, Alt: auto)",
+            Action::StopAutoZoom => "stop the auto zoom",
+            Action::MoveUp => "move up",
+            Action::MoveDown => "move down",
+            Action::MoveLeft => "move left",
+            Action::MoveRight => "move right",
+            Action::ToggleInfo => "information display",
+            Action::ToggleLegend => "color legend",
+            Action::ToggleIterationHistogram => "iteration histogram",
+            Action::ToggleMinimap => "minimap of the whole set",
+            Action::TogglePeriodAnalysis => "color the interior by period",
+            Action::ToggleExternalRays => "equipotentials and external rays",
+            Action::CycleHudCorner => "corner of the information",
+            Action::CycleHudOpacity => "opacity of the text panel",
+            Action::ToggleHudShadow => "text shadow",
+            Action::InvertHudColors => "swap the text colors",
+            Action::ToggleCostMap => "cost map of the tiles",
+            Action::CycleAntialias => "cycle antialiasing",
+            Action::CyclePalette => "cycle palettes",
+            Action::CycleColoring => "cycle coloring modes (Shift: interior coloring)",
+            Action::ToggleAutoExposure => "auto exposure",
+            Action::CycleFractal => "cycle fractals",
+            Action::HalveIterations => "halve the iteration limit",
+            Action::DoubleIterations => "double the iteration limit",
+            Action::ToggleAutoIterations => "automatic iteration limit",
+            Action::CycleNorm => "cycle bailout norms",
+            Action::CycleBands => "cycle the color bands",
+            Action::ShiftBands => "shift the color bands",
+            Action::IncreaseExponent => "increase the exponent",
+            Action::DecreaseExponent => "decrease the exponent",
+            Action::ToggleExperiment => "experiment mode",
+            Action::CommitExperiment => "render the experiment",
+            Action::IncreaseBailout => "increase the bailout",
+            Action::DecreaseBailout => "decrease the bailout",
+            Action::IncreaseRelaxation => "increase the relaxation",
+            Action::DecreaseRelaxation => "decrease the relaxation",
+            Action::NudgeZ0Left => "move z0 left",
+            Action::NudgeZ0Right => "move z0 right",
+            Action::NudgeZ0Up => "move z0 up",
+            Action::NudgeZ0Down => "move z0 down",
+            Action::CycleBuddhabrot => "cycle the Buddhabrot",
+            Action::IncreaseExposure => "increase the exposure",
+            Action::DecreaseExposure => "decrease the exposure",
+            Action::ToggleGpuColoring => "GPU coloring",
+            Action::ToggleColorCycling => "cycle the palette",
+            Action::IncreaseGamma => "increase the gamma",
+            Action::DecreaseGamma => "decrease the gamma",
+            Action::Dump => "log the view and statistics",
+            Action::CopyView => "copy the view",
+            Action::ExportAnimation => "export a zoom animation",
+            Action::AddKeyframe => "mark a keyframe (Shift: clear them)",
+            Action::ExportKeyframes => "export the animation through the keyframes",
+            Action::CycleThreads => "halve the rendering threads (Shift: double them)",
+            Action::ExportDistanceField => "export the distance field",
+            Action::ExportIterations => "export the iterations",
+            Action::ExportTransect => "export the ruler transect",
+            Action::ExportImage => "preview/export the image",
+            Action::CycleFit => "cycle the export fit",
+            Action::SaveBookmark => "save a bookmark",
+            Action::JumpBookmark(index) => return format!("go to bookmark {}", index + 1),
+            Action::Tutorial => "tutorial",
+            Action::ToggleInfoCard => "information card",
+            Action::CloseOrbit => "close the orbit or preview",
+            Action::ToggleRuler => "ruler",
+            Action::ToggleSettings => "settings panel",
+            Action::ToggleFullscreen => "full screen",
+            Action::ToggleHelp => "this help",
+            Action::Drag => "drag",
+            Action::Recenter => "recenter",
+            Action::Scroll => "scroll",
+            Action::InspectOrbit => "inspect an orbit",
+            Action::ExplainPoint => "explain a point",
+            Action::ProbePeriod => "find the period of a point",
+            Action::ZoomRect => "zoom to a rectangle",
+        };
+        String::from(description)
+    }
+}
+
+/// Key bindings of the actions. An action may be bound to several keys.
+pub struct Bindings {
+    keys: Vec<(VirtualKeyCode, Action)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Action::*;
+        use VirtualKeyCode as Key;
+
+        Self {
+            keys: vec![
+                (Key::Q, Quit),
+                (Key::Space, Reset),
+                (Key::Back, UndoView),
+                (Key::PageUp, ZoomIn),
+                (Key::PageDown, ZoomOut),
+                (Key::Escape, StopAutoZoom),
+                (Key::Up, MoveUp),
+                (Key::K, MoveUp),
+                (Key::Down, MoveDown),
+                (Key::J, MoveDown),
+                (Key::Left, MoveLeft),
+                (Key::H, MoveLeft),
+                (Key::Right, MoveRight),
+                (Key::L, MoveRight),
+                (Key::I, ToggleInfo),
+                (Key::G, ToggleLegend),
+                (Key::Grave, ToggleIterationHistogram),
+                (Key::Key0, ToggleMinimap),
+                (Key::Numpad5, TogglePeriodAnalysis),
+                (Key::NumpadDivide, ToggleExternalRays),
+                (Key::F3, CycleHudCorner),
+                (Key::F4, CycleHudOpacity),
+                (Key::F5, ToggleHudShadow),
+                (Key::F6, InvertHudColors),
+                (Key::W, ToggleCostMap),
+                (Key::A, CycleAntialias),
+                (Key::P, CyclePalette),
+                (Key::X, CycleColoring),
+                (Key::Slash, ToggleAutoExposure),
+                (Key::F, CycleFractal),
+                (Key::LBracket, HalveIterations),
+                (Key::RBracket, DoubleIterations),
+                (Key::Backslash, ToggleAutoIterations),
+                (Key::E, CycleNorm),
+                (Key::N, CycleBands),
+                (Key::M, ShiftBands),
+                (Key::Equals, IncreaseExponent),
+                (Key::NumpadAdd, IncreaseExponent),
+                (Key::Minus, DecreaseExponent),
+                (Key::NumpadSubtract, DecreaseExponent),
+                (Key::R, ToggleExperiment),
+                (Key::Return, CommitExperiment),
+                (Key::F8, IncreaseBailout),
+                (Key::F7, DecreaseBailout),
+                (Key::F10, IncreaseRelaxation),
+                (Key::F9, DecreaseRelaxation),
+                (Key::Numpad4, NudgeZ0Left),
+                (Key::Numpad6, NudgeZ0Right),
+                (Key::Numpad8, NudgeZ0Up),
+                (Key::Numpad2, NudgeZ0Down),
+                (Key::Z, CycleBuddhabrot),
+                (Key::Apostrophe, IncreaseExposure),
+                (Key::Semicolon, DecreaseExposure),
+                (Key::U, ToggleGpuColoring),
+                (Key::Y, ToggleColorCycling),
+                (Key::Period, IncreaseGamma),
+                (Key::Comma, DecreaseGamma),
+                (Key::D, Dump),
+                (Key::C, CopyView),
+                (Key::V, ExportAnimation),
+                (Key::Numpad0, AddKeyframe),
+                (Key::NumpadEnter, ExportKeyframes),
+                (Key::NumpadMultiply, CycleThreads),
+                (Key::S, ExportDistanceField),
+                (Key::End, ExportIterations),
+                (Key::Insert, ExportTransect),
+                (Key::Delete, ExportImage),
+                (Key::F12, CycleFit),
+                (Key::B, SaveBookmark),
+                (Key::Key1, JumpBookmark(0)),
+                (Key::Key2, JumpBookmark(1)),
+                (Key::Key3, JumpBookmark(2)),
+                (Key::Key4, JumpBookmark(3)),
+                (Key::Key5, JumpBookmark(4)),
+                (Key::Key6, JumpBookmark(5)),
+                (Key::Key7, JumpBookmark(6)),
+                (Key::Key8, JumpBookmark(7)),
+                (Key::Key9, JumpBookmark(8)),
+                (Key::T, Tutorial),
+                (Key::F2, ToggleInfoCard),
+                (Key::O, CloseOrbit),
+                (Key::Home, ToggleRuler),
+                (Key::Tab, ToggleSettings),
+                (Key::F11, ToggleFullscreen),
+                (Key::F1, ToggleHelp),
+            ],
+        }
+    }
+}
+
+impl Bindings {
+    /// Actions whose key was pressed in the current input step. A typed
+    /// `?` opens the help instead of what its key is bound to, such as the
+    /// auto exposure of `/` where `?` is Shift+/.
+    pub fn pressed(&self, input: &WinitInputHelper) -> Vec<Action> {
+        if input
+            .text()
+            .iter()
+            .any(|typed| matches!(typed, TextChar::Char(HELP_CHARACTER)))
+        {
+            return vec![Action::ToggleHelp];
+        }
+        let mut actions = Vec::new();
+        for (key, action) in &self.keys {
+            if input.key_pressed(*key) && !actions.contains(action) {
+                actions.push(*action);
+            }
+        }
+        actions
+    }
+
+    /// Actions bound to `key`, as if it were pressed alone.
+    pub fn actions_of(&self, key: VirtualKeyCode) -> Vec<Action> {
+        self.keys
+            .iter()
+            .filter(|(bound, _)| *bound == key)
+            .map(|(_, action)| *action)
+            .collect()
+    }
+
+    /// Bind `key` to `action`, replacing the action the key had.
+    pub fn bind(&mut self, key: VirtualKeyCode, action: Action) {
+        self.unbind(key);
+        self.keys.push((key, action));
+    }
+
+    /// Remove the binding of `key`.
+    pub fn unbind(&mut self, key: VirtualKeyCode) {
+        self.keys.retain(|(bound, _)| *bound != key);
+    }
+
+    /// Every bound action with the names of its keys, in the order of the
+    /// bindings, for the help and `--list-bindings`.
+    pub fn list(&self) -> Vec<(Action, Vec<String>)> {
+        let mut list: Vec<(Action, Vec<String>)> = Vec::new();
+        for (key, action) in &self.keys {
+            let name = format!("{:?}", key);
+            match list.iter_mut().find(|(listed, _)| listed == action) {
+                Some((_, keys)) => keys.push(name),
+                None => list.push((*action, vec![name])),
+            }
+        }
+        let help = String::from(HELP_CHARACTER);
+        match list
+            .iter_mut()
+            .find(|(listed, _)| *listed == Action::ToggleHelp)
+        {
+            Some((_, keys)) => keys.push(help),
+            None => list.push((Action::ToggleHelp, vec![help])),
+        }
+        list
+    }
+
+    /// Name of the first key bound to `action`, for on-screen prompts.
+    pub fn key_name(&self, action: Action) -> String {
+        self.keys
+            .iter()
+            .find(|(_, bound)| *bound == action)
+            .map(|(key, _)| format!("{:?}", key))
+            .unwrap_or_else(|| String::from("(unbound)"))
+    }
+}
@@ -0,0 +1,282 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::double_double::DoubleDouble;
+use crate::export;
+use crate::mandelbrot::{Mandelbrot, RenderOptions, View};
+use crate::parallel::*;
+use crate::progress::Progress;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::video::{Video, VideoOptions};
+
+/// Views of an exponential zoom from `start` to `end` in `frames` steps.
+///
+/// The scale changes by a constant ratio per frame, and the center moves in
+/// proportion to the scale change so that the end point stays still on the
+/// screen while zooming in toward it.
+pub fn zoom_path(start: View, end: View, frames: usize) -> Vec<View> {
+    let last = frames.saturating_sub(1).max(1) as f64;
+    (0..frames)
+        .map(|frame| zoom_view(start, end, frame as f64 / last))
+        .collect()
+}
+
+/// The view at the part `t`, from 0 to 1, of the zoom from `start` to
+/// `end` of zoom_path().
+pub fn zoom_view(start: View, end: View, t: f64) -> View {
+    let scale = start.scale * (end.scale / start.scale).powf(t);
+    let weight = if start.scale == end.scale {
+        1.0 - t
+    } else {
+        (scale - end.scale) / (start.scale - end.scale)
+    };
+    View {
+        center_x: end.center_x + (start.center_x - end.center_x) * weight,
+        center_y: end.center_y + (start.center_y - end.center_y) * weight,
+        scale,
+    }
+}
+
+/// Parameters of a zoom animation export.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AnimationJob {
+    pub frames: usize,
+    pub width: u32,
+    pub height: u32,
+    pub max_round: Option<usize>,
+    // tables go after the plain values in TOML
+    pub start: View,
+    pub end: View,
+    pub options: RenderOptions,
+}
+
+pub fn frame_path(dir: &Path, number: usize) -> PathBuf {
+    dir.join(format!("frame_{:05}.png", number))
+}
+
+fn preview_path(dir: &Path, number: usize) -> PathBuf {
+    dir.join(format!("preview_{:05}.png", number))
+}
+
+fn render_view(job: &AnimationJob, view: View, frame: &mut [u8]) {
+    let max_round = job
+        .max_round
+        .unwrap_or_else(|| Mandelbrot::auto_max_round(view.scale));
+    render(job.width, job.height, &job.options, view, max_round, frame);
+}
+
+/// Render a frame of an animation, without the information display.
+pub fn render(
+    width: u32,
+    height: u32,
+    options: &RenderOptions,
+    view: View,
+    max_round: usize,
+    frame: &mut [u8],
+) {
+    let center = (view.center_x.into(), view.center_y.into());
+    render_precise(width, height, options, center, view.scale, max_round, frame);
+}
+
+/// Render a frame like render() around a center in double-double.
+pub fn render_precise(
+    width: u32,
+    height: u32,
+    options: &RenderOptions,
+    (x, y): (DoubleDouble, DoubleDouble),
+    scale: f64,
+    max_round: usize,
+    frame: &mut [u8],
+) {
+    let mut mandelbrot = Mandelbrot::new(width, height);
+    mandelbrot.set_precise_view(x, y, scale, max_round);
+    mandelbrot.options = options.clone();
+    mandelbrot.info = false;
+    mandelbrot.draw(frame);
+}
+
+/// Render each view of the zoom path to `dir/frame_NNNNN.png`.
+///
+/// Completed frames are recorded in the progress file of `dir`; with
+/// `resume`, the frames already written by an interrupted export of the same
+/// job are verified and skipped.
+pub fn export_frames(
+    job: &AnimationJob,
+    dir: &Path,
+    resume: bool,
+) -> Result<(), png::EncodingError> {
+    fs::create_dir_all(dir)?;
+    let mut progress = Progress::open(dir, job.clone(), resume, |number| frame_path(dir, number))?;
+
+    let mut frame = vec![0_u8; 4 * job.width as usize * job.height as usize];
+    for (number, view) in zoom_path(job.start, job.end, job.frames)
+        .into_iter()
+        .enumerate()
+    {
+        if progress.is_completed(number) {
+            continue;
+        }
+
+        render_view(job, view, &mut frame);
+
+        let mut png = Vec::new();
+        export::write_png(&mut png, job.width, job.height, &frame)?;
+        let path = frame_path(dir, number);
+        fs::write(&path, &png)?;
+        progress.complete(number, &png)?;
+        info!("frame {}/{}: {}", number + 1, job.frames, path.display());
+    }
+    Ok(())
+}
+
+/// Render each view of the zoom path and encode it into the video file at
+/// `path` with ffmpeg.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_video(job: &AnimationJob, path: &Path, options: &VideoOptions) -> io::Result<()> {
+    let mut video = Video::start(path, job.width, job.height, options)?;
+    let mut frame = vec![0_u8; 4 * job.width as usize * job.height as usize];
+    for (number, view) in zoom_path(job.start, job.end, job.frames)
+        .into_iter()
+        .enumerate()
+    {
+        render_view(job, view, &mut frame);
+        video.write(&frame)?;
+        info!("frame {}/{}", number + 1, job.frames);
+    }
+    video.finish()
+}
+
+/// A fully rendered frame of the zoom path.
+struct Keyframe {
+    view: View,
+    frame: Vec<u8>,
+}
+
+impl Keyframe {
+    /// Bilinearly interpolated color at the point (x, y) of the complex
+    /// plane, or None outside of the keyframe.
+    fn sample(&self, width: usize, height: usize, x: f64, y: f64) -> Option<[f64; 3]> {
+        let pixel_x = (x - self.view.center_x) / self.view.scale + width as f64 / 2.0;
+        let pixel_y = (self.view.center_y - y) / self.view.scale + height as f64 / 2.0;
+        let max_x = (width - 1) as f64;
+        let max_y = (height - 1) as f64;
+        if !(0.0..=max_x).contains(&pixel_x) || !(0.0..=max_y).contains(&pixel_y) {
+            return None;
+        }
+
+        let x0 = (pixel_x.floor() as usize).min(width.saturating_sub(2));
+        let y0 = (pixel_y.floor() as usize).min(height.saturating_sub(2));
+        let x1 = (x0 + 1).min(width - 1);
+        let y1 = (y0 + 1).min(height - 1);
+        let fx = pixel_x - x0 as f64;
+        let fy = pixel_y - y0 as f64;
+
+        let mut color = [0.0; 3];
+        for (px, py, weight) in [
+            (x0, y0, (1.0 - fx) * (1.0 - fy)),
+            (x1, y0, fx * (1.0 - fy)),
+            (x0, y1, (1.0 - fx) * fy),
+            (x1, y1, fx * fy),
+        ] {
+            let index = 4 * (py * width + px);
+            for (channel, value) in color.iter_mut().zip(&self.frame[index..index + 3]) {
+                *channel += *value as f64 * weight;
+            }
+        }
+        Some(color)
+    }
+}
+
+// Synthesize the frame of `view` by warping the neighboring keyframes onto
+// it and cross-fading them by `t`, the position between the two. Pixels
+// outside of the deeper keyframe come from the wider one alone.
+fn morph(
+    view: View,
+    width: usize,
+    height: usize,
+    from: &Keyframe,
+    to: &Keyframe,
+    t: f64,
+) -> Vec<u8> {
+    let mut frame = vec![0_u8; 4 * width * height];
+    install(|| {
+        frame
+            .par_chunks_exact_mut(4 * width)
+            .enumerate()
+            .for_each(|(row, pixels)| {
+                let y = view.center_y - (row as f64 - height as f64 / 2.0) * view.scale;
+                for (column, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+                    let x = view.center_x + (column as f64 - width as f64 / 2.0) * view.scale;
+                    let color = match (
+                        from.sample(width, height, x, y),
+                        to.sample(width, height, x, y),
+                    ) {
+                        (Some(a), Some(b)) => [0, 1, 2].map(|i| a[i] * (1.0 - t) + b[i] * t),
+                        (Some(a), None) => a,
+                        (None, Some(b)) => b,
+                        (None, None) => [0.0; 3],
+                    };
+                    for (channel, value) in pixel.iter_mut().zip(color) {
+                        *channel = value.round() as u8;
+                    }
+                    pixel[3] = 0xff;
+                }
+            });
+    });
+    frame
+}
+
+/// Quick preview of the zoom animation in `dir/preview_NNNNN.png`.
+///
+/// Only every `interval`-th frame (and the last one) is rendered; the frames
+/// in between are synthesized from the two neighboring keyframes, which
+/// takes a fraction of the time of a full render.
+pub fn preview_frames(
+    job: &AnimationJob,
+    dir: &Path,
+    interval: usize,
+) -> Result<(), png::EncodingError> {
+    fs::create_dir_all(dir)?;
+    let views = zoom_path(job.start, job.end, job.frames);
+    let width = job.width as usize;
+    let height = job.height as usize;
+
+    let mut numbers: Vec<usize> = (0..views.len()).step_by(interval.max(1)).collect();
+    if numbers.last() != Some(&(views.len() - 1)) {
+        numbers.push(views.len() - 1);
+    }
+    let keyframe = |number: usize| {
+        let mut frame = vec![0_u8; 4 * width * height];
+        render_view(job, views[number], &mut frame);
+        info!("keyframe {}/{}", number + 1, job.frames);
+        Keyframe {
+            view: views[number],
+            frame,
+        }
+    };
+    let save = |number: usize, frame: &[u8]| -> Result<(), png::EncodingError> {
+        let path = preview_path(dir, number);
+        export::save_png(&path, job.width, job.height, frame)?;
+        info!("preview {}/{}: {}", number + 1, job.frames, path.display());
+        Ok(())
+    };
+
+    // only the two keyframes around the frames being written are kept
+    let mut prev = keyframe(numbers[0]);
+    save(numbers[0], &prev.frame)?;
+    for pair in numbers.windows(2) {
+        let (prev_key, key) = (pair[0], pair[1]);
+        let next = keyframe(key);
+        for (number, view) in views.iter().enumerate().take(key).skip(prev_key + 1) {
+            let t = (number - prev_key) as f64 / (key - prev_key) as f64;
+            save(number, &morph(*view, width, height, &prev, &next, t))?;
+        }
+        save(key, &next.frame)?;
+        prev = next;
+    }
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use clap::ValueEnum;
+
+use crate::canvas::{Canvas, FOREGROUND, LINE_HEIGHT, SHADOW};
+use crate::double_double::DoubleDouble;
+use crate::histogram::IterationCounts;
+use crate::mandelbrot::Mandelbrot;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Annotation {
+    /// Bar with a round length in complex-plane units
+    ScaleBar,
+    /// Center position and pixel size
+    Coordinates,
+    /// Real and imaginary axes
+    Axes,
+    /// Palette bar labeled with iteration counts
+    Legend,
+}
+
+const MARGIN: usize = 5;
+
+/// Text magnification that keeps the overlays as legible as in the 640x480
+/// window when rendering at print resolution.
+pub fn auto_text_scale(height: u32) -> usize {
+    (height as usize / 480).max(1)
+}
+
+pub fn annotate(
+    canvas: &mut Canvas,
+    mandelbrot: &Mandelbrot,
+    annotations: &[Annotation],
+    text_scale: usize,
+) {
+    for annotation in annotations {
+        match annotation {
+            Annotation::ScaleBar => scale_bar(canvas, mandelbrot, text_scale),
+            Annotation::Coordinates => coordinates(canvas, mandelbrot, text_scale),
+            Annotation::Axes => axes(canvas, mandelbrot, text_scale),
+            Annotation::Legend => legend(canvas, mandelbrot, text_scale),
+        }
+    }
+}
+
+// Largest 1, 2 or 5 times a power of ten that does not exceed `length`.
+fn round_length(length: f64) -> f64 {
+    let magnitude = 10_f64.powf(length.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|mantissa| mantissa * magnitude)
+        .find(|candidate| *candidate <= length)
+        .unwrap_or(magnitude)
+}
+
+fn scale_bar(canvas: &mut Canvas, mandelbrot: &Mandelbrot, text_scale: usize) {
+    let length = round_length(canvas.width as f64 / 5.0 * mandelbrot.scale);
+    let bar_width = (length / mandelbrot.scale).round() as usize;
+    let bar_height = 3 * text_scale;
+    let x = MARGIN * text_scale;
+    let y = canvas
+        .height
+        .saturating_sub(MARGIN * text_scale + bar_height);
+
+    canvas.fill_rect(
+        x,
+        y,
+        bar_width + 2 * text_scale,
+        bar_height + 2 * text_scale,
+        SHADOW,
+    );
+    canvas.fill_rect(x, y, bar_width, bar_height, FOREGROUND);
+
+    let label = format!("{:e}", length);
+    let label_y = y.saturating_sub(LINE_HEIGHT * text_scale);
+    canvas.draw_text(x, label_y, &label, text_scale);
+}
+
+fn coordinates(canvas: &mut Canvas, mandelbrot: &Mandelbrot, text_scale: usize) {
+    let lines = [
+        format!("x: {}", mandelbrot.center_x),
+        format!("y: {}", mandelbrot.center_y),
+        format!("scale: {}", mandelbrot.scale),
+    ];
+    for (i, line) in lines.iter().enumerate() {
+        let y = MARGIN * text_scale + i * LINE_HEIGHT * text_scale;
+        canvas.draw_text(MARGIN * text_scale, y, line, text_scale);
+    }
+}
+
+fn axes(canvas: &mut Canvas, mandelbrot: &Mandelbrot, text_scale: usize) {
+    let (origin_x, origin_y) = mandelbrot.precise_pixel_of(DoubleDouble::ZERO, DoubleDouble::ZERO);
+
+    if origin_y >= 0.0 && origin_y < canvas.height as f64 {
+        let y = origin_y as usize;
+        canvas.fill_rect(0, y, canvas.width, text_scale, FOREGROUND);
+        let label_x = canvas
+            .width
+            .saturating_sub(MARGIN * text_scale + Canvas::text_width("Re", text_scale));
+        canvas.draw_text(label_x, y + 2 * text_scale, "Re", text_scale);
+    }
+    if origin_x >= 0.0 && origin_x < canvas.width as f64 {
+        let x = origin_x as usize;
+        canvas.fill_rect(x, 0, text_scale, canvas.height, FOREGROUND);
+        canvas.draw_text(x + 3 * text_scale, MARGIN * text_scale, "Im", text_scale);
+    }
+}
+
+const LEGEND_WIDTH: usize = 256;
+const LEGEND_HEIGHT: usize = 8;
+const LEGEND_TICKS: usize = 4;
+
+/// Draw the palette from 0 to max_round as a horizontal bar in the
+/// bottom-right corner, with the iteration count of each tick below it.
+pub fn legend(canvas: &mut Canvas, mandelbrot: &Mandelbrot, text_scale: usize) {
+    let bar_width = LEGEND_WIDTH * text_scale;
+    let bar_height = LEGEND_HEIGHT * text_scale;
+    let label_width = Canvas::text_width(&mandelbrot.max_round.to_string(), text_scale);
+    let x = canvas
+        .width
+        .saturating_sub(MARGIN * text_scale + bar_width + label_width / 2);
+    let y = canvas
+        .height
+        .saturating_sub(MARGIN * text_scale + LINE_HEIGHT * text_scale + bar_height);
+
+    canvas.fill_rect(
+        x,
+        y,
+        bar_width + 2 * text_scale,
+        bar_height + 2 * text_scale,
+        SHADOW,
+    );
+    for column in 0..bar_width {
+        let round = column * mandelbrot.max_round / bar_width;
+        let rgba = mandelbrot.round_to_color(round);
+        canvas.fill_rect(x + column, y, 1, bar_height, rgba);
+    }
+
+    for tick in 0..=LEGEND_TICKS {
+        let tick_x = x + tick * bar_width / LEGEND_TICKS;
+        let round = tick * mandelbrot.max_round / LEGEND_TICKS;
+        let label = round.to_string();
+        let label_x = tick_x.saturating_sub(Canvas::text_width(&label, text_scale) / 2);
+        canvas.fill_rect(
+            tick_x,
+            y + bar_height,
+            text_scale,
+            2 * text_scale,
+            FOREGROUND,
+        );
+        canvas.draw_text(label_x, y + bar_height + 3 * text_scale, &label, text_scale);
+    }
+}
+
+// periods shown by the period key, and the size of each swatch
+const KEY_PERIODS: usize = 12;
+const KEY_SWATCH: usize = 20;
+
+/// Draw the colors of the periods 1 to KEY_PERIODS of the period analysis
+/// as a row of swatches at the bottom center, each labeled with its period.
+pub fn period_key(canvas: &mut Canvas, mandelbrot: &Mandelbrot, text_scale: usize) {
+    let swatch = KEY_SWATCH * text_scale;
+    let width = KEY_PERIODS * swatch;
+    let x = canvas.width.saturating_sub(width) / 2;
+    let y = canvas
+        .height
+        .saturating_sub(MARGIN * text_scale + LINE_HEIGHT * text_scale + swatch);
+
+    canvas.fill_rect(
+        x.saturating_sub(text_scale),
+        y.saturating_sub(text_scale),
+        width + 2 * text_scale,
+        swatch + 2 * text_scale,
+        SHADOW,
+    );
+    for period in 1..=KEY_PERIODS {
+        let swatch_x = x + (period - 1) * swatch;
+        canvas.fill_rect(swatch_x, y, swatch, swatch, mandelbrot.period_color(period));
+        let label = period.to_string();
+        let label_x = swatch_x + swatch.saturating_sub(Canvas::text_width(&label, text_scale)) / 2;
+        canvas.draw_text(label_x, y + swatch + 3 * text_scale, &label, text_scale);
+    }
+}
+
+// half the length of the arms of the probe marker, and the gap in its middle
+const PROBE_ARM: usize = 8;
+const PROBE_GAP: usize = 2;
+
+/// Mark the pixel (x, y) of the probed point with a crosshair whose arms
+/// leave the point itself visible.
+pub fn probe_marker(canvas: &mut Canvas, x: f64, y: f64) {
+    if x < 0.0 || y < 0.0 || x >= canvas.width as f64 || y >= canvas.height as f64 {
+        return;
+    }
+    let (x, y) = (x as usize, y as usize);
+    // the shadow first, a pixel wider around the arms
+    for (color, grow) in [(SHADOW, 1), (FOREGROUND, 0)] {
+        let near = PROBE_ARM + grow;
+        let far = PROBE_GAP + 1 - grow;
+        let length = PROBE_ARM - PROBE_GAP + 2 * grow;
+        let thickness = 1 + 2 * grow;
+        let (left, top) = (x.saturating_sub(grow), y.saturating_sub(grow));
+        canvas.fill_rect(x.saturating_sub(near), top, length, thickness, color);
+        canvas.fill_rect(x + far, top, length, thickness, color);
+        canvas.fill_rect(left, y.saturating_sub(near), thickness, length, color);
+        canvas.fill_rect(left, y + far, thickness, length, color);
+    }
+}
+
+const HISTOGRAM_BAR_WIDTH: usize = 4;
+const HISTOGRAM_HEIGHT: usize = 64;
+// weight of the panel behind the histogram over the image
+const HISTOGRAM_PANEL_ALPHA: f64 = 0.6;
+
+/// Draw the escape iterations of the samples of a frame as a histogram in
+/// the bottom-left corner: a bar in the palette color of every range of
+/// iterations and an outlined one for the points inside the set, on a
+/// logarithmic scale so that the rare counts still show.
+pub fn iteration_histogram(
+    canvas: &mut Canvas,
+    mandelbrot: &Mandelbrot,
+    counts: &IterationCounts,
+    text_scale: usize,
+) {
+    let bar_width = HISTOGRAM_BAR_WIDTH * text_scale;
+    let chart_height = HISTOGRAM_HEIGHT * text_scale;
+    let bins_width = counts.bins.len() * bar_width;
+    let interior_x = bins_width + 2 * bar_width;
+    let x = MARGIN * text_scale;
+    let label_y = canvas
+        .height
+        .saturating_sub(MARGIN * text_scale + LINE_HEIGHT * text_scale);
+    let bottom = label_y.saturating_sub(text_scale);
+    let top = bottom.saturating_sub(chart_height);
+    let title_y = top.saturating_sub(LINE_HEIGHT * text_scale);
+
+    let samples = counts.samples();
+    let title = format!(
+        "iterations of {} samples, {:.0}% inside",
+        samples,
+        100.0 * counts.interior as f64 / samples.max(1) as f64
+    );
+    let padding = 3 * text_scale;
+    let panel_width = (interior_x + bar_width).max(Canvas::text_width(&title, text_scale));
+    canvas.blend_rect(
+        x.saturating_sub(padding),
+        title_y.saturating_sub(padding),
+        panel_width + 2 * padding,
+        canvas.height - title_y.saturating_sub(padding),
+        canvas.theme.background.map(f64::from),
+        HISTOGRAM_PANEL_ALPHA,
+    );
+
+    let largest = counts
+        .bins
+        .iter()
+        .copied()
+        .chain([counts.interior])
+        .max()
+        .unwrap_or(0);
+    let height = |count: usize| {
+        if count == 0 {
+            0
+        } else {
+            let height = (count as f64).ln_1p() / (largest as f64).ln_1p() * chart_height as f64;
+            (height.round() as usize).max(1)
+        }
+    };
+    for (bin, count) in counts.bins.iter().enumerate() {
+        let bar_height = height(*count);
+        let rgba = mandelbrot.round_to_color(counts.first_round(bin));
+        canvas.fill_rect(
+            x + bin * bar_width,
+            bottom.saturating_sub(bar_height),
+            bar_width,
+            bar_height,
+            rgba,
+        );
+    }
+    let interior_height = height(counts.interior);
+    canvas.stroke_rect(
+        x + interior_x,
+        bottom.saturating_sub(interior_height),
+        bar_width,
+        interior_height,
+        FOREGROUND,
+    );
+    canvas.fill_rect(x, bottom, bins_width, text_scale, FOREGROUND);
+
+    canvas.draw_text(x, title_y, &title, text_scale);
+    canvas.draw_text(x, label_y, "0", text_scale);
+    let limit = counts.max_round.to_string();
+    let limit_x = (x + bins_width).saturating_sub(Canvas::text_width(&limit, text_scale));
+    canvas.draw_text(limit_x, label_y, &limit, text_scale);
+}
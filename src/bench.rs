@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use crate::mandelbrot::Mandelbrot;
+use crate::parallel;
+use crate::platform::Instant;
+use crate::simd;
+
+// size of the frame every scene is rendered at
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+// renders of each scene, of which the fastest counts
+const RUNS: usize = 3;
+
+/// A viewport that stresses one part of the renderer.
+struct Scene {
+    name: &'static str,
+    description: &'static str,
+    x: f64,
+    y: f64,
+    scale: f64,
+    max_round: usize,
+}
+
+// Fixed from version to version, so that the timings compare.
+const SCENES: &[Scene] = &[
+    Scene {
+        name: "shallow",
+        description: "the whole set",
+        x: -0.7,
+        y: 0.0,
+        scale: 0.004,
+        max_round: 512,
+    },
+    Scene {
+        name: "deep",
+        description: "seahorse valley at 1e-12",
+        x: -0.743_643_887_037_158,
+        y: 0.131_825_904_205_311,
+        scale: 1e-12,
+        max_round: 4096,
+    },
+    Scene {
+        name: "interior",
+        description: "mostly the main cardioid",
+        x: -0.2,
+        y: 0.0,
+        scale: 0.0008,
+        max_round: 4096,
+    },
+    Scene {
+        name: "filaments",
+        description: "around a Misiurewicz point",
+        x: -0.775_683_77,
+        y: 0.136_467_37,
+        scale: 2e-7,
+        max_round: 4096,
+    },
+];
+
+fn render(scene: &Scene, frame: &mut [u8]) -> Duration {
+    let mut mandelbrot = Mandelbrot::new(WIDTH, HEIGHT);
+    mandelbrot.set_view(scene.x, scene.y, scene.scale, scene.max_round);
+    mandelbrot.info = false;
+    let start = Instant::now();
+    mandelbrot.draw(frame);
+    start.elapsed()
+}
+
+/// Render each scene a few times at a fixed size and print the fastest
+/// time of each and its pixels per second, with the kernel and the
+/// threads they ran on.
+pub fn run() {
+    let mut frame = vec![0_u8; 4 * WIDTH as usize * HEIGHT as usize];
+    // the kernel is picked at the first frame
+    render(&SCENES[0], &mut frame);
+    println!(
+        "{}x{}, best of {} runs, kernel {}, {} threads",
+        WIDTH,
+        HEIGHT,
+        RUNS,
+        simd::kernel_name(),
+        parallel::threads()
+    );
+    let pixels = WIDTH as f64 * HEIGHT as f64;
+    let mut total = Duration::ZERO;
+    for scene in SCENES {
+        let best = (0..RUNS)
+            .map(|_| render(scene, &mut frame))
+            .min()
+            .unwrap_or_default();
+        total += best;
+        println!(
+            "{:<10} {:>9.1} ms {:>8.2} Mpixel/s  {}",
+            scene.name,
+            best.as_secs_f64() * 1000.0,
+            pixels / best.as_secs_f64() / 1e6,
+            scene.description
+        );
+    }
+    println!("{:<10} {:>9.1} ms", "total", total.as_secs_f64() * 1000.0);
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::double_double::{self, DoubleDouble};
+use crate::mandelbrot::{
+    check_exponent, check_iterations, check_norm_power, check_scale, default_exponent,
+    default_norm_power, Fractal, Mandelbrot, Norm,
+};
+
+pub const BOOKMARK_FILE: &str = "bookmarks.toml";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Bookmark {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+    /// low parts of the center in double-double, for the deep views
+    #[serde(default, skip_serializing_if = "double_double::is_exact")]
+    pub x_lo: f64,
+    #[serde(default, skip_serializing_if = "double_double::is_exact")]
+    pub y_lo: f64,
+    pub scale: f64,
+    pub iterations: usize,
+    #[serde(default)]
+    pub fractal: Fractal,
+    #[serde(default = "default_exponent")]
+    pub exponent: f64,
+    #[serde(default)]
+    pub norm: Norm,
+    #[serde(default = "default_norm_power")]
+    pub norm_power: f64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+impl Bookmark {
+    pub fn from_view(name: String, mandelbrot: &Mandelbrot) -> Self {
+        let (x, y) = mandelbrot.center();
+        Self {
+            name,
+            x: x.hi,
+            y: y.hi,
+            x_lo: x.lo,
+            y_lo: y.lo,
+            scale: mandelbrot.scale,
+            iterations: mandelbrot.max_round,
+            fractal: mandelbrot.options.fractal,
+            exponent: mandelbrot.options.exponent,
+            norm: mandelbrot.options.norm,
+            norm_power: mandelbrot.options.norm_power,
+            note: String::new(),
+        }
+    }
+
+    /// Go to the bookmarked view, unless the file holds a value the
+    /// command line would reject, which is left unapplied.
+    pub fn apply(&self, mandelbrot: &mut Mandelbrot) -> Result<(), String> {
+        check_exponent(self.exponent)?;
+        check_iterations(self.iterations)?;
+        check_norm_power(self.norm_power)?;
+        check_scale(self.scale)?;
+        if ![self.x, self.y, self.x_lo, self.y_lo]
+            .iter()
+            .all(|value| value.is_finite())
+        {
+            return Err(String::from("the center must be finite"));
+        }
+        mandelbrot.options.fractal = self.fractal;
+        mandelbrot.options.exponent = self.exponent;
+        mandelbrot.options.norm = self.norm;
+        mandelbrot.options.norm_power = self.norm_power;
+        mandelbrot.set_precise_view(
+            DoubleDouble::from(self.x) + self.x_lo,
+            DoubleDouble::from(self.y) + self.y_lo,
+            self.scale,
+            self.iterations,
+        );
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Bookmarks {
+    #[serde(default)]
+    pub bookmark: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    /// Read the bookmark file. A missing file is an empty bookmark list.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents =
+            toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents)
+    }
+
+    pub fn add(&mut self, mandelbrot: &Mandelbrot, note: String) -> &Bookmark {
+        let name = format!("bookmark {}", self.bookmark.len() + 1);
+        self.bookmark.push(Bookmark {
+            note,
+            ..Bookmark::from_view(name, mandelbrot)
+        });
+        self.bookmark.last().unwrap()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Bookmark> {
+        self.bookmark.get(index)
+    }
+
+    /// Indices of the bookmarks whose name or note contains `query`,
+    /// ignoring case.
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let query = query.to_lowercase();
+        (0..self.bookmark.len())
+            .filter(|&i| {
+                let bookmark = &self.bookmark[i];
+                bookmark.name.to_lowercase().contains(&query)
+                    || bookmark.note.to_lowercase().contains(&query)
+            })
+            .collect()
+    }
+}
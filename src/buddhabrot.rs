@@ -0,0 +1,189 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::canvas::{Canvas, Theme};
+use crate::mandelbrot::View;
+use crate::parallel::*;
+use crate::random::Random;
+
+const MARGIN: usize = 5;
+// the starting points are drawn from this square around the origin, which
+// holds every orbit that escapes
+const SAMPLE_RANGE: f64 = 2.0;
+// samples added per frame by the progressive loop
+pub const SAMPLES_PER_FRAME: usize = 20_000;
+// samples given to one random sequence
+const CHUNK_SAMPLES: usize = 1_000;
+// iteration limits of the red, green and blue channels of the Nebulabrot
+const NEBULA_LIMITS: [usize; 3] = [2_000, 200, 20];
+const BUDDHA_LIMIT: usize = 1_000;
+pub const MIN_EXPOSURE: f64 = 0.05;
+pub const MAX_EXPOSURE: f64 = 20.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// one iteration limit, in gray
+    Buddhabrot,
+    /// a different iteration limit in each color channel
+    Nebulabrot,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Buddhabrot => "buddhabrot",
+            Self::Nebulabrot => "nebulabrot",
+        }
+    }
+
+    fn limits(self) -> [usize; 3] {
+        match self {
+            Self::Buddhabrot => [BUDDHA_LIMIT; 3],
+            Self::Nebulabrot => NEBULA_LIMITS,
+        }
+    }
+}
+
+// The main cardioid and the period-2 bulb never escape, so sampling them
+// would only waste the iteration limit.
+fn never_escapes(x: f64, y: f64) -> bool {
+    let q = (x - 0.25) * (x - 0.25) + y * y;
+    q * (q + (x - 0.25)) <= y * y / 4.0 || (x + 1.0) * (x + 1.0) + y * y <= 1.0 / 16.0
+}
+
+/// Density of the escaping orbits of random points of z^2 + c, accumulated
+/// over frames and tone-mapped for display.
+pub struct Buddhabrot {
+    width: usize,
+    height: usize,
+    view: View,
+    pub mode: Mode,
+    pub exposure: f64,
+    // visits of every pixel, per color channel
+    density: Vec<[AtomicU32; 3]>,
+    samples: u64,
+}
+
+impl Buddhabrot {
+    pub fn new(width: usize, height: usize, mode: Mode) -> Self {
+        Self {
+            width,
+            height,
+            view: View::default(),
+            mode,
+            exposure: 1.0,
+            density: (0..width * height).map(|_| Default::default()).collect(),
+            samples: 0,
+        }
+    }
+
+    /// Start over when the view moves, since the density belongs to it.
+    pub fn set_view(&mut self, view: View) {
+        if view != self.view {
+            self.view = view;
+            self.clear();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for pixel in &self.density {
+            for channel in pixel {
+                channel.store(0, Ordering::Relaxed);
+            }
+        }
+        self.samples = 0;
+    }
+
+    pub fn scale_exposure(&mut self, factor: f64) {
+        self.exposure = (self.exposure * factor).clamp(MIN_EXPOSURE, MAX_EXPOSURE);
+    }
+
+    // Pixel of the point (x, y) of the complex plane.
+    fn pixel_of(&self, x: f64, y: f64) -> Option<usize> {
+        let px = (x - self.view.center_x) / self.view.scale + self.width as f64 / 2.0;
+        let py = (self.view.center_y - y) / self.view.scale + self.height as f64 / 2.0;
+        let inside = |p: f64, size: usize| (0.0..size as f64).contains(&p);
+        (inside(px, self.width) && inside(py, self.height))
+            .then(|| py as usize * self.width + px as usize)
+    }
+
+    /// Iterate `count` more random points and add the orbits of those that
+    /// escape to the channels whose limit they escaped within.
+    pub fn sample(&mut self, count: usize) {
+        let limits = self.mode.limits();
+        let max_limit = limits.into_iter().max().unwrap_or(0);
+        let first_chunk = self.samples / CHUNK_SAMPLES as u64;
+        let chunks = count.div_ceil(CHUNK_SAMPLES);
+        install(|| {
+            (0..chunks).into_par_iter().for_each(|chunk| {
+                let mut random = Random::new(first_chunk + chunk as u64);
+                let mut orbit = Vec::with_capacity(max_limit);
+                for _ in 0..CHUNK_SAMPLES {
+                    let cx = random.next() * SAMPLE_RANGE;
+                    let cy = random.next() * SAMPLE_RANGE;
+                    if never_escapes(cx, cy) {
+                        continue;
+                    }
+                    orbit.clear();
+                    let (mut x, mut y) = (0.0_f64, 0.0_f64);
+                    let mut escape = None;
+                    for round in 1..=max_limit {
+                        (x, y) = (x * x - y * y + cx, 2.0 * x * y + cy);
+                        if x * x + y * y > 4.0 {
+                            escape = Some(round);
+                            break;
+                        }
+                        // z1 = c would only paint the sampled square
+                        if round > 1 {
+                            orbit.push((x, y));
+                        }
+                    }
+                    let round = match escape {
+                        Some(round) => round,
+                        None => continue,
+                    };
+                    for (x, y) in &orbit {
+                        if let Some(pixel) = self.pixel_of(*x, *y) {
+                            for (channel, limit) in limits.iter().enumerate() {
+                                if round <= *limit {
+                                    self.density[pixel][channel].fetch_add(1, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+        });
+        self.samples += (chunks * CHUNK_SAMPLES) as u64;
+    }
+
+    /// Tone-map the density into `frame`: the square root of the density
+    /// relative to the densest pixel of each channel, times the exposure.
+    pub fn draw(&self, frame: &mut [u8], close_key: &str, theme: Theme) {
+        let mut max = [1_u32; 3];
+        for pixel in &self.density {
+            for (max, channel) in max.iter_mut().zip(pixel) {
+                *max = (*max).max(channel.load(Ordering::Relaxed));
+            }
+        }
+        frame
+            .par_chunks_exact_mut(4)
+            .zip(&self.density)
+            .for_each(|(rgba, pixel)| {
+                for channel in 0..3 {
+                    let density = pixel[channel].load(Ordering::Relaxed) as f64;
+                    let value = self.exposure * (density / max[channel] as f64).sqrt();
+                    rgba[channel] = (value.min(1.0) * 255.0) as u8;
+                }
+                rgba[3] = 0xff;
+            });
+
+        let mut canvas = Canvas::new(frame, self.width, self.height);
+        canvas.theme = theme;
+        let lines = [
+            format!("{}: {} samples", self.mode.name(), self.samples),
+            format!("exposure: {:.2}", self.exposure),
+            format!("({} to leave)", close_key),
+        ];
+        canvas.draw_text_block(&lines, MARGIN);
+    }
+}
@@ -0,0 +1,405 @@
+use font8x8::{UnicodeFonts, BASIC_FONTS};
+use serde::Deserialize;
+
+use crate::cycling::IterationColors;
+
+pub const GLYPH_SIZE: usize = 8;
+pub const GLYPH_ADVANCE: usize = 9;
+pub const LINE_HEIGHT: usize = 12;
+
+pub const FOREGROUND: [u8; 4] = [0xb0, 0xb0, 0xb0, 0xff];
+pub const SHADOW: [u8; 4] = [0x00, 0x00, 0x00, 0xff];
+// steps of the panel opacity cycled in the window
+const OPACITY_STEPS: [f64; 4] = [0.0, 0.25, 0.5, 0.75];
+// space between the text and the edge of its panel
+const PANEL_PADDING: usize = 3;
+// height of the progress bar along the bottom edge
+const PROGRESS_HEIGHT: usize = 4;
+
+/// Corner of the window where the information display goes, or of an
+/// exported image where its caption goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomRight,
+    BottomLeft,
+}
+
+impl Corner {
+    pub fn next(self) -> Self {
+        match self {
+            Self::TopLeft => Self::TopRight,
+            Self::TopRight => Self::BottomRight,
+            Self::BottomRight => Self::BottomLeft,
+            Self::BottomLeft => Self::TopLeft,
+        }
+    }
+}
+
+/// Colors of the text drawn over the image, and where the information
+/// display goes.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub foreground: [u8; 3],
+    /// color of the shadow and of the panels behind text blocks
+    pub background: [u8; 3],
+    /// opacity of the panels, 0 to 1 (0 draws none)
+    pub opacity: f64,
+    /// opacity of the text and its shadow, 0 to 1
+    pub text_opacity: f64,
+    pub shadow: bool,
+    pub corner: Corner,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let [r, g, b, _] = FOREGROUND;
+        let [shadow_r, shadow_g, shadow_b, _] = SHADOW;
+        Self {
+            foreground: [r, g, b],
+            background: [shadow_r, shadow_g, shadow_b],
+            opacity: 0.0,
+            text_opacity: 1.0,
+            shadow: true,
+            corner: Corner::TopLeft,
+        }
+    }
+}
+
+impl Theme {
+    pub fn next_opacity(&mut self) {
+        self.opacity = OPACITY_STEPS
+            .into_iter()
+            .find(|step| *step > self.opacity)
+            .unwrap_or(0.0);
+    }
+
+    /// Swap the foreground and background colors.
+    pub fn invert(&mut self) {
+        std::mem::swap(&mut self.foreground, &mut self.background);
+    }
+}
+
+/// RGBA frame buffer with clipped drawing primitives.
+pub struct Canvas<'a> {
+    frame: &'a mut [u8],
+    pub width: usize,
+    pub height: usize,
+    pub theme: Theme,
+    /// Colors of the pixels that keep escape iterations (alpha 0), with
+    /// the offset the palette is advanced by, which are colored before
+    /// anything is blended over them.
+    pub iteration_colors: Option<(IterationColors, usize)>,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(frame: &'a mut [u8], width: usize, height: usize) -> Self {
+        assert!(frame.len() >= 4 * width * height);
+        Self {
+            frame,
+            width,
+            height,
+            theme: Theme::default(),
+            iteration_colors: None,
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, rgba: [u8; 4]) {
+        let x_end = x.saturating_add(width).min(self.width);
+        let y_end = y.saturating_add(height).min(self.height);
+        for py in y..y_end {
+            for px in x..x_end {
+                let pos = 4 * (px + py * self.width);
+                self.frame[pos..(pos + 4)].copy_from_slice(&rgba);
+            }
+        }
+    }
+
+    // The pixel at `pos` colored, when it keeps an escape iteration and the
+    // colors are known, to blend over.
+    fn decode(&mut self, pos: usize) {
+        let pixel = &mut self.frame[pos..(pos + 4)];
+        if let (0x00, Some((colors, offset))) = (pixel[3], &self.iteration_colors) {
+            let rgba = colors.color(pixel, *offset);
+            pixel.copy_from_slice(&rgba);
+        }
+    }
+
+    /// Draw a bar along the bottom edge filled to `progress` (0 to 1) in
+    /// the foreground color of the theme.
+    pub fn draw_progress(&mut self, progress: f64) {
+        let y = self.height.saturating_sub(PROGRESS_HEIGHT);
+        let filled = (progress.clamp(0.0, 1.0) * self.width as f64) as usize;
+        let [r, g, b] = self.theme.foreground;
+        let [shadow_r, shadow_g, shadow_b] = self.theme.background;
+        self.fill_rect(0, y, filled, PROGRESS_HEIGHT, [r, g, b, 0xff]);
+        self.fill_rect(
+            filled,
+            y,
+            self.width - filled,
+            PROGRESS_HEIGHT,
+            [shadow_r, shadow_g, shadow_b, 0xff],
+        );
+    }
+
+    /// Mix `rgb` into the rectangle with the weight `alpha` (0 to 1).
+    pub fn blend_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        rgb: [f64; 3],
+        alpha: f64,
+    ) {
+        let x_end = x.saturating_add(width).min(self.width);
+        let y_end = y.saturating_add(height).min(self.height);
+        for py in y..y_end {
+            for px in x..x_end {
+                let pos = 4 * (px + py * self.width);
+                self.decode(pos);
+                for (channel, value) in self.frame[pos..(pos + 3)].iter_mut().zip(rgb) {
+                    *channel = (*channel as f64 * (1.0 - alpha) + value * alpha) as u8;
+                }
+                self.frame[pos + 3] = 0xff;
+            }
+        }
+    }
+
+    /// Blend a premultiplied color (channels 0 to 1) over the pixel.
+    pub fn blend_premultiplied(&mut self, x: usize, y: usize, rgba: [f32; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let pos = 4 * (x + y * self.width);
+        self.decode(pos);
+        for (channel, value) in self.frame[pos..(pos + 3)].iter_mut().zip(rgba) {
+            *channel = (value * 255.0 + *channel as f32 * (1.0 - rgba[3]))
+                .round()
+                .min(255.0) as u8;
+        }
+        self.frame[pos + 3] = 0xff;
+    }
+
+    /// Copy an RGBA image of `width` x `height` pixels with its top-left
+    /// corner at (x, y), clipped to the canvas.
+    pub fn draw_image(&mut self, x: usize, y: usize, width: usize, height: usize, image: &[u8]) {
+        let x_end = x.saturating_add(width).min(self.width);
+        let y_end = y.saturating_add(height).min(self.height);
+        if x >= x_end {
+            return;
+        }
+        for py in y..y_end {
+            let source = 4 * (py - y) * width;
+            let pos = 4 * (x + py * self.width);
+            self.frame[pos..pos + 4 * (x_end - x)]
+                .copy_from_slice(&image[source..source + 4 * (x_end - x)]);
+        }
+    }
+
+    /// One pixel wide line between two pixel positions, clipped to the
+    /// canvas first, since the ends may be far outside it after zooming in.
+    pub fn draw_line(&mut self, start: (f64, f64), end: (f64, f64), rgba: [u8; 4]) {
+        let (width, height) = (self.width as f64, self.height as f64);
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        // Liang-Barsky: the part of the segment with 0 <= x < width, 0 <= y < height
+        let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+        for (p, q) in [
+            (-dx, start.0),
+            (dx, width - 1.0 - start.0),
+            (-dy, start.1),
+            (dy, height - 1.0 - start.1),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return;
+                }
+            } else if p < 0.0 {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+        if t0 > t1 {
+            return;
+        }
+        let steps = ((t1 - t0) * dx.abs().max(dy.abs())).ceil().max(1.0) as usize;
+        for i in 0..=steps {
+            let t = t0 + (t1 - t0) * i as f64 / steps as f64;
+            let (x, y) = (start.0 + t * dx, start.1 + t * dy);
+            self.fill_rect(x.round() as usize, y.round() as usize, 1, 1, rgba);
+        }
+    }
+
+    /// One pixel wide outline of the rectangle.
+    pub fn stroke_rect(&mut self, x: usize, y: usize, width: usize, height: usize, rgba: [u8; 4]) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.fill_rect(x, y, width, 1, rgba);
+        self.fill_rect(x, y + height - 1, width, 1, rgba);
+        self.fill_rect(x, y, 1, height, rgba);
+        self.fill_rect(x + width - 1, y, 1, height, rgba);
+    }
+
+    pub fn text_width(text: &str, scale: usize) -> usize {
+        text.chars().count() * GLYPH_ADVANCE * scale
+    }
+
+    /// Draw `text` with its top-left corner at (x, y), magnifying each glyph
+    /// pixel to a `scale` x `scale` block. The text and its shadow are mixed
+    /// into the image by their coverage of each pixel times the text opacity
+    /// of the theme. Pixels outside the canvas are skipped.
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str, scale: usize) {
+        self.draw_text_in_strip(x, y, text, scale, 0);
+    }
+
+    /// Draw `text` at (x, y) of an image whose rows from `top` on are the
+    /// canvas, the part of it that falls into those rows.
+    pub fn draw_text_in_strip(&mut self, x: usize, y: usize, text: &str, scale: usize, top: usize) {
+        let scale = scale.max(1);
+        let (glyphs, width, height) = glyph_mask(text, scale);
+        let shadow = if self.theme.shadow {
+            shadow_mask(&glyphs, width, height, (SHADOW_SIZE - 1) * scale + 1)
+        } else {
+            Vec::new()
+        };
+        let alpha = self.theme.text_opacity.clamp(0.0, 1.0) as f32;
+        let background = self.theme.background.map(f32::from);
+        let foreground = self.theme.foreground.map(f32::from);
+
+        let x_end = x.saturating_add(width).min(self.width);
+        let y_end = y.saturating_add(height).min(top + self.height);
+        for py in y.max(top)..y_end {
+            for px in x..x_end {
+                let i = (px - x) + (py - y) * width;
+                let shadow = shadow.get(i).copied().unwrap_or(0.0) * alpha;
+                let glyph = glyphs[i] * alpha;
+                if shadow == 0.0 && glyph == 0.0 {
+                    continue;
+                }
+                let pos = 4 * (px + (py - top) * self.width);
+                self.decode(pos);
+                let pixel = &mut self.frame[pos..(pos + 4)];
+                for channel in 0..3 {
+                    let mut value = pixel[channel] as f32;
+                    value += (background[channel] - value) * shadow;
+                    value += (foreground[channel] - value) * glyph;
+                    pixel[channel] = value.round() as u8;
+                }
+                pixel[3] = 0xff;
+            }
+        }
+    }
+
+    /// Draw `lines` in the corner of the theme, `margin` pixels from the
+    /// edges, over a panel when the theme has an opacity.
+    pub fn draw_text_block(&mut self, lines: &[String], margin: usize) {
+        let width = lines
+            .iter()
+            .map(|line| Self::text_width(line, 1))
+            .max()
+            .unwrap_or(0);
+        let height = lines.len() * LINE_HEIGHT;
+        let x = match self.theme.corner {
+            Corner::TopLeft | Corner::BottomLeft => margin,
+            Corner::TopRight | Corner::BottomRight => self.width.saturating_sub(margin + width),
+        };
+        let y = match self.theme.corner {
+            Corner::TopLeft | Corner::TopRight => margin,
+            Corner::BottomLeft | Corner::BottomRight => self.height.saturating_sub(margin + height),
+        };
+        if self.theme.opacity > 0.0 {
+            let background = self.theme.background.map(f64::from);
+            self.blend_rect(
+                x.saturating_sub(PANEL_PADDING),
+                y.saturating_sub(PANEL_PADDING),
+                width + 2 * PANEL_PADDING,
+                height + 2 * PANEL_PADDING,
+                background,
+                self.theme.opacity,
+            );
+        }
+        for (i, line) in lines.iter().enumerate() {
+            self.draw_text(x, y + i * LINE_HEIGHT, line, 1);
+        }
+    }
+}
+
+// Glyph pixels cast a shadow this many glyph pixels wide and high, down
+// and to the right of their top-left corner.
+const SHADOW_SIZE: usize = 3;
+
+// Coverage (0 to 1) of every pixel by the glyphs of `text` magnified
+// `scale` times, with room for the shadow at the right and bottom, and the
+// width and height of the mask. Magnified glyphs are resampled bilinearly
+// and cut at half coverage with a one-pixel ramp, which keeps their straight
+// edges sharp and smooths the staircases of their diagonals and corners.
+fn glyph_mask(text: &str, scale: usize) -> (Vec<f32>, usize, usize) {
+    let cell = GLYPH_SIZE * scale;
+    let width = text.chars().count() * GLYPH_ADVANCE * scale + SHADOW_SIZE * scale;
+    let height = cell + SHADOW_SIZE * scale;
+    let mut mask = vec![0.0_f32; width * height];
+    for (i, chr) in text.chars().enumerate() {
+        let glyph = match BASIC_FONTS.get(chr) {
+            Some(glyph) => glyph,
+            None => continue,
+        };
+        let bit = |column: isize, row: isize| -> f32 {
+            let inside = (0..GLYPH_SIZE as isize).contains(&column)
+                && (0..GLYPH_SIZE as isize).contains(&row);
+            if inside && glyph[row as usize] & 1 << column != 0 {
+                1.0
+            } else {
+                0.0
+            }
+        };
+        let left = i * GLYPH_ADVANCE * scale;
+        for y in 0..cell {
+            for x in 0..cell {
+                let coverage = if scale == 1 {
+                    bit(x as isize, y as isize)
+                } else {
+                    // pixel center in glyph pixels, relative to their centers
+                    let gx = (x as f32 + 0.5) / scale as f32 - 0.5;
+                    let gy = (y as f32 + 0.5) / scale as f32 - 0.5;
+                    let (column, row) = (gx.floor() as isize, gy.floor() as isize);
+                    let (fx, fy) = (gx - gx.floor(), gy - gy.floor());
+                    let top = bit(column, row) * (1.0 - fx) + bit(column + 1, row) * fx;
+                    let bottom = bit(column, row + 1) * (1.0 - fx) + bit(column + 1, row + 1) * fx;
+                    let value = top * (1.0 - fy) + bottom * fy;
+                    ((value - 0.5) * scale as f32 + 0.5).clamp(0.0, 1.0)
+                };
+                mask[left + x + y * width] = coverage;
+            }
+        }
+    }
+    (mask, width, height)
+}
+
+// Coverage of the shadow: the glyph coverage spread `size` pixels down and
+// to the right, taking the maximum so that overlapping shadows do not add
+// up.
+fn shadow_mask(glyphs: &[f32], width: usize, height: usize, size: usize) -> Vec<f32> {
+    let mut rows = vec![0.0_f32; glyphs.len()];
+    for y in 0..height {
+        for x in 0..width {
+            rows[x + y * width] = (x.saturating_sub(size - 1)..=x)
+                .map(|from| glyphs[from + y * width])
+                .fold(0.0, f32::max);
+        }
+    }
+    let mut shadow = vec![0.0_f32; glyphs.len()];
+    for y in 0..height {
+        for x in 0..width {
+            shadow[x + y * width] = (y.saturating_sub(size - 1)..=y)
+                .map(|from| rows[x + from * width])
+                .fold(0.0, f32::max);
+        }
+    }
+    shadow
+}
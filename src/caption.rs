@@ -0,0 +1,62 @@
+use crate::annotate;
+use crate::canvas::{Canvas, Corner, LINE_HEIGHT};
+use crate::mandelbrot::View;
+
+const MARGIN: usize = 5;
+// width in pixels of the initial view, whose area is the 1x of the
+// magnification
+const INITIAL_WIDTH: f64 = 640.0;
+
+/// A line of text burned into an exported image: the text given, followed
+/// by the center of the view and its magnification.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Caption {
+    pub text: String,
+    pub corner: Corner,
+    /// magnification of the text, derived from the image height when
+    /// omitted
+    pub text_scale: Option<usize>,
+}
+
+impl Caption {
+    /// The caption of an image `width` pixels wide showing `view`, with as
+    /// many decimals in the coordinates as tell its pixels apart.
+    pub fn line(&self, view: View, width: u32) -> String {
+        let decimals = (-view.scale.log10()).ceil().max(0.0) as usize + 1;
+        let magnification = INITIAL_WIDTH * View::default().scale / (view.scale * width as f64);
+        let view = format!(
+            "{:.*} {:+.*}i  x{:.3e}",
+            decimals, view.center_x, decimals, view.center_y, magnification
+        );
+        if self.text.is_empty() {
+            view
+        } else {
+            format!("{}  {}", self.text, view)
+        }
+    }
+
+    /// Draw the caption of an image of `size` pixels showing `view` into
+    /// the strip of its rows from `top` on, the canvas (the whole image when
+    /// `top` is 0 and the canvas is as high as the image).
+    pub fn draw(&self, canvas: &mut Canvas, view: View, size: (u32, u32), top: usize) {
+        let height = size.1 as usize;
+        let text_scale = self
+            .text_scale
+            .unwrap_or_else(|| annotate::auto_text_scale(size.1));
+        let line = self.line(view, size.0);
+        let margin = MARGIN * text_scale;
+        let x = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => margin,
+            Corner::TopRight | Corner::BottomRight => canvas
+                .width
+                .saturating_sub(margin + Canvas::text_width(&line, text_scale)),
+        };
+        let y = match self.corner {
+            Corner::TopLeft | Corner::TopRight => margin,
+            Corner::BottomLeft | Corner::BottomRight => {
+                height.saturating_sub(margin + LINE_HEIGHT * text_scale)
+            }
+        };
+        canvas.draw_text_in_strip(x, y, &line, text_scale, top);
+    }
+}
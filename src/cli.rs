@@ -0,0 +1,422 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+use crate::annotate::Annotation;
+use crate::canvas::Corner;
+use crate::daily;
+use crate::data::DataFormat;
+use crate::formula::Formula;
+use crate::framing::Fit;
+use crate::mandelbrot::{
+    check_exponent, check_iterations, check_norm_power, check_scale, Antialias, Coloring, Fractal,
+    Interior, Norm, MAX_BAILOUT, MAX_RELAXATION, MIN_BAILOUT, MIN_RELAXATION,
+};
+use crate::palette::Blend;
+use crate::rays::Angle;
+use crate::tile::TileOrder;
+
+fn parse_norm_power(value: &str) -> Result<f64, String> {
+    let power: f64 = value
+        .parse()
+        .map_err(|_| format!("not a number: {}", value))?;
+    check_norm_power(power)
+}
+
+fn parse_scale(value: &str) -> Result<f64, String> {
+    let scale: f64 = value
+        .parse()
+        .map_err(|_| format!("not a number: {}", value))?;
+    check_scale(scale)
+}
+
+// A number within min..=max.
+fn parse_within(value: &str, min: f64, max: f64) -> Result<f64, String> {
+    let number: f64 = value
+        .parse()
+        .map_err(|_| format!("not a number: {}", value))?;
+    if (min..=max).contains(&number) {
+        Ok(number)
+    } else {
+        Err(format!("must be {} to {}", min, max))
+    }
+}
+
+// A size given as WIDTHxHEIGHT.
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("not WIDTHxHEIGHT: {}", value))?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(format!("not a positive WIDTHxHEIGHT: {}", value)),
+    }
+}
+
+fn parse_bailout(value: &str) -> Result<f64, String> {
+    parse_within(value, MIN_BAILOUT, MAX_BAILOUT)
+}
+
+fn parse_relaxation(value: &str) -> Result<f64, String> {
+    parse_within(value, MIN_RELAXATION, MAX_RELAXATION)
+}
+
+fn parse_exponent(value: &str) -> Result<f64, String> {
+    let exponent: f64 = value
+        .parse()
+        .map_err(|_| format!("not a number: {}", value))?;
+    check_exponent(exponent)
+}
+
+fn parse_iterations(value: &str) -> Result<u32, String> {
+    let iterations: usize = value
+        .parse()
+        .map_err(|_| format!("not a number: {}", value))?;
+    check_iterations(iterations).map(|iterations| iterations as u32)
+}
+
+/// Self-study program for drawing the Mandelbrot set.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+#[command(group(clap::ArgGroup::new("animation").args(["frames", "keyframes"])))]
+pub struct Cli {
+    /// Configuration file of the startup defaults
+    /// (~/.config/mandelbrot/config.toml when omitted)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Print the actions and the keys bound to them, after the
+    /// configuration file, and exit
+    #[arg(long)]
+    pub list_bindings: bool,
+
+    /// Start the window from the command line and the configuration file
+    /// instead of where it was when it last quit
+    #[arg(long)]
+    pub fresh: bool,
+
+    /// Open the window borderless over the whole monitor, with a frame of
+    /// the monitor's resolution (F11 switches back)
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Render in strips and keep no auxiliary frame buffers (selected
+    /// automatically when little memory is available)
+    #[arg(long)]
+    pub low_memory: bool,
+
+    /// Number of threads that render, instead of one per core (overrides
+    /// `threads` of the configuration file)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: Option<u32>,
+
+    /// Fill rectangles whose border has a single iteration count instead of
+    /// computing every pixel, for faster renders that may miss filaments
+    #[arg(long)]
+    pub subdivision: bool,
+
+    /// Cap the iterations of the tiles whose samples all escape quickly
+    /// near their slowest sample, for faster renders of mixed views
+    #[arg(long)]
+    pub adaptive_iterations: bool,
+
+    /// Keep the automatic iteration limit as its formula sets it instead of
+    /// raising it when many pixels around the set escape near the limit
+    #[arg(long)]
+    pub no_iteration_tuning: bool,
+
+    /// Order in which the window renders and shows the tiles of a frame
+    #[arg(long, value_enum, default_value_t = TileOrder::CenterOut)]
+    pub tile_order: TileOrder,
+
+    /// Render a single image without opening a window
+    #[arg(long, requires = "output")]
+    pub render: bool,
+
+    /// Render the fractal of the day: a view picked at random, seeded by
+    /// the date, in a random preset palette (for cron jobs and wallpaper
+    /// rotators)
+    #[arg(long, requires = "output", conflicts_with_all = ["render", "serve"])]
+    pub daily: bool,
+
+    /// Date of the --daily image instead of today (YYYY-MM-DD, UTC)
+    #[arg(long, requires = "daily", value_parser = daily::parse_date)]
+    pub date: Option<i64>,
+
+    /// Check the iteration kernels and the renderer against a reference
+    /// implementation on a canonical set of viewports, and exit with status
+    /// 1 if any check fails
+    #[arg(long, conflicts_with_all = ["render", "serve", "daily"])]
+    pub verify: bool,
+
+    /// Render a fixed set of scenes (shallow, deep, interior, filaments) at
+    /// a fixed size and print the time and pixels per second of each
+    #[arg(long, conflicts_with_all = ["render", "serve", "daily", "verify"])]
+    pub bench: bool,
+
+    /// Serve rendered images over HTTP (GET /render?x=&y=&scale=&width=&height=&iterations=&palette=&fractal=&exponent=&norm=)
+    #[arg(long, conflicts_with = "render")]
+    pub serve: bool,
+
+    /// Address the HTTP server listens on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub listen: String,
+
+    /// Number of images the HTTP server renders at the same time
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+    pub jobs: u32,
+
+    /// Number of requests waiting for a free job before the server answers 503
+    #[arg(long, default_value_t = 16)]
+    pub queue: u32,
+
+    /// Number of map tiles (GET /{z}/{x}/{y}.png) the HTTP server keeps in memory
+    #[arg(long, default_value_t = 1024)]
+    pub tile_cache: u32,
+
+    /// Real part of the center position, where the window or the headless
+    /// render starts (-0.7 when omitted)
+    #[arg(long, allow_negative_numbers = true)]
+    pub x: Option<f64>,
+
+    /// Imaginary part of the center position (0 when omitted)
+    #[arg(long, allow_negative_numbers = true)]
+    pub y: Option<f64>,
+
+    /// Width of one pixel in the complex plane (0.005 when omitted)
+    #[arg(long, value_parser = parse_scale)]
+    pub scale: Option<f64>,
+
+    /// Image width in pixels (640, or 1920 with --daily, when omitted;
+    /// 1920 for the exports of the window; the frame of --script)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: Option<u32>,
+
+    /// Image height in pixels (480, or 1080 with --daily, when omitted;
+    /// 1080 for the exports of the window; the frame of --script)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub height: Option<u32>,
+
+    /// Maximum iteration count (chosen from the scale when omitted)
+    #[arg(long, value_parser = parse_iterations)]
+    pub iterations: Option<u32>,
+
+    /// Escape-time formula to draw (mandelbrot when omitted)
+    #[arg(long, value_enum)]
+    pub fractal: Option<Fractal>,
+
+    /// Exponent d of z^d + c (fractional values are allowed)
+    #[arg(long, default_value_t = 2.0, value_parser = parse_exponent)]
+    pub exponent: f64,
+
+    /// Formula z' = f(z, c) to iterate instead of the fractal's, such as
+    /// "z^3 + c*z" (+ - * / ^, i, abs, conj, exp, log, sin, cos)
+    #[arg(long, value_parser = Formula::parse)]
+    pub formula: Option<Formula>,
+
+    /// Bailout test of the escape time
+    #[arg(long, value_enum, default_value_t = Norm::Euclidean)]
+    pub norm: Norm,
+
+    /// p of the power norm
+    #[arg(long, default_value_t = 3.0, value_parser = parse_norm_power)]
+    pub norm_power: f64,
+
+    /// Bailout radius of the escape test
+    #[arg(long, default_value_t = 2.0, value_parser = parse_bailout)]
+    pub bailout: f64,
+
+    /// Real part of the starting point z0 of the orbits
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub z0_x: f64,
+
+    /// Imaginary part of the starting point z0 of the orbits
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub z0_y: f64,
+
+    /// Relaxation r of z' = (1 - r) z + r (z^d + c)
+    #[arg(long, default_value_t = 1.0, value_parser = parse_relaxation)]
+    pub relaxation: f64,
+
+    /// Color palette: a preset (classic, fire, ocean, rainbow, grayscale)
+    /// or a palette TOML file
+    #[arg(long)]
+    pub palette: Option<String>,
+
+    /// How escape iterations map to palette colors
+    #[arg(long, value_enum, default_value_t = Coloring::Iterations)]
+    pub coloring: Coloring,
+
+    /// How the points of the set are colored
+    #[arg(long, value_enum, default_value_t = Interior::Black)]
+    pub interior: Interior,
+
+    /// Draw the equipotentials and the external rays of these angles, as
+    /// fractions of a turn (comma separated, e.g. 1/3,2/3), over the
+    /// quadratic Mandelbrot set of the window or the headless render
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["frames", "data", "sdf", "poster"])]
+    pub rays: Vec<Angle>,
+
+    /// Spread the palette over the 1st to 99th percentile of the escape
+    /// iterations of the frame (iterations and distance colorings)
+    #[arg(long)]
+    pub auto_exposure: bool,
+
+    /// Divide each palette cycle into this many single-colored bands
+    /// (0 keeps the gradient smooth)
+    #[arg(long, default_value_t = 0)]
+    pub bands: u32,
+
+    /// Shift of the band boundaries in iterations
+    #[arg(long, default_value_t = 0, requires = "bands")]
+    pub band_offset: u32,
+
+    /// Where the palette colors are mixed, and the shading and the
+    /// antialiasing samples averaged
+    #[arg(long, value_enum, default_value_t = Blend::Srgb)]
+    pub blend: Blend,
+
+    /// WGSL file with the coloring function of the GPU coloring (see
+    /// shaders/color.wgsl), compiled again whenever it is saved; starts the
+    /// window with the GPU coloring on
+    #[arg(long, conflicts_with_all = ["render", "serve", "daily", "verify"])]
+    pub shader: Option<PathBuf>,
+
+    /// Run a session of the window from a script of key presses, mouse
+    /// input and checks instead of opening the window (see src/script.rs),
+    /// and exit with status 1 at the first command that fails. Starts from
+    /// --config, or the defaults, not the user's configuration file
+    #[arg(long, conflicts_with_all = ["render", "serve", "daily", "verify", "shader"])]
+    pub script: Option<PathBuf>,
+
+    /// Run a Rhai script that moves the view and renders and saves frames
+    /// (see src/rhai_script.rs), with the frame of --width and --height.
+    /// Starts from --config, or the defaults, not the user's configuration
+    /// file
+    #[cfg(all(feature = "rhai", not(target_arch = "wasm32")))]
+    #[arg(long, conflicts_with_all = ["render", "serve", "daily", "verify", "shader", "script"])]
+    pub rhai: Option<PathBuf>,
+
+    /// Supersampling of the window or the headless render
+    #[arg(long, value_enum, default_value_t = Antialias::Off)]
+    pub antialias: Antialias,
+
+    /// Output PNG file of the headless render or --daily (directory with
+    /// --frames)
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// Render a zoom animation of this many frames, from the initial view
+    /// to the given location, as numbered PNGs in the output directory
+    #[arg(long, requires = "render", value_parser = clap::value_parser!(u32).range(1..))]
+    pub frames: Option<u32>,
+
+    /// Render the animation through the keyframes of this file (marked in
+    /// the window) as numbered PNGs in the output directory
+    #[arg(long, requires = "render", conflicts_with = "frames")]
+    pub keyframes: Option<PathBuf>,
+
+    /// Continue an interrupted --frames export in the output directory,
+    /// verifying and keeping the frames it has already written
+    #[arg(long, requires = "frames")]
+    pub resume: bool,
+
+    /// Render only every this many frames of the --frames export and
+    /// interpolate the others, writing a quick preview_NNNNN.png sequence
+    #[arg(long, requires = "frames", conflicts_with = "resume", value_parser = clap::value_parser!(u32).range(1..))]
+    pub preview: Option<u32>,
+
+    /// Pipe the frames of --frames or --keyframes to ffmpeg, which encodes
+    /// them into the video file --output instead of numbered PNGs
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, requires = "animation", conflicts_with_all = ["resume", "preview", "farm"])]
+    pub video: bool,
+
+    /// Frames per second of the --video
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, requires = "video", default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+    pub fps: u32,
+
+    /// Encoder of the --video, as ffmpeg names it (libx264, libx265,
+    /// libvpx-vp9, ...)
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, requires = "video", default_value = "libx264")]
+    pub codec: String,
+
+    /// Bit rate of the --video, like 8M, instead of the default quality of
+    /// the encoder
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, requires = "video")]
+    pub bitrate: Option<String>,
+
+    /// Hand the headless render out to --worker instances connecting to
+    /// this address: the frames of --frames, or strips of rows of a single
+    /// image, assembled here
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, requires = "render", conflicts_with_all = ["preview", "data", "sdf", "poster", "fit", "rays"])]
+    pub farm: Option<String>,
+
+    /// Render the pieces handed out by a --farm coordinator at this address
+    /// until it has no more
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, conflicts_with_all = ["render", "serve", "daily", "verify", "script"])]
+    pub worker: Option<String>,
+
+    /// Write a 16-bit signed distance field of the view to the output
+    /// instead of the colored image (Mandelbrot formula only)
+    #[arg(long, requires = "render", conflicts_with_all = ["frames", "annotate"])]
+    pub sdf: bool,
+
+    /// Write the escape iteration of every pixel of the view to the output
+    /// instead of the colored image, 0 inside the set. Given to the window,
+    /// the format of its export of the iterations (npy when omitted)
+    #[arg(long, value_enum, conflicts_with_all = ["frames", "sdf", "poster", "annotate", "caption", "note", "fit"])]
+    pub data: Option<DataFormat>,
+
+    /// Points the window samples along the segment of the ruler for the
+    /// transect export
+    #[arg(long, default_value_t = 1000, value_parser = clap::value_parser!(u32).range(2..))]
+    pub transect_samples: u32,
+
+    /// Render the image a strip of rows at a time, written to the PNG file
+    /// as it is done, so that the memory used stays the same at any size
+    /// (posters of 16000x12000 and more)
+    #[arg(long, requires = "render", conflicts_with_all = ["frames", "sdf", "annotate"])]
+    pub poster: bool,
+
+    /// Overlays burned into the headless render (comma separated)
+    #[arg(long, value_enum, value_delimiter = ',', requires = "render")]
+    pub annotate: Vec<Annotation>,
+
+    /// Magnification of the annotation text (derived from the height when omitted)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub text_scale: Option<u32>,
+
+    /// Caption burned into the headless render and the exports of the
+    /// window: the text, followed by the center and the magnification of
+    /// the view ("" for these only)
+    #[arg(long, conflicts_with_all = ["frames", "sdf"])]
+    pub caption: Option<String>,
+
+    /// Corner of the image the caption goes in
+    #[arg(long, value_enum, default_value_t = Corner::BottomRight, requires = "caption")]
+    pub caption_corner: Corner,
+
+    /// Magnification of the caption text (derived from the height when
+    /// omitted)
+    #[arg(long, requires = "caption", value_parser = clap::value_parser!(u32).range(1..))]
+    pub caption_scale: Option<u32>,
+
+    /// Free-text note stored in the PNG of the headless render or --sdf
+    #[arg(long, requires = "render", conflicts_with = "frames")]
+    pub note: Option<String>,
+
+    /// Keep the area a window of --fit-from pixels shows at --scale,
+    /// fitting it to the image size by cropping, extending the view or
+    /// letterboxing (the initial mode of the window's export)
+    #[arg(long, value_enum, conflicts_with_all = ["frames", "sdf", "annotate"])]
+    pub fit: Option<Fit>,
+
+    /// Size of the window the --fit view is framed in
+    #[arg(long, default_value = "640x480", value_parser = parse_size, requires = "fit")]
+    pub fit_from: (u32, u32),
+}
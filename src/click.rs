@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use crate::platform::Instant;
+
+// the presses of a double click are at most this many frame pixels apart
+const DOUBLE_CLICK_DISTANCE: f64 = 4.0;
+// fingers land less precisely than the pointer
+const DOUBLE_TAP_DISTANCE: f64 = 16.0;
+// time between the presses of a double click unless configured
+pub const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(700);
+
+/// Tells double clicks from single ones: two presses of the button close
+/// together in both time and place.
+pub struct Clicks {
+    interval: Duration,
+    distance: f64,
+    // the press that may start a double click
+    first: Option<(Instant, (f64, f64))>,
+}
+
+impl Clicks {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            distance: DOUBLE_CLICK_DISTANCE,
+            first: None,
+        }
+    }
+
+    /// Double taps of a finger on a touch screen, which may land further
+    /// apart than the clicks of a mouse.
+    pub fn taps(interval: Duration) -> Self {
+        Self {
+            distance: DOUBLE_TAP_DISTANCE,
+            ..Self::new(interval)
+        }
+    }
+
+    /// Whether a press at `position` (in frame pixels) at `now` completes
+    /// a double click. The press ending a double click does not start
+    /// another one.
+    pub fn press(&mut self, position: (f64, f64), now: Instant) -> bool {
+        let double = self.first.is_some_and(|(time, (x, y))| {
+            now.duration_since(time) <= self.interval
+                && (position.0 - x).hypot(position.1 - y) <= self.distance
+        });
+        self.first = if double { None } else { Some((now, position)) };
+        double
+    }
+
+    /// Follow the pointer while the button is held: a press that turns
+    /// into a drag does not start a double click.
+    pub fn moved(&mut self, position: (f64, f64)) {
+        if self
+            .first
+            .is_some_and(|(_, (x, y))| (position.0 - x).hypot(position.1 - y) > self.distance)
+        {
+            self.first = None;
+        }
+    }
+}
+
+/// The left button over the view: a double click recenters the view, and
+/// a drag moves the image with the pointer and the view on release.
+pub struct LeftButton {
+    clicks: Clicks,
+    // where the drag started, or was last applied to the view, while the
+    // button pressed over the view is held; the drag and release that end
+    // a double click do nothing
+    pressed: Option<(f64, f64)>,
+    /// How far the image is dragged from where it was rendered, in frame
+    /// pixels. It stays after the release, until the frame of the new view
+    /// replaces the image.
+    pub offset: (f64, f64),
+}
+
+impl LeftButton {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            clicks: Clicks::new(interval),
+            pressed: None,
+            offset: (0.0, 0.0),
+        }
+    }
+
+    /// Press at `position` at `now`; whether it completes a double click.
+    pub fn press(&mut self, position: (f64, f64), now: Instant) -> bool {
+        let double_clicked = self.clicks.press(position, now);
+        self.pressed = (!double_clicked).then_some(position);
+        double_clicked
+    }
+
+    /// The pointer at `position` with the button held: how far the drag
+    /// went since the last call, if it moved. With `follow` (the low-memory
+    /// mode, which keeps no copy of the image to move) the view is to move
+    /// that much at once; otherwise the image moves to `offset`.
+    pub fn drag(&mut self, position: (f64, f64), follow: bool) -> Option<(f64, f64)> {
+        let pressed = self.pressed?;
+        self.clicks.moved(position);
+        let offset = (position.0 - pressed.0, position.1 - pressed.1);
+        if follow {
+            if offset == (0.0, 0.0) {
+                return None;
+            }
+            self.pressed = Some(position);
+            Some(offset)
+        } else if offset != self.offset {
+            let step = (offset.0 - self.offset.0, offset.1 - self.offset.1);
+            self.offset = offset;
+            Some(step)
+        } else {
+            None
+        }
+    }
+
+    /// Release at `position`, or where the image was dragged last when the
+    /// pointer is not over the view: how far the view is to move. A release
+    /// of the button pressed elsewhere, such as on the panel, moves nothing.
+    pub fn release(&mut self, position: Option<(f64, f64)>, follow: bool) -> Option<(f64, f64)> {
+        let pressed = self.pressed.take()?;
+        let offset = match position {
+            Some((x, y)) => (x - pressed.0, y - pressed.1),
+            None => self.offset,
+        };
+        if offset == (0.0, 0.0) {
+            return None;
+        }
+        if !follow {
+            self.offset = offset;
+        }
+        Some(offset)
+    }
+
+    /// The frame of the view the drag moved to arrived.
+    pub fn frame_arrived(&mut self) {
+        self.offset = (0.0, 0.0);
+    }
+}
@@ -0,0 +1,171 @@
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use winit::event::VirtualKeyCode;
+
+use crate::action::Action;
+use crate::canvas::Theme;
+use crate::controls::ResetStage;
+use crate::mandelbrot::{check_iterations, check_scale, AutoIterations};
+
+/// Key binding added by the configuration file. It replaces the default
+/// binding of the same key.
+#[derive(Deserialize, Debug)]
+pub struct KeyBinding {
+    pub key: VirtualKeyCode,
+    pub action: Action,
+}
+
+/// Startup defaults. Every setting is optional:
+///
+/// ```toml
+/// width = 800
+/// height = 600
+/// x = -0.745
+/// y = 0.105
+/// scale = 0.00005
+/// iterations = 1024
+/// palette = "fire"
+/// threads = 4
+/// low_memory = true
+/// double_click_ms = 400
+/// reset = ["view", "aesthetics", "all"]
+/// unbind = ["Q"]
+///
+/// [hud]
+/// foreground = [255, 255, 255]
+/// background = [0, 0, 64]
+/// opacity = 0.5
+/// text_opacity = 0.8
+/// shadow = false
+/// corner = "bottom-left"
+///
+/// [gamepad]
+/// dead_zone = 0.15
+/// trigger_dead_zone = 0.05
+///
+/// [auto_iterations]
+/// base = 512
+/// per_decade = 128
+/// reference_scale = 0.005
+///
+/// [[bind]]
+/// key = "W"
+/// action = "zoom-in"
+///
+/// [[bind]]
+/// key = "Key0"
+/// action = { jump-bookmark = 9 }
+/// ```
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub scale: Option<f64>,
+    pub iterations: Option<usize>,
+    pub palette: Option<String>,
+    pub threads: Option<usize>,
+    /// None selects the low-memory mode from the available memory
+    pub low_memory: Option<bool>,
+    /// longest time between the clicks of a double click
+    pub double_click_ms: Option<u64>,
+    /// what the reset key does at each press in a row
+    pub reset: Option<Vec<ResetStage>>,
+    pub auto_iterations: Option<AutoIterations>,
+    #[serde(default)]
+    pub hud: Theme,
+    #[serde(default)]
+    pub gamepad: GamepadConfig,
+    #[serde(default)]
+    pub bind: Vec<KeyBinding>,
+    /// keys whose default binding is removed
+    #[serde(default)]
+    pub unbind: Vec<VirtualKeyCode>,
+}
+
+/// Dead zones of the game controllers: the share of the travel of the
+/// sticks and of the triggers that does nothing, so that a stick at rest
+/// does not drift the view.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadConfig {
+    pub dead_zone: f32,
+    pub trigger_dead_zone: f32,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.15,
+            trigger_dead_zone: 0.05,
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Config {
+    /// `~/.config/mandelbrot/config.toml` or the equivalent of the platform.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("mandelbrot").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let config: Self = toml::from_str(&contents).map_err(|e| invalid(&e.to_string()))?;
+        if config.width == Some(0) || config.height == Some(0) {
+            return Err(invalid("width and height must be positive"));
+        }
+        if config.double_click_ms == Some(0) {
+            return Err(invalid("double_click_ms must be positive"));
+        }
+        if config.reset.as_ref().is_some_and(Vec::is_empty) {
+            return Err(invalid("reset needs at least one stage"));
+        }
+        if config.threads == Some(0) {
+            return Err(invalid("threads must be positive"));
+        }
+        if let Some(iterations) = config.iterations {
+            check_iterations(iterations).map_err(|e| invalid(&e))?;
+        }
+        if let Some(scale) = config.scale {
+            check_scale(scale).map_err(|e| invalid(&e))?;
+        }
+        if config.auto_iterations.is_some_and(|formula| {
+            !(formula.reference_scale.is_finite() && formula.reference_scale > 0.0)
+        }) {
+            return Err(invalid("reference_scale must be positive"));
+        }
+        if !(0.0..=1.0).contains(&config.hud.opacity) {
+            return Err(invalid("hud opacity must be 0 to 1"));
+        }
+        if !(0.0..=1.0).contains(&config.hud.text_opacity) {
+            return Err(invalid("hud text_opacity must be 0 to 1"));
+        }
+        if !(0.0..1.0).contains(&config.gamepad.dead_zone)
+            || !(0.0..1.0).contains(&config.gamepad.trigger_dead_zone)
+        {
+            return Err(invalid("gamepad dead zones must be 0 to less than 1"));
+        }
+        Ok(config)
+    }
+
+    /// Read the file at the default path. A missing file is an empty
+    /// configuration.
+    pub fn load_default() -> io::Result<Self> {
+        let path = match Self::default_path() {
+            Some(path) => path,
+            None => return Ok(Self::default()),
+        };
+        match Self::load(&path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use log::{error, info};
+use serde::Deserialize;
+
+use crate::action::Action;
+use crate::bookmark::Bookmark;
+use crate::mandelbrot::{Antialias, Coloring, Interior, Mandelbrot};
+use crate::palette::{Bands, Blend, Palette};
+
+// frame pixels moved by the arrow keys
+const KEY_MOVE: f64 = 10.0;
+
+/// The actions `Controls::apply` handles.
+pub const ACTIONS: &[Action] = &[
+    Action::Reset,
+    Action::ZoomIn,
+    Action::ZoomOut,
+    Action::StopAutoZoom,
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::ToggleInfo,
+    Action::CycleAntialias,
+    Action::CyclePalette,
+    Action::CycleColoring,
+    Action::ToggleAutoExposure,
+    Action::CycleFractal,
+    Action::CycleNorm,
+    Action::CycleBands,
+    Action::ShiftBands,
+    Action::HalveIterations,
+    Action::DoubleIterations,
+    Action::ToggleAutoIterations,
+    Action::ToggleLegend,
+    Action::ToggleIterationHistogram,
+    Action::ToggleMinimap,
+    Action::TogglePeriodAnalysis,
+    Action::ToggleExternalRays,
+];
+
+/// What a press of the reset key does. Presses in a row go through the
+/// stages, and the first stage comes again after the last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResetStage {
+    /// back to the last bookmark jumped to when it is of the current
+    /// fractal, to the initial view otherwise
+    View,
+    /// the palette, colorings, bands, antialiasing, blending, auto exposure
+    /// and iteration limit the window started with
+    Aesthetics,
+    /// everything, as at the start (including the information display)
+    All,
+}
+
+pub const DEFAULT_RESET: &[ResetStage] =
+    &[ResetStage::View, ResetStage::Aesthetics, ResetStage::All];
+
+// the aesthetics the window started with, restored by the reset
+#[derive(Clone, Copy, Debug, Default)]
+struct Aesthetics {
+    palette_index: usize,
+    coloring: Coloring,
+    interior: Interior,
+    bands: Bands,
+    antialias: Antialias,
+    blend: Blend,
+    auto_exposure: bool,
+    /// None for the automatic iteration limit
+    iterations: Option<usize>,
+}
+
+/// What the keys do to the view and its options, shared by the window and
+/// the scripted sessions.
+pub struct Controls {
+    pub palettes: Vec<Palette>,
+    pub palette_index: usize,
+    /// zoom applied at every step while auto zooming, 0 otherwise
+    pub auto_zoom: f64,
+    pub reset_stages: Vec<ResetStage>,
+    // presses of the reset key in a row
+    reset_presses: usize,
+    /// the last bookmark jumped to, where the reset goes back first
+    pub bookmark: Option<Bookmark>,
+    start: Aesthetics,
+}
+
+impl Controls {
+    /// The preset palettes, starting at `palette` (a preset name or a
+    /// palette file) when given; a palette file joins the presets.
+    pub fn new(palette: Option<&str>) -> Self {
+        let mut palettes = Palette::presets();
+        let mut palette_index = 0;
+        if let Some(name) = palette {
+            match Palette::from_name_or_path(name) {
+                Ok(palette) => {
+                    palette_index = palettes
+                        .iter()
+                        .position(|preset| *preset == palette)
+                        .unwrap_or_else(|| {
+                            palettes.insert(0, palette);
+                            0
+                        });
+                }
+                Err(e) => error!("failed to load palette {}: {}", name, e),
+            }
+        }
+        Self {
+            palettes,
+            palette_index,
+            auto_zoom: 0.0,
+            reset_stages: DEFAULT_RESET.to_vec(),
+            reset_presses: 0,
+            bookmark: None,
+            start: Aesthetics::default(),
+        }
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palettes[self.palette_index]
+    }
+
+    /// Make `palette` the current one, in place of the palette of the same
+    /// name, or else after the others.
+    pub fn use_palette(&mut self, palette: Palette) {
+        match self
+            .palettes
+            .iter()
+            .position(|other| other.name == palette.name)
+        {
+            Some(index) => {
+                self.palettes[index] = palette;
+                self.palette_index = index;
+            }
+            None => {
+                self.palettes.push(palette);
+                self.palette_index = self.palettes.len() - 1;
+            }
+        }
+    }
+
+    /// Keep the options of `mandelbrot` as the ones the reset goes back to.
+    pub fn remember_start(&mut self, mandelbrot: &Mandelbrot) {
+        self.start = Aesthetics {
+            palette_index: self.palette_index,
+            coloring: mandelbrot.options.coloring,
+            interior: mandelbrot.options.interior,
+            bands: mandelbrot.options.bands,
+            antialias: mandelbrot.options.antialias,
+            blend: mandelbrot.options.blend,
+            auto_exposure: mandelbrot.auto_exposure(),
+            iterations: (!mandelbrot.auto_iterations).then_some(mandelbrot.max_round),
+        };
+    }
+
+    fn reset(&mut self, mandelbrot: &mut Mandelbrot) {
+        let Some(&stage) = self
+            .reset_stages
+            .get(self.reset_presses % self.reset_stages.len().max(1))
+        else {
+            return;
+        };
+        self.reset_presses += 1;
+        match stage {
+            ResetStage::View => match &self.bookmark {
+                Some(bookmark) if bookmark.fractal == mandelbrot.options.fractal => {
+                    info!("reset: back to {}", bookmark.name);
+                    if let Err(e) = bookmark.apply(mandelbrot) {
+                        error!("{}: {}", bookmark.name, e);
+                    }
+                }
+                _ => {
+                    info!("reset: back to the initial view");
+                    let home = mandelbrot.home;
+                    let max_round = mandelbrot.max_round_for(home.scale);
+                    mandelbrot.set_view(home.center_x, home.center_y, home.scale, max_round);
+                }
+            },
+            ResetStage::Aesthetics => {
+                info!("reset: aesthetics");
+                self.restore_aesthetics(mandelbrot);
+            }
+            ResetStage::All => {
+                info!("reset: everything");
+                mandelbrot.reset();
+                self.restore_aesthetics(mandelbrot);
+            }
+        }
+    }
+
+    fn restore_aesthetics(&mut self, mandelbrot: &mut Mandelbrot) {
+        let start = self.start;
+        self.palette_index = start.palette_index.min(self.palettes.len() - 1);
+        mandelbrot.options.palette = self.palette().clone();
+        mandelbrot.options.coloring = start.coloring;
+        mandelbrot.options.interior = start.interior;
+        mandelbrot.options.bands = start.bands;
+        mandelbrot.options.antialias = start.antialias;
+        mandelbrot.options.blend = start.blend;
+        mandelbrot.set_auto_exposure(start.auto_exposure);
+        match start.iterations {
+            Some(iterations) => mandelbrot.set_max_round(iterations),
+            None => {
+                mandelbrot.auto_iterations = true;
+                mandelbrot.max_round = mandelbrot.max_round_for(mandelbrot.scale);
+            }
+        }
+    }
+
+    /// Apply the actions that change only the view and its options. The
+    /// zoom keys take small steps with `shift` and start the auto zoom with
+    /// `alt`.
+    pub fn apply(
+        &mut self,
+        mandelbrot: &mut Mandelbrot,
+        actions: &[Action],
+        shift: bool,
+        alt: bool,
+    ) {
+        if actions.contains(&Action::Reset) {
+            self.auto_zoom = 0.0;
+            self.reset(mandelbrot);
+            mandelbrot.request_redraw();
+        } else if !actions.is_empty() {
+            // anything else in between starts the stages over
+            self.reset_presses = 0;
+        }
+
+        let zoom_param = |direction: f64| {
+            if alt {
+                (0.4 * direction, true)
+            } else if self.auto_zoom != 0.0 {
+                (0.0, true)
+            } else if shift {
+                (0.1 * direction, false)
+            } else {
+                (3.0 * direction, false)
+            }
+        };
+        let (zoom_param, auto_zoom_update) = if actions.contains(&Action::ZoomIn) {
+            zoom_param(1.0)
+        } else if actions.contains(&Action::ZoomOut) {
+            zoom_param(-1.0)
+        } else {
+            (self.auto_zoom, false)
+        };
+        if zoom_param != 0.0 {
+            if !mandelbrot.zoom(zoom_param) {
+                self.auto_zoom = 0.0;
+            }
+            mandelbrot.request_redraw();
+        }
+        if actions.contains(&Action::StopAutoZoom) {
+            self.auto_zoom = 0.0;
+        } else if auto_zoom_update {
+            self.auto_zoom = zoom_param;
+        }
+
+        // in frame pixels, the same on every monitor
+        let (key_move, move_x, move_y) = if actions.contains(&Action::MoveUp) {
+            (true, 0.0, KEY_MOVE)
+        } else if actions.contains(&Action::MoveDown) {
+            (true, 0.0, -KEY_MOVE)
+        } else if actions.contains(&Action::MoveLeft) {
+            (true, -KEY_MOVE, 0.0)
+        } else if actions.contains(&Action::MoveRight) {
+            (true, KEY_MOVE, 0.0)
+        } else {
+            (false, 0.0, 0.0)
+        };
+        if key_move {
+            mandelbrot.move_center(move_x, move_y);
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::ToggleInfo) {
+            mandelbrot.info = !mandelbrot.info;
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::CycleAntialias) {
+            mandelbrot.options.antialias = mandelbrot.options.antialias.next();
+            info!("antialias: {}", mandelbrot.options.antialias.name());
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::CyclePalette) {
+            self.palette_index = (self.palette_index + 1) % self.palettes.len();
+            mandelbrot.options.palette = self.palette().clone();
+            info!("palette: {}", mandelbrot.options.palette.name);
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::CycleColoring) {
+            if shift {
+                mandelbrot.options.interior = mandelbrot.options.interior.next();
+                info!("interior: {}", mandelbrot.options.interior.name());
+            } else {
+                mandelbrot.options.coloring = mandelbrot.options.coloring.next();
+                info!("coloring: {}", mandelbrot.options.coloring.name());
+            }
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::ToggleAutoExposure) {
+            mandelbrot.set_auto_exposure(!mandelbrot.auto_exposure());
+            info!(
+                "auto exposure: {}",
+                if mandelbrot.auto_exposure() {
+                    "on"
+                } else {
+                    "off"
+                }
+            );
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::CycleFractal) {
+            mandelbrot.options.fractal = mandelbrot.options.fractal.next();
+            info!("fractal: {}", mandelbrot.options.fractal.name());
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::CycleNorm) {
+            mandelbrot.options.norm = mandelbrot.options.norm.next();
+            info!("norm: {}", mandelbrot.options.norm.name());
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::CycleBands) {
+            mandelbrot.options.bands = mandelbrot.options.bands.next_count();
+            info!("bands: {}", mandelbrot.options.bands.count);
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::ShiftBands) {
+            let period = mandelbrot.options.palette.period();
+            mandelbrot.options.bands = mandelbrot.options.bands.shift(period);
+            info!("band offset: {}", mandelbrot.options.bands.offset);
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::HalveIterations) {
+            mandelbrot.scale_max_round(0.5);
+        }
+        if actions.contains(&Action::DoubleIterations) {
+            mandelbrot.scale_max_round(2.0);
+        }
+        if actions.contains(&Action::ToggleAutoIterations) {
+            mandelbrot.toggle_auto_iterations();
+        }
+
+        if actions.contains(&Action::ToggleLegend) {
+            mandelbrot.legend = !mandelbrot.legend;
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::ToggleIterationHistogram) {
+            mandelbrot.toggle_iteration_histogram();
+            mandelbrot.request_redraw();
+        }
+
+        if actions.contains(&Action::ToggleMinimap) {
+            mandelbrot.toggle_minimap();
+        }
+
+        if actions.contains(&Action::TogglePeriodAnalysis) {
+            mandelbrot.set_period_analysis(!mandelbrot.period_analysis());
+            info!("period analysis: {}", mandelbrot.period_analysis());
+        }
+        if actions.contains(&Action::ToggleExternalRays) {
+            mandelbrot.set_external_rays(!mandelbrot.external_rays());
+            info!("external rays: {}", mandelbrot.external_rays());
+        }
+    }
+}
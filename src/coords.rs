@@ -0,0 +1,44 @@
+use pixels::Pixels;
+use winit::dpi::PhysicalPosition;
+use winit::window::Window;
+use winit_input_helper::WinitInputHelper;
+
+// Window input comes in physical pixels of the window surface, which is a
+// multiple of the logical size on HiDPI monitors and changes with resizes
+// and moves between monitors. The view works in pixels of the frame, which
+// keeps its size and is drawn scaled and centered in the surface by
+// `pixels`. Every position and distance given to the view goes through
+// here first.
+
+/// Frame pixel under the pointer, clamped to the frame when the pointer is
+/// over the border around it.
+pub fn pointer(pixels: &Pixels, input: &WinitInputHelper) -> Option<(f64, f64)> {
+    let position = input.mouse()?;
+    let (x, y) = pixels
+        .window_pos_to_pixel(position)
+        .unwrap_or_else(|position| pixels.clamp_pixel_pos(position));
+    Some((x as f64, y as f64))
+}
+
+/// Frame pixel under a finger on a touch screen, clamped to the frame like
+/// the pointer.
+pub fn touch(pixels: &Pixels, location: PhysicalPosition<f64>) -> (f64, f64) {
+    let (x, y) = pixels
+        .window_pos_to_pixel((location.x as f32, location.y as f32))
+        .unwrap_or_else(|position| pixels.clamp_pixel_pos(position));
+    (x as f64, y as f64)
+}
+
+/// Keep the surface at the physical size of the window. A move to a
+/// monitor with another scale factor changes that size without a resize
+/// event on some platforms, so the window is asked for its size then too.
+pub fn fit_surface(pixels: &mut Pixels, window: &Window, input: &WinitInputHelper) {
+    let size = match (input.window_resized(), input.scale_factor_changed()) {
+        (Some(size), _) => size,
+        (None, Some(_)) => window.inner_size(),
+        (None, None) => return,
+    };
+    if size.width > 0 && size.height > 0 {
+        pixels.resize_surface(size.width, size.height);
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use crate::canvas::{Canvas, LINE_HEIGHT};
+use crate::tile::{Tile, TileStats};
+
+const MARGIN: usize = 5;
+// weight of the heat color over the image
+const HEAT_ALPHA: f64 = 0.6;
+// tiles narrower than this get no label
+const LABEL_MIN_WIDTH: usize = 40;
+
+/// Rendering work summed over every frame of the session.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SessionCost {
+    pub frames: usize,
+    pub tiles: usize,
+    pub escaped: usize,
+    pub interior: usize,
+    /// CPU time spent in tiles, summed over the threads
+    pub tile_time: Duration,
+}
+
+impl SessionCost {
+    pub fn add_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    pub fn add_tile(&mut self, stats: &TileStats) {
+        self.tiles += 1;
+        self.escaped += stats.escaped;
+        self.interior += stats.interior;
+        self.tile_time += stats.time;
+    }
+
+    pub fn message(&self) -> String {
+        let pixels = self.escaped + self.interior;
+        format!(
+            "session: {} frames, {:.1} Mpixels ({:.0}% interior), {:.2} s in tiles",
+            self.frames,
+            pixels as f64 / 1e6,
+            100.0 * self.interior as f64 / pixels.max(1) as f64,
+            self.tile_time.as_secs_f64()
+        )
+    }
+}
+
+// black, red, yellow, white as `t` goes from 0 to 1
+fn heat_color(t: f64) -> [f64; 3] {
+    let ramp = |offset: f64| (3.0 * t - offset).clamp(0.0, 1.0) * 255.0;
+    [ramp(0.0), ramp(1.0), ramp(2.0)]
+}
+
+/// Tint every tile by its rendering time relative to the slowest tile and
+/// label it with the time in milliseconds.
+pub fn draw_map(canvas: &mut Canvas, tiles: &[Tile], stats: &[TileStats]) {
+    let max_time = stats
+        .iter()
+        .map(|stats| stats.time)
+        .max()
+        .unwrap_or_default()
+        .max(Duration::from_nanos(1));
+    for (tile, stats) in tiles.iter().zip(stats) {
+        let t = stats.time.as_secs_f64() / max_time.as_secs_f64();
+        canvas.blend_rect(
+            tile.x,
+            tile.y,
+            tile.width,
+            tile.height,
+            heat_color(t),
+            HEAT_ALPHA,
+        );
+        if tile.width >= LABEL_MIN_WIDTH && tile.height >= LINE_HEIGHT {
+            let label = format!("{:.1}", stats.time.as_secs_f64() * 1e3);
+            canvas.draw_text(tile.x + 2, tile.y + 2, &label, 1);
+        }
+    }
+
+    let legend = format!("tile cost: 0 to {:.1} ms", max_time.as_secs_f64() * 1e3);
+    let y = canvas.height.saturating_sub(MARGIN + LINE_HEIGHT);
+    canvas.draw_text(MARGIN, y, &legend, 1);
+}
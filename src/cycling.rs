@@ -0,0 +1,63 @@
+use crate::gpu::CYCLE_STEP;
+use crate::palette::{Bands, Blend, Palette};
+use crate::parallel::*;
+
+/// Palette cycling on the CPU, for when the GPU coloring is off: the frame
+/// rendered keeps the escape iteration of every pixel instead of its color
+/// (see `Mandelbrot::set_color_cycling`), and every frame shown is colored
+/// from it with the palette advanced a little further, without iterating
+/// again.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ColorCycling {
+    pub offset: usize,
+}
+
+impl ColorCycling {
+    /// Color the pixels of `frame` that hold escape iterations (alpha 0),
+    /// leaving the overlays drawn over them, and advance the palette.
+    pub fn colorize(&mut self, frame: &mut [u8], palette: &Palette, bands: Bands, blend: Blend) {
+        let colors = IterationColors::new(palette, bands, blend);
+        let offset = self.offset;
+        install(|| {
+            frame.par_chunks_exact_mut(4).for_each(|pixel| {
+                if pixel[3] == 0x00 {
+                    let rgba = colors.color(pixel, offset);
+                    pixel.copy_from_slice(&rgba);
+                }
+            })
+        });
+        self.offset = (self.offset + CYCLE_STEP) % colors.period();
+    }
+}
+
+/// One palette cycle, looked up by the escape iterations that frames keep
+/// in their pixels (alpha 0) instead of interpolated per pixel.
+#[derive(Clone, Debug)]
+pub struct IterationColors {
+    colors: Vec<[u8; 4]>,
+}
+
+impl IterationColors {
+    pub fn new(palette: &Palette, bands: Bands, blend: Blend) -> Self {
+        let period = palette.period().max(1);
+        Self {
+            colors: (0..period)
+                .map(|round| palette.color(bands.quantize(round, period), blend))
+                .collect(),
+        }
+    }
+
+    pub fn period(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// Color of the escape iteration kept in `pixel` with the palette
+    /// advanced by `offset`.
+    pub fn color(&self, pixel: &[u8], offset: usize) -> [u8; 4] {
+        match u32::from_le_bytes([pixel[0], pixel[1], pixel[2], 0]) as usize {
+            // inside the set
+            0 => [0x00, 0x00, 0x00, 0xff],
+            rounds => self.colors[(rounds + offset) % self.colors.len()],
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use clap::ValueEnum;
+use log::info;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::export;
+use crate::info_card::InfoCard;
+use crate::mandelbrot::{Antialias, Fractal, Mandelbrot, RenderOptions, View};
+use crate::palette::Palette;
+use crate::platform::{SystemTime, UNIX_EPOCH};
+use crate::random::Random;
+
+// wallpaper size unless --width and --height are given
+pub const WIDTH: u32 = 1920;
+pub const HEIGHT: u32 = 1080;
+// views tried before the most detailed one is rendered
+const CANDIDATES: usize = 32;
+const THUMBNAIL_WIDTH: u32 = 64;
+const THUMBNAIL_HEIGHT: u32 = 36;
+// the starting points of the info cards are framed for this window
+const CARD_WIDTH: f64 = 640.0;
+const CARD_HEIGHT: f64 = 480.0;
+// zoom below the starting point, up to this many decades
+const MAX_DEPTH: f64 = 3.0;
+
+// Days from 1970-01-01 to the given date of the proleptic Gregorian
+// calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// Inverse of days_from_civil.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Day number of a YYYY-MM-DD date, for `--date`.
+pub fn parse_date(value: &str) -> Result<i64, String> {
+    let invalid = || format!("not a YYYY-MM-DD date: {}", value);
+    let fields: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = fields[..] else {
+        return Err(invalid());
+    };
+    let year: i64 = year.parse().map_err(|_| invalid())?;
+    let month: u32 = month.parse().map_err(|_| invalid())?;
+    let day: u32 = day.parse().map_err(|_| invalid())?;
+    let days = days_from_civil(year, month, day);
+    if civil_from_days(days) != (year, month, day) {
+        return Err(invalid());
+    }
+    Ok(days)
+}
+
+/// Day number of the current date in UTC.
+pub fn today() -> i64 {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    (seconds / 86_400) as i64
+}
+
+fn date_name(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Number of distinct colors in a thumbnail of `view`, which is low for
+// views inside the set or far from it.
+fn detail(view: View, width: u32, options: &RenderOptions) -> usize {
+    let mut mandelbrot = Mandelbrot::new(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT);
+    mandelbrot.center_x = view.center_x;
+    mandelbrot.center_y = view.center_y;
+    mandelbrot.scale = view.scale * width as f64 / THUMBNAIL_WIDTH as f64;
+    mandelbrot.max_round = Mandelbrot::auto_max_round(view.scale);
+    mandelbrot.options = options.clone();
+    mandelbrot.info = false;
+    let mut frame = vec![0_u8; 4 * (THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT) as usize];
+    mandelbrot.draw(&mut frame);
+    frame
+        .chunks_exact(4)
+        .map(|rgba| [rgba[0], rgba[1], rgba[2]])
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+/// Render the fractal of the day `days` to `output`: the most detailed of
+/// a few random views around the starting points of the info cards, in a
+/// random preset palette. The same day always gives the same image.
+pub fn render(
+    days: i64,
+    width: u32,
+    height: u32,
+    low_memory: bool,
+    output: &Path,
+) -> Result<(), png::EncodingError> {
+    let mut random = Random::new(days as u64);
+    let starts: Vec<(Fractal, View, String)> = Fractal::value_variants()
+        .iter()
+        .flat_map(|fractal| {
+            InfoCard::builtin(*fractal)
+                .start
+                .into_iter()
+                .map(move |start| (*fractal, start.view(), start.name))
+        })
+        .collect();
+    let mut palettes = Palette::presets();
+    let palette = palettes.swap_remove(random.index(palettes.len()));
+
+    let mut best: Option<(usize, Fractal, View, &str)> = None;
+    for _ in 0..CANDIDATES {
+        let (fractal, start, name) = &starts[random.index(starts.len())];
+        let depth = (random.next() + 1.0) / 2.0 * MAX_DEPTH;
+        let view = View {
+            center_x: start.center_x + random.next() * start.scale * CARD_WIDTH / 2.0,
+            center_y: start.center_y + random.next() * start.scale * CARD_HEIGHT / 2.0,
+            scale: start.scale * CARD_WIDTH / width as f64 / 10_f64.powf(depth),
+        };
+        let options = RenderOptions {
+            fractal: *fractal,
+            palette: palette.clone(),
+            ..RenderOptions::default()
+        };
+        let detail = detail(view, width, &options);
+        if best.as_ref().is_none_or(|(most, ..)| detail > *most) {
+            best = Some((detail, *fractal, view, name));
+        }
+    }
+    // the info cards always have starting points
+    let (_, fractal, view, name) = best.unwrap();
+    info!(
+        "fractal of {}: {} near {} at ({}, {}), scale {}, palette {}",
+        date_name(days),
+        fractal.name(),
+        name,
+        view.center_x,
+        view.center_y,
+        view.scale,
+        palette.name
+    );
+
+    let mut mandelbrot = Mandelbrot::new(width, height);
+    mandelbrot.center_x = view.center_x;
+    mandelbrot.center_y = view.center_y;
+    mandelbrot.scale = view.scale;
+    mandelbrot.max_round = Mandelbrot::auto_max_round(view.scale);
+    mandelbrot.options = RenderOptions {
+        fractal,
+        antialias: Antialias::Adaptive,
+        palette,
+        ..RenderOptions::default()
+    };
+    mandelbrot.info = false;
+    mandelbrot.low_memory = low_memory;
+    let mut frame = vec![0_u8; 4 * width as usize * height as usize];
+    mandelbrot.draw(&mut frame);
+    info!("{}", mandelbrot.rendering_time_message());
+    export::save_png(output, width, height, &frame)
+}
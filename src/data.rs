@@ -0,0 +1,95 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::mandelbrot::Mandelbrot;
+
+// first bytes of the raw format
+const RAW_MAGIC: &[u8; 8] = b"MBITER01";
+
+/// File format of the escape iterations of a view.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DataFormat {
+    /// one line of comma-separated values per row of pixels
+    Csv,
+    /// NumPy array of unsigned 32-bit integers, of shape (height, width)
+    #[default]
+    Npy,
+    /// a 48-byte header followed by little-endian unsigned 32-bit integers,
+    /// row by row (see the README)
+    Raw,
+}
+
+impl DataFormat {
+    /// File the window exports the escape iterations to in the format.
+    pub fn file(self) -> &'static str {
+        match self {
+            Self::Csv => "iterations.csv",
+            Self::Npy => "iterations.npy",
+            Self::Raw => "iterations.raw",
+        }
+    }
+}
+
+/// Write the escape iteration of every pixel of the view of `mandelbrot`,
+/// row by row from the top left, 0 for the points of the set.
+pub fn save(mandelbrot: &Mandelbrot, format: DataFormat, path: &Path) -> io::Result<()> {
+    let iterations = mandelbrot.escape_iterations();
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer, mandelbrot, format, &iterations)?;
+    writer.flush()
+}
+
+fn write<W: Write>(
+    writer: &mut W,
+    mandelbrot: &Mandelbrot,
+    format: DataFormat,
+    iterations: &[u32],
+) -> io::Result<()> {
+    let width = mandelbrot.width as usize;
+    match format {
+        DataFormat::Csv => {
+            for row in iterations.chunks_exact(width) {
+                let line: Vec<String> = row.iter().map(u32::to_string).collect();
+                writeln!(writer, "{}", line.join(","))?;
+            }
+        }
+        DataFormat::Npy => {
+            let mut header = format!(
+                "{{'descr': '<u4', 'fortran_order': False, 'shape': ({}, {}), }}",
+                mandelbrot.height, mandelbrot.width
+            );
+            // magic, version and length take 10 bytes, and the whole header
+            // is padded with spaces to a multiple of 64 ending in a newline
+            let length = (10 + header.len() + 1).div_ceil(64) * 64 - 10;
+            while header.len() < length - 1 {
+                header.push(' ');
+            }
+            header.push('\n');
+            writer.write_all(b"\x93NUMPY\x01\x00")?;
+            writer.write_all(&(length as u16).to_le_bytes())?;
+            writer.write_all(header.as_bytes())?;
+            write_values(writer, iterations)?;
+        }
+        DataFormat::Raw => {
+            writer.write_all(RAW_MAGIC)?;
+            writer.write_all(&mandelbrot.width.to_le_bytes())?;
+            writer.write_all(&mandelbrot.height.to_le_bytes())?;
+            writer
+                .write_all(&(mandelbrot.max_round.min(u32::MAX as usize) as u32).to_le_bytes())?;
+            writer.write_all(&[0; 4])?;
+            writer.write_all(&mandelbrot.center_x.to_le_bytes())?;
+            writer.write_all(&mandelbrot.center_y.to_le_bytes())?;
+            writer.write_all(&mandelbrot.scale.to_le_bytes())?;
+            write_values(writer, iterations)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_values<W: Write>(writer: &mut W, iterations: &[u32]) -> io::Result<()> {
+    for value in iterations {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
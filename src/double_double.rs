@@ -0,0 +1,257 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+// 2^27 + 1, which splits an f64 into two halves of 26 bits
+const SPLITTER: f64 = 134_217_729.0;
+// decimal digits read at a time by parse(), exact in an f64
+const DIGIT_CHUNK: usize = 15;
+// largest power of ten exact in an f64
+const MAX_EXACT_POWER: i32 = 22;
+
+/// A number as the unevaluated sum of two f64, `hi + lo` with `lo` at most
+/// half an ulp of `hi`: about 106 bits of mantissa, twice those of f64, at
+/// the cost of a dozen f64 operations per multiplication. The view uses it
+/// where f64 coordinates no longer tell the pixels apart.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+// a + b exactly, as the rounded sum and its error
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_part = sum - a;
+    (sum, (a - (sum - b_part)) + (b - b_part))
+}
+
+// two_sum() when |a| >= |b|
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    (sum, b - (sum - a))
+}
+
+fn split(a: f64) -> (f64, f64) {
+    let t = SPLITTER * a;
+    let hi = t - (t - a);
+    (hi, a - hi)
+}
+
+// a * b exactly, as the rounded product and its error (Dekker), without
+// relying on a fused multiply-add the CPU may not have
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let product = a * b;
+    let (a_hi, a_lo) = split(a);
+    let (b_hi, b_lo) = split(b);
+    let error = ((a_hi * b_hi - product) + a_hi * b_lo + a_lo * b_hi) + a_lo * b_lo;
+    (product, error)
+}
+
+impl DoubleDouble {
+    pub const ZERO: Self = Self { hi: 0.0, lo: 0.0 };
+
+    pub fn abs(self) -> Self {
+        if self.hi < 0.0 {
+            -self
+        } else {
+            self
+        }
+    }
+
+    pub fn square(self) -> Self {
+        let (product, error) = two_prod(self.hi, self.hi);
+        let error = error + 2.0 * self.hi * self.lo;
+        let (hi, lo) = quick_two_sum(product, error);
+        Self { hi, lo }
+    }
+
+    /// Read a decimal number such as `-0.7436438870371587047521915` or
+    /// `2.5e-21` to the precision of double-double, where parsing it as an
+    /// f64 would keep 17 digits; None for a number beyond the range of f64.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (mantissa, exponent) = match text.find(['e', 'E']) {
+            Some(index) => (&text[..index], text[index + 1..].parse::<i32>().ok()?),
+            None => (text, 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = whole.as_bytes().iter().chain(fraction.as_bytes());
+        if whole.len() + fraction.len() == 0 || !digits.clone().all(u8::is_ascii_digit) {
+            return None;
+        }
+
+        let digits: Vec<f64> = digits.map(|digit| (digit - b'0') as f64).collect();
+        let mut value = Self::ZERO;
+        for chunk in digits.chunks(DIGIT_CHUNK) {
+            let chunk_value = chunk.iter().fold(0.0, |value, digit| value * 10.0 + digit);
+            value = value * 10_f64.powi(chunk.len() as i32) + chunk_value;
+        }
+        let mut exponent = exponent.checked_sub(fraction.len() as i32)?;
+        // the value reaches 0 or infinity long before a huge exponent is used up
+        while exponent != 0 && value.hi != 0.0 && value.hi.is_finite() {
+            let step = exponent.clamp(-MAX_EXACT_POWER, MAX_EXACT_POWER);
+            let power = 10_f64.powi(step.abs());
+            value = if step > 0 {
+                value * power
+            } else {
+                value / power
+            };
+            exponent -= step;
+        }
+        if !(value.hi.is_finite() && value.lo.is_finite()) {
+            return None;
+        }
+        Some(if negative { -value } else { value })
+    }
+}
+
+/// Whether the low part `lo` of a saved number is zero, so that files keep
+/// the plain f64 of the points it holds exactly.
+pub fn is_exact(lo: &f64) -> bool {
+    *lo == 0.0
+}
+
+impl From<f64> for DoubleDouble {
+    fn from(value: f64) -> Self {
+        Self { hi: value, lo: 0.0 }
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let (sum, error) = two_sum(self.hi, other.hi);
+        let (low_sum, low_error) = two_sum(self.lo, other.lo);
+        let (sum, error) = quick_two_sum(sum, error + low_sum);
+        let (hi, lo) = quick_two_sum(sum, error + low_error);
+        Self { hi, lo }
+    }
+}
+
+impl Add<f64> for DoubleDouble {
+    type Output = Self;
+
+    fn add(self, other: f64) -> Self {
+        let (sum, error) = two_sum(self.hi, other);
+        let (hi, lo) = quick_two_sum(sum, error + self.lo);
+        Self { hi, lo }
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let (product, error) = two_prod(self.hi, other.hi);
+        let error = error + (self.hi * other.lo + self.lo * other.hi);
+        let (hi, lo) = quick_two_sum(product, error);
+        Self { hi, lo }
+    }
+}
+
+impl Mul<f64> for DoubleDouble {
+    type Output = Self;
+
+    fn mul(self, other: f64) -> Self {
+        let (product, error) = two_prod(self.hi, other);
+        let (hi, lo) = quick_two_sum(product, error + self.lo * other);
+        Self { hi, lo }
+    }
+}
+
+impl Div<f64> for DoubleDouble {
+    type Output = Self;
+
+    // the quotient of the high part, corrected by that of the remainder
+    fn div(self, other: f64) -> Self {
+        let quotient = self.hi / other;
+        let (product, error) = two_prod(quotient, other);
+        let remainder = (self.hi - product - error) + self.lo;
+        let (hi, lo) = quick_two_sum(quotient, remainder / other);
+        Self { hi, lo }
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2^-n, exact in an f64
+    fn tiny(n: i32) -> f64 {
+        2_f64.powi(-n)
+    }
+
+    #[test]
+    fn error_free_transformations_keep_the_rounding_error() {
+        assert_eq!(two_sum(1.0, tiny(60)), (1.0, tiny(60)));
+        assert_eq!(quick_two_sum(1.0, tiny(60)), (1.0, tiny(60)));
+        let a = 1.0 + tiny(30);
+        assert_eq!(two_prod(a, a), (1.0 + tiny(29), tiny(60)));
+        assert_eq!(split(a), (1.0, tiny(30)));
+    }
+
+    #[test]
+    fn arithmetic_keeps_what_f64_loses() {
+        let one = DoubleDouble::from(1.0);
+        let small = DoubleDouble::from(tiny(70));
+        assert_eq!((one + small) - one, small);
+        assert_eq!(one + tiny(70) - one, small);
+        assert_eq!((one + small) * (one + small) - one, small * 2.0);
+        assert_eq!((one + small).square(), (one + small) * (one + small));
+        assert_eq!(-(one + small), DoubleDouble::from(-1.0) - small);
+        assert_eq!((-(one + small)).abs(), one + small);
+    }
+
+    #[test]
+    fn division_is_exact_to_double_double() {
+        let third = DoubleDouble::from(1.0) / 3.0;
+        assert_eq!(third.hi, 1.0 / 3.0);
+        assert!((third * 3.0 - DoubleDouble::from(1.0)).hi.abs() < 1e-31);
+    }
+
+    #[test]
+    fn parse_reads_more_digits_than_f64() {
+        let tenth = DoubleDouble::parse("0.1").unwrap();
+        assert_eq!(tenth.hi, 0.1);
+        assert!((tenth * 10.0 - DoubleDouble::from(1.0)).hi.abs() < 1e-31);
+        assert!(
+            (DoubleDouble::from(0.1) * 10.0 - DoubleDouble::from(1.0))
+                .hi
+                .abs()
+                > 1e-18
+        );
+
+        let x = DoubleDouble::parse("-0.7436438870371587047521915").unwrap();
+        assert_eq!(x.hi, -0.7436438870371587);
+        assert_ne!(x.lo, 0.0);
+        assert_eq!(DoubleDouble::parse("+2.5e-21").unwrap().hi, 2.5e-21);
+        assert_eq!(DoubleDouble::parse(" 3 ").unwrap(), DoubleDouble::from(3.0));
+        assert_eq!(DoubleDouble::parse("1e-400").unwrap().hi, 0.0);
+        for text in ["", "-", ".", "1e", "1.5x", "0x10", "1e400"] {
+            assert_eq!(DoubleDouble::parse(text), None, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::canvas::Canvas;
+use crate::double_double::DoubleDouble;
+use crate::mandelbrot::{Fractal, Mandelbrot};
+use crate::orbit::{OrbitStats, PANEL_COLUMNS};
+
+// Parts of z^2 + c whose membership has a closed form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Component {
+    MainCardioid,
+    Period2Bulb,
+}
+
+impl Component {
+    fn of(x: f64, y: f64) -> Option<Self> {
+        let q = (x - 0.25) * (x - 0.25) + y * y;
+        if q * (q + (x - 0.25)) <= y * y / 4.0 {
+            Some(Self::MainCardioid)
+        } else if (x + 1.0) * (x + 1.0) + y * y <= 1.0 / 16.0 {
+            Some(Self::Period2Bulb)
+        } else {
+            None
+        }
+    }
+}
+
+// Split `text` into lines of at most `columns` characters at spaces.
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > columns {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// What is known about a point, in sentences for learners, with its orbit.
+pub struct Explanation {
+    stats: OrbitStats,
+    paragraphs: Vec<String>,
+}
+
+impl Explanation {
+    /// Explain the point (x, y) of the view of `mandelbrot`.
+    pub fn new(mandelbrot: &Mandelbrot, x: DoubleDouble, y: DoubleDouble) -> Self {
+        let stats = OrbitStats::compute(mandelbrot, x, y);
+        let (x, y) = (x.hi, y.hi);
+        let options = &mandelbrot.options;
+        let (max_round, pixel_size) = (mandelbrot.max_round, mandelbrot.scale);
+        // the closed forms are those of z^2 + c from z = 0
+        let quadratic = options.fractal == Fractal::Mandelbrot && mandelbrot.is_quadratic();
+        let mut paragraphs = vec![format!(
+            "Formula: {}, d = {}.",
+            options.fractal.name(),
+            mandelbrot.degree()
+        )];
+
+        if mandelbrot.is_newton() {
+            paragraphs.push(match (stats.escape, stats.root) {
+                (Some(round), Some(root)) => format!(
+                    "Newton's method on z^d - 1 reaches root {} after {} \
+                     iterations; the root picks the color and the iterations \
+                     the shade.",
+                    root, round
+                ),
+                _ => format!(
+                    "Newton's method on z^d - 1 does not settle on a root \
+                     within {} iterations: the point is on a boundary between \
+                     the basins, or a higher limit may decide it.",
+                    max_round
+                ),
+            });
+        } else {
+            match (
+                quadratic.then(|| Component::of(x, y)).flatten(),
+                stats.escape,
+            ) {
+                (Some(Component::MainCardioid), _) => paragraphs.push(String::from(
+                    "Inside the set, in the main cardioid: z settles on a single \
+                     fixed point (period 1).",
+                )),
+                (Some(Component::Period2Bulb), _) => paragraphs.push(String::from(
+                    "Inside the set, in the period-2 bulb left of the cardioid: z \
+                     ends up alternating between two points.",
+                )),
+                (None, Some(round)) => {
+                    paragraphs.push(format!(
+                        "Outside the set: |z| passed the bailout after {} iterations, \
+                         so this pixel gets the color of {}.",
+                        round, round
+                    ));
+                    if let Some(distance) = stats.distance {
+                        paragraphs.push(format!(
+                            "The set is about {:.3e} away, {:.1} pixels at this zoom.",
+                            distance,
+                            distance / pixel_size
+                        ));
+                    }
+                }
+                (None, None) => match stats.period {
+                    Some(period) => {
+                        paragraphs.push(format!(
+                            "Inside the set: the orbit falls into a cycle of period {}.",
+                            period
+                        ));
+                        if quadratic {
+                            paragraphs.push(format!(
+                                "The point lies in a component of period {}: a bulb \
+                                 or a small copy of the whole set.",
+                                period
+                            ));
+                        }
+                    }
+                    None => paragraphs.push(format!(
+                        "No escape within {} iterations and no cycle found: the point \
+                         is inside or too close to the boundary to tell. A higher \
+                         iteration limit may decide it.",
+                        max_round
+                    )),
+                },
+            }
+        }
+        paragraphs.push(format!(
+            "|z| stayed between {:.4} and {:.4}; the orbit is plotted below.",
+            stats.min_abs, stats.max_abs
+        ));
+
+        Self { stats, paragraphs }
+    }
+
+    fn lines(&self, close_key: &str) -> Vec<String> {
+        let mut lines = vec![
+            String::from("about the point"),
+            format!(" x: {:.12}", self.stats.x),
+            format!(" y: {:.12}", self.stats.y),
+        ];
+        for paragraph in &self.paragraphs {
+            lines.push(String::new());
+            lines.extend(wrap(paragraph, PANEL_COLUMNS));
+        }
+        lines.push(String::new());
+        lines.push(format!("({} to close)", close_key));
+        lines
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, close_key: &str) {
+        self.stats.draw_panel(canvas, &self.lines(close_key));
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// PNG text keyword of the notes attached to images
+pub const NOTE_KEYWORD: &str = "Comment";
+
+pub fn write_png<W: Write>(
+    writer: W,
+    width: u32,
+    height: u32,
+    frame: &[u8],
+) -> Result<(), png::EncodingError> {
+    write_png_with_note(writer, width, height, frame, "")
+}
+
+/// Like `write_png`, with `note` in a text chunk unless it is empty.
+pub fn write_png_with_note<W: Write>(
+    writer: W,
+    width: u32,
+    height: u32,
+    frame: &[u8],
+    note: &str,
+) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if !note.is_empty() {
+        encoder.add_itxt_chunk(NOTE_KEYWORD.to_string(), note.to_string())?;
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(frame)?;
+    Ok(())
+}
+
+pub fn save_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    frame: &[u8],
+) -> Result<(), png::EncodingError> {
+    save_png_with_note(path, width, height, frame, "")
+}
+
+pub fn save_png_with_note(
+    path: &Path,
+    width: u32,
+    height: u32,
+    frame: &[u8],
+    note: &str,
+) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+    write_png_with_note(BufWriter::new(file), width, height, frame, note)
+}
+
+/// A PNG file at `path` written row by row, for images too large to hold
+/// in memory: write the RGBA rows in order, then `finish` it.
+pub fn png_stream(
+    path: &Path,
+    width: u32,
+    height: u32,
+    note: &str,
+) -> Result<png::StreamWriter<'static, BufWriter<File>>, png::EncodingError> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if !note.is_empty() {
+        encoder.add_itxt_chunk(NOTE_KEYWORD.to_string(), note.to_string())?;
+    }
+    encoder.write_header()?.into_stream_writer()
+}
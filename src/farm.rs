@@ -0,0 +1,295 @@
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::animation::{self, AnimationJob};
+use crate::double_double::DoubleDouble;
+use crate::export;
+use crate::mandelbrot::{Mandelbrot, RenderOptions, View};
+
+// rows of the strips a single image is split into
+const STRIP_HEIGHT: u32 = 64;
+// bytes of the longest piece a worker takes, far more than the options of
+// any view need
+const MAX_PIECE_BYTES: usize = 1 << 20;
+
+/// A part of the work the coordinator hands to a worker: a frame of an
+/// animation, or a strip of rows of a large image rendered like the strips
+/// of a poster, with the samples of the whole image.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Piece {
+    number: usize,
+    width: u32,
+    height: u32,
+    // rows of the image the piece covers
+    top: u32,
+    rows: u32,
+    max_round: usize,
+    // low parts of the center in double-double, for the deep views
+    x_lo: f64,
+    y_lo: f64,
+    auto_exposure: bool,
+    subdivision: bool,
+    adaptive_iterations: bool,
+    // tables go after the plain values in TOML
+    view: View,
+    options: RenderOptions,
+}
+
+impl Piece {
+    // The rows `top..top + rows` of the view of `mandelbrot`.
+    fn of(number: usize, mandelbrot: &Mandelbrot, top: u32, rows: u32) -> Self {
+        let (x, y) = mandelbrot.center();
+        Self {
+            number,
+            width: mandelbrot.width,
+            height: mandelbrot.height,
+            top,
+            rows,
+            max_round: mandelbrot.max_round,
+            x_lo: x.lo,
+            y_lo: y.lo,
+            auto_exposure: mandelbrot.auto_exposure(),
+            subdivision: mandelbrot.subdivision,
+            adaptive_iterations: mandelbrot.adaptive_iterations,
+            view: mandelbrot.view(),
+            options: mandelbrot.options.clone(),
+        }
+    }
+
+    // bytes of the RGBA pixels of the piece
+    fn size(&self) -> usize {
+        4 * self.width as usize * self.rows as usize
+    }
+
+    fn render(&self) -> Vec<u8> {
+        let mut mandelbrot = Mandelbrot::new(self.width, self.height);
+        mandelbrot.set_precise_view(
+            DoubleDouble::from(self.view.center_x) + self.x_lo,
+            DoubleDouble::from(self.view.center_y) + self.y_lo,
+            self.view.scale,
+            self.max_round,
+        );
+        mandelbrot.options = self.options.clone();
+        mandelbrot.info = false;
+        mandelbrot.set_auto_exposure(self.auto_exposure);
+        mandelbrot.subdivision = self.subdivision;
+        mandelbrot.adaptive_iterations = self.adaptive_iterations;
+        let mut pixels = vec![0_u8; self.size()];
+        if self.rows == self.height {
+            mandelbrot.draw(&mut pixels);
+        } else {
+            mandelbrot.sample_strips();
+            mandelbrot.draw_strip(&mut pixels, self.top as usize, self.rows as usize);
+        }
+        pixels
+    }
+}
+
+// A message is its length in 8 bytes, little endian, then its bytes; an
+// empty one tells the worker there is no more work.
+fn send(stream: &mut TcpStream, message: &[u8]) -> io::Result<()> {
+    stream.write_all(&(message.len() as u64).to_le_bytes())?;
+    stream.write_all(message)?;
+    stream.flush()
+}
+
+// A message of at most `limit` bytes; a longer one is refused before
+// anything is allocated for it.
+fn receive(stream: &mut TcpStream, limit: usize) -> io::Result<Vec<u8>> {
+    let mut length = [0_u8; 8];
+    stream.read_exact(&mut length)?;
+    let length = u64::from_le_bytes(length);
+    if length > limit as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "message of {} bytes, more than the {} expected",
+                length, limit
+            ),
+        ));
+    }
+    let mut message = vec![0_u8; length as usize];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+// A piece as it is sent to the workers.
+struct Encoded {
+    number: usize,
+    // bytes of the RGBA pixels that come back
+    size: usize,
+    message: String,
+}
+
+// Hand the pieces out to the workers that connect to `listen` and pass
+// each rendered piece with its number to `finished`, in the order they
+// come back. A piece whose worker goes away goes back to the queue.
+fn distribute(
+    listen: &str,
+    pieces: Vec<Piece>,
+    mut finished: impl FnMut(usize, Vec<u8>) -> io::Result<()>,
+) -> io::Result<()> {
+    let total = pieces.len();
+    let queue = pieces
+        .into_iter()
+        .map(|piece| {
+            Ok(Encoded {
+                number: piece.number,
+                size: piece.size(),
+                message: toml::to_string(&piece).map_err(invalid_data)?,
+            })
+        })
+        .collect::<io::Result<VecDeque<_>>>()?;
+    let queue = Arc::new(Mutex::new(queue));
+    let listener = TcpListener::bind(listen)?;
+    info!("waiting for workers on {}", listener.local_addr()?);
+    let (sender, receiver) = mpsc::channel();
+    {
+        let queue = queue.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let queue = queue.clone();
+                        let sender = sender.clone();
+                        thread::spawn(move || serve_worker(stream, &queue, &sender));
+                    }
+                    Err(e) => error!("failed to accept a worker: {}", e),
+                }
+            }
+        });
+    }
+    for done in 1..=total {
+        // the accepting thread keeps a sender for as long as it runs
+        let (number, frame) = receiver.recv().map_err(invalid_data)?;
+        finished(number, frame)?;
+        info!("piece {} done ({}/{})", number, done, total);
+    }
+    Ok(())
+}
+
+fn serve_worker(
+    mut stream: TcpStream,
+    queue: &Mutex<VecDeque<Encoded>>,
+    finished: &Sender<(usize, Vec<u8>)>,
+) {
+    let worker = stream
+        .peer_addr()
+        .map_or_else(|_| String::from("worker"), |addr| addr.to_string());
+    info!("{} connected", worker);
+    loop {
+        let piece = queue.lock().unwrap().pop_front();
+        let Some(piece) = piece else {
+            // no more work for this one; the others may still be busy
+            let _ = send(&mut stream, &[]);
+            info!("{} done", worker);
+            return;
+        };
+        let result = send(&mut stream, piece.message.as_bytes())
+            .and_then(|_| receive(&mut stream, piece.size));
+        match result {
+            Ok(frame) if frame.len() == piece.size => {
+                if finished.send((piece.number, frame)).is_err() {
+                    return;
+                }
+            }
+            Ok(_) => {
+                error!("{} sent a frame of the wrong size", worker);
+                queue.lock().unwrap().push_back(piece);
+                return;
+            }
+            Err(e) => {
+                error!("{} went away: {}", worker, e);
+                queue.lock().unwrap().push_back(piece);
+                return;
+            }
+        }
+    }
+}
+
+/// Render `mandelbrot` by strips of rows on the workers that connect to
+/// `listen`, and assemble its frame.
+pub fn render(mandelbrot: &Mandelbrot, listen: &str) -> io::Result<Vec<u8>> {
+    let (width, height) = (mandelbrot.width, mandelbrot.height);
+    let pieces: Vec<Piece> = (0..height)
+        .step_by(STRIP_HEIGHT as usize)
+        .enumerate()
+        .map(|(number, top)| Piece::of(number, mandelbrot, top, STRIP_HEIGHT.min(height - top)))
+        .collect();
+    let mut frame = vec![0_u8; 4 * width as usize * height as usize];
+    let strip_bytes = 4 * width as usize * STRIP_HEIGHT as usize;
+    distribute(listen, pieces, |number, strip| {
+        let start = number * strip_bytes;
+        frame[start..start + strip.len()].copy_from_slice(&strip);
+        Ok(())
+    })?;
+    Ok(frame)
+}
+
+/// Render the frames of the zoom animation `job` on the workers that
+/// connect to `listen` into `dir/frame_NNNNN.png`. The frames already in
+/// `dir` are kept, so that an interrupted export can be run again.
+pub fn export_frames(job: &AnimationJob, dir: &Path, listen: &str) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let pieces: Vec<Piece> = animation::zoom_path(job.start, job.end, job.frames)
+        .into_iter()
+        .enumerate()
+        .filter(|(number, _)| !animation::frame_path(dir, *number).exists())
+        .map(|(number, view)| {
+            // a frame as animation::render() draws it
+            let mut mandelbrot = Mandelbrot::new(job.width, job.height);
+            let max_round = job
+                .max_round
+                .unwrap_or_else(|| Mandelbrot::auto_max_round(view.scale));
+            mandelbrot.set_view(view.center_x, view.center_y, view.scale, max_round);
+            mandelbrot.options = job.options.clone();
+            Piece::of(number, &mandelbrot, 0, job.height)
+        })
+        .collect();
+    distribute(listen, pieces, |number, frame| {
+        // written aside first, so that an interrupted export leaves no
+        // partial frame to keep
+        let path = animation::frame_path(dir, number);
+        let temporary = path.with_extension("png.tmp");
+        export::save_png(&temporary, job.width, job.height, &frame).map_err(invalid_data)?;
+        fs::rename(&temporary, &path)?;
+        info!("frame {}/{}: {}", number + 1, job.frames, path.display());
+        Ok(())
+    })
+}
+
+/// Connect to the coordinator at `address` and render the pieces it hands
+/// out until it has no more.
+pub fn work(address: &str) -> io::Result<()> {
+    let mut stream = TcpStream::connect(address)?;
+    info!("connected to {}", address);
+    loop {
+        let message = match receive(&mut stream, MAX_PIECE_BYTES) {
+            Ok(message) => message,
+            // the coordinator quits once it has every piece
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if message.is_empty() {
+            info!("no more work");
+            return Ok(());
+        }
+        let piece: Piece = std::str::from_utf8(&message)
+            .map_err(invalid_data)
+            .and_then(|message| toml::from_str(message).map_err(invalid_data))?;
+        info!("rendering piece {}", piece.number);
+        send(&mut stream, &piece.render())?;
+    }
+}
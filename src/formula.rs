@@ -0,0 +1,397 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::mandelbrot::{power, Fractal};
+
+type Complex = (f64, f64);
+// a compiled part of the formula, evaluated at z and c
+type Compiled = Box<dyn Fn(Complex, Complex) -> Complex + Send + Sync>;
+type Function = fn(Complex) -> Complex;
+
+// integer exponents up to this are multiplied out, the larger ones go
+// through exp and log
+const MAX_MULTIPLIED: f64 = 16.0;
+// largest literal exponent a formula takes
+const MAX_FORMULA_EXPONENT: f64 = 64.0;
+// deepest nesting of parentheses, calls, negations and powers a formula
+// takes, far within the stack of the recursive parser
+const MAX_FORMULA_DEPTH: usize = 256;
+
+const FUNCTIONS: &[(&str, Function)] = &[
+    ("abs", |(x, y)| (x.abs(), y.abs())),
+    ("conj", |(x, y)| (x, -y)),
+    ("exp", exp),
+    ("log", log),
+    ("sin", |(x, y)| (x.sin() * y.cosh(), x.cos() * y.sinh())),
+    ("cos", |(x, y)| (x.cos() * y.cosh(), -x.sin() * y.sinh())),
+];
+
+fn mul((a, b): Complex, (c, d): Complex) -> Complex {
+    (a * c - b * d, a * d + b * c)
+}
+
+fn div((a, b): Complex, (c, d): Complex) -> Complex {
+    let denominator = c * c + d * d;
+    ((a * c + b * d) / denominator, (b * c - a * d) / denominator)
+}
+
+fn exp((x, y): Complex) -> Complex {
+    let r = x.exp();
+    (r * y.cos(), r * y.sin())
+}
+
+fn log((x, y): Complex) -> Complex {
+    (x.hypot(y).ln(), y.atan2(x))
+}
+
+enum Expr {
+    Z,
+    C,
+    Number(Complex),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    Call(Function, Box<Expr>),
+}
+
+impl Expr {
+    fn compile(self) -> Compiled {
+        match self {
+            Expr::Z => Box::new(|z, _| z),
+            Expr::C => Box::new(|_, c| c),
+            Expr::Number(value) => Box::new(move |_, _| value),
+            Expr::Neg(a) => {
+                let a = a.compile();
+                Box::new(move |z, c| {
+                    let (x, y) = a(z, c);
+                    (-x, -y)
+                })
+            }
+            Expr::Add(a, b) => {
+                let (a, b) = (a.compile(), b.compile());
+                Box::new(move |z, c| {
+                    let ((ax, ay), (bx, by)) = (a(z, c), b(z, c));
+                    (ax + bx, ay + by)
+                })
+            }
+            Expr::Sub(a, b) => {
+                let (a, b) = (a.compile(), b.compile());
+                Box::new(move |z, c| {
+                    let ((ax, ay), (bx, by)) = (a(z, c), b(z, c));
+                    (ax - bx, ay - by)
+                })
+            }
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.compile(), b.compile());
+                Box::new(move |z, c| mul(a(z, c), b(z, c)))
+            }
+            Expr::Div(a, b) => {
+                let (a, b) = (a.compile(), b.compile());
+                Box::new(move |z, c| div(a(z, c), b(z, c)))
+            }
+            // small integer exponents are multiplied out like those of
+            // z^d + c, negative ones into the reciprocal; the others go
+            // through exp and log
+            Expr::Pow(a, b) => match *b {
+                Expr::Number((exponent, 0.0))
+                    if exponent.fract() == 0.0 && exponent.abs() <= MAX_MULTIPLIED =>
+                {
+                    let a = a.compile();
+                    if exponent == 0.0 {
+                        Box::new(|_, _| (1.0, 0.0))
+                    } else if exponent < 0.0 {
+                        Box::new(move |z, c| {
+                            let (x, y) = a(z, c);
+                            div((1.0, 0.0), power(Fractal::Mandelbrot, x, y, -exponent))
+                        })
+                    } else {
+                        Box::new(move |z, c| {
+                            let (x, y) = a(z, c);
+                            power(Fractal::Mandelbrot, x, y, exponent)
+                        })
+                    }
+                }
+                b => {
+                    let (a, b) = (a.compile(), b.compile());
+                    Box::new(move |z, c| exp(mul(b(z, c), log(a(z, c)))))
+                }
+            },
+            Expr::Call(function, a) => {
+                let a = a.compile();
+                Box::new(move |z, c| function(a(z, c)))
+            }
+        }
+    }
+}
+
+// Recursive descent over the characters of the formula, spaces skipped.
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.source[self.position..];
+        let trimmed = rest.trim_start();
+        self.position += rest.len() - trimmed.len();
+        trimmed.chars().next()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&mut self, expected: &str) -> String {
+        match self.peek() {
+            Some(found) => format!(
+                "expected {} at {}, found `{}`",
+                expected,
+                self.position + 1,
+                found
+            ),
+            None => format!("expected {} at the end", expected),
+        }
+    }
+
+    // Parse one more level of nesting, starting at the 1-based position
+    // `start`, or fail before the recursion runs out of stack.
+    fn nested(
+        &mut self,
+        start: usize,
+        parse: impl FnOnce(&mut Self) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        if self.depth == MAX_FORMULA_DEPTH {
+            return Err(format!("formula nested too deeply at {}", start));
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    // sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        loop {
+            if self.eat('+') {
+                expr = Expr::Add(Box::new(expr), Box::new(self.product()?));
+            } else if self.eat('-') {
+                expr = Expr::Sub(Box::new(expr), Box::new(self.product()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    // product := unary (('*' | '/') unary)*
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        loop {
+            if self.eat('*') {
+                expr = Expr::Mul(Box::new(expr), Box::new(self.unary()?));
+            } else if self.eat('/') {
+                expr = Expr::Div(Box::new(expr), Box::new(self.unary()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    // unary := '-' unary | atom ('^' unary)?
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(match self.nested(self.position, Self::unary)? {
+                Expr::Number((x, y)) => Expr::Number((-x, -y)),
+                expr => Expr::Neg(Box::new(expr)),
+            });
+        }
+        let base = self.atom()?;
+        if self.eat('^') {
+            let start = self.position + 1;
+            let exponent = self.nested(self.position, Self::unary)?;
+            if let Expr::Number((exponent, _)) = exponent {
+                if exponent.abs() > MAX_FORMULA_EXPONENT {
+                    return Err(format!(
+                        "exponent {} at {} is not within -{} to {}",
+                        exponent, start, MAX_FORMULA_EXPONENT, MAX_FORMULA_EXPONENT
+                    ));
+                }
+            }
+            Ok(Expr::Pow(Box::new(base), Box::new(exponent)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    // atom := number | 'z' | 'c' | 'i' | function '(' sum ')' | '(' sum ')'
+    fn atom(&mut self) -> Result<Expr, String> {
+        let start = match self.peek() {
+            Some(start) => start,
+            None => return Err(self.error("a number, z, c, i or a function")),
+        };
+        if self.eat('(') {
+            let expr = self.nested(self.position, Self::sum)?;
+            return if self.eat(')') {
+                Ok(expr)
+            } else {
+                Err(self.error("`)`"))
+            };
+        }
+        let rest = &self.source[self.position..];
+        if start.is_ascii_digit() || start == '.' {
+            let length = rest
+                .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+                .unwrap_or(rest.len());
+            let number = rest[..length]
+                .parse()
+                .map_err(|_| format!("not a number: {}", &rest[..length]))?;
+            self.position += length;
+            return Ok(Expr::Number((number, 0.0)));
+        }
+        let length = rest
+            .find(|ch: char| !ch.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let name = &rest[..length];
+        let expr = match name {
+            "z" => Expr::Z,
+            "c" => Expr::C,
+            "i" => Expr::Number((0.0, 1.0)),
+            _ => {
+                let function = FUNCTIONS
+                    .iter()
+                    .find(|(function, _)| *function == name)
+                    .map(|(_, function)| *function);
+                let Some(function) = function else {
+                    return Err(if name.is_empty() {
+                        self.error("a number, z, c, i or a function")
+                    } else {
+                        format!("unknown name `{}` at {}", name, self.position + 1)
+                    });
+                };
+                self.position += length;
+                if !self.eat('(') {
+                    return Err(self.error("`(`"));
+                }
+                let argument = self.nested(self.position, Self::sum)?;
+                if !self.eat(')') {
+                    return Err(self.error("`)`"));
+                }
+                return Ok(Expr::Call(function, Box::new(argument)));
+            }
+        };
+        self.position += length;
+        Ok(expr)
+    }
+}
+
+/// A formula z' = f(z, c) typed by the user, such as `z^3 + c*z`, compiled
+/// once into a closure the kernels call every iteration. It has the
+/// operators + - * / ^, parentheses, numbers, the imaginary unit i and the
+/// functions abs (of both parts, as in the Burning Ship), conj, exp, log,
+/// sin and cos.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Formula {
+    source: String,
+    compiled: Arc<Compiled>,
+}
+
+impl Formula {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            source,
+            position: 0,
+            depth: 0,
+        };
+        let expr = parser.sum()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("an operator"));
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            compiled: Arc::new(expr.compile()),
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The next point of the orbit after z, for the pixel at c.
+    pub fn iterate(&self, z: Complex, c: Complex) -> Complex {
+        (self.compiled)(z, c)
+    }
+}
+
+impl fmt::Debug for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Formula({:?})", self.source)
+    }
+}
+
+impl PartialEq for Formula {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl TryFrom<String> for Formula {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, String> {
+        Self::parse(&source)
+    }
+}
+
+impl From<Formula> for String {
+    fn from(formula: Formula) -> Self {
+        formula.source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser_reports_where_the_formula_goes_wrong() {
+        for (source, error) in [
+            ("", "expected a number, z, c, i or a function at the end"),
+            ("z +", "expected a number, z, c, i or a function at the end"),
+            (
+                "z + $",
+                "expected a number, z, c, i or a function at 5, found `$`",
+            ),
+            ("(z + c", "expected `)` at the end"),
+            ("sin z", "expected `(` at 5, found `z`"),
+            ("sin(z c", "expected `)` at 7, found `c`"),
+            ("z z", "expected an operator at 3, found `z`"),
+            ("z^2 + q", "unknown name `q` at 7"),
+            ("1.2.3 + c", "not a number: 1.2.3"),
+            ("z^-65 + c", "exponent -65 at 3 is not within -64 to 64"),
+            (&"-".repeat(300), "formula nested too deeply at 257"),
+        ] {
+            assert_eq!(Formula::parse(source).unwrap_err(), error, "{}", source);
+        }
+    }
+
+    #[test]
+    fn parser_keeps_the_precedence_of_the_operators() {
+        let formula = Formula::parse(" z^2 + c ").unwrap();
+        assert_eq!(formula.source(), "z^2 + c");
+        assert_eq!(formula.iterate((1.0, 1.0), (0.5, 0.0)), (0.5, 2.0));
+        let formula = Formula::parse("-z^2 * 2 - c / 2").unwrap();
+        assert_eq!(formula.iterate((3.0, 0.0), (4.0, 0.0)), (-20.0, 0.0));
+    }
+}
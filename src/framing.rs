@@ -0,0 +1,240 @@
+use std::sync::mpsc::{self, Receiver};
+
+use crate::canvas::{self, Canvas, LINE_HEIGHT};
+use crate::mandelbrot::{Mandelbrot, View};
+use crate::platform;
+
+// fraction of the window the preview of an export may cover
+const PREVIEW_SIZE: f64 = 0.75;
+// darkening of the window around the preview
+const PREVIEW_DIM: f64 = 0.6;
+const MARGIN: usize = 5;
+pub const EXPORT_FILE: &str = "export.png";
+
+/// How a view framed in one aspect ratio goes into an image of another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Fit {
+    /// fill the image, cutting off what sticks out of it
+    #[default]
+    Crop,
+    /// show all of the view and render more of the plane around it
+    Extend,
+    /// show all of the view with black bars around it
+    Letterbox,
+}
+
+impl Fit {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Crop => Self::Extend,
+            Self::Extend => Self::Letterbox,
+            Self::Letterbox => Self::Crop,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Crop => "crop",
+            Self::Extend => "extend",
+            Self::Letterbox => "letterbox",
+        }
+    }
+}
+
+/// A view framed in `framed` pixels, fitted to an image of `size` pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Framing {
+    /// view of the image
+    pub view: View,
+    /// the framed area in pixels of the image (x, y, width, height)
+    pub framed: (usize, usize, usize, usize),
+    pub fit: Fit,
+}
+
+impl Framing {
+    pub fn new(view: View, framed: (u32, u32), size: (u32, u32), fit: Fit) -> Self {
+        let framed_width = framed.0 as f64 * view.scale;
+        let framed_height = framed.1 as f64 * view.scale;
+        let scale_x = framed_width / size.0 as f64;
+        let scale_y = framed_height / size.1 as f64;
+        let scale = match fit {
+            Fit::Crop => scale_x.min(scale_y),
+            Fit::Extend | Fit::Letterbox => scale_x.max(scale_y),
+        };
+        let width = ((framed_width / scale).round() as usize).min(size.0 as usize);
+        let height = ((framed_height / scale).round() as usize).min(size.1 as usize);
+        Self {
+            view: View { scale, ..view },
+            framed: (
+                (size.0 as usize - width) / 2,
+                (size.1 as usize - height) / 2,
+                width,
+                height,
+            ),
+            fit,
+        }
+    }
+
+    /// Blacken the bars of a letterboxed image, or of the strip of its rows
+    /// from `top` on.
+    pub fn letterbox(&self, canvas: &mut Canvas, top: usize) {
+        if self.fit != Fit::Letterbox {
+            return;
+        }
+        let (x, y, width, height) = self.framed;
+        let black = [0x00, 0x00, 0x00, 0xff];
+        // the rows of the canvas in the framed area
+        let start = y.saturating_sub(top).min(canvas.height);
+        let end = (y + height).saturating_sub(top).min(canvas.height);
+        canvas.fill_rect(0, 0, canvas.width, start, black);
+        canvas.fill_rect(0, end, canvas.width, canvas.height, black);
+        canvas.fill_rect(0, start, x, end - start, black);
+        canvas.fill_rect(x + width, start, canvas.width, end - start, black);
+    }
+}
+
+/// A copy of `mandelbrot` that renders its view, framed at its own size,
+/// into an image of `size` pixels.
+pub fn job(mandelbrot: &Mandelbrot, size: (u32, u32), fit: Fit) -> (Mandelbrot, Framing) {
+    let framing = Framing::new(
+        mandelbrot.view(),
+        (mandelbrot.width, mandelbrot.height),
+        size,
+        fit,
+    );
+    let mut job = Mandelbrot::new(size.0, size.1);
+    job.options = mandelbrot.options.clone();
+    job.max_round = mandelbrot.max_round;
+    job.subdivision = mandelbrot.subdivision;
+    job.adaptive_iterations = mandelbrot.adaptive_iterations;
+    job.set_auto_exposure(mandelbrot.auto_exposure());
+    job.low_memory = mandelbrot.low_memory;
+    job.info = false;
+    job.center_x = framing.view.center_x;
+    job.center_y = framing.view.center_y;
+    job.scale = framing.view.scale;
+    (job, framing)
+}
+
+/// Render `mandelbrot`, framed at its own size, into an image of `size`
+/// pixels.
+pub fn render(mandelbrot: &Mandelbrot, size: (u32, u32), fit: Fit) -> Vec<u8> {
+    let (job, framing) = job(mandelbrot, size, fit);
+    render_job(job, &framing, size)
+}
+
+fn render_job(mut job: Mandelbrot, framing: &Framing, size: (u32, u32)) -> Vec<u8> {
+    let mut frame = vec![0_u8; 4 * size.0 as usize * size.1 as usize];
+    job.draw(&mut frame);
+    framing.letterbox(
+        &mut Canvas::new(&mut frame, size.0 as usize, size.1 as usize),
+        0,
+    );
+    frame
+}
+
+// what a preview is rendered from
+type PreviewKey = (View, usize, Fit);
+
+// a preview rendered in the background, with what it was rendered from
+struct RenderedPreview {
+    key: PreviewKey,
+    image: Vec<u8>,
+    size: (u32, u32),
+    framed: (usize, usize, usize, usize),
+}
+
+/// Small render of an export over the window, to check its framing
+/// before the long render starts. It is rendered off the event loop, one
+/// render at a time, and shown once it arrives.
+pub struct ExportPreview {
+    pub width: u32,
+    pub height: u32,
+    pub fit: Fit,
+    // the preview shown, once one arrived
+    shown: Option<RenderedPreview>,
+    // the render under way, with what it is rendered from
+    pending: Option<(PreviewKey, Receiver<RenderedPreview>)>,
+}
+
+impl ExportPreview {
+    pub fn new(width: u32, height: u32, fit: Fit) -> Self {
+        Self {
+            width,
+            height,
+            fit,
+            shown: None,
+            pending: None,
+        }
+    }
+
+    /// Render the preview again when the view or the fit changed, once the
+    /// render under way is done; whether a new preview arrived to show.
+    pub fn update(&mut self, mandelbrot: &Mandelbrot) -> bool {
+        let key = (mandelbrot.view(), mandelbrot.max_round, self.fit);
+        let wanted = |preview: &RenderedPreview| preview.key == key;
+        if self.pending.is_none() && !self.shown.as_ref().is_some_and(wanted) {
+            // the export, scaled down to fit in the window
+            let ratio = (PREVIEW_SIZE * mandelbrot.width as f64 / self.width as f64)
+                .min(PREVIEW_SIZE * mandelbrot.height as f64 / self.height as f64);
+            let size = (
+                ((self.width as f64 * ratio) as u32).max(1),
+                ((self.height as f64 * ratio) as u32).max(1),
+            );
+            let (job, framing) = job(mandelbrot, size, self.fit);
+            let (sender, receiver) = mpsc::channel();
+            platform::spawn(move || {
+                let _ = sender.send(RenderedPreview {
+                    key,
+                    image: render_job(job, &framing, size),
+                    size,
+                    framed: framing.framed,
+                });
+            });
+            self.pending = Some((key, receiver));
+        }
+
+        let arrived = match &self.pending {
+            Some((_, receiver)) => receiver.try_recv().ok(),
+            None => None,
+        };
+        match arrived {
+            Some(preview) => {
+                self.pending = None;
+                self.shown = Some(preview);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Draw the preview at the center of the window over the dimmed view,
+    /// with the outline of the window's area in it.
+    pub fn draw(&self, canvas: &mut Canvas, keys: &str) {
+        let background = canvas.theme.background.map(f64::from);
+        canvas.blend_rect(0, 0, canvas.width, canvas.height, background, PREVIEW_DIM);
+        let mut left = MARGIN;
+        if let Some(preview) = &self.shown {
+            let (width, height) = (preview.size.0 as usize, preview.size.1 as usize);
+            left = canvas.width.saturating_sub(width) / 2;
+            let top = canvas.height.saturating_sub(height) / 2;
+            canvas.draw_image(left, top, width, height, &preview.image);
+            let (x, y, framed_width, framed_height) = preview.framed;
+            canvas.stroke_rect(
+                left + x,
+                top + y,
+                framed_width,
+                framed_height,
+                canvas::FOREGROUND,
+            );
+        }
+
+        let lines = [
+            format!("export {}x{}, {}", self.width, self.height, self.fit.name()),
+            keys.to_string(),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            canvas.draw_text(left, MARGIN + i * LINE_HEIGHT, line, 1);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use gilrs::{Axis, Button, EventType, Gilrs};
+use log::{error, info};
+use std::time::Duration;
+
+use crate::action::Action;
+use crate::config::GamepadConfig;
+use crate::platform::Instant;
+
+// frame pixels the view moves per second with the stick pushed all the way
+const PAN_SPEED: f64 = 600.0;
+// wheel steps per second with a trigger pulled all the way
+const ZOOM_SPEED: f64 = 10.0;
+// longest time a poll accounts for, so that a slow frame does not jump
+const MAX_STEP: Duration = Duration::from_millis(100);
+
+/// What the buttons of a controller do.
+const BUTTONS: &[(Button, Action)] = &[
+    (Button::South, Action::ToggleInfo),
+    (Button::East, Action::CloseOrbit),
+    (Button::West, Action::ToggleLegend),
+    (Button::North, Action::ToggleIterationHistogram),
+    (Button::LeftTrigger, Action::CyclePalette),
+    (Button::RightTrigger, Action::CycleColoring),
+    (Button::Select, Action::ToggleInfoCard),
+    (Button::Start, Action::Reset),
+    (Button::DPadUp, Action::MoveUp),
+    (Button::DPadDown, Action::MoveDown),
+    (Button::DPadLeft, Action::MoveLeft),
+    (Button::DPadRight, Action::MoveRight),
+];
+
+/// What the controllers did since the last poll.
+#[derive(Default)]
+pub struct GamepadInput {
+    pub actions: Vec<Action>,
+    /// whole frame pixels to move the center by, y up
+    pub pan: (f64, f64),
+    /// wheel steps, positive to zoom in
+    pub zoom: f64,
+}
+
+/// The game controllers: the left stick pans, the right trigger zooms in
+/// and the left one out, and the buttons toggle the overlays.
+pub struct Gamepads {
+    gilrs: Gilrs,
+    config: GamepadConfig,
+    last_poll: Instant,
+    // fraction of a pixel the pan has not moved yet, kept so that the
+    // frame is only ever shifted by whole pixels
+    pan_rest: (f64, f64),
+}
+
+impl Gamepads {
+    /// None when the controllers cannot be read.
+    pub fn new(config: GamepadConfig) -> Option<Self> {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            Err(e) => {
+                error!("failed to open the game controllers: {}", e);
+                return None;
+            }
+        };
+        for (_, gamepad) in gilrs.gamepads() {
+            info!("gamepad {}", gamepad.name());
+        }
+        Some(Self {
+            gilrs,
+            config,
+            last_poll: Instant::now(),
+            pan_rest: (0.0, 0.0),
+        })
+    }
+
+    pub fn poll(&mut self) -> GamepadInput {
+        let mut input = GamepadInput::default();
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => input.actions.extend(
+                    BUTTONS
+                        .iter()
+                        .filter(|(bound, _)| *bound == button)
+                        .map(|(_, action)| *action),
+                ),
+                EventType::Connected => {
+                    info!("gamepad {} connected", self.gilrs.gamepad(event.id).name());
+                }
+                EventType::Disconnected => info!("gamepad disconnected"),
+                _ => {}
+            }
+        }
+
+        let now = Instant::now();
+        let step = (now - self.last_poll).min(MAX_STEP).as_secs_f64();
+        self.last_poll = now;
+        let (mut pan_x, mut pan_y) = self.pan_rest;
+        for (_, gamepad) in self.gilrs.gamepads() {
+            let (x, y) = stick(
+                gamepad.value(Axis::LeftStickX),
+                gamepad.value(Axis::LeftStickY),
+                self.config.dead_zone,
+            );
+            pan_x += x * PAN_SPEED * step;
+            pan_y += y * PAN_SPEED * step;
+            let trigger = |button| {
+                gamepad.button_data(button).map_or(0.0, |data| {
+                    past_dead_zone(data.value(), self.config.trigger_dead_zone)
+                })
+            };
+            input.zoom += (trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2))
+                * ZOOM_SPEED
+                * step;
+        }
+        input.pan = (pan_x.trunc(), pan_y.trunc());
+        self.pan_rest = (pan_x.fract(), pan_y.fract());
+        input
+    }
+}
+
+// Position of a stick with a round dead zone at the center, past which
+// the travel goes again from 0 to 1.
+fn stick(x: f32, y: f32, dead_zone: f32) -> (f64, f64) {
+    let length = x.hypot(y);
+    if length <= dead_zone {
+        return (0.0, 0.0);
+    }
+    let scale = past_dead_zone(length.min(1.0), dead_zone) / length as f64;
+    (x as f64 * scale, y as f64 * scale)
+}
+
+fn past_dead_zone(value: f32, dead_zone: f32) -> f64 {
+    ((value - dead_zone) / (1.0 - dead_zone)).max(0.0) as f64
+}
@@ -0,0 +1,61 @@
+use crate::mandelbrot::Mandelbrot;
+use crate::platform::Instant;
+
+// time in seconds in which the zoom still to go falls to 1/e
+const TIME_CONSTANT: f64 = 0.05;
+// wheel steps left over that are zoomed at once
+const SNAP: f64 = 1e-3;
+
+/// Zoom of the wheel, eased toward its target over a few frames about the
+/// pixel under the pointer, which stays where it is like in a map
+/// application. Steps scrolled while it is still going add to the target.
+#[derive(Default)]
+pub struct ZoomGlide {
+    // wheel steps still to zoom, positive to zoom in
+    remaining: f64,
+    // frame pixel the zoom is about
+    anchor: (f64, f64),
+    last: Option<Instant>,
+}
+
+impl ZoomGlide {
+    pub fn is_active(&self) -> bool {
+        self.remaining != 0.0
+    }
+
+    /// Zoom `steps` more wheel steps about the frame pixel `anchor`.
+    pub fn add(&mut self, steps: f64, anchor: (f64, f64), now: Instant) {
+        if !self.is_active() {
+            self.last = Some(now);
+        }
+        self.remaining += steps;
+        self.anchor = anchor;
+    }
+
+    /// Zoom `mandelbrot` by the part of the remaining steps due at `now`;
+    /// whether the view changed.
+    pub fn step(&mut self, mandelbrot: &mut Mandelbrot, now: Instant) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        let elapsed = self
+            .last
+            .map_or(0.0, |last| now.duration_since(last).as_secs_f64());
+        self.last = Some(now);
+        let mut steps = self.remaining * (1.0 - (-elapsed / TIME_CONSTANT).exp());
+        if (self.remaining - steps).abs() < SNAP {
+            steps = self.remaining;
+        }
+        if steps == 0.0 {
+            return false;
+        }
+        let scale = mandelbrot.scale;
+        mandelbrot.zoom_at(self.anchor.0, self.anchor.1, 1.07_f64.powf(steps));
+        self.remaining -= steps;
+        if mandelbrot.scale == scale {
+            // stopped by the limits of the scale
+            self.remaining = 0.0;
+        }
+        true
+    }
+}
@@ -0,0 +1,485 @@
+use log::info;
+use pixels::{wgpu, PixelsContext};
+use std::borrow::Cow;
+use std::error::Error;
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+
+use crate::canvas::{Canvas, LINE_HEIGHT};
+use crate::palette::{Bands, Blend, Palette};
+use crate::platform::{Instant, SystemTime};
+
+// texels per row of the palette texture
+const PALETTE_WIDTH: u32 = 1024;
+pub const MIN_GAMMA: f32 = 0.2;
+pub const MAX_GAMMA: f32 = 5.0;
+/// Iterations the palette advances per frame while cycling.
+pub const CYCLE_STEP: usize = 2;
+const SHADER_TEMPLATE: &str = include_str!("../shaders/coloring.wgsl");
+// the coloring function of the template unless --shader gives one
+const DEFAULT_COLOR: &str = include_str!("../shaders/color.wgsl");
+// line of the template the coloring function replaces
+const COLOR_PLACEHOLDER: &str = "// {color}";
+// lines of a shader error shown over the image
+const MAX_ERROR_LINES: usize = 8;
+const MARGIN: usize = 5;
+
+// Directory of the shaders that passed the validation of compile() before,
+// named by the checksum of their source. Only that validation, done here
+// for the line numbers of its errors, is skipped for them: wgpu still
+// parses and validates the module, and the driver still builds the
+// pipeline, every time, as wgpu 0.12 caches neither.
+fn validated_shader_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("mandelbrot").join("shaders"))
+}
+
+fn validated_shader_path(source: &str) -> Option<PathBuf> {
+    validated_shader_dir()
+        .map(|dir| dir.join(format!("{:08x}.wgsl", crc32fast::hash(source.as_bytes()))))
+}
+
+/// The coloring shader with `color` as its coloring function, or why it
+/// does not compile, with the line numbers of `color` where the error is
+/// in it.
+fn compile(color: &str) -> Result<String, String> {
+    let first_line = SHADER_TEMPLATE
+        .lines()
+        .position(|line| line == COLOR_PLACEHOLDER)
+        .expect("no placeholder in coloring.wgsl");
+    let source = SHADER_TEMPLATE.replacen(COLOR_PLACEHOLDER, color, 1);
+    let validated = validated_shader_path(&source);
+    if let Some(path) = &validated {
+        if fs::read_to_string(path).is_ok_and(|contents| contents == source) {
+            info!("shader validated before: {}", path.display());
+            return Ok(source);
+        }
+    }
+    // "line N: " of the coloring function, from a line of the whole shader
+    let prefix = |line: usize| {
+        let color_lines = color.lines().count().max(1);
+        if line > first_line && line <= first_line + color_lines {
+            format!("line {}: ", line - first_line)
+        } else {
+            String::from("coloring.wgsl: ")
+        }
+    };
+
+    let module = naga::front::wgsl::parse_str(&source).map_err(|e| {
+        let (line, column) = e.location(&source);
+        format!("{}column {}: {}", prefix(line), column, e)
+    })?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .map_err(|e| {
+        // the innermost span, the expression or statement at fault
+        let location = e
+            .spans()
+            .filter_map(|(span, _)| span.to_range())
+            .last()
+            .map(|range| prefix(source[..range.start].matches('\n').count() + 1))
+            .unwrap_or_default();
+        let mut message = format!("{}{}", location, e);
+        let mut cause = e.source();
+        while let Some(error) = cause {
+            message.push_str(&format!(": {}", error));
+            cause = error.source();
+        }
+        message
+    })?;
+    if let Some(path) = &validated {
+        // this only saves time: a shader that cannot be stored is
+        // validated again next time
+        let stored = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(path, &source));
+        if let Err(e) = stored {
+            info!("cannot keep the shader in {}: {}", path.display(), e);
+        }
+    }
+    Ok(source)
+}
+
+/// File with the coloring function of --shader, compiled again whenever
+/// it is saved.
+struct ShaderFile {
+    path: PathBuf,
+    // whether the file was read, and its modification time then
+    read: bool,
+    modified: Option<SystemTime>,
+    // why the file last read does not compile; the previous shader stays
+    error: Option<String>,
+}
+
+/// Coloring of the frame on the GPU: `Mandelbrot` stores the escape
+/// iteration of every pixel in the frame instead of a color, and the shader
+/// looks the colors up in a palette texture. Cycling the palette and
+/// changing the gamma cost no rendering.
+pub struct GpuColoring {
+    pipeline: wgpu::RenderPipeline,
+    pipeline_layout: wgpu::PipelineLayout,
+    render_texture_format: wgpu::TextureFormat,
+    shader_file: Option<ShaderFile>,
+    start: Instant,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampled_frame: wgpu::TextureView,
+    uniform_buffer: wgpu::Buffer,
+    // palette texture and what it was built from
+    bind_group: Option<(wgpu::BindGroup, Palette, Bands, Blend)>,
+    pub offset: usize,
+    pub gamma: f32,
+    pub cycling: bool,
+}
+
+impl GpuColoring {
+    /// GPU coloring with the coloring function of `shader`, or the default
+    /// one.
+    pub fn new(
+        context: &PixelsContext,
+        render_texture_format: wgpu::TextureFormat,
+        shader: Option<&Path>,
+    ) -> Self {
+        let device = &context.device;
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("coloring_bind_group_layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("coloring_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let source = compile(DEFAULT_COLOR).expect("coloring.wgsl does not compile");
+        let pipeline = create_pipeline(context, &pipeline_layout, render_texture_format, source);
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("coloring_uniform_buffer"),
+            size: 32,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut gpu = Self {
+            pipeline,
+            pipeline_layout,
+            render_texture_format,
+            shader_file: shader.map(|path| ShaderFile {
+                path: path.to_path_buf(),
+                read: false,
+                modified: None,
+                error: None,
+            }),
+            start: Instant::now(),
+            bind_group_layout,
+            sampled_frame: context
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            uniform_buffer,
+            bind_group: None,
+            offset: 0,
+            gamma: 1.0,
+            cycling: false,
+        };
+        gpu.reload(context);
+        gpu
+    }
+
+    /// Compile the --shader file again when it was saved since it was last
+    /// read. A file that does not compile leaves the previous shader in
+    /// place and its error to `draw_error()`.
+    pub fn reload(&mut self, context: &PixelsContext) {
+        let file = match &mut self.shader_file {
+            Some(file) => file,
+            None => return,
+        };
+        let modified = fs::metadata(&file.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if file.read && modified == file.modified {
+            return;
+        }
+        file.read = true;
+        file.modified = modified;
+        let compiled = fs::read_to_string(&file.path)
+            .map_err(|e| format!("cannot read the file: {}", e))
+            .and_then(|color| compile(&color));
+        match compiled {
+            Ok(source) => {
+                self.pipeline = create_pipeline(
+                    context,
+                    &self.pipeline_layout,
+                    self.render_texture_format,
+                    source,
+                );
+                file.error = None;
+                info!("shader {} loaded", file.path.display());
+            }
+            Err(error) => {
+                info!("shader {}: {}", file.path.display(), error);
+                file.error = Some(error);
+            }
+        }
+    }
+
+    /// Draw the error of the --shader file, if it does not compile, at the
+    /// bottom of the image.
+    pub fn draw_error(&self, canvas: &mut Canvas) {
+        let (path, error) = match &self.shader_file {
+            Some(ShaderFile {
+                path,
+                error: Some(error),
+                ..
+            }) => (path, error),
+            _ => return,
+        };
+        let mut lines = vec![format!(
+            "{} does not compile, the last shader that did stays:",
+            path.display()
+        )];
+        lines.extend(error.lines().take(MAX_ERROR_LINES).map(String::from));
+        let height = lines.len() * LINE_HEIGHT + 2 * MARGIN;
+        let top = canvas.height.saturating_sub(height);
+        let background = canvas.theme.background.map(f64::from);
+        canvas.blend_rect(0, top, canvas.width, height, background, 0.75);
+        for (i, line) in lines.iter().enumerate() {
+            canvas.draw_text(MARGIN, top + MARGIN + i * LINE_HEIGHT, line, 1);
+        }
+    }
+
+    /// Multiply the gamma by `factor`.
+    pub fn scale_gamma(&mut self, factor: f32) {
+        self.gamma = (self.gamma * factor).clamp(MIN_GAMMA, MAX_GAMMA);
+    }
+
+    /// Sample the frame texture of `context` again after a resize of the
+    /// frame, which replaces the texture.
+    pub fn frame_resized(&mut self, context: &PixelsContext) {
+        self.sampled_frame = context
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.bind_group = None;
+    }
+
+    // Upload one palette cycle with the bands applied, unless the texture
+    // already holds it.
+    fn update_palette(
+        &mut self,
+        context: &PixelsContext,
+        palette: &Palette,
+        bands: Bands,
+        blend: Blend,
+    ) {
+        if let Some((_, uploaded, uploaded_bands, uploaded_blend)) = &self.bind_group {
+            if uploaded == palette && *uploaded_bands == bands && *uploaded_blend == blend {
+                return;
+            }
+        }
+
+        let period = palette.period();
+        let size = wgpu::Extent3d {
+            width: PALETTE_WIDTH,
+            height: (period as u32).div_ceil(PALETTE_WIDTH),
+            depth_or_array_layers: 1,
+        };
+        let mut texels = vec![0_u8; (size.width * size.height) as usize * 4];
+        for (round, texel) in texels.chunks_exact_mut(4).take(period).enumerate() {
+            texel.copy_from_slice(&palette.color(bands.quantize(round, period), blend));
+        }
+
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("coloring_palette_texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        context.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(size.width * 4),
+                rows_per_image: NonZeroU32::new(size.height),
+            },
+            size,
+        );
+        let palette_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = context
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("coloring_bind_group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&self.sampled_frame),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&palette_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+        self.bind_group = Some((bind_group, palette.clone(), bands, blend));
+    }
+
+    /// Build the palette texture and queue a draw into a texture of the
+    /// size of the window, without waiting for it, so that the GPU has
+    /// finished the upload and the first use of the pipeline by the time
+    /// the first frame is colored on it.
+    pub fn warm_up(
+        &mut self,
+        context: &PixelsContext,
+        palette: &Palette,
+        bands: Bands,
+        blend: Blend,
+    ) {
+        let device = &context.device;
+        let (x, y, width, height) = context.scaling_renderer.clip_rect();
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("coloring_warm_up_texture"),
+            size: wgpu::Extent3d {
+                width: (x + width).max(1),
+                height: (y + height).max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.render_texture_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("coloring_warm_up_encoder"),
+        });
+        self.render(&mut encoder, &view, context, palette, bands, blend);
+        context.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Draw the frame to `render_target` in place of the scaling renderer
+    /// of pixels, advancing the palette when it is cycling.
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        render_target: &wgpu::TextureView,
+        context: &PixelsContext,
+        palette: &Palette,
+        bands: Bands,
+        blend: Blend,
+    ) {
+        self.update_palette(context, palette, bands, blend);
+        let period = palette.period();
+        if self.cycling {
+            self.offset = (self.offset + CYCLE_STEP) % period;
+        }
+
+        // same placement as the scaling renderer
+        let (x, y, width, height) = context.scaling_renderer.clip_rect();
+        let scale = (width as f32 / context.texture_extent.width as f32).max(1.0);
+        let mut locals = Vec::with_capacity(32);
+        locals.extend_from_slice(&(x as f32).to_le_bytes());
+        locals.extend_from_slice(&(y as f32).to_le_bytes());
+        locals.extend_from_slice(&scale.to_le_bytes());
+        locals.extend_from_slice(&(period as u32).to_le_bytes());
+        locals.extend_from_slice(&((self.offset % period) as u32).to_le_bytes());
+        locals.extend_from_slice(&PALETTE_WIDTH.to_le_bytes());
+        locals.extend_from_slice(&self.gamma.to_le_bytes());
+        locals.extend_from_slice(&self.start.elapsed().as_secs_f32().to_le_bytes());
+        context.queue.write_buffer(&self.uniform_buffer, 0, &locals);
+
+        let bind_group = match &self.bind_group {
+            Some((bind_group, ..)) => bind_group,
+            None => return,
+        };
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("coloring_render_pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: render_target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, bind_group, &[]);
+        rpass.set_scissor_rect(x, y, width, height);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+fn create_pipeline(
+    context: &PixelsContext,
+    layout: &wgpu::PipelineLayout,
+    render_texture_format: wgpu::TextureFormat,
+    source: String,
+) -> wgpu::RenderPipeline {
+    let device = &context.device;
+    let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("coloring_shader"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Owned(source)),
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("coloring_pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &module,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        fragment: Some(wgpu::FragmentState {
+            module: &module,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format: render_texture_format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        multiview: None,
+    })
+}
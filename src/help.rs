@@ -0,0 +1,116 @@
+use crate::action::{Action, Bindings};
+use crate::canvas::{Canvas, LINE_HEIGHT};
+
+const MARGIN: usize = 10;
+// space between the columns of the help
+const COLUMN_GAP: usize = 18;
+// opacity of the panel darkening the image behind the help
+const PANEL_OPACITY: f64 = 0.8;
+
+// what the mouse and the fingers do, which no binding changes
+const POINTER: &[(&str, &str)] = &[
+    ("Wheel", "zoom toward the pointer"),
+    ("Left drag", "move the view"),
+    ("Double click", "recenter on the point"),
+    ("Right drag", "zoom to a rectangle"),
+    ("Middle click", "orbit statistics"),
+    ("Ctrl+click", "explain the point"),
+    ("Shift+click", "find the period"),
+    ("Pinch", "zoom and pan (touch)"),
+    ("Double tap", "recenter (touch)"),
+];
+
+/// Every key binding and what the mouse does, over the image, in as many
+/// columns as fit the window. When they do not fit, the key of the help
+/// turns the pages, and closes the help after the last one.
+#[derive(Default)]
+pub struct Help {
+    page: Option<usize>,
+    // pages at the size of the last drawing
+    pages: usize,
+}
+
+impl Help {
+    pub fn is_visible(&self) -> bool {
+        self.page.is_some()
+    }
+
+    /// Show the help, turn its page or close it.
+    pub fn advance(&mut self) {
+        self.page = match self.page {
+            None => Some(0),
+            Some(page) if page + 1 < self.pages => Some(page + 1),
+            Some(_) => None,
+        };
+    }
+
+    pub fn close(&mut self) {
+        self.page = None;
+    }
+
+    fn entries(bindings: &Bindings) -> Vec<String> {
+        let mut rows: Vec<(String, String)> = bindings
+            .list()
+            .into_iter()
+            .map(|(action, keys)| (keys.join(", "), action.description()))
+            .collect();
+        rows.extend(
+            POINTER
+                .iter()
+                .map(|(input, description)| (input.to_string(), description.to_string())),
+        );
+        let keys_width = rows.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0);
+        rows.into_iter()
+            .map(|(keys, description)| {
+                format!("{:<width$}  {}", keys, description, width = keys_width)
+            })
+            .collect()
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas, bindings: &Bindings) {
+        let Some(page) = self.page else {
+            return;
+        };
+        let entries = Self::entries(bindings);
+        let column_width = entries
+            .iter()
+            .map(|entry| Canvas::text_width(entry, 1))
+            .max()
+            .unwrap_or(0);
+        // a title line and a blank one above the columns
+        let rows = (canvas.height.saturating_sub(2 * MARGIN) / LINE_HEIGHT)
+            .saturating_sub(2)
+            .max(1);
+        let columns = ((canvas.width.saturating_sub(2 * MARGIN) + COLUMN_GAP)
+            / (column_width + COLUMN_GAP))
+            .max(1);
+        let per_page = rows * columns;
+        self.pages = entries.len().div_ceil(per_page);
+        let page = page.min(self.pages - 1);
+
+        let background = canvas.theme.background.map(f64::from);
+        canvas.blend_rect(0, 0, canvas.width, canvas.height, background, PANEL_OPACITY);
+        let key = bindings.key_name(Action::ToggleHelp);
+        let title = if self.pages > 1 {
+            format!(
+                "Keys and mouse, page {}/{} ({} for the next page)",
+                page + 1,
+                self.pages,
+                key
+            )
+        } else {
+            format!("Keys and mouse ({} to close)", key)
+        };
+        canvas.draw_text(MARGIN, MARGIN, &title, 1);
+        let top = MARGIN + 2 * LINE_HEIGHT;
+        for (i, entry) in entries
+            .iter()
+            .skip(page * per_page)
+            .take(per_page)
+            .enumerate()
+        {
+            let x = MARGIN + (i / rows) * (column_width + COLUMN_GAP);
+            canvas.draw_text(x, top + (i % rows) * LINE_HEIGHT, entry, 1);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+/// Cumulative distribution of the escape iterations of a frame, which the
+/// histogram coloring spreads evenly over a palette cycle.
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    // distinct escape iterations in increasing order, each with the number
+    // of samples that escaped at or before it
+    cumulative: Vec<(usize, usize)>,
+    /// iteration limit of the samples
+    pub max_round: usize,
+    // iteration limits of the earlier histograms of the same view, in
+    // increasing order; the rounds from one limit to the next get a palette
+    // cycle of their own
+    limits: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn new(mut rounds: Vec<usize>, max_round: usize) -> Self {
+        rounds.sort_unstable();
+        let mut cumulative: Vec<(usize, usize)> = Vec::new();
+        for (count, round) in rounds.into_iter().enumerate() {
+            match cumulative.last_mut() {
+                Some(last) if last.0 == round => last.1 = count + 1,
+                _ => cumulative.push((round, count + 1)),
+            }
+        }
+        Self {
+            cumulative,
+            max_round,
+            limits: Vec::new(),
+        }
+    }
+
+    /// Keep the colors of `previous`, sampled from the same view with a
+    /// lower iteration limit: the samples that escaped within that limit
+    /// are spread as they were, and only the ones that escaped after it
+    /// go on to the next palette cycle, instead of every pixel changing
+    /// color when the limit is raised. The cycles of earlier raises are
+    /// kept as long as the limit stays above them.
+    pub fn keep_colors(&mut self, previous: &Self) {
+        self.limits = previous
+            .limits
+            .iter()
+            .copied()
+            .filter(|limit| *limit < self.max_round)
+            .collect();
+        if previous.max_round < self.max_round {
+            self.limits.push(previous.max_round);
+        }
+    }
+
+    // Number of samples that escaped before `round`.
+    fn count_below(&self, round: usize) -> usize {
+        match self
+            .cumulative
+            .binary_search_by_key(&round, |(round, _)| *round)
+        {
+            Ok(0) | Err(0) => 0,
+            Ok(i) | Err(i) => self.cumulative[i - 1].1,
+        }
+    }
+
+    /// Fraction of the samples that escaped at or before `round`, among
+    /// the ones between the same two iteration limits.
+    pub fn rank(&self, round: usize) -> f64 {
+        let segment = self.limits.partition_point(|limit| *limit <= round);
+        let low = self.count_below(match segment {
+            0 => 0,
+            _ => self.limits[segment - 1],
+        });
+        let high = self.count_below(self.limits.get(segment).copied().unwrap_or(usize::MAX));
+        if high == low {
+            return 0.0;
+        }
+        (self.count_below(round.saturating_add(1)) - low) as f64 / (high - low) as f64
+    }
+
+    /// Position of `round` in the palette cycles of `period` iterations.
+    pub fn spread(&self, round: usize, period: usize) -> usize {
+        let segment = self.limits.partition_point(|limit| *limit <= round);
+        let position = ((self.rank(round) * period as f64) as usize).min(period.saturating_sub(1));
+        segment * period + position
+    }
+}
+
+// percentiles of the escape iterations the auto exposure stretches the
+// palette between, so that a few outliers do not wash out the rest
+const EXPOSURE_LOW: f64 = 0.01;
+const EXPOSURE_HIGH: f64 = 0.99;
+
+/// Range of escape iterations the auto exposure stretches a palette cycle
+/// over: from the 1st to the 99th percentile of a frame, taken as smooth
+/// iteration values, with the samples that escaped at the same iteration
+/// spread evenly across it, so that the range changes steadily rather than
+/// by whole iterations as the view moves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Exposure {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Exposure {
+    /// The range of `rounds`, reordered on the way; None without any.
+    pub fn new(rounds: &mut [usize]) -> Option<Self> {
+        if rounds.is_empty() {
+            return None;
+        }
+        let mut percentile = |fraction: f64| {
+            let rank = rounds.len() as f64 * fraction;
+            let index = (rank as usize).min(rounds.len() - 1);
+            let round = *rounds.select_nth_unstable(index).1;
+            let below = rounds.iter().filter(|sample| **sample < round).count();
+            let at = rounds.iter().filter(|sample| **sample == round).count();
+            round as f64 + ((rank - below as f64) / at as f64).clamp(0.0, 1.0)
+        };
+        let low = percentile(EXPOSURE_LOW);
+        let high = percentile(EXPOSURE_HIGH);
+        Some(Self { low, high })
+    }
+
+    /// Position of `round` in a palette cycle of `period` iterations,
+    /// clamped to the ends of the cycle outside the range.
+    pub fn spread(self, round: usize, period: usize) -> usize {
+        let last = period.saturating_sub(1);
+        let round = round as f64;
+        if self.high <= self.low {
+            return if round <= self.low { 0 } else { last };
+        }
+        let position = (round.clamp(self.low, self.high) - self.low) / (self.high - self.low);
+        ((position * last as f64) as usize).min(last)
+    }
+}
+
+// bars of the iteration histogram overlay
+const BINS: usize = 64;
+
+/// Number of samples of a frame in each of up to 64 equal ranges of escape
+/// iterations up to the limit, and inside the set, for the overlay that
+/// shows them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IterationCounts {
+    /// samples escaped in each range, from iteration 0 up
+    pub bins: Vec<usize>,
+    /// samples that never escaped
+    pub interior: usize,
+    /// iteration limit of the samples
+    pub max_round: usize,
+}
+
+impl IterationCounts {
+    pub fn new(samples: &[Option<usize>], max_round: usize) -> Self {
+        let max_round = max_round.max(1);
+        let count = BINS.min(max_round);
+        let mut bins = vec![0; count];
+        let mut interior = 0;
+        for sample in samples {
+            match sample {
+                Some(round) => bins[(round * count / max_round).min(count - 1)] += 1,
+                None => interior += 1,
+            }
+        }
+        Self {
+            bins,
+            interior,
+            max_round,
+        }
+    }
+
+    /// First escape iteration of the range of bin `bin`.
+    pub fn first_round(&self, bin: usize) -> usize {
+        (bin * self.max_round).div_ceil(self.bins.len())
+    }
+
+    pub fn samples(&self) -> usize {
+        self.bins.iter().sum::<usize>() + self.interior
+    }
+}
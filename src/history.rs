@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use crate::double_double::DoubleDouble;
+use crate::mandelbrot::Mandelbrot;
+use crate::platform::Instant;
+
+// oldest viewports are dropped beyond this many
+const MAX_ENTRIES: usize = 100;
+// a viewport is recorded once the view has stayed there this long, so that
+// a drag or a burst of key presses makes a single entry
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Entry {
+    /// the center to the precision of double-double, for the deep views
+    pub center: (DoubleDouble, DoubleDouble),
+    pub scale: f64,
+    pub max_round: usize,
+}
+
+impl Entry {
+    pub fn of(mandelbrot: &Mandelbrot) -> Self {
+        Self {
+            center: mandelbrot.center(),
+            scale: mandelbrot.scale,
+            max_round: mandelbrot.max_round,
+        }
+    }
+
+    pub fn apply(&self, mandelbrot: &mut Mandelbrot) {
+        let (x, y) = self.center;
+        mandelbrot.set_precise_view(x, y, self.scale, self.max_round);
+    }
+}
+
+/// Viewports visited in the window, navigated back and forward like the
+/// history of a web browser.
+pub struct History {
+    entries: Vec<Entry>,
+    // index of the entry on screen
+    position: usize,
+    // the view seen by the last observe() and since when
+    pending: Entry,
+    pending_since: Instant,
+}
+
+impl History {
+    pub fn new(mandelbrot: &Mandelbrot) -> Self {
+        let entry = Entry::of(mandelbrot);
+        Self {
+            entries: vec![entry],
+            position: 0,
+            pending: entry,
+            pending_since: Instant::now(),
+        }
+    }
+
+    /// Follow the view of every step, recording it once it settles. A new
+    /// entry drops the entries ahead of the current one.
+    pub fn observe(&mut self, mandelbrot: &Mandelbrot) {
+        let entry = Entry::of(mandelbrot);
+        if entry != self.pending {
+            self.pending = entry;
+            self.pending_since = Instant::now();
+            return;
+        }
+        if entry == self.entries[self.position] || self.pending_since.elapsed() < SETTLE_TIME {
+            return;
+        }
+        self.entries.truncate(self.position + 1);
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.position = self.entries.len() - 1;
+    }
+
+    /// The previous viewport, or the current one if the view has moved
+    /// since it was recorded.
+    pub fn back(&mut self, mandelbrot: &Mandelbrot) -> Option<Entry> {
+        if Entry::of(mandelbrot) == self.entries[self.position] {
+            self.position = self.position.checked_sub(1)?;
+        } else {
+            // not recorded yet: keep it to come forward to
+            self.entries.truncate(self.position + 1);
+            self.entries.push(Entry::of(mandelbrot));
+        }
+        Some(self.go_to(self.position))
+    }
+
+    pub fn forward(&mut self) -> Option<Entry> {
+        if self.position + 1 >= self.entries.len() {
+            return None;
+        }
+        Some(self.go_to(self.position + 1))
+    }
+
+    fn go_to(&mut self, position: usize) -> Entry {
+        self.position = position;
+        let entry = self.entries[position];
+        self.pending = entry;
+        entry
+    }
+}
@@ -0,0 +1,77 @@
+use serde::Deserialize;
+
+use crate::canvas::{Canvas, LINE_HEIGHT};
+use crate::mandelbrot::{Fractal, View};
+
+// one file per formula in cards/
+fn card_file(fractal: Fractal) -> &'static str {
+    match fractal {
+        Fractal::Mandelbrot => include_str!("../cards/mandelbrot.toml"),
+        Fractal::BurningShip => include_str!("../cards/burning_ship.toml"),
+        Fractal::Tricorn => include_str!("../cards/tricorn.toml"),
+        Fractal::Newton => include_str!("../cards/newton.toml"),
+    }
+}
+
+const MARGIN: usize = 5;
+const TOP: usize = 110;
+
+#[derive(Deserialize, Debug)]
+pub struct StartingPoint {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+    pub scale: f64,
+}
+
+impl StartingPoint {
+    pub fn view(&self) -> View {
+        View {
+            center_x: self.x,
+            center_y: self.y,
+            scale: self.scale,
+        }
+    }
+}
+
+/// Explanation of a fractal formula with places worth visiting.
+#[derive(Deserialize, Debug)]
+pub struct InfoCard {
+    pub name: String,
+    pub definition: Vec<String>,
+    #[serde(default)]
+    pub parameters: Vec<String>,
+    #[serde(default)]
+    pub start: Vec<StartingPoint>,
+}
+
+impl InfoCard {
+    pub fn builtin(fractal: Fractal) -> Self {
+        toml::from_str(card_file(fractal)).expect("malformed info card")
+    }
+
+    fn lines(&self, close_key: &str) -> Vec<String> {
+        let mut lines = vec![self.name.clone(), String::new()];
+        lines.extend(self.definition.iter().cloned());
+        if !self.parameters.is_empty() {
+            lines.push(String::new());
+            lines.extend(self.parameters.iter().cloned());
+        }
+        if !self.start.is_empty() {
+            lines.push(String::new());
+            lines.push(String::from("Press the number to go to:"));
+            for (i, start) in self.start.iter().take(9).enumerate() {
+                lines.push(format!(" {}: {}", i + 1, start.name));
+            }
+        }
+        lines.push(String::new());
+        lines.push(format!("({} to close)", close_key));
+        lines
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, close_key: &str) {
+        for (i, line) in self.lines(close_key).iter().enumerate() {
+            canvas.draw_text(MARGIN, TOP + i * LINE_HEIGHT, line, 1);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::animation;
+use crate::double_double::{self, DoubleDouble};
+use crate::export;
+use crate::mandelbrot::{check_iterations, check_scale, Mandelbrot, RenderOptions};
+use crate::palette::Palette;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::video::{Video, VideoOptions};
+
+pub const KEYFRAME_FILE: &str = "keyframes.toml";
+// frames from a keyframe marked in the window to the next one
+const DEFAULT_FRAMES: usize = 60;
+// frames from a keyframe to the next at most: over an hour at 24 fps
+const MAX_FRAMES: usize = 100_000;
+
+fn default_frames() -> usize {
+    DEFAULT_FRAMES
+}
+
+/// How the frames of a segment are spread between its keyframes.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    /// evenly
+    Linear,
+    /// starting slowly
+    EaseIn,
+    /// ending slowly
+    EaseOut,
+    /// starting and ending slowly
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    // The part of the way from one keyframe to the next at the part `t` of
+    // the frames between them.
+    fn apply(self, t: f64) -> f64 {
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Self::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// A view marked on the way of an animation, with the palette and the
+/// iteration limit it is seen with, and the frames to the next keyframe.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    pub x: f64,
+    pub y: f64,
+    /// low parts of the center in double-double, for the deep views
+    #[serde(default, skip_serializing_if = "double_double::is_exact")]
+    pub x_lo: f64,
+    #[serde(default, skip_serializing_if = "double_double::is_exact")]
+    pub y_lo: f64,
+    pub scale: f64,
+    /// iteration limit, or None for the auto limit at the scale of each frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<usize>,
+    #[serde(default = "default_frames")]
+    pub frames: usize,
+    #[serde(default)]
+    pub easing: Easing,
+    // tables go after the plain values in TOML
+    pub palette: Palette,
+}
+
+impl Keyframe {
+    pub fn from_view(mandelbrot: &Mandelbrot) -> Self {
+        let (x, y) = mandelbrot.center();
+        Self {
+            x: x.hi,
+            y: y.hi,
+            x_lo: x.lo,
+            y_lo: y.lo,
+            scale: mandelbrot.scale,
+            iterations: (!mandelbrot.auto_iterations).then_some(mandelbrot.max_round),
+            frames: DEFAULT_FRAMES,
+            easing: Easing::default(),
+            palette: mandelbrot.options.palette.clone(),
+        }
+    }
+
+    // An error for the values the command line would reject.
+    fn check(&self) -> Result<(), String> {
+        check_scale(self.scale)?;
+        if let Some(iterations) = self.iterations {
+            check_iterations(iterations)?;
+        }
+        if self.frames > MAX_FRAMES {
+            return Err(format!("frames must be at most {}", MAX_FRAMES));
+        }
+        if ![self.x, self.y, self.x_lo, self.y_lo]
+            .iter()
+            .all(|value| value.is_finite())
+        {
+            return Err(String::from("the center must be finite"));
+        }
+        Ok(())
+    }
+
+    fn center(&self) -> (DoubleDouble, DoubleDouble) {
+        (
+            DoubleDouble::from(self.x) + self.x_lo,
+            DoubleDouble::from(self.y) + self.y_lo,
+        )
+    }
+
+    fn max_round(&self) -> usize {
+        self.iterations
+            .unwrap_or_else(|| Mandelbrot::auto_max_round(self.scale))
+    }
+}
+
+/// A frame of the animation through the keyframes.
+pub struct Frame {
+    pub center: (DoubleDouble, DoubleDouble),
+    pub scale: f64,
+    pub max_round: usize,
+    pub palette: Palette,
+}
+
+/// The keyframe file, in the order of the animation.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Keyframes {
+    #[serde(default)]
+    pub keyframe: Vec<Keyframe>,
+}
+
+impl Keyframes {
+    /// Read the keyframe file. A missing file has no keyframes; one with a
+    /// value the command line would reject is an error.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let keyframes: Self = match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        for (number, keyframe) in keyframes.keyframe.iter().enumerate() {
+            keyframe.check().map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("keyframe {}: {}", number + 1, e),
+                )
+            })?;
+        }
+        Ok(keyframes)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents =
+            toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents)
+    }
+
+    /// The frames of the animation, one at a time: each segment from a
+    /// keyframe to the next in the frames of the first, eased as it says,
+    /// then the last keyframe. The scale changes exponentially like in the
+    /// zoom animations, the center moves in double-double, the iteration
+    /// limit of the auto mode follows the scale, and the palettes are
+    /// blended.
+    pub fn frames(&self) -> impl Iterator<Item = Frame> + '_ {
+        let segments = self.keyframe.windows(2).flat_map(|pair| {
+            let (from, to) = (&pair[0], &pair[1]);
+            (0..from.frames).map(move |number| {
+                let t = from.easing.apply(number as f64 / from.frames as f64);
+                segment_frame(from, to, t)
+            })
+        });
+        let last = self.keyframe.last().map(|last| Frame {
+            center: last.center(),
+            scale: last.scale,
+            max_round: last.max_round(),
+            palette: last.palette.clone(),
+        });
+        segments.chain(last)
+    }
+}
+
+// The frame at the part `t` of the way from `from` to `to`, like
+// animation::zoom_view() with the center in double-double.
+fn segment_frame(from: &Keyframe, to: &Keyframe, t: f64) -> Frame {
+    let scale = from.scale * (to.scale / from.scale).powf(t);
+    let weight = if from.scale == to.scale {
+        1.0 - t
+    } else {
+        (scale - to.scale) / (from.scale - to.scale)
+    };
+    let ((from_x, from_y), (to_x, to_y)) = (from.center(), to.center());
+    let center = (
+        to_x + (from_x - to_x) * weight,
+        to_y + (from_y - to_y) * weight,
+    );
+    let max_round = match (from.iterations, to.iterations) {
+        (None, None) => Mandelbrot::auto_max_round(scale),
+        _ => {
+            let (start, end) = (from.max_round() as f64, to.max_round() as f64);
+            (start * (end / start).powf(t)).round() as usize
+        }
+    };
+    let palette = if from.palette == to.palette || t == 0.0 {
+        from.palette.clone()
+    } else {
+        from.palette.blend(&to.palette, t)
+    };
+    Frame {
+        center,
+        scale,
+        max_round,
+        palette,
+    }
+}
+
+/// Render the animation through `keyframes` at `width`x`height` with
+/// `options` into `dir/frame_NNNNN.png`.
+pub fn export_frames(
+    keyframes: &Keyframes,
+    width: u32,
+    height: u32,
+    options: &RenderOptions,
+    dir: &Path,
+) -> Result<(), png::EncodingError> {
+    fs::create_dir_all(dir)?;
+    render_frames(keyframes, width, height, options, |number, image| {
+        let path = animation::frame_path(dir, number);
+        export::save_png(&path, width, height, image)?;
+        info!("frame {}: {}", number + 1, path.display());
+        Ok(())
+    })
+}
+
+/// Render the animation through `keyframes` like export_frames() and
+/// encode it into the video file at `path` with ffmpeg.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_video(
+    keyframes: &Keyframes,
+    width: u32,
+    height: u32,
+    options: &RenderOptions,
+    path: &Path,
+    video_options: &VideoOptions,
+) -> Result<(), png::EncodingError> {
+    let mut video = Video::start(path, width, height, video_options)?;
+    render_frames(keyframes, width, height, options, |number, image| {
+        video.write(image)?;
+        info!("frame {}", number + 1);
+        Ok(())
+    })?;
+    Ok(video.finish()?)
+}
+
+// Render the frames of the animation in order, passing each with its
+// number to `finished`.
+fn render_frames(
+    keyframes: &Keyframes,
+    width: u32,
+    height: u32,
+    options: &RenderOptions,
+    mut finished: impl FnMut(usize, &[u8]) -> Result<(), png::EncodingError>,
+) -> Result<(), png::EncodingError> {
+    if keyframes.keyframe.len() < 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "an animation needs at least two keyframes",
+        )
+        .into());
+    }
+    let mut image = vec![0_u8; 4 * width as usize * height as usize];
+    for (number, frame) in keyframes.frames().enumerate() {
+        let options = RenderOptions {
+            palette: frame.palette,
+            ..options.clone()
+        };
+        animation::render_precise(
+            width,
+            height,
+            &options,
+            frame.center,
+            frame.scale,
+            frame.max_round,
+            &mut image,
+        );
+        finished(number, &image)?;
+    }
+    Ok(())
+}
@@ -1,423 +1,1720 @@
-use env_logger;
-use font8x8::{UnicodeFonts, BASIC_FONTS};
+mod action;
+mod animation;
+mod annotate;
+mod bench;
+mod bookmark;
+mod buddhabrot;
+mod canvas;
+mod caption;
+mod cli;
+mod click;
+mod config;
+mod controls;
+mod coords;
+mod cost;
+mod cycling;
+mod daily;
+mod data;
+mod double_double;
+mod explain;
+mod export;
+#[cfg(not(target_arch = "wasm32"))]
+mod farm;
+mod formula;
+mod framing;
+#[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+mod gamepad;
+mod glide;
+mod gpu;
+mod help;
+mod histogram;
+mod history;
+mod info_card;
+mod keyframes;
+mod mandelbrot;
+mod memory;
+mod minimap;
+mod orbit;
+mod palette;
+mod palette_editor;
+mod parallel;
+mod platform;
+mod poster;
+mod progress;
+mod random;
+mod rays;
+mod renderer;
+#[cfg(all(feature = "rhai", not(target_arch = "wasm32")))]
+mod rhai_script;
+mod ruler;
+mod script;
+mod sdf;
+#[cfg(not(target_arch = "wasm32"))]
+mod serve;
+mod session;
+mod settings;
+mod simd;
+mod tile;
+mod touch;
+mod transect;
+mod tutorial;
+mod verify;
+#[cfg(not(target_arch = "wasm32"))]
+mod video;
+
+use action::{Action, Bindings};
+use animation::AnimationJob;
+use bookmark::{Bookmarks, BOOKMARK_FILE};
+use buddhabrot::Buddhabrot;
+use canvas::Canvas;
+use caption::Caption;
+use clap::Parser;
+use cli::Cli;
+use click::LeftButton;
+use config::Config;
+use controls::Controls;
+use cycling::{ColorCycling, IterationColors};
+use explain::Explanation;
+use framing::{ExportPreview, Framing};
+use glide::ZoomGlide;
+use gpu::GpuColoring;
+use help::Help;
+use history::History;
+use info_card::InfoCard;
+use keyframes::{Keyframe, Keyframes, KEYFRAME_FILE};
 use log::{error, info};
+use mandelbrot::{Mandelbrot, RenderOptions, Variation, View};
+use orbit::OrbitStats;
+use palette::{Bands, Palette};
 use pixels::{Error, Pixels, SurfaceTexture};
-use rayon::prelude::*;
-use std::time::{Duration, Instant};
-use winit::dpi::{LogicalSize, PhysicalPosition};
-use winit::event::{Event, VirtualKeyCode};
+use platform::{Clipboard, Instant};
+use renderer::Renderer;
+use ruler::Ruler;
+use script::Session;
+use session::SavedSession;
+use settings::{Settings, SettingsPanel};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use touch::Touches;
+use tutorial::Tutorial;
+use winit::dpi::{LogicalSize, PhysicalSize};
+use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
+use winit::window::{Fullscreen, Window, WindowBuilder};
 use winit_input_helper::WinitInputHelper;
 
+// window size unless the configuration file sets one
 const WINDOW_WIDTH: u32 = 640;
 const WINDOW_HEIGHT: u32 = 480;
+// image size of the headless render unless given on the command line
+const RENDER_WIDTH: u32 = 640;
+const RENDER_HEIGHT: u32 = 480;
+
+// smaller selections are taken as a click and ignored
+const MIN_SELECTION: f32 = 4.0;
+
+// While the view is being scrolled, dragged or auto-zoomed, it is drawn at
+// a lower resolution chosen from the time of the last full frame, and at
+// full resolution once the input has been still for SETTLE_TIME.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+const PREVIEW_THRESHOLDS: [(Duration, usize); 2] = [
+    (Duration::from_millis(100), 8),
+    (Duration::from_millis(20), 4),
+];
 
-struct Mandelbrot {
-    drawn: bool,
-    center_x: f64,
-    center_y: f64,
-    scale: f64,
-    max_round: usize,
-    info: bool,
-    rendering_time: Duration,
-    min_scale: f64,
-    max_scale: f64,
+// Block size of the preview for full frames taking `full_frame_time`.
+fn preview_block(full_frame_time: Duration) -> usize {
+    PREVIEW_THRESHOLDS
+        .iter()
+        .find(|(threshold, _)| full_frame_time > *threshold)
+        .map_or(1, |(_, block)| *block)
 }
 
-impl Mandelbrot {
-    fn new() -> Self {
-        Self {
-            drawn: false,
-            center_x: -0.7,
-            center_y: 0.0,
-            scale: 0.005,
-            max_round: 512,
-            info: true,
-            rendering_time: Duration::ZERO,
-            min_scale: f64::EPSILON,
-            max_scale: 0.1,
-        }
+// The experiment mode nudges the exponent, z0 and the relaxation in steps
+// of 1 / EXPERIMENT_STEPS and the bailout by EXPERIMENT_BAILOUT_FACTOR, and
+// previews the result until it is committed, whatever the frame time.
+const EXPERIMENT_STEPS: f64 = 20.0;
+const EXPERIMENT_BAILOUT_FACTOR: f64 = 1.25;
+const EXPERIMENT_MIN_PREVIEW: usize = 4;
+
+fn experiment_preview(full_frame_time: Duration) -> usize {
+    preview_block(full_frame_time).max(EXPERIMENT_MIN_PREVIEW)
+}
+
+// `value` moved by `steps` steps of the experiment mode, rounded so that
+// repeated steps land on round values.
+fn nudge(value: f64, steps: f64) -> f64 {
+    (value * EXPERIMENT_STEPS + steps).round() / EXPERIMENT_STEPS
+}
+
+// size of the image exported from the window unless given on the command
+// line
+const EXPORT_WIDTH: u32 = 1920;
+const EXPORT_HEIGHT: u32 = 1080;
+const ANIMATION_DIR: &str = "frames";
+const ANIMATION_FRAMES: usize = 120;
+const KEYFRAME_DIR: &str = "keyframes";
+
+// The palette given on the command line, or the default one.
+fn cli_palette(cli: &Cli) -> std::io::Result<Palette> {
+    match &cli.palette {
+        Some(name) => Palette::from_name_or_path(name),
+        None => Ok(Palette::default()),
     }
+}
 
-    fn request_redraw(&mut self) {
-        self.drawn = false;
+fn cli_bands(cli: &Cli) -> Bands {
+    Bands {
+        count: cli.bands as usize,
+        offset: cli.band_offset as usize,
     }
+}
+
+fn cli_caption(cli: &Cli) -> Option<Caption> {
+    cli.caption.as_ref().map(|text| Caption {
+        text: text.clone(),
+        corner: cli.caption_corner,
+        text_scale: cli.caption_scale.map(|scale| scale as usize),
+    })
+}
 
-    fn move_center(&mut self, x: f64, y: f64) {
-        self.center_x += x * self.scale;
-        self.center_y += y * self.scale;
-        info!("center ({}, {})", self.center_x, self.center_y);
+fn cli_variation(cli: &Cli) -> Variation {
+    Variation {
+        bailout: cli.bailout,
+        z0_x: cli.z0_x,
+        z0_y: cli.z0_y,
+        relaxation: cli.relaxation,
     }
+}
 
-    fn set_center(&mut self, x: f64, y: f64) {
-        self.center_x += (x - (WINDOW_WIDTH as f64 / 2.0)) * self.scale;
-        self.center_y += ((WINDOW_HEIGHT as f64 / 2.0) - y) * self.scale;
-        info!("center ({}, {})", self.center_x, self.center_y);
+// The view given on the command line, with the coordinates left out taken
+// from `home`.
+fn cli_view(cli: &Cli, home: View) -> View {
+    View {
+        center_x: cli.x.unwrap_or(home.center_x),
+        center_y: cli.y.unwrap_or(home.center_y),
+        scale: cli.scale.unwrap_or(home.scale),
     }
+}
 
-    fn zoom(&mut self, in_out: f64) -> bool {
-        self.scale = self.scale * 1.07_f64.powf(-1.0 * in_out);
-        self.max_round = if self.scale > 0.000005 { 512 } else { 1024 };
-        info!("scale {}, max_round {}", self.scale, self.max_round);
+// --low-memory forces the mode on, and the configuration file decides
+// before the available memory does.
+fn low_memory(cli: &Cli, config: &Config) -> bool {
+    if cli.low_memory {
+        return true;
+    }
+    config.low_memory.unwrap_or_else(|| {
+        let low = memory::is_low();
+        if low {
+            info!("little memory available, using the low-memory mode");
+        }
+        low
+    })
+}
 
-        if self.scale > self.max_scale {
-            self.scale = self.max_scale;
-            return false;
+// Copy `source` into `frame` moved by (shift_x, shift_y) pixels, leaving
+// black what it no longer covers.
+fn blit_shifted(
+    frame: &mut [u8],
+    source: &[u8],
+    width: usize,
+    height: usize,
+    (shift_x, shift_y): (isize, isize),
+) {
+    for (row, pixels) in frame.chunks_exact_mut(4 * width).take(height).enumerate() {
+        pixels.chunks_exact_mut(4).for_each(|pixel| {
+            pixel.copy_from_slice(&[0x00, 0x00, 0x00, 0xff]);
+        });
+        let source_row = row as isize - shift_y;
+        if source_row < 0 || source_row >= height as isize {
+            continue;
         }
-        if self.scale < self.min_scale {
-            info!("scale is smaller than machine epsilon: {}", self.scale);
-            self.scale = self.min_scale;
-            return false;
+        let start = shift_x.clamp(0, width as isize) as usize;
+        let end = (width as isize + shift_x).clamp(0, width as isize) as usize;
+        if start >= end {
+            continue;
         }
-        true
+        let source_start = 4 * (source_row as usize * width + (start as isize - shift_x) as usize);
+        pixels[4 * start..4 * end]
+            .copy_from_slice(&source[source_start..source_start + 4 * (end - start)]);
     }
+}
 
-    fn reset(&mut self) {
-        self.drawn = false;
-        self.center_x = -0.7;
-        self.center_y = 0.0;
-        self.scale = 0.005;
-        self.max_round = 512;
-        self.info = true;
-        self.rendering_time = Duration::ZERO;
-        self.min_scale = f64::EPSILON;
-        self.max_scale = 0.1;
+#[cfg(not(target_arch = "wasm32"))]
+fn cli_video(cli: &Cli) -> video::VideoOptions {
+    video::VideoOptions {
+        fps: cli.fps,
+        codec: cli.codec.clone(),
+        bitrate: cli.bitrate.clone(),
     }
+}
+
+fn render_to_file(cli: &Cli, low_memory: bool) -> Result<(), png::EncodingError> {
+    // `requires = "output"` guarantees the path is present in render mode
+    let output = cli.output.as_ref().unwrap();
+    let width = cli.width.unwrap_or(RENDER_WIDTH);
+    let height = cli.height.unwrap_or(RENDER_HEIGHT);
+    let options = RenderOptions {
+        fractal: cli.fractal.unwrap_or_default(),
+        exponent: cli.exponent,
+        norm: cli.norm,
+        norm_power: cli.norm_power,
+        antialias: cli.antialias,
+        bands: cli_bands(cli),
+        coloring: cli.coloring,
+        interior: cli.interior,
+        palette: cli_palette(cli)?,
+        variation: cli_variation(cli),
+        blend: cli.blend,
+        formula: cli.formula.clone(),
+    };
 
-    fn check_divergence(&self, pos_x: f64, pos_y: f64, max_round: usize) -> Option<usize> {
-        if pos_x >= 2.0 || pos_y >= 2.0 {
-            return Some(1);
+    if let Some(frames) = cli.frames {
+        let job = AnimationJob {
+            frames: frames as usize,
+            width,
+            height,
+            max_round: cli.iterations.map(|iterations| iterations as usize),
+            start: View::default(),
+            end: cli_view(cli, View::default()),
+            options,
         };
+        if let Some(interval) = cli.preview {
+            return animation::preview_frames(&job, output, interval as usize);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if cli.video {
+            return Ok(animation::export_video(&job, output, &cli_video(cli))?);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(listen) = &cli.farm {
+            return Ok(farm::export_frames(&job, output, listen)?);
+        }
+        return animation::export_frames(&job, output, cli.resume);
+    }
+
+    if let Some(path) = &cli.keyframes {
+        let keyframes = Keyframes::load(path)?;
+        #[cfg(not(target_arch = "wasm32"))]
+        if cli.video {
+            return keyframes::export_video(
+                &keyframes,
+                width,
+                height,
+                &options,
+                output,
+                &cli_video(cli),
+            );
+        }
+        return keyframes::export_frames(&keyframes, width, height, &options, output);
+    }
+
+    // a fitted view is framed at the size it was chosen for
+    let (view_width, view_height) = cli.fit.map_or((width, height), |_| cli.fit_from);
+    let mut mandelbrot = Mandelbrot::new(view_width, view_height);
+    let view = cli_view(cli, View::default());
+    mandelbrot.center_x = view.center_x;
+    mandelbrot.center_y = view.center_y;
+    mandelbrot.scale = view.scale;
+    mandelbrot.max_round = match cli.iterations {
+        Some(iterations) => iterations as usize,
+        None => Mandelbrot::auto_max_round(view.scale),
+    };
+    mandelbrot.options = options;
+    mandelbrot.info = false;
+    mandelbrot.low_memory = low_memory;
+    mandelbrot.subdivision = cli.subdivision;
+    mandelbrot.adaptive_iterations = cli.adaptive_iterations;
+    mandelbrot.iteration_tuning = !cli.no_iteration_tuning;
+    mandelbrot.set_auto_exposure(cli.auto_exposure);
+    if !cli.rays.is_empty() {
+        mandelbrot.set_ray_angles(cli.rays.clone());
+        mandelbrot.set_external_rays(true);
+    }
+    if let Some(format) = cli.data {
+        return Ok(data::save(&mandelbrot, format, output)?);
+    }
+    if cli.sdf {
+        return sdf::save(&mandelbrot, output, cli.note.as_deref().unwrap_or(""));
+    }
 
-        let mut xn: f64 = 0.0;
-        let mut yn: f64 = 0.0;
-        let mut xn_1_power: f64 = 0.0;
-        let mut yn_1_power: f64 = 0.0;
+    if cli.poster {
+        let fit = cli.fit.unwrap_or_default();
+        let note = cli.note.as_deref().unwrap_or("");
+        let caption = cli_caption(cli);
+        let size = (width, height);
+        return poster::save(&mandelbrot, size, fit, caption.as_ref(), output, note);
+    }
 
-        let mut round: usize = 1;
-        while round < max_round {
-            let xn_1 = xn;
-            let yn_1 = yn;
+    let mut frame = match cli.fit {
+        Some(fit) => framing::render(&mandelbrot, (width, height), fit),
+        #[cfg(not(target_arch = "wasm32"))]
+        None if cli.farm.is_some() => farm::render(&mandelbrot, cli.farm.as_ref().unwrap())?,
+        None => {
+            let mut frame = vec![0_u8; 4 * width as usize * height as usize];
+            mandelbrot.draw(&mut frame);
+            info!("{}", mandelbrot.rendering_time_message());
+            frame
+        }
+    };
 
-            xn = xn_1_power - yn_1_power + pos_x;
-            yn = 2.0 * xn_1 * yn_1 + pos_y;
+    if !cli.annotate.is_empty() {
+        let text_scale = match cli.text_scale {
+            Some(text_scale) => text_scale as usize,
+            None => annotate::auto_text_scale(height),
+        };
+        let mut canvas = Canvas::new(&mut frame, width as usize, height as usize);
+        annotate::annotate(&mut canvas, &mandelbrot, &cli.annotate, text_scale);
+    }
 
-            // faster than xn.powf(2.0) or nx.powi(2)
-            xn_1_power = xn * xn;
-            yn_1_power = yn * yn;
+    if let Some(caption) = cli_caption(cli) {
+        let view = match cli.fit {
+            Some(fit) => Framing::new(mandelbrot.view(), cli.fit_from, (width, height), fit).view,
+            None => mandelbrot.view(),
+        };
+        let mut canvas = Canvas::new(&mut frame, width as usize, height as usize);
+        caption.draw(&mut canvas, view, (width, height), 0);
+    }
 
-            if (xn_1_power + yn_1_power) >= 4.0 {
-                return Some(round);
-            }
-            round += 1
+    let note = cli.note.as_deref().unwrap_or("");
+    export::save_png_with_note(output, width, height, &frame, note)
+}
+
+fn main() -> Result<(), Error> {
+    platform::init_logger();
+    let mut cli = Cli::parse();
+
+    let config = match &cli.config {
+        Some(path) => Config::load(path).unwrap_or_else(|e| {
+            error!("failed to load {}: {}", path.display(), e);
+            std::process::exit(1);
+        }),
+        // scripts run the same way on every machine
+        None if cli.script.is_some() => Config::default(),
+        #[cfg(all(feature = "rhai", not(target_arch = "wasm32")))]
+        None if cli.rhai.is_some() => Config::default(),
+        None => Config::load_default().unwrap_or_else(|e| {
+            error!("failed to load the configuration file: {}", e);
+            Config::default()
+        }),
+    };
+    let threads = cli
+        .threads
+        .map(|threads| threads as usize)
+        .or(config.threads);
+    if let Err(e) = parallel::set_threads(threads) {
+        error!("failed to set the number of threads: {}", e);
+    }
+    let palette_given = cli.palette.is_some();
+    if !palette_given {
+        cli.palette = config.palette.clone();
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(address) = &cli.worker {
+        if let Err(e) = farm::work(address) {
+            error!("worker failed: {}", e);
+            std::process::exit(1);
         }
-        return None;
+        return Ok(());
     }
 
-    fn text(&mut self, frame: &mut [u8], x: usize, y: usize, text_string: &str) {
-        if y >= WINDOW_HEIGHT as usize || x >= WINDOW_WIDTH as usize {
-            return;
+    if cli.render {
+        if let Err(e) = render_to_file(&cli, low_memory(&cli, &config)) {
+            error!("render_to_file() failed: {}", e);
+            std::process::exit(1);
         }
-        for (i, chr) in text_string.chars().enumerate() {
-            let mut frame_index = 4 * (x + (i * 9) + (y * WINDOW_WIDTH as usize));
-            if chr != ' ' {
-                if let Some(glyph) = BASIC_FONTS.get(chr) {
-                    for bitmap in &glyph {
-                        for bit in 0..8 {
-                            match *bitmap & 1 << bit {
-                                0 => (),
-                                _ => {
-                                    let font_white: [u8; 12] = [
-                                        0xb0, 0xb0, 0xb0, 0xff, // white
-                                        0x00, 0x00, 0x00, 0xff, // black
-                                        0x00, 0x00, 0x00, 0xff, // black
-                                    ];
-
-                                    let pos = frame_index + (4 * bit);
-                                    let pixel = &mut frame[pos..(pos + 12)];
-                                    pixel.copy_from_slice(&font_white);
-
-                                    let font_black: [u8; 12] = [
-                                        0x00, 0x00, 0x00, 0xff, // black
-                                        0x00, 0x00, 0x00, 0xff, // black
-                                        0x00, 0x00, 0x00, 0xff, // black
-                                    ];
-
-                                    let pos = frame_index + (4 * (bit + WINDOW_WIDTH as usize));
-                                    let pixel = &mut frame[pos..(pos + 12)];
-                                    pixel.copy_from_slice(&font_black);
-
-                                    let pos =
-                                        frame_index + (4 * (bit + (2 * WINDOW_WIDTH) as usize));
-                                    let pixel = &mut frame[pos..(pos + 12)];
-                                    pixel.copy_from_slice(&font_black);
-                                }
-                            }
-                        }
-                        frame_index += 4 * WINDOW_WIDTH as usize;
-                    }
-                }
-            }
+        return Ok(());
+    }
+
+    if let Some(path) = &cli.script {
+        let (mandelbrot, controls) = start_view(
+            &cli,
+            &config,
+            cli.width.unwrap_or(WINDOW_WIDTH),
+            cli.height.unwrap_or(WINDOW_HEIGHT),
+        );
+        let mut session = Session::new(
+            mandelbrot,
+            controls,
+            bindings(&config),
+            LeftButton::new(double_click_interval(&config)),
+            Touches::new(double_click_interval(&config)),
+        );
+        session.data_format = cli.data.unwrap_or_default();
+        if let Err(e) = session.run_file(path) {
+            error!("{}", e);
+            std::process::exit(1);
         }
+        return Ok(());
     }
 
-    fn round_to_color(&self, round: usize) -> [u8; 4] {
-        let section_size = 256_usize;
-        let color_table: [(usize, usize, usize); 5] = [
-            (0x00, 0x00, 0x80),
-            (0x00, 0xff, 0x00),
-            (0xff, 0xff, 0x00),
-            (0x00, 0xff, 0xff),
-            (0x00, 0x00, 0xff),
-        ];
-
-        let table_number = round / section_size;
-        assert!(table_number + 1 < color_table.len());
-        let color_index = round % section_size;
-
-        let (r0, g0, b0) = color_table[table_number];
-        let (r1, g1, b1) = color_table[table_number + 1];
-        let interporation = |a, b| {
-            (((a * (section_size - color_index) + b * color_index) / section_size) & 0xff) as u8
-        };
+    #[cfg(all(feature = "rhai", not(target_arch = "wasm32")))]
+    if let Some(path) = &cli.rhai {
+        let (mandelbrot, _) = start_view(
+            &cli,
+            &config,
+            cli.width.unwrap_or(WINDOW_WIDTH),
+            cli.height.unwrap_or(WINDOW_HEIGHT),
+        );
+        if let Err(e) = rhai_script::run_file(path, mandelbrot) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-        let r = interporation(r0, r1);
-        let g = interporation(g0, g1);
-        let b = interporation(b0, b1);
+    if cli.list_bindings {
+        for (action, keys) in bindings(&config).list() {
+            println!("{}: {}", action.name(), keys.join(", "));
+        }
+        return Ok(());
+    }
 
-        [r, g, b, 0xff]
+    if cli.bench {
+        bench::run();
+        return Ok(());
     }
 
-    fn draw(&mut self, frame: &mut [u8]) {
-        if self.drawn {
-            return;
+    if cli.verify {
+        if !verify::run() {
+            std::process::exit(1);
         }
+        return Ok(());
+    }
 
-        let start_time = Instant::now();
-        let min_x = self.center_x - ((self.scale * WINDOW_WIDTH as f64) / 2.0);
-        let max_y = self.center_y + ((self.scale * WINDOW_HEIGHT as f64) / 2.0);
-
-        frame
-            .par_chunks_exact_mut(4)
-            .enumerate()
-            .for_each(|(i, pixel)| {
-                let x = min_x + ((i % WINDOW_WIDTH as usize) as f64) * self.scale;
-                let y = max_y - ((i / WINDOW_WIDTH as usize) as f64) * self.scale;
-                let rgba = match self.check_divergence(x, y, self.max_round) {
-                    Some(round) => self.round_to_color(round),
-                    None => [0x00, 0x00, 0x00, 0xff],
-                };
+    if cli.daily {
+        // `requires = "output"` guarantees the path is present
+        let output = cli.output.as_ref().unwrap();
+        if let Err(e) = daily::render(
+            cli.date.unwrap_or_else(daily::today),
+            cli.width.unwrap_or(daily::WIDTH),
+            cli.height.unwrap_or(daily::HEIGHT),
+            low_memory(&cli, &config),
+            output,
+        ) {
+            error!("daily::render() failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-                pixel.copy_from_slice(&rgba);
-            });
-        self.rendering_time = start_time.elapsed();
-        let rendering_time_msg = format!(
-            "rendering time: {}.{:04}[sec]",
-            self.rendering_time.as_secs(),
-            self.rendering_time.subsec_nanos() / 1000000
-        );
-        info!("{}", rendering_time_msg);
-        if self.info {
-            self.text(frame, 5, 5, format!("x: {}", self.center_x).as_str());
-            self.text(frame, 5, 17, format!("y: {}", self.center_y).as_str());
-            self.text(frame, 5, 29, format!("scale: {}", self.scale).as_str());
-            self.text(frame, 5, 41, rendering_time_msg.as_str());
+    #[cfg(not(target_arch = "wasm32"))]
+    if cli.serve {
+        let options = serve::ServeOptions {
+            listen: cli.listen.clone(),
+            jobs: cli.jobs as usize,
+            queue: cli.queue as usize,
+            tile_cache: cli.tile_cache as usize,
+        };
+        if let Err(e) = serve::run(&options) {
+            error!("serve::run() failed: {}", e);
+            std::process::exit(1);
         }
+        return Ok(());
+    }
 
-        self.drawn = true;
+    let session = if cli.fresh {
+        None
+    } else {
+        SavedSession::default_path().and_then(|path| {
+            SavedSession::load(&path).unwrap_or_else(|e| {
+                error!("failed to load {}: {}", path.display(), e);
+                None
+            })
+        })
+    };
+    // the command line overrides the session, which overrides the
+    // configuration file
+    if let Some(session) = &session {
+        if !palette_given {
+            cli.palette = Some(session.palette.clone());
+        }
+        cli.fractal = cli.fractal.or(Some(session.fractal));
     }
-}
 
-fn main() -> Result<(), Error> {
-    env_logger::init();
+    let window_width = config.width.unwrap_or(WINDOW_WIDTH);
+    let window_height = config.height.unwrap_or(WINDOW_HEIGHT);
+
     let event_loop = EventLoop::new();
-    let mut input = WinitInputHelper::new();
     let window = {
-        let size = LogicalSize::new(WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
+        let size = LogicalSize::new(window_width as f64, window_height as f64);
+        let inner_size = session.as_ref().map_or(size, |session| {
+            LogicalSize::new(
+                session.window_width.max(window_width) as f64,
+                session.window_height.max(window_height) as f64,
+            )
+        });
         WindowBuilder::new()
             .with_title("Mandelbrot")
-            .with_inner_size(size)
+            .with_inner_size(inner_size)
             .with_min_inner_size(size)
             .build(&event_loop)
             .unwrap()
     };
 
-    let mut pixels = {
-        let window_size = window.inner_size();
-        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WINDOW_WIDTH, WINDOW_HEIGHT, surface_texture)?
+    // browsers only hand out the GPU asynchronously: the window starts once
+    // its frame is created
+    #[cfg(target_arch = "wasm32")]
+    {
+        platform::attach_canvas(&window);
+        wasm_bindgen_futures::spawn_local(async move {
+            let window_size = window.inner_size();
+            let surface_texture =
+                SurfaceTexture::new(window_size.width, window_size.height, &window);
+            let pixels = Pixels::new_async(window_width, window_height, surface_texture).await;
+            match pixels {
+                Ok(pixels) => run_window(cli, config, session, event_loop, window, pixels),
+                Err(e) => error!("failed to create the frame: {}", e),
+            }
+        });
+        Ok(())
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let pixels = {
+            let window_size = window.inner_size();
+            let surface_texture =
+                SurfaceTexture::new(window_size.width, window_size.height, &window);
+            Pixels::new(window_width, window_height, surface_texture)?
+        };
+        run_window(cli, config, session, event_loop, window, pixels)
+    }
+}
+
+/// The view and the keys of a session in a window of `width` by `height`
+/// frame pixels, set up from the command line and the configuration file.
+fn start_view(cli: &Cli, config: &Config, width: u32, height: u32) -> (Mandelbrot, Controls) {
+    let mut mandelbrot = Mandelbrot::new(width, height);
+    // the command line overrides the configuration file
+    let home = View::default();
+    mandelbrot.home = cli_view(
+        cli,
+        View {
+            center_x: config.x.unwrap_or(home.center_x),
+            center_y: config.y.unwrap_or(home.center_y),
+            scale: config.scale.unwrap_or(home.scale),
+        },
+    );
+    if let Some(formula) = config.auto_iterations {
+        mandelbrot.iteration_formula = formula;
+    }
+    mandelbrot.reset();
+    mandelbrot.low_memory = low_memory(cli, config);
+    mandelbrot.subdivision = cli.subdivision;
+    mandelbrot.adaptive_iterations = cli.adaptive_iterations;
+    mandelbrot.iteration_tuning = !cli.no_iteration_tuning;
+    mandelbrot.tile_order = cli.tile_order;
+    mandelbrot.hud = config.hud;
+    let iterations = cli.iterations.map(|iterations| iterations as usize);
+    if let Some(iterations) = iterations.or(config.iterations) {
+        mandelbrot.max_round = iterations;
+        mandelbrot.auto_iterations = false;
+    }
+    let mut controls = Controls::new(cli.palette.as_deref());
+    mandelbrot.options.palette = controls.palette().clone();
+    mandelbrot.options.fractal = cli.fractal.unwrap_or_default();
+    mandelbrot.options.exponent = cli.exponent;
+    mandelbrot.options.norm = cli.norm;
+    mandelbrot.options.norm_power = cli.norm_power;
+    mandelbrot.options.bands = cli_bands(cli);
+    mandelbrot.options.coloring = cli.coloring;
+    mandelbrot.options.interior = cli.interior;
+    if !cli.rays.is_empty() {
+        mandelbrot.set_ray_angles(cli.rays.clone());
+        mandelbrot.set_external_rays(true);
+    }
+    mandelbrot.options.antialias = cli.antialias;
+    mandelbrot.options.variation = cli_variation(cli);
+    mandelbrot.options.blend = cli.blend;
+    mandelbrot.options.formula = cli.formula.clone();
+    mandelbrot.set_auto_exposure(cli.auto_exposure);
+    if let Some(stages) = &config.reset {
+        controls.reset_stages = stages.clone();
+    }
+    controls.remember_start(&mandelbrot);
+    (mandelbrot, controls)
+}
+
+fn bindings(config: &Config) -> Bindings {
+    let mut bindings = Bindings::default();
+    for key in &config.unbind {
+        bindings.unbind(*key);
+    }
+    for binding in &config.bind {
+        bindings.bind(binding.key, binding.action);
+    }
+    bindings
+}
+
+fn double_click_interval(config: &Config) -> Duration {
+    config
+        .double_click_ms
+        .map_or(click::DOUBLE_CLICK_INTERVAL, Duration::from_millis)
+}
+
+/// Switch the window to borderless full screen on its monitor, or back,
+/// and return the size of the frame for it: the resolution of the monitor,
+/// or `windowed`.
+fn set_fullscreen(window: &Window, fullscreen: bool, windowed: (u32, u32)) -> (u32, u32) {
+    if !fullscreen {
+        window.set_fullscreen(None);
+        return windowed;
+    }
+    window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+    window
+        .current_monitor()
+        .map(|monitor| monitor.size())
+        .filter(|size| size.width > 0 && size.height > 0)
+        .map_or(windowed, |size| (size.width, size.height))
+}
+
+/// Save what the window shows to the session file, with the size of the
+/// window (`windowed` while it is full screen).
+fn save_session(
+    mandelbrot: &Mandelbrot,
+    controls: &Controls,
+    palette: Option<&str>,
+    window: &Window,
+    windowed: Option<PhysicalSize<u32>>,
+) {
+    let path = match SavedSession::default_path() {
+        Some(path) => path,
+        None => return,
+    };
+    // a palette that is not a preset came from the palette file
+    let current = controls.palette();
+    let palette = match palette {
+        Some(file) if !Palette::presets().contains(current) => file.to_string(),
+        _ => current.name.clone(),
     };
+    let size = match (window.fullscreen(), windowed) {
+        (Some(_), Some(size)) => size,
+        _ => window.inner_size(),
+    };
+    let size = size.to_logical::<u32>(window.scale_factor());
+    let session = SavedSession::of(mandelbrot, palette, (size.width, size.height));
+    if let Err(e) = session.save(&path) {
+        error!("failed to save {}: {}", path.display(), e);
+    }
+}
 
-    let mut mandelbrot = Mandelbrot::new();
-    let mut pressed_pos_x = 0.0;
-    let mut pressed_pos_y = 0.0;
-    let mut pressed_time = Instant::now();
-    let mut dobule_clicked = false;
-    let mut shiftkey_pressed = false;
-    let mut altkey_pressed = false;
-    let mut auto_zoom_param = 0.0;
+/// Handle the input of the window and draw its frames until the program
+/// quits, starting from the view of `session` when given and saving the
+/// session on quit.
+fn run_window(
+    cli: Cli,
+    config: Config,
+    session: Option<SavedSession>,
+    event_loop: EventLoop<()>,
+    window: Window,
+    mut pixels: Pixels,
+) -> ! {
+    // the frame has this size in the window, and the monitor's in full
+    // screen
+    let frame_size = (
+        config.width.unwrap_or(WINDOW_WIDTH),
+        config.height.unwrap_or(WINDOW_HEIGHT),
+    );
+    let (mut window_width, mut window_height) = frame_size;
+    let mut input = WinitInputHelper::new();
+    let (mut mandelbrot, mut controls) = start_view(&cli, &config, window_width, window_height);
+    let view_given = cli.x.is_some() || cli.y.is_some() || cli.scale.is_some();
+    if let Some(session) = session.filter(|_| !view_given && cli.iterations.is_none()) {
+        session.apply_view(&mut mandelbrot);
+    }
+    // size of the window before it went full screen, for the session
+    let mut windowed_size = None;
+    if cli.fullscreen {
+        windowed_size = Some(window.inner_size());
+        (window_width, window_height) = set_fullscreen(&window, true, frame_size);
+        pixels.resize_buffer(window_width, window_height);
+        mandelbrot.resize(window_width, window_height);
+    }
+    let mut left_button = LeftButton::new(double_click_interval(&config));
+    let mut touches = Touches::new(double_click_interval(&config));
+    let mut zoom_glide = ZoomGlide::default();
+    // what the fingers did since the input was last handled
+    let mut touch_actions = Vec::new();
+    let mut last_interaction = Instant::now();
+    // parameters of the formula are nudged and previewed until committed
+    let mut experiment = false;
+    let mut full_frame_time = Duration::ZERO;
+    let bookmark_path = Path::new(BOOKMARK_FILE);
+    let mut bookmarks = Bookmarks::load(bookmark_path).unwrap_or_else(|e| {
+        error!("failed to load {}: {}", BOOKMARK_FILE, e);
+        Bookmarks::default()
+    });
+    let keyframe_path = Path::new(KEYFRAME_FILE);
+    let mut keyframes = Keyframes::load(keyframe_path).unwrap_or_else(|e| {
+        error!("failed to load {}: {}", KEYFRAME_FILE, e);
+        Keyframes::default()
+    });
+    // one export of each animation at a time
+    let exporting_animation = Arc::new(AtomicBool::new(false));
+    let exporting_keyframes = Arc::new(AtomicBool::new(false));
+    let mut history = History::new(&mandelbrot);
+    let bindings = bindings(&config);
+    let mut tutorial = Tutorial::default();
+    let mut info_card = InfoCard::builtin(mandelbrot.options.fractal);
+    let mut info_card_visible = false;
+    let mut help = Help::default();
+    let mut orbit: Option<OrbitStats> = None;
+    let mut explanation: Option<Explanation> = None;
+    // measurement between two clicked points, while the left button measures
+    let mut ruler: Option<Ruler> = None;
+    // corners of the zoom rectangle being dragged with the right button
+    let mut selection: Option<((f32, f32), (f32, f32))> = None;
+    // palette cycling of the frames colored on the CPU
+    let mut color_cycling = ColorCycling::default();
+    // created after the first frame is shown, at the start with --shader
+    let mut gpu_coloring: Option<GpuColoring> = None;
+    let mut gpu_warmed_up = false;
+    mandelbrot.gpu_status = Some(String::from("warming up"));
+    let shader = cli.shader.clone();
+    if shader.is_some() {
+        gpu_coloring = Some(GpuColoring::new(
+            pixels.context(),
+            pixels.render_texture_format(),
+            shader.as_deref(),
+        ));
+        mandelbrot.set_gpu_coloring(true);
+    }
+    // replaces the escape-time image while it is set
+    let mut buddhabrot: Option<Buddhabrot> = None;
+    // the low-memory mode draws on the event loop instead, without a copy
+    // of the frame
+    let mut renderer = Renderer::default();
+    // last frame of the renderer, before the overlays
+    let mut drawn_frame = Vec::new();
+    let mut clipboard = Clipboard::default();
+    let mut settings_panel = SettingsPanel::default();
+    // framing of the image export, shown until the export starts
+    let mut export_preview: Option<ExportPreview> = None;
+    let export_size = (
+        cli.width.unwrap_or(EXPORT_WIDTH),
+        cli.height.unwrap_or(EXPORT_HEIGHT),
+    );
+    let export_fit = cli.fit.unwrap_or_default();
+    let export_caption = cli_caption(&cli);
+    #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+    let mut gamepads = gamepad::Gamepads::new(config.gamepad);
 
     event_loop.run(move |event, _, control_flow| {
         if let Event::RedrawRequested(_) = event {
-            mandelbrot.draw(pixels.get_frame());
-            if pixels
-                .render()
+            match &mut buddhabrot {
+                Some(buddhabrot) => {
+                    buddhabrot.set_view(mandelbrot.view());
+                    buddhabrot.sample(buddhabrot::SAMPLES_PER_FRAME);
+                    let close_key = bindings.key_name(Action::CycleBuddhabrot);
+                    buddhabrot.draw(pixels.get_frame(), &close_key, mandelbrot.hud);
+                }
+                None if mandelbrot.low_memory => {
+                    mandelbrot.draw(pixels.get_frame());
+                    if mandelbrot.preview == 1 {
+                        full_frame_time = mandelbrot.rendering_time;
+                    }
+                }
+                None => {
+                    renderer.start(&mut mandelbrot);
+                    if let Some((job, frame)) = renderer.finished() {
+                        if job.preview == 1 {
+                            full_frame_time = job.rendering_time;
+                        }
+                        mandelbrot.finish(job);
+                        drawn_frame = frame;
+                        if !input.mouse_held(0) {
+                            left_button.frame_arrived();
+                        }
+                    } else if left_button.offset == (0.0, 0.0) {
+                        // the tiles of the frame in progress replace the
+                        // last one as they come
+                        let length = 4 * window_width as usize * window_height as usize;
+                        for (drawn, colors) in renderer.drawn_tiles() {
+                            if drawn_frame.len() != length {
+                                drawn_frame = vec![0; length];
+                            }
+                            tile::blit(&mut drawn_frame, window_width as usize, &drawn, &colors);
+                        }
+                    }
+                    let frame = pixels.get_frame();
+                    let drag_offset = left_button.offset;
+                    if drag_offset != (0.0, 0.0) && frame.len() == drawn_frame.len() {
+                        blit_shifted(
+                            frame,
+                            &drawn_frame,
+                            window_width as usize,
+                            window_height as usize,
+                            (drag_offset.0 as isize, drag_offset.1 as isize),
+                        );
+                    } else if frame.len() == drawn_frame.len() {
+                        frame.copy_from_slice(&drawn_frame);
+                    }
+                    if mandelbrot.color_cycling() && !mandelbrot.gpu_coloring() {
+                        let options = &mandelbrot.options;
+                        color_cycling.colorize(
+                            frame,
+                            &options.palette,
+                            options.bands,
+                            options.blend,
+                        );
+                    }
+                }
+            }
+            let mut canvas = Canvas::new(
+                pixels.get_frame(),
+                window_width as usize,
+                window_height as usize,
+            );
+            canvas.theme = mandelbrot.hud;
+            // the escape iterations the GPU colors are colored as it would
+            // before the overlays are blended over them
+            if let Some(gpu) = gpu_coloring.as_ref().filter(|_| mandelbrot.gpu_coloring()) {
+                let options = &mandelbrot.options;
+                canvas.iteration_colors = Some((
+                    IterationColors::new(&options.palette, options.bands, options.blend),
+                    gpu.offset,
+                ));
+            }
+            if let Some(progress) = renderer.progress().filter(|_| buddhabrot.is_none()) {
+                canvas.draw_progress(progress);
+            }
+            if tutorial.is_active() {
+                tutorial.draw(&mut canvas, &bindings);
+            }
+            if info_card_visible {
+                let close_key = bindings.key_name(Action::ToggleInfoCard);
+                info_card.draw(&mut canvas, &close_key);
+            }
+            if let Some(((x0, y0), (x1, y1))) = selection {
+                canvas.stroke_rect(
+                    x0.min(x1) as usize,
+                    y0.min(y1) as usize,
+                    (x1 - x0).abs() as usize + 1,
+                    (y1 - y0).abs() as usize + 1,
+                    canvas::FOREGROUND,
+                );
+            }
+            if let Some(orbit) = &orbit {
+                let close_key = bindings.key_name(Action::CloseOrbit);
+                orbit.draw(&mut canvas, &close_key);
+            }
+            if let Some(explanation) = &explanation {
+                let close_key = bindings.key_name(Action::CloseOrbit);
+                explanation.draw(&mut canvas, &close_key);
+            }
+            if let Some(ruler) = &ruler {
+                let close_key = bindings.key_name(Action::ToggleRuler);
+                let export_key = bindings.key_name(Action::ExportTransect);
+                ruler.draw(&mut canvas, &mandelbrot, &close_key, &export_key);
+            }
+            if let Some(preview) = &export_preview {
+                let keys = format!(
+                    "{}: change the fit, {}: export, {}: cancel",
+                    bindings.key_name(Action::CycleFit),
+                    bindings.key_name(Action::ExportImage),
+                    bindings.key_name(Action::CloseOrbit)
+                );
+                preview.draw(&mut canvas, &keys);
+            }
+            if let Some(gpu) = gpu_coloring.as_ref().filter(|_| mandelbrot.gpu_coloring()) {
+                gpu.draw_error(&mut canvas);
+            }
+            help.draw(&mut canvas, &bindings);
+            settings_panel.draw(&mut canvas);
+            let result = match &mut gpu_coloring {
+                Some(gpu) if mandelbrot.gpu_coloring() => {
+                    pixels.render_with(|encoder, render_target, context| {
+                        let options = &mandelbrot.options;
+                        gpu.render(
+                            encoder,
+                            render_target,
+                            context,
+                            &options.palette,
+                            options.bands,
+                            options.blend,
+                        );
+                        Ok(())
+                    })
+                }
+                _ => pixels.render(),
+            };
+            if result
                 .map_err(|e| error!("pixels.render() failed: {}", e))
                 .is_err()
             {
                 *control_flow = ControlFlow::Exit;
                 return;
             }
+
+            // ready before the first use, so that turning the GPU coloring on
+            // does not stall a frame on building its pipeline
+            if !gpu_warmed_up {
+                gpu_warmed_up = true;
+                let start = Instant::now();
+                let gpu = gpu_coloring.get_or_insert_with(|| {
+                    GpuColoring::new(
+                        pixels.context(),
+                        pixels.render_texture_format(),
+                        shader.as_deref(),
+                    )
+                });
+                let options = &mandelbrot.options;
+                gpu.warm_up(
+                    pixels.context(),
+                    &options.palette,
+                    options.bands,
+                    options.blend,
+                );
+                let time = start.elapsed();
+                info!("gpu coloring warmed up in {:?}", time);
+                mandelbrot.gpu_status = Some(format!("ready ({} ms to build)", time.as_millis()));
+                mandelbrot.request_redraw();
+            }
+        }
+
+        if let Event::WindowEvent {
+            event: WindowEvent::Touch(touch),
+            ..
+        } = &event
+        {
+            let position = coords::touch(&pixels, touch.location);
+            if let Some(gesture) = touches.touch(touch.id, touch.phase, position, Instant::now()) {
+                touch_actions.push(gesture.apply(&mut mandelbrot));
+            }
         }
 
         if input.update(&event) {
-            if input.key_pressed(VirtualKeyCode::Q) || input.quit() {
+            let mut actions = bindings.pressed(&input);
+            actions.append(&mut touch_actions);
+            #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+            if let Some(gamepads) = &mut gamepads {
+                // the stick moves the view like a drag and the triggers zoom
+                // like the wheel
+                let gamepad = gamepads.poll();
+                actions.extend(gamepad.actions);
+                if gamepad.pan != (0.0, 0.0) {
+                    actions.push(Action::Drag);
+                    mandelbrot.move_center(gamepad.pan.0, gamepad.pan.1);
+                    mandelbrot.request_redraw();
+                }
+                if gamepad.zoom != 0.0 {
+                    actions.push(Action::Scroll);
+                    mandelbrot.zoom(gamepad.zoom);
+                    mandelbrot.request_redraw();
+                }
+            }
+
+            if actions.contains(&Action::Quit) || input.quit() {
+                info!("{}", mandelbrot.session_cost.message());
+                save_session(
+                    &mandelbrot,
+                    &controls,
+                    cli.palette.as_deref(),
+                    &window,
+                    windowed_size,
+                );
                 *control_flow = ControlFlow::Exit;
                 return;
             }
 
-            if let Some(size) = input.window_resized() {
-                pixels.resize_surface(size.width, size.height);
-            }
+            coords::fit_surface(&mut pixels, &window, &input);
 
-            if input.key_pressed(VirtualKeyCode::Space) {
-                auto_zoom_param = 0.0;
-                mandelbrot.reset();
+            if actions.contains(&Action::ToggleSettings) {
+                settings_panel.toggle();
+            }
+            let before = Settings::of(&mandelbrot, controls.palette_index);
+            let mut settings = before;
+            let panel = settings_panel.update(
+                &pixels,
+                &input,
+                &mut settings,
+                &controls.palettes,
+                &mandelbrot,
+                &mut bookmarks,
+            );
+            if settings_panel.has_keyboard() {
+                // the keys go to the text field being edited
+                actions.clear();
+            }
+            actions.extend(panel.actions);
+            if panel.bookmarks_edited {
+                if let Err(e) = bookmarks.save(bookmark_path) {
+                    error!("failed to save {}: {}", BOOKMARK_FILE, e);
+                }
+            }
+            if let Some(destination) = panel.destination {
+                let scale = destination.scale.unwrap_or(mandelbrot.scale);
+                controls.auto_zoom = 0.0;
+                if let Err(e) = mandelbrot.go_to(destination.x, destination.y, scale) {
+                    error!("failed to go to the view: {}", e);
+                }
+            }
+            if settings != before {
+                settings.apply(&mut mandelbrot, &controls.palettes);
+                controls.palette_index = settings.palette;
+                if settings.fractal != before.fractal {
+                    info_card = InfoCard::builtin(settings.fractal);
+                }
+                mandelbrot.request_redraw();
+            } else if panel.changed && mandelbrot.low_memory {
+                // the low-memory mode draws the panel over the image itself
                 mandelbrot.request_redraw();
             }
+            // after the settings, which set the palette from its index
+            if let Some(palette) = panel.palette {
+                controls.use_palette(palette);
+                mandelbrot.options.palette = controls.palette().clone();
+                mandelbrot.request_redraw();
+            }
+            // the view ignores the mouse while it works the panel
+            let pointer_free = !settings_panel.has_pointer();
+
+            if actions.contains(&Action::ToggleFullscreen) {
+                let fullscreen = window.fullscreen().is_none();
+                if fullscreen {
+                    windowed_size = Some(window.inner_size());
+                }
+                (window_width, window_height) = set_fullscreen(&window, fullscreen, frame_size);
+                pixels.resize_buffer(window_width, window_height);
+                mandelbrot.resize(window_width, window_height);
+                if let Some(gpu) = &mut gpu_coloring {
+                    gpu.frame_resized(pixels.context());
+                }
+                // both hold pixels of the old size
+                drawn_frame.clear();
+                buddhabrot = None;
+            }
+
+            if actions.contains(&Action::UndoView) {
+                let entry = if input.held_shift() {
+                    history.forward()
+                } else {
+                    history.back(&mandelbrot)
+                };
+                match entry {
+                    Some(entry) => {
+                        controls.auto_zoom = 0.0;
+                        entry.apply(&mut mandelbrot);
+                        mandelbrot.request_redraw();
+                    }
+                    None => info!("no more views in the history"),
+                }
+            }
 
-            if input.mouse_pressed(0) {
-                if let Some((x, y)) = input.mouse() {
-                    let click_interval = pressed_time.elapsed().as_millis();
-                    info!("click interval {}", click_interval);
-                    let (pixel_x, pixel_y) = pixels
-                        .window_pos_to_pixel((x, y))
-                        .unwrap_or_else(|pos| pixels.clamp_pixel_pos(pos));
-                    if pressed_time.elapsed().as_millis() < 700 {
-                        dobule_clicked = true;
+            if pointer_free && input.mouse_pressed(0) && input.held_control() {
+                if let Some((pixel_x, pixel_y)) = coords::pointer(&pixels, &input) {
+                    let (point_x, point_y) = mandelbrot.precise_point_at(pixel_x, pixel_y);
+                    info!("explain ({}, {})", point_x.hi, point_y.hi);
+                    actions.push(Action::ExplainPoint);
+                    orbit = None;
+                    explanation = Some(Explanation::new(&mandelbrot, point_x, point_y));
+                    mandelbrot.request_redraw();
+                }
+            } else if pointer_free && input.mouse_pressed(0) && input.held_shift() {
+                if let Some((pixel_x, pixel_y)) = coords::pointer(&pixels, &input) {
+                    let point = mandelbrot.point_at(pixel_x, pixel_y);
+                    let probe = mandelbrot.probe_period(point.0, point.1);
+                    info!("probe ({}, {}): {}", point.0, point.1, probe.message());
+                    actions.push(Action::ProbePeriod);
+                    mandelbrot.probe = Some((point, probe));
+                    mandelbrot.request_redraw();
+                }
+            } else if pointer_free && input.mouse_pressed(0) && ruler.is_some() {
+                if let (Some((pixel_x, pixel_y)), Some(ruler)) =
+                    (coords::pointer(&pixels, &input), ruler.as_mut())
+                {
+                    ruler.click(mandelbrot.point_at(pixel_x, pixel_y));
+                    mandelbrot.request_redraw();
+                }
+            } else if pointer_free && input.mouse_pressed(0) {
+                if let Some((pixel_x, pixel_y)) = coords::pointer(&pixels, &input) {
+                    if left_button.press((pixel_x, pixel_y), Instant::now()) {
                         info!("double clicked");
-                        mandelbrot.set_center(pixel_x as f64, pixel_y as f64);
+                        actions.push(Action::Recenter);
+                        mandelbrot.set_center(pixel_x, pixel_y);
                         mandelbrot.request_redraw();
-                    } else {
-                        dobule_clicked = false;
-                        pressed_pos_x = pixel_x as f64;
-                        pressed_pos_y = pixel_y as f64;
                     }
-                    pressed_time = Instant::now();
                 }
             }
 
-            if input.mouse_released(0) {
-                if dobule_clicked == false {
-                    if let Some((x, y)) = input.mouse() {
-                        let (released_pos_x, released_pos_y) = pixels
-                            .window_pos_to_pixel((x, y))
-                            .unwrap_or_else(|pos| pixels.clamp_pixel_pos(pos));
-                        let (drag_vector_x, drag_vector_y) = (
-                            pressed_pos_x - released_pos_x as f64,
-                            -1.0 * (pressed_pos_y - released_pos_y as f64),
-                        );
-                        info!("drag: ({}, {})", drag_vector_x, drag_vector_y);
-                        mandelbrot.move_center(drag_vector_x, drag_vector_y);
+            if let Some(ruler) = &mut ruler {
+                if let Some((pixel_x, pixel_y)) = coords::pointer(&pixels, &input) {
+                    if ruler.follow(mandelbrot.point_at(pixel_x, pixel_y)) {
+                        // redraw to erase the previous segment
+                        mandelbrot.request_redraw();
+                    }
+                }
+            }
+
+            // the image follows the pointer while the button is held
+            if pointer_free
+                && ruler.is_none()
+                && input.mouse_held(0)
+                && !input.mouse_pressed(0)
+                && !input.held_control()
+                && !input.held_shift()
+            {
+                if let Some(position) = coords::pointer(&pixels, &input) {
+                    if let Some(step) = left_button.drag(position, mandelbrot.low_memory) {
+                        actions.push(Action::Drag);
+                        if mandelbrot.low_memory {
+                            // no copy of the frame to move: the view follows
+                            mandelbrot.move_center(-step.0, step.1);
+                            mandelbrot.request_redraw();
+                        }
+                    }
+                }
+            }
+
+            if input.mouse_released(0)
+                && !input.held_control()
+                && !input.held_shift()
+                && ruler.is_none()
+            {
+                // where the button went up, or where the image was dragged
+                // last when that is over the panel
+                let position = coords::pointer(&pixels, &input).filter(|_| pointer_free);
+                if let Some(offset) = left_button.release(position, mandelbrot.low_memory) {
+                    info!("drag: ({}, {})", -offset.0, offset.1);
+                    mandelbrot.move_center(-offset.0, offset.1);
+                    mandelbrot.request_redraw();
+                }
+            }
+
+            if pointer_free && input.mouse_pressed(1) {
+                if let Some((pixel_x, pixel_y)) = coords::pointer(&pixels, &input) {
+                    let corner = (pixel_x as f32, pixel_y as f32);
+                    selection = Some((corner, corner));
+                }
+            }
+
+            if let Some((start, end)) = selection {
+                if let Some((pixel_x, pixel_y)) = coords::pointer(&pixels, &input) {
+                    let corner = (pixel_x as f32, pixel_y as f32);
+                    if corner != end {
+                        selection = Some((start, corner));
+                        // redraw to erase the previous rectangle
                         mandelbrot.request_redraw();
                     }
                 }
             }
 
+            if input.mouse_released(1) {
+                if let Some(((x0, y0), (x1, y1))) = selection.take() {
+                    if (x1 - x0).abs() >= MIN_SELECTION && (y1 - y0).abs() >= MIN_SELECTION {
+                        info!("zoom to ({}, {}) - ({}, {})", x0, y0, x1, y1);
+                        actions.push(Action::ZoomRect);
+                        controls.auto_zoom = 0.0;
+                        mandelbrot.zoom_to_rect(x0 as f64, y0 as f64, x1 as f64, y1 as f64);
+                    }
+                    mandelbrot.request_redraw();
+                }
+            }
+
+            // middle click
+            if pointer_free && input.mouse_pressed(2) {
+                if let Some((pixel_x, pixel_y)) = coords::pointer(&pixels, &input) {
+                    let (point_x, point_y) = mandelbrot.precise_point_at(pixel_x, pixel_y);
+                    info!("inspect ({}, {})", point_x.hi, point_y.hi);
+                    actions.push(Action::InspectOrbit);
+                    explanation = None;
+                    orbit = Some(OrbitStats::compute(&mandelbrot, point_x, point_y));
+                    mandelbrot.request_redraw();
+                }
+            }
+
+            if actions.contains(&Action::CloseOrbit)
+                && (orbit.is_some() || explanation.is_some() || mandelbrot.probe.is_some())
+            {
+                orbit = None;
+                explanation = None;
+                mandelbrot.probe = None;
+                mandelbrot.request_redraw();
+            }
+
             let scroll_diff = input.scroll_diff();
-            if scroll_diff.abs() != 0.0 {
+            if pointer_free && scroll_diff.abs() != 0.0 {
                 info!("scroll: {}", scroll_diff);
-                mandelbrot.zoom(scroll_diff as f64);
+                actions.push(Action::Scroll);
+                let center = (
+                    mandelbrot.width as f64 / 2.0,
+                    mandelbrot.height as f64 / 2.0,
+                );
+                let anchor = coords::pointer(&pixels, &input).unwrap_or(center);
+                zoom_glide.add(scroll_diff as f64, anchor, Instant::now());
+            }
+            if zoom_glide.step(&mut mandelbrot, Instant::now()) {
                 mandelbrot.request_redraw();
             }
 
-            if input.key_pressed(VirtualKeyCode::LShift) {
-                shiftkey_pressed = true;
-            } else if input.key_released(VirtualKeyCode::LShift) {
-                shiftkey_pressed = false;
+            controls.apply(
+                &mut mandelbrot,
+                &actions,
+                input.held_shift(),
+                input.held_alt(),
+            );
+            if actions.contains(&Action::CycleFractal) {
+                info_card = InfoCard::builtin(mandelbrot.options.fractal);
             }
 
-            if input.key_pressed(VirtualKeyCode::LAlt) {
-                altkey_pressed = true;
-            } else if input.key_released(VirtualKeyCode::LAlt) {
-                altkey_pressed = false;
+            let exponent_step = if actions.contains(&Action::IncreaseExponent) {
+                1.0
+            } else if actions.contains(&Action::DecreaseExponent) {
+                -1.0
+            } else {
+                0.0
+            };
+            if exponent_step != 0.0 {
+                // the experiment mode moves the exponent in small steps, the
+                // keys alone to the next integer within the range
+                let exponent = mandelbrot.options.exponent;
+                let exponent = if experiment {
+                    nudge(exponent, exponent_step)
+                        .clamp(mandelbrot::MIN_EXPONENT, mandelbrot::MAX_EXPONENT)
+                } else {
+                    let next = if exponent_step > 0.0 {
+                        exponent.floor() + 1.0
+                    } else {
+                        exponent.ceil() - 1.0
+                    };
+                    next.clamp(
+                        mandelbrot::MIN_EXPONENT.ceil(),
+                        mandelbrot::MAX_EXPONENT.floor(),
+                    )
+                };
+                mandelbrot.options.exponent = exponent;
+                info!("exponent: {}", exponent);
+                mandelbrot.request_redraw();
+                if experiment {
+                    mandelbrot.preview = experiment_preview(full_frame_time);
+                }
             }
 
-            let calc_zoom_param = |direction: f64| {
-                if altkey_pressed {
-                    (0.4 * direction, true)
-                } else if auto_zoom_param != 0.0 {
-                    (0.0, true)
-                } else if shiftkey_pressed {
-                    (0.1 * direction, false)
+            if actions.contains(&Action::ToggleExperiment) {
+                experiment = !experiment;
+                info!("experiment mode: {}", if experiment { "on" } else { "off" });
+                mandelbrot.preview = if experiment {
+                    experiment_preview(full_frame_time)
                 } else {
-                    (3.0 * direction, false)
+                    1
+                };
+                mandelbrot.request_redraw();
+            }
+            if experiment {
+                let variation = &mut mandelbrot.options.variation;
+                let before = *variation;
+                for action in &actions {
+                    match action {
+                        Action::IncreaseBailout => {
+                            variation.bailout = (variation.bailout * EXPERIMENT_BAILOUT_FACTOR)
+                                .min(mandelbrot::MAX_BAILOUT)
+                        }
+                        Action::DecreaseBailout => {
+                            variation.bailout = (variation.bailout / EXPERIMENT_BAILOUT_FACTOR)
+                                .max(mandelbrot::MIN_BAILOUT)
+                        }
+                        Action::IncreaseRelaxation => {
+                            variation.relaxation =
+                                nudge(variation.relaxation, 1.0).min(mandelbrot::MAX_RELAXATION)
+                        }
+                        Action::DecreaseRelaxation => {
+                            variation.relaxation =
+                                nudge(variation.relaxation, -1.0).max(mandelbrot::MIN_RELAXATION)
+                        }
+                        Action::NudgeZ0Left => variation.z0_x = nudge(variation.z0_x, -1.0),
+                        Action::NudgeZ0Right => variation.z0_x = nudge(variation.z0_x, 1.0),
+                        Action::NudgeZ0Down => variation.z0_y = nudge(variation.z0_y, -1.0),
+                        Action::NudgeZ0Up => variation.z0_y = nudge(variation.z0_y, 1.0),
+                        _ => {}
+                    }
                 }
-            };
+                if *variation != before {
+                    info!("variation: {}", variation.message());
+                    mandelbrot.preview = experiment_preview(full_frame_time);
+                    mandelbrot.request_redraw();
+                }
+                if actions.contains(&Action::CommitExperiment) && mandelbrot.preview > 1 {
+                    mandelbrot.preview = 1;
+                    mandelbrot.request_redraw();
+                }
+            }
 
-            let (zoom_param, auto_zoom_update) = if input.key_pressed(VirtualKeyCode::PageUp) {
-                calc_zoom_param(1.0)
-            } else if input.key_pressed(VirtualKeyCode::PageDown) {
-                calc_zoom_param(-1.0)
-            } else {
-                (auto_zoom_param, false)
-            };
-            if zoom_param != 0.0 {
-                let zoom_result = mandelbrot.zoom(zoom_param);
-                if zoom_result == false {
-                    auto_zoom_param = 0.0;
+            if actions.contains(&Action::CycleBuddhabrot) {
+                buddhabrot = match buddhabrot.take() {
+                    None => Some(Buddhabrot::new(
+                        window_width as usize,
+                        window_height as usize,
+                        buddhabrot::Mode::Buddhabrot,
+                    )),
+                    Some(mut buddhabrot) if buddhabrot.mode == buddhabrot::Mode::Buddhabrot => {
+                        buddhabrot.mode = buddhabrot::Mode::Nebulabrot;
+                        buddhabrot.clear();
+                        Some(buddhabrot)
+                    }
+                    Some(_) => {
+                        // the frame holds the density image
+                        mandelbrot.request_redraw();
+                        None
+                    }
+                };
+                if let Some(buddhabrot) = &buddhabrot {
+                    info!("{}", buddhabrot.mode.name());
                 }
-                mandelbrot.request_redraw();
             }
 
-            if input.key_pressed(VirtualKeyCode::Escape) {
-                auto_zoom_param = 0.0;
-            } else if auto_zoom_update {
-                auto_zoom_param = zoom_param;
+            if let Some(buddhabrot) = &mut buddhabrot {
+                if actions.contains(&Action::IncreaseExposure) {
+                    buddhabrot.scale_exposure(1.25);
+                    info!("exposure: {}", buddhabrot.exposure);
+                }
+                if actions.contains(&Action::DecreaseExposure) {
+                    buddhabrot.scale_exposure(1.0 / 1.25);
+                    info!("exposure: {}", buddhabrot.exposure);
+                }
             }
 
-            let (key_move, move_x, move_y) =
-                if input.key_pressed(VirtualKeyCode::Up) || input.key_pressed(VirtualKeyCode::K) {
-                    (true, 0.0, 10.0)
-                } else if input.key_pressed(VirtualKeyCode::Down)
-                    || input.key_pressed(VirtualKeyCode::J)
-                {
-                    (true, 0.0, -10.0)
-                } else if input.key_pressed(VirtualKeyCode::Left)
-                    || input.key_pressed(VirtualKeyCode::H)
-                {
-                    (true, -10.0, 0.0)
-                } else if input.key_pressed(VirtualKeyCode::Right)
-                    || input.key_pressed(VirtualKeyCode::L)
-                {
-                    (true, 10.0, 0.0)
+            if actions.contains(&Action::ToggleGpuColoring) {
+                if gpu_coloring.is_none() {
+                    gpu_coloring = Some(GpuColoring::new(
+                        pixels.context(),
+                        pixels.render_texture_format(),
+                        shader.as_deref(),
+                    ));
+                }
+                mandelbrot.set_gpu_coloring(!mandelbrot.gpu_coloring());
+                info!("gpu coloring: {}", mandelbrot.gpu_coloring());
+            }
+
+            if actions.contains(&Action::ToggleColorCycling) && !mandelbrot.gpu_coloring() {
+                if mandelbrot.low_memory {
+                    // the frame is drawn in place, with no copy to color again
+                    info!("color cycling needs the GPU coloring in the low-memory mode");
                 } else {
-                    (false, 0.0, 0.0)
-                };
-            if key_move {
-                let scale_factor = window.scale_factor();
-                let center_p_pos = PhysicalPosition::new(move_x, move_y);
-                let center_offset = center_p_pos.to_logical(scale_factor);
-                mandelbrot.move_center(center_offset.x, center_offset.y);
+                    mandelbrot.set_color_cycling(!mandelbrot.color_cycling());
+                    info!("color cycling: {}", mandelbrot.color_cycling());
+                }
+            }
+
+            if let Some(gpu) = gpu_coloring.as_mut().filter(|_| mandelbrot.gpu_coloring()) {
+                gpu.reload(pixels.context());
+                if actions.contains(&Action::ToggleColorCycling) {
+                    gpu.cycling = !gpu.cycling;
+                    info!("color cycling: {}", gpu.cycling);
+                }
+                if actions.contains(&Action::IncreaseGamma) {
+                    gpu.scale_gamma(1.1);
+                    info!("gamma: {}", gpu.gamma);
+                }
+                if actions.contains(&Action::DecreaseGamma) {
+                    gpu.scale_gamma(1.0 / 1.1);
+                    info!("gamma: {}", gpu.gamma);
+                }
+            }
+
+            if actions.contains(&Action::CycleHudCorner) {
+                mandelbrot.hud.corner = mandelbrot.hud.corner.next();
+                mandelbrot.request_redraw();
+            }
+
+            if actions.contains(&Action::CycleHudOpacity) {
+                mandelbrot.hud.next_opacity();
                 mandelbrot.request_redraw();
             }
 
-            if input.key_pressed(VirtualKeyCode::I) {
-                mandelbrot.info = !mandelbrot.info;
+            if actions.contains(&Action::ToggleHudShadow) {
+                mandelbrot.hud.shadow = !mandelbrot.hud.shadow;
                 mandelbrot.request_redraw();
             }
 
-            if input.key_pressed(VirtualKeyCode::D) {
+            if actions.contains(&Action::InvertHudColors) {
+                mandelbrot.hud.invert();
+                mandelbrot.request_redraw();
+            }
+
+            if actions.contains(&Action::ToggleCostMap) {
+                mandelbrot.cost_map = !mandelbrot.cost_map;
+                mandelbrot.request_redraw();
+            }
+
+            if actions.contains(&Action::Dump) {
                 println!();
                 println!("x: {}", mandelbrot.center_x);
                 println!("y: {}", mandelbrot.center_y);
                 println!("scale: {}", mandelbrot.scale);
-                println!(
-                    "rendering time: {}.{:04}[sec]",
-                    mandelbrot.rendering_time.as_secs(),
-                    mandelbrot.rendering_time.subsec_nanos() / 1000000
+                println!("{}", mandelbrot.remaining_zoom_message());
+                println!("{}", mandelbrot.rendering_time_message());
+                println!("{}", mandelbrot.session_cost.message());
+            }
+
+            if actions.contains(&Action::CopyView) {
+                let text = format!(
+                    "x: {}\ny: {}\nscale: {}\niterations: {}\n{}\n",
+                    mandelbrot.center_x,
+                    mandelbrot.center_y,
+                    mandelbrot.scale,
+                    mandelbrot.max_round,
+                    mandelbrot.command_line()
                 );
+                match clipboard.set_text(text) {
+                    Ok(()) => println!("view copied to the clipboard"),
+                    Err(e) => error!("failed to copy to the clipboard: {}", e),
+                }
+            }
+
+            if actions.contains(&Action::ExportAnimation) {
+                let job = AnimationJob {
+                    frames: ANIMATION_FRAMES,
+                    width: window_width,
+                    height: window_height,
+                    max_round: None,
+                    start: View::default(),
+                    end: mandelbrot.view(),
+                    options: mandelbrot.options.clone(),
+                };
+                let started = platform::spawn_unless_busy(&exporting_animation, move || {
+                    let result = animation::export_frames(&job, Path::new(ANIMATION_DIR), false);
+                    match result {
+                        Ok(()) => println!("zoom animation exported"),
+                        Err(e) => error!("export_frames() failed: {}", e),
+                    }
+                });
+                if started {
+                    println!("exporting zoom animation to {}/", ANIMATION_DIR);
+                } else {
+                    println!("the zoom animation is still being exported");
+                }
+            }
+
+            if actions.contains(&Action::CycleThreads) {
+                let (threads, all) = (parallel::threads(), parallel::max_threads());
+                let threads = if input.held_shift() {
+                    (2 * threads).min(all)
+                } else if threads == 1 {
+                    all
+                } else {
+                    threads / 2
+                };
+                match parallel::set_threads((threads < all).then_some(threads)) {
+                    Ok(()) => println!("rendering on {} threads", threads),
+                    Err(e) => error!("failed to set the number of threads: {}", e),
+                }
+                mandelbrot.request_redraw();
+            }
+
+            if actions.contains(&Action::AddKeyframe) {
+                if input.held_shift() {
+                    keyframes.keyframe.clear();
+                    println!("cleared the keyframes");
+                } else {
+                    keyframes.keyframe.push(Keyframe::from_view(&mandelbrot));
+                    println!("keyframe {}", keyframes.keyframe.len());
+                }
+                if let Err(e) = keyframes.save(keyframe_path) {
+                    error!("failed to save {}: {}", KEYFRAME_FILE, e);
+                }
+            }
+
+            if actions.contains(&Action::ExportKeyframes) {
+                let job = Keyframes {
+                    keyframe: keyframes.keyframe.clone(),
+                };
+                let options = mandelbrot.options.clone();
+                let (width, height) = (window_width, window_height);
+                let started = platform::spawn_unless_busy(&exporting_keyframes, move || {
+                    let dir = Path::new(KEYFRAME_DIR);
+                    match keyframes::export_frames(&job, width, height, &options, dir) {
+                        Ok(()) => println!("keyframe animation exported"),
+                        Err(e) => error!("failed to export the keyframe animation: {}", e),
+                    }
+                });
+                if started {
+                    println!("exporting the keyframe animation to {}/", KEYFRAME_DIR);
+                } else {
+                    println!("the keyframe animation is still being exported");
+                }
+            }
+
+            if actions.contains(&Action::ExportDistanceField) {
+                let job = mandelbrot.clone();
+                let note = settings_panel.note.clone();
+                println!("exporting the distance field to {}", sdf::SDF_FILE);
+                platform::spawn(
+                    move || match sdf::save(&job, Path::new(sdf::SDF_FILE), &note) {
+                        Ok(()) => println!("distance field exported"),
+                        Err(e) => error!("failed to export the distance field: {}", e),
+                    },
+                );
+            }
+
+            if actions.contains(&Action::ExportIterations) {
+                let job = mandelbrot.clone();
+                let format = cli.data.unwrap_or_default();
+                println!("exporting the escape iterations to {}", format.file());
+                platform::spawn(
+                    move || match data::save(&job, format, Path::new(format.file())) {
+                        Ok(()) => println!("escape iterations exported"),
+                        Err(e) => error!("failed to export the escape iterations: {}", e),
+                    },
+                );
+            }
+
+            if actions.contains(&Action::ExportTransect) {
+                match ruler.as_ref().and_then(Ruler::segment) {
+                    Some((start, end)) => {
+                        let job = mandelbrot.clone();
+                        let samples = cli.transect_samples as usize;
+                        println!(
+                            "exporting {} samples of the ruler's segment to {}",
+                            samples,
+                            transect::TRANSECT_FILE
+                        );
+                        platform::spawn(move || {
+                            let path = Path::new(transect::TRANSECT_FILE);
+                            match transect::save(&job, start, end, samples, path) {
+                                Ok(()) => println!("transect exported"),
+                                Err(e) => error!("failed to export the transect: {}", e),
+                            }
+                        });
+                    }
+                    None => info!("click the two ends of a segment with the ruler first"),
+                }
+            }
+
+            if actions.contains(&Action::ExportImage) {
+                match export_preview.take() {
+                    None => {
+                        let (width, height) = export_size;
+                        export_preview = Some(ExportPreview::new(width, height, export_fit));
+                    }
+                    Some(preview) => {
+                        let job = mandelbrot.clone();
+                        let note = settings_panel.note.clone();
+                        let caption = export_caption.clone();
+                        println!(
+                            "exporting {}x{} ({}) to {}",
+                            preview.width,
+                            preview.height,
+                            preview.fit.name(),
+                            framing::EXPORT_FILE
+                        );
+                        platform::spawn(move || {
+                            let size = (preview.width, preview.height);
+                            let path = Path::new(framing::EXPORT_FILE);
+                            let fit = preview.fit;
+                            match poster::save(&job, size, fit, caption.as_ref(), path, &note) {
+                                Ok(()) => println!("image exported"),
+                                Err(e) => error!("failed to export the image: {}", e),
+                            }
+                        });
+                    }
+                }
+                mandelbrot.request_redraw();
+            }
+            if actions.contains(&Action::CloseOrbit) && export_preview.is_some() {
+                export_preview = None;
+                mandelbrot.request_redraw();
+            }
+            if let Some(preview) = &mut export_preview {
+                if actions.contains(&Action::CycleFit) {
+                    preview.fit = preview.fit.next();
+                    info!("fit: {}", preview.fit.name());
+                }
+                if preview.update(&mandelbrot) {
+                    mandelbrot.request_redraw();
+                }
+            }
+
+            if actions.contains(&Action::SaveBookmark) {
+                let bookmark = bookmarks.add(&mandelbrot, settings_panel.note.clone());
+                println!("saved {}", bookmark.name);
+                if let Err(e) = bookmarks.save(bookmark_path) {
+                    error!("failed to save {}: {}", BOOKMARK_FILE, e);
+                }
+            }
+
+            if actions.contains(&Action::ToggleRuler) {
+                ruler = match ruler {
+                    Some(_) => None,
+                    None => Some(Ruler::default()),
+                };
+                mandelbrot.request_redraw();
+            }
+
+            if actions.contains(&Action::ToggleInfoCard) {
+                info_card_visible = !info_card_visible;
+                mandelbrot.request_redraw();
+            }
+
+            if actions.contains(&Action::ToggleHelp) {
+                help.advance();
+                mandelbrot.request_redraw();
+            } else if actions.contains(&Action::CloseOrbit) && help.is_visible() {
+                help.close();
+                mandelbrot.request_redraw();
+            }
+
+            for action in &actions {
+                if let Action::JumpBookmark(index) = action {
+                    // while the info card is shown, the number keys select
+                    // its starting points instead of the bookmarks
+                    if info_card_visible {
+                        if let Some(start) = info_card.start.get(*index) {
+                            info!("go to {}", start.name);
+                            controls.auto_zoom = 0.0;
+                            info_card_visible = false;
+                            let view = start.view();
+                            mandelbrot.set_view(
+                                view.center_x,
+                                view.center_y,
+                                view.scale,
+                                mandelbrot.max_round_for(view.scale),
+                            );
+                        }
+                    } else if let Some(bookmark) = bookmarks.get(*index) {
+                        match bookmark.apply(&mut mandelbrot) {
+                            Ok(()) => {
+                                info!("jump to {}", bookmark.name);
+                                controls.auto_zoom = 0.0;
+                                controls.bookmark = Some(bookmark.clone());
+                                settings_panel.note = bookmark.note.clone();
+                            }
+                            Err(e) => error!("{} in {}: {}", bookmark.name, BOOKMARK_FILE, e),
+                        }
+                    }
+                }
+            }
+
+            if actions.contains(&Action::Tutorial) {
+                tutorial.toggle();
+                mandelbrot.request_redraw();
+            } else {
+                for action in &actions {
+                    if tutorial.observe(*action) {
+                        mandelbrot.request_redraw();
+                    }
+                }
+            }
+
+            history.observe(&mandelbrot);
+
+            // a drag only moves the view in the low-memory mode; otherwise
+            // the full frame is rendered on release, mostly reusing pixels
+            let interacting = controls.auto_zoom != 0.0
+                || zoom_glide.is_active()
+                || (mandelbrot.low_memory && actions.contains(&Action::Drag))
+                || actions.contains(&Action::Scroll);
+            if interacting {
+                last_interaction = Instant::now();
+                mandelbrot.preview = preview_block(full_frame_time);
+            } else if mandelbrot.preview > 1
+                && !experiment
+                && last_interaction.elapsed() >= SETTLE_TIME
+            {
+                mandelbrot.preview = 1;
+                mandelbrot.request_redraw();
             }
 
             window.request_redraw();
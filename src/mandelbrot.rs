@@ -0,0 +1,3215 @@
+use clap::ValueEnum;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::annotate;
+use crate::canvas::{Canvas, Theme};
+use crate::cost::{self, SessionCost};
+use crate::cycling::IterationColors;
+use crate::double_double::DoubleDouble;
+use crate::formula::Formula;
+use crate::histogram::{Exposure, Histogram, IterationCounts};
+use crate::minimap::Minimap;
+use crate::palette::{self, Bands, Blend, Palette};
+use crate::parallel::*;
+use crate::platform::Instant;
+use crate::rays::{Angle, ExternalRays};
+use crate::simd::{self, LANES};
+use crate::tile::{self, CancelToken, Tile, TileOrder, TilePlan, TileStats, TILE_SIZE};
+
+// Orbit positions closer than this to the saved checkpoint are treated as
+// periodic by the periodicity check.
+pub const PERIODICITY_EPSILON: f64 = 1e-13;
+
+// Rectangles of the Mariani–Silver subdivision with a side this short or
+// shorter are computed pixel by pixel instead of split further.
+const MIN_SUBDIVISION: usize = 4;
+
+// Pixel spacing, in ulps of the center coordinates, below which neighboring
+// pixels stop getting distinct f64 coordinates and the image breaks into
+// blocks.
+const PRECISION_ULPS: f64 = 8.0;
+// Smallest scale of the formulas the double-double kernel handles, where
+// its 106 bits still resolve the pixels around the set.
+const DOUBLE_DOUBLE_MIN_SCALE: f64 = 1e-30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct View {
+    pub center_x: f64,
+    pub center_y: f64,
+    pub scale: f64,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            center_x: -0.7,
+            center_y: 0.0,
+            scale: 0.005,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Antialias {
+    #[default]
+    Off,
+    /// 2x2 samples per pixel
+    #[value(name = "2x2")]
+    X2,
+    /// 4x4 samples per pixel
+    #[value(name = "4x4")]
+    X4,
+    /// 4x4 samples on pixels that differ from their neighbors
+    Adaptive,
+}
+
+impl Antialias {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::X2,
+            Self::X2 => Self::X4,
+            Self::X4 => Self::Adaptive,
+            Self::Adaptive => Self::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::X2 => "2x2",
+            Self::X4 => "4x4",
+            Self::Adaptive => "adaptive",
+        }
+    }
+
+    // samples per side of a pixel in the first pass
+    fn grid(self) -> usize {
+        match self {
+            Self::Off | Self::Adaptive => 1,
+            Self::X2 => 2,
+            Self::X4 => 4,
+        }
+    }
+}
+
+/// Escape-time formula iterated for each point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fractal {
+    /// z^2 + c
+    #[default]
+    Mandelbrot,
+    /// (|Re z| + i|Im z|)^2 + c
+    BurningShip,
+    /// conj(z)^2 + c, also known as the Mandelbar set
+    Tricorn,
+    /// Newton's method on z^d - 1, colored by the root it converges to
+    Newton,
+}
+
+impl Fractal {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Mandelbrot => Self::BurningShip,
+            Self::BurningShip => Self::Tricorn,
+            Self::Tricorn => Self::Newton,
+            Self::Newton => Self::Mandelbrot,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mandelbrot => "mandelbrot",
+            Self::BurningShip => "burning ship",
+            Self::Tricorn => "tricorn",
+            Self::Newton => "newton",
+        }
+    }
+}
+
+/// How escape iterations are turned into palette colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Coloring {
+    /// the palette advances with the iteration count
+    #[default]
+    Iterations,
+    /// one palette cycle spread over the iteration counts of the frame by
+    /// their cumulative frequency
+    Histogram,
+    /// the iteration colors darkened near the boundary of the set by the
+    /// distance estimate (Mandelbrot formula only)
+    Distance,
+    /// the smallest distance of the orbit to the origin
+    PointTrap,
+    /// the smallest distance of the orbit to the real and imaginary axes
+    LineTrap,
+    /// the smallest distance of the orbit to the unit circle
+    RingTrap,
+    /// the average over the orbit of a sine of the angle of z, blended
+    /// between the last two iterations for smooth stripes
+    Stripe,
+    /// the iteration colors darkened where z escapes below the real axis,
+    /// a checkerboard along the external rays (binary decomposition)
+    Decomposition,
+}
+
+impl Coloring {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Iterations => Self::Histogram,
+            Self::Histogram => Self::Distance,
+            Self::Distance => Self::PointTrap,
+            Self::PointTrap => Self::LineTrap,
+            Self::LineTrap => Self::RingTrap,
+            Self::RingTrap => Self::Stripe,
+            Self::Stripe => Self::Decomposition,
+            Self::Decomposition => Self::Iterations,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Iterations => "iterations",
+            Self::Histogram => "histogram",
+            Self::Distance => "distance",
+            Self::PointTrap => "point trap",
+            Self::LineTrap => "line trap",
+            Self::RingTrap => "ring trap",
+            Self::Stripe => "stripe",
+            Self::Decomposition => "binary decomposition",
+        }
+    }
+
+    fn is_trap(self) -> bool {
+        matches!(self, Self::PointTrap | Self::LineTrap | Self::RingTrap)
+    }
+
+    // Distance of the orbit point (x, y) to the trap of the coloring.
+    fn trap_distance(self, x: f64, y: f64) -> f64 {
+        match self {
+            Self::LineTrap => x.abs().min(y.abs()),
+            Self::RingTrap => (x.hypot(y) - 1.0).abs(),
+            _ => x.hypot(y),
+        }
+    }
+}
+
+/// How the points of the set are colored, apart from the coloring of the
+/// escape iterations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Interior {
+    /// flat black
+    #[default]
+    Black,
+    /// one palette cycle over |z| at the iteration limit, from 0 to the
+    /// bailout radius
+    Magnitude,
+    /// a color of the palette per period of the cycle the orbit settles on
+    Period,
+    /// the palette advances with the iterations the orbit takes to settle
+    /// on its cycle
+    Convergence,
+}
+
+impl Interior {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Black => Self::Magnitude,
+            Self::Magnitude => Self::Period,
+            Self::Period => Self::Convergence,
+            Self::Convergence => Self::Black,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Black => "black",
+            Self::Magnitude => "magnitude",
+            Self::Period => "period",
+            Self::Convergence => "convergence",
+        }
+    }
+}
+
+// The histogram coloring samples every HISTOGRAM_STEP-th pixel in both
+// directions, twice as far apart in the low-memory mode.
+const HISTOGRAM_STEP: usize = 2;
+// Images rendered in strips sample it on a coarser grid of about this many
+// pixels.
+const STRIP_HISTOGRAM_SAMPLES: f64 = 1_048_576.0;
+
+// Bailout |z| of the distance estimation, far beyond 2 so that the estimate
+// converges.
+const DISTANCE_RADIUS: f64 = 1000.0;
+// Distance from the boundary, in pixels, within which the distance coloring
+// darkens the image.
+const DISTANCE_WIDTH: f64 = 2.0;
+
+// Palette cycles of the trap colorings per unit of the square root of the
+// trap distance.
+const TRAP_CYCLES: f64 = 2.0;
+
+// Stripes of the stripe average coloring per turn of the angle of z, and
+// the bailout |z| it escapes at, far beyond 2 so that the blend between
+// the last two averages is smooth.
+const STRIPE_DENSITY: f64 = 5.0;
+const STRIPE_RADIUS: f64 = 1000.0;
+
+// Bailout |z| of the binary decomposition, far beyond 2 so that the edges
+// of its cells come close to the external rays and the equipotentials,
+// and the light left in the cells where z escapes below the real axis.
+const DECOMPOSITION_RADIUS: f64 = 1000.0;
+const DECOMPOSITION_SHADE: f64 = 0.3;
+
+// Points of an orbit that has settled on its cycle closer than this to each
+// other are the same point of the cycle, for its period. The distance
+// between other points of the cycle is far larger, but an orbit that
+// settles slowly can come back this close to the checkpoint of the
+// periodicity check after a multiple of its period.
+const INTERIOR_EPSILON: f64 = 1e-9;
+
+// Square of the Newton step below which the iteration has converged.
+const NEWTON_TOLERANCE: f64 = 1e-18;
+// Brightness kept by the Newton coloring per iteration to converge, and
+// the darkest it gets.
+const NEWTON_FADE: f64 = 0.95;
+const NEWTON_MIN_SHADE: f64 = 0.2;
+
+// Sum of the channel differences to a neighbor above which the adaptive
+// antialiasing supersamples a pixel.
+const ADAPTIVE_THRESHOLD: u32 = 96;
+const ADAPTIVE_GRID: usize = 4;
+
+// Pixels between the samples of a tile, its border included, from which
+// the adaptive iteration limits estimate the iterations the tile needs.
+const LIMIT_SAMPLE_STEP: usize = 8;
+
+// The tuning of the iteration limit raises it while more than LATE_SHARE
+// of the pixels next to the set escape in the last quarter of the limit,
+// doubling it up to MAX_ITERATION_BOOST times the auto limit. Frames that
+// keep no iterations are sampled every TUNING_STEP pixels.
+const LATE_SHARE: f64 = 0.25;
+const MAX_ITERATION_BOOST: usize = 16;
+const MIN_TUNING_BORDER: usize = 64;
+const TUNING_STEP: usize = 4;
+
+/// Test that decides when an orbit has escaped. The Euclidean circle gives
+/// the usual smooth bands; the others change the shape of the bands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Norm {
+    /// |z| >= 2
+    #[default]
+    Euclidean,
+    /// |Re z| + |Im z|, a diamond
+    L1,
+    /// max(|Re z|, |Im z|), a square
+    Linf,
+    /// |Re z|
+    Real,
+    /// |Im z|
+    Imaginary,
+    /// (|Re z|^p + |Im z|^p)^(1/p) with p given by the norm power
+    Power,
+}
+
+pub fn default_norm_power() -> f64 {
+    3.0
+}
+
+impl Norm {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Euclidean => Self::L1,
+            Self::L1 => Self::Linf,
+            Self::Linf => Self::Real,
+            Self::Real => Self::Imaginary,
+            Self::Imaginary => Self::Power,
+            Self::Power => Self::Euclidean,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Euclidean => "euclidean",
+            Self::L1 => "l1",
+            Self::Linf => "linf",
+            Self::Real => "real",
+            Self::Imaginary => "imaginary",
+            Self::Power => "power",
+        }
+    }
+
+    pub fn value(self, x: f64, y: f64, power: f64) -> f64 {
+        match self {
+            Self::Euclidean => (x * x + y * y).sqrt(),
+            Self::L1 => x.abs() + y.abs(),
+            Self::Linf => x.abs().max(y.abs()),
+            Self::Real => x.abs(),
+            Self::Imaginary => y.abs(),
+            Self::Power => (x.abs().powf(power) + y.abs().powf(power)).powf(1.0 / power),
+        }
+    }
+
+    /// Bailout value of the norm. Norms that can exceed |z| get a larger
+    /// one, so that points of the set never count as escaped.
+    pub fn radius(self, power: f64) -> f64 {
+        match self {
+            Self::L1 => 2.0 * 2.0_f64.sqrt(),
+            Self::Power if power < 2.0 => 2.0 * 2.0_f64.powf(1.0 / power - 0.5),
+            _ => 2.0,
+        }
+    }
+}
+
+pub const MIN_EXPONENT: f64 = 1.5;
+pub const MAX_EXPONENT: f64 = 16.0;
+
+/// `exponent` if it is a d of z^d + c the kernels take, for every way of
+/// setting it: the command line, bookmarks and scripts.
+pub fn check_exponent(exponent: f64) -> Result<f64, String> {
+    if (MIN_EXPONENT..=MAX_EXPONENT).contains(&exponent) {
+        Ok(exponent)
+    } else {
+        Err(format!(
+            "exponent must be {} to {}",
+            MIN_EXPONENT, MAX_EXPONENT
+        ))
+    }
+}
+
+/// `power` if it is the power of a power norm.
+pub fn check_norm_power(power: f64) -> Result<f64, String> {
+    if power.is_finite() && power >= 1.0 {
+        Ok(power)
+    } else {
+        Err(String::from("norm power must be 1 or more"))
+    }
+}
+
+pub const MIN_BAILOUT: f64 = 0.5;
+pub const MAX_BAILOUT: f64 = 1000.0;
+pub const MIN_RELAXATION: f64 = 0.05;
+pub const MAX_RELAXATION: f64 = 2.0;
+
+/// Departures from the textbook iteration, for hunting variations of the
+/// formula. The default leaves it unchanged.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Variation {
+    /// bailout radius, scaled for the norms that use a larger one
+    pub bailout: f64,
+    /// starting point of the orbit instead of 0
+    pub z0_x: f64,
+    pub z0_y: f64,
+    /// r of z' = (1 - r) z + r (z^d + c), 1 for the plain iteration
+    pub relaxation: f64,
+}
+
+impl Default for Variation {
+    fn default() -> Self {
+        Self {
+            bailout: 2.0,
+            z0_x: 0.0,
+            z0_y: 0.0,
+            relaxation: 1.0,
+        }
+    }
+}
+
+impl Variation {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn message(&self) -> String {
+        format!(
+            "bailout {}, z0 ({}, {}), relaxation {}",
+            self.bailout, self.z0_x, self.z0_y, self.relaxation
+        )
+    }
+}
+
+pub fn default_exponent() -> f64 {
+    2.0
+}
+
+/// Settings that change how a view is rendered, carried over to offscreen
+/// renders such as exported animations.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RenderOptions {
+    #[serde(default)]
+    pub fractal: Fractal,
+    /// d of z^d + c
+    #[serde(default = "default_exponent")]
+    pub exponent: f64,
+    #[serde(default)]
+    pub norm: Norm,
+    #[serde(default = "default_norm_power")]
+    pub norm_power: f64,
+    pub antialias: Antialias,
+    #[serde(default)]
+    pub coloring: Coloring,
+    #[serde(default)]
+    pub interior: Interior,
+    #[serde(default)]
+    pub blend: Blend,
+    /// iterated instead of the fractal's formula when given
+    #[serde(default)]
+    pub formula: Option<Formula>,
+    // tables go after the plain values in TOML
+    #[serde(default)]
+    pub bands: Bands,
+    pub palette: Palette,
+    #[serde(default)]
+    pub variation: Variation,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            fractal: Fractal::default(),
+            exponent: default_exponent(),
+            norm: Norm::default(),
+            norm_power: default_norm_power(),
+            antialias: Antialias::default(),
+            bands: Bands::default(),
+            coloring: Coloring::default(),
+            interior: Interior::default(),
+            palette: Palette::default(),
+            variation: Variation::default(),
+            blend: Blend::default(),
+            formula: None,
+        }
+    }
+}
+
+// Escape iteration of a pixel as the frame keeps it, 0 inside the set.
+fn iteration_value(round: Option<usize>) -> u32 {
+    round.map_or(0, |round| round.min(u32::MAX as usize) as u32)
+}
+
+// Share of the escaped pixels next to the set, in rows of `width` of
+// escape iterations, that escaped in the last quarter of `limit`, the
+// pixels beyond which count as the set. None when too few pixels border
+// the set to tell.
+fn late_share(values: &[u32], width: usize, limit: u32) -> Option<f64> {
+    let inside = |index: usize| values[index] == 0 || values[index] > limit;
+    let late = limit - limit / 4;
+    let (mut border, mut escaped_late) = (0_usize, 0_usize);
+    for (index, &value) in values.iter().enumerate() {
+        if inside(index) {
+            continue;
+        }
+        let x = index % width;
+        let next_to_set = (x > 0 && inside(index - 1))
+            || (x + 1 < width && inside(index + 1))
+            || (index >= width && inside(index - width))
+            || (index + width < values.len() && inside(index + width));
+        if next_to_set {
+            border += 1;
+            if value >= late {
+                escaped_late += 1;
+            }
+        }
+    }
+    (border >= MIN_TUNING_BORDER).then(|| escaped_late as f64 / border as f64)
+}
+
+/// z^d after the fractal's folding of z.
+// Integer exponents are multiplied out; the others go through polar form.
+pub fn power(fractal: Fractal, x: f64, y: f64, exponent: f64) -> (f64, f64) {
+    let (x, y) = match fractal {
+        Fractal::Mandelbrot | Fractal::Newton => (x, y),
+        Fractal::BurningShip => (x.abs(), y.abs()),
+        Fractal::Tricorn => (x, -y),
+    };
+    if exponent.fract() == 0.0 {
+        let (mut px, mut py) = (x, y);
+        for _ in 1..exponent as usize {
+            (px, py) = (px * x - py * y, px * y + py * x);
+        }
+        (px, py)
+    } else {
+        let r = (x * x + y * y).powf(exponent / 2.0);
+        let theta = y.atan2(x) * exponent;
+        (r * theta.cos(), r * theta.sin())
+    }
+}
+
+/// z^2 + c after the fractal's folding of z, in double-double: the step of
+/// the deep views.
+pub fn quadratic_step(
+    fractal: Fractal,
+    (x, y): (DoubleDouble, DoubleDouble),
+    (c_x, c_y): (DoubleDouble, DoubleDouble),
+) -> (DoubleDouble, DoubleDouble) {
+    let product = x * y * 2.0;
+    let product = match fractal {
+        Fractal::Mandelbrot | Fractal::Newton => product,
+        Fractal::BurningShip => product.abs(),
+        Fractal::Tricorn => -product,
+    };
+    (x.square() - y.square() + c_x, product + c_y)
+}
+
+/// Brent's cycle detection: the orbit is compared with a saved point that
+/// is replaced after 1, 2, 4, 8... steps, so that a cycle of any period is
+/// caught within a few times its length.
+pub struct Cycle<T> {
+    saved: T,
+    steps: usize,
+    interval: usize,
+}
+
+impl<T: Copy> Cycle<T> {
+    /// Follow the orbit from `start`.
+    pub fn new(start: T) -> Self {
+        Self {
+            saved: start,
+            steps: 0,
+            interval: 1,
+        }
+    }
+
+    /// The steps since the saved point when the next point of the orbit,
+    /// `point`, is back at it by `near`.
+    pub fn returns(&mut self, point: T, near: impl Fn(T, T) -> bool) -> Option<usize> {
+        self.steps += 1;
+        if near(point, self.saved) {
+            return Some(self.steps);
+        }
+        if self.steps == self.interval {
+            self.saved = point;
+            self.steps = 0;
+            self.interval *= 2;
+        }
+        None
+    }
+}
+
+/// Whether two points of an orbit are the same within PERIODICITY_EPSILON.
+pub fn near((x, y): (f64, f64), (other_x, other_y): (f64, f64)) -> bool {
+    (x - other_x).abs() < PERIODICITY_EPSILON && (y - other_y).abs() < PERIODICITY_EPSILON
+}
+
+/// The points of an orbit after z0, one per iteration, without end.
+pub struct Orbit<'a> {
+    mandelbrot: &'a Mandelbrot,
+    c: (f64, f64),
+    z: (f64, f64),
+}
+
+impl Iterator for Orbit<'_> {
+    type Item = (f64, f64);
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<(f64, f64)> {
+        self.z = self.mandelbrot.step(self.z, self.c);
+        Some(self.z)
+    }
+}
+
+const HUD_MARGIN: usize = 5;
+
+pub const MIN_ITERATIONS: usize = 16;
+pub const MAX_ITERATIONS: usize = 1 << 24;
+
+/// `iterations` if it is an iteration limit that can be set by hand.
+pub fn check_iterations(iterations: usize) -> Result<usize, String> {
+    if (2..=MAX_ITERATIONS).contains(&iterations) {
+        Ok(iterations)
+    } else {
+        Err(format!("iterations must be 2 to {}", MAX_ITERATIONS))
+    }
+}
+
+/// `scale` if it is the pixel size of a view.
+pub fn check_scale(scale: f64) -> Result<f64, String> {
+    if scale.is_finite() && scale > 0.0 {
+        Ok(scale)
+    } else {
+        Err(String::from("scale must be positive"))
+    }
+}
+
+/// Iteration limit growing with the zoom depth: `base` iterations at
+/// `reference_scale` and `per_decade` more for every tenfold zoom beyond it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoIterations {
+    pub base: usize,
+    pub per_decade: usize,
+    pub reference_scale: f64,
+}
+
+impl Default for AutoIterations {
+    fn default() -> Self {
+        Self {
+            base: 512,
+            per_decade: 128,
+            reference_scale: View::default().scale,
+        }
+    }
+}
+
+impl AutoIterations {
+    pub fn max_round(&self, scale: f64) -> usize {
+        let decades = (self.reference_scale / scale).log10().max(0.0);
+        let max_round = self.base as f64 + self.per_decade as f64 * decades;
+        (max_round.round() as usize).clamp(MIN_ITERATIONS, MAX_ITERATIONS)
+    }
+}
+
+// Difference from a whole number of pixels up to which a pan reuses the
+// previous frame.
+const PAN_TOLERANCE: f64 = 1e-6;
+
+// Colors of the pixels of a tile, their escape iterations when the frame
+// keeps them, and what rendering it found.
+type TileResult = (Vec<[u8; 4]>, Vec<u32>, TileStats);
+
+// Results of the tiles of a task.
+type TaskResult = Vec<TileResult>;
+
+// What the samples of a histogram depend on besides the iteration limit:
+// the view to the precision of double-double, the frame size and the
+// options with those that only color the escape iterations left at their
+// defaults.
+type SampleKey = (
+    View,
+    (DoubleDouble, DoubleDouble),
+    (u32, u32),
+    RenderOptions,
+);
+
+/// What became of the orbit of a point whose period was looked up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PeriodProbe {
+    /// it escaped at this iteration
+    Escapes(usize),
+    /// it settled on a cycle of this period
+    Period(usize),
+    /// Newton's method converged to this root at this iteration
+    Root(usize, usize),
+    /// it neither escaped nor settled on a cycle within this many
+    /// iterations
+    Undecided(usize),
+}
+
+impl PeriodProbe {
+    pub fn message(self) -> String {
+        match self {
+            Self::Escapes(round) => format!("escapes at {}", round),
+            Self::Period(period) => format!("period {}", period),
+            Self::Root(root, round) => format!("root {} at {}", root, round),
+            Self::Undecided(max_round) => format!("no cycle in {}", max_round),
+        }
+    }
+}
+
+// The last rendered frame, before the overlays, with what it was rendered
+// from. Shared with the copies of take_job() until one of them changes it.
+#[derive(Clone)]
+struct Rendered {
+    width: u32,
+    height: u32,
+    center: (DoubleDouble, DoubleDouble),
+    scale: f64,
+    max_round: usize,
+    options: RenderOptions,
+    frame: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct Mandelbrot {
+    drawn: bool,
+    pub width: u32,
+    pub height: u32,
+    pub center_x: f64,
+    pub center_y: f64,
+    pub scale: f64,
+    pub max_round: usize,
+    pub info: bool,
+    pub legend: bool,
+    /// colors and placement of the text over the image
+    pub hud: Theme,
+    pub options: RenderOptions,
+    pub rendering_time: Duration,
+    /// view restored by reset()
+    pub home: View,
+    /// whether zooming sets max_round from iteration_formula
+    pub auto_iterations: bool,
+    pub iteration_formula: AutoIterations,
+    // the center to the precision of double-double, valid while its high
+    // parts are center_x and center_y
+    precise_center: (DoubleDouble, DoubleDouble),
+    max_scale: f64,
+    tile_stats: Vec<TileStats>,
+    rendered: Option<Arc<Rendered>>,
+    // escape iterations of the pixels of the rendered frame, empty when its
+    // coloring needs more than them, shared like `rendered`
+    iterations: Arc<Vec<u32>>,
+    gpu_coloring: bool,
+    /// state of the GPU coloring in the information display, set by the
+    /// window
+    pub gpu_status: Option<String>,
+    color_cycling: bool,
+    /// Render in strips of tiles and keep no copy of the last frame, at the
+    /// cost of re-rendering whole frames when panning.
+    pub low_memory: bool,
+    /// Fill rectangles whose border has a single iteration count without
+    /// computing their inside (Mariani–Silver), at the risk of missing
+    /// filaments that pass between the border pixels.
+    pub subdivision: bool,
+    /// Cap the iterations of the tiles whose samples all escape quickly
+    /// near their slowest sample, at the risk of missing filaments that
+    /// pass between the samples.
+    pub adaptive_iterations: bool,
+    /// Raise the iteration limit of the auto mode and draw the frame again
+    /// when many of the pixels around the set escape near the limit.
+    pub iteration_tuning: bool,
+    // factor of the auto limits found by the tuning, kept for the next views
+    iteration_boost: usize,
+    /// whether the render cost of the tiles is drawn over the image
+    pub cost_map: bool,
+    pub session_cost: SessionCost,
+    /// Pixels per side of the blocks drawn from a single sample while the
+    /// view is moving, 1 for full-resolution frames.
+    pub preview: usize,
+    /// checked before every tile, to abandon a frame that is out of date
+    pub cancel: CancelToken,
+    /// order of the tiles of full frames
+    pub tile_order: TileOrder,
+    // row of the view the frame starts at, for the strips of draw_strip()
+    first_row: usize,
+    /// receives every tile of a full frame as soon as it is rendered
+    pub tile_sender: Option<Sender<(Tile, Vec<[u8; 4]>)>>,
+    // distribution of the escape iterations for the histogram coloring,
+    // with what it was sampled from
+    histogram: Option<(SampleKey, Histogram)>,
+    auto_exposure: bool,
+    // iterations the auto exposure stretched the palette over in the last
+    // full frame
+    exposure: Option<Exposure>,
+    iteration_histogram: bool,
+    // escape iterations of the samples of the last full frame, for the
+    // iteration histogram overlay
+    iteration_counts: Option<IterationCounts>,
+    // what the distributions above were last sampled from, with the
+    // iteration limit and the step of the grid of samples
+    sampled: Option<(SampleKey, usize, usize)>,
+    minimap: bool,
+    // overview of the whole set for the minimap, with the options it was
+    // rendered with
+    overview: Option<Minimap>,
+    // the points of the set colored by their period whatever the interior
+    // coloring, with a key of the colors
+    period_analysis: bool,
+    /// point whose period is shown in the information display and marked
+    /// in the image
+    pub probe: Option<((f64, f64), PeriodProbe)>,
+    // equipotentials and external rays drawn over the quadratic set
+    external_rays: bool,
+    // angles of the rays, with the rays and the equipotentials once drawn
+    rays: ExternalRays,
+}
+
+impl Mandelbrot {
+    pub fn new(width: u32, height: u32) -> Self {
+        let view = View::default();
+        Self {
+            drawn: false,
+            width,
+            height,
+            center_x: view.center_x,
+            center_y: view.center_y,
+            scale: view.scale,
+            max_round: 512,
+            info: true,
+            legend: false,
+            hud: Theme::default(),
+            options: RenderOptions::default(),
+            rendering_time: Duration::ZERO,
+            home: view,
+            auto_iterations: true,
+            iteration_formula: AutoIterations::default(),
+            precise_center: (view.center_x.into(), view.center_y.into()),
+            max_scale: 0.1,
+            tile_stats: Vec::new(),
+            rendered: None,
+            iterations: Arc::default(),
+            gpu_coloring: false,
+            gpu_status: None,
+            color_cycling: false,
+            low_memory: false,
+            subdivision: false,
+            adaptive_iterations: false,
+            iteration_tuning: false,
+            iteration_boost: 1,
+            cost_map: false,
+            session_cost: SessionCost::default(),
+            preview: 1,
+            cancel: CancelToken::default(),
+            tile_order: TileOrder::default(),
+            tile_sender: None,
+            first_row: 0,
+            histogram: None,
+            auto_exposure: false,
+            exposure: None,
+            iteration_histogram: false,
+            iteration_counts: None,
+            sampled: None,
+            minimap: false,
+            period_analysis: false,
+            probe: None,
+            overview: None,
+            external_rays: false,
+            rays: ExternalRays::default(),
+        }
+    }
+
+    pub fn request_redraw(&mut self) {
+        self.drawn = false;
+    }
+
+    /// A copy to draw the current view elsewhere, or None when the last
+    /// one is still up to date. The copy shares the last frame and its
+    /// iterations instead of copying them.
+    pub fn take_job(&mut self) -> Option<Self> {
+        if self.drawn {
+            return None;
+        }
+        self.drawn = true;
+        let mut job = self.clone();
+        job.drawn = false;
+        Some(job)
+    }
+
+    /// Take over what drawing `job` learned for the next frames.
+    pub fn finish(&mut self, job: Self) {
+        self.tile_stats = job.tile_stats;
+        self.rendered = job.rendered;
+        self.iterations = job.iterations;
+        self.histogram = job.histogram;
+        self.exposure = job.exposure;
+        self.iteration_counts = job.iteration_counts;
+        self.sampled = job.sampled;
+        self.overview = job.overview;
+        self.rays = job.rays;
+        if self.auto_iterations && job.iteration_boost != self.iteration_boost {
+            self.iteration_boost = job.iteration_boost;
+            self.max_round = self.max_round_for(self.scale);
+        }
+        self.session_cost = job.session_cost;
+        self.rendering_time = job.rendering_time;
+    }
+
+    pub fn auto_exposure(&self) -> bool {
+        self.auto_exposure
+    }
+
+    /// Stretch a palette cycle over the 1st to the 99th percentile of the
+    /// escape iterations of every full frame, for the iteration and distance
+    /// colorings, instead of advancing the palette with the iterations.
+    pub fn set_auto_exposure(&mut self, auto_exposure: bool) {
+        self.auto_exposure = auto_exposure;
+        self.exposure = None;
+        self.rendered = None;
+        self.drawn = false;
+    }
+
+    /// Show or hide the histogram of the escape iterations of the frame,
+    /// sampled again from the next frame.
+    pub fn toggle_iteration_histogram(&mut self) {
+        self.iteration_histogram = !self.iteration_histogram;
+        self.iteration_counts = None;
+        self.drawn = false;
+    }
+
+    /// Show or hide the overview of the whole set with the area of the view
+    /// marked on it.
+    pub fn toggle_minimap(&mut self) {
+        self.minimap = !self.minimap;
+        self.drawn = false;
+    }
+
+    pub fn period_analysis(&self) -> bool {
+        self.period_analysis
+    }
+
+    /// Color the points of the set by their period, and show the colors of
+    /// the periods, or go back to the interior coloring of the options.
+    pub fn set_period_analysis(&mut self, period_analysis: bool) {
+        self.period_analysis = period_analysis;
+        self.rendered = None;
+        self.drawn = false;
+    }
+
+    pub fn external_rays(&self) -> bool {
+        self.external_rays
+    }
+
+    /// Draw the equipotentials and the external rays over the image of the
+    /// quadratic Mandelbrot set.
+    pub fn set_external_rays(&mut self, external_rays: bool) {
+        self.external_rays = external_rays;
+        self.drawn = false;
+    }
+
+    /// Trace the external rays of `angles` instead.
+    pub fn set_ray_angles(&mut self, angles: Vec<Angle>) {
+        self.rays = ExternalRays::new(angles);
+        self.drawn = false;
+    }
+
+    // Whether the fractal is the one the rays and the equipotentials are
+    // computed for.
+    fn shows_external_rays(&self) -> bool {
+        self.options.fractal == Fractal::Mandelbrot && self.is_quadratic()
+    }
+
+    // Whether the auto exposure colors the frame.
+    fn exposes(&self) -> bool {
+        self.auto_exposure
+            && matches!(
+                self.options.coloring,
+                Coloring::Iterations | Coloring::Distance
+            )
+    }
+
+    pub fn gpu_coloring(&self) -> bool {
+        self.gpu_coloring
+    }
+
+    /// Store escape iterations in the frame for `gpu::GpuColoring` instead
+    /// of colors: 24 bits in RGB with alpha 0, so that the overlays drawn
+    /// over them stay recognizable. Antialiasing averages colors, so it
+    /// keeps the CPU colors.
+    pub fn set_gpu_coloring(&mut self, gpu_coloring: bool) {
+        self.gpu_coloring = gpu_coloring;
+        self.rendered = None;
+        self.drawn = false;
+    }
+
+    pub fn color_cycling(&self) -> bool {
+        self.color_cycling
+    }
+
+    /// Store escape iterations in the frame like the GPU coloring, for
+    /// `cycling::ColorCycling` to color them on the CPU.
+    pub fn set_color_cycling(&mut self, color_cycling: bool) {
+        self.color_cycling = color_cycling;
+        self.rendered = None;
+        self.drawn = false;
+    }
+
+    pub fn move_center(&mut self, x: f64, y: f64) {
+        let (center_x, center_y) = self.center();
+        self.set_center_to(center_x + x * self.scale, center_y + y * self.scale);
+        info!("center ({}, {})", self.center_x, self.center_y);
+    }
+
+    pub fn set_center(&mut self, x: f64, y: f64) {
+        let (center_x, center_y) = self.precise_point_at(x, y);
+        self.set_center_to(center_x, center_y);
+        info!("center ({}, {})", self.center_x, self.center_y);
+    }
+
+    /// The center to the precision of double-double: the low parts are
+    /// dropped once `center_x` or `center_y` have been set to something else.
+    pub fn center(&self) -> (DoubleDouble, DoubleDouble) {
+        let (x, y) = self.precise_center;
+        (
+            if x.hi == self.center_x {
+                x
+            } else {
+                self.center_x.into()
+            },
+            if y.hi == self.center_y {
+                y
+            } else {
+                self.center_y.into()
+            },
+        )
+    }
+
+    fn set_center_to(&mut self, x: DoubleDouble, y: DoubleDouble) {
+        self.center_x = x.hi;
+        self.center_y = y.hi;
+        self.precise_center = (x, y);
+    }
+
+    /// `point_at` to the precision of double-double.
+    pub fn precise_point_at(&self, x: f64, y: f64) -> (DoubleDouble, DoubleDouble) {
+        let (center_x, center_y) = self.center();
+        (
+            center_x + (x - (self.width as f64 / 2.0)) * self.scale,
+            center_y + ((self.height as f64 / 2.0) - y) * self.scale,
+        )
+    }
+
+    /// Point of the complex plane under the pixel (x, y).
+    pub fn point_at(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.center_x + (x - (self.width as f64 / 2.0)) * self.scale,
+            self.center_y + ((self.height as f64 / 2.0) - y) * self.scale,
+        )
+    }
+
+    /// Pixel position of the point (x, y) of the complex plane, the inverse
+    /// of `point_at`.
+    pub fn pixel_of(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            (x - self.center_x) / self.scale + self.width as f64 / 2.0,
+            self.height as f64 / 2.0 - (y - self.center_y) / self.scale,
+        )
+    }
+
+    /// `pixel_of` for a point given to the precision of double-double.
+    pub fn precise_pixel_of(&self, x: DoubleDouble, y: DoubleDouble) -> (f64, f64) {
+        let (center_x, center_y) = self.center();
+        (
+            (x - center_x).hi / self.scale + self.width as f64 / 2.0,
+            self.height as f64 / 2.0 - (y - center_y).hi / self.scale,
+        )
+    }
+
+    /// Go to the view centered on (x, y), to the precision of double-double,
+    /// at `scale`, with the iteration limit for it, unless the center is not
+    /// finite or the scale not that of a view.
+    pub fn go_to(&mut self, x: DoubleDouble, y: DoubleDouble, scale: f64) -> Result<(), String> {
+        if ![x.hi, x.lo, y.hi, y.lo]
+            .iter()
+            .all(|value| value.is_finite())
+        {
+            return Err(String::from("the center must be finite"));
+        }
+        let scale = check_scale(scale)?;
+        self.set_precise_view(x, y, scale, self.max_round_for(scale));
+        Ok(())
+    }
+
+    /// Zoom so that the rectangle between the pixels (x0, y0) and (x1, y1)
+    /// fills the window, keeping the aspect ratio of the window.
+    pub fn zoom_to_rect(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        let (center_x, center_y) = self.precise_point_at((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+        let ratio = ((x1 - x0).abs() / self.width as f64).max((y1 - y0).abs() / self.height as f64);
+        let scale = self.scale * ratio;
+        self.set_precise_view(center_x, center_y, scale, self.max_round_for(scale));
+    }
+
+    /// Zoom by `factor` (above 1 to zoom in), keeping the point under the
+    /// pixel (x, y) where it is.
+    pub fn zoom_at(&mut self, x: f64, y: f64, factor: f64) {
+        let (point_x, point_y) = self.precise_point_at(x, y);
+        let scale = (self.scale / factor).clamp(self.min_scale(), self.max_scale);
+        let center_x = point_x + -(x - self.width as f64 / 2.0) * scale;
+        let center_y = point_y + -(self.height as f64 / 2.0 - y) * scale;
+        self.set_precise_view(center_x, center_y, scale, self.max_round_for(scale));
+    }
+
+    /// Change the frame to `width` by `height` pixels, keeping the area of
+    /// the plane the old frame showed, and the home view, in view.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let factor = (self.width as f64 / width as f64).max(self.height as f64 / height as f64);
+        self.width = width;
+        self.height = height;
+        self.scale = (self.scale * factor).clamp(self.min_scale(), self.max_scale);
+        self.home.scale *= factor;
+        self.max_round = self.max_round_for(self.scale);
+        self.drawn = false;
+        info!("frame {}x{}, scale {}", width, height, self.scale);
+    }
+
+    pub fn view(&self) -> View {
+        View {
+            center_x: self.center_x,
+            center_y: self.center_y,
+            scale: self.scale,
+        }
+    }
+
+    pub fn set_view(&mut self, x: f64, y: f64, scale: f64, max_round: usize) {
+        self.center_x = x;
+        self.center_y = y;
+        self.scale = scale.clamp(self.min_scale(), self.max_scale);
+        self.max_round = max_round;
+        self.drawn = false;
+        info!(
+            "center ({}, {}), scale {}, max_round {}",
+            self.center_x, self.center_y, self.scale, self.max_round
+        );
+    }
+
+    /// `set_view` with the center to the precision of double-double.
+    pub fn set_precise_view(
+        &mut self,
+        x: DoubleDouble,
+        y: DoubleDouble,
+        scale: f64,
+        max_round: usize,
+    ) {
+        self.set_view(x.hi, y.hi, scale, max_round);
+        self.precise_center = (x, y);
+    }
+
+    /// Iteration limit of the default formula at `scale`.
+    pub fn auto_max_round(scale: f64) -> usize {
+        AutoIterations::default().max_round(scale)
+    }
+
+    /// Iteration limit for a view of `scale`: from the formula in the auto
+    /// mode, or the current limit in the manual mode.
+    pub fn max_round_for(&self, scale: f64) -> usize {
+        if self.auto_iterations {
+            let max_round = self.iteration_formula.max_round(scale) * self.iteration_boost;
+            max_round.min(MAX_ITERATIONS)
+        } else {
+            self.max_round
+        }
+    }
+
+    /// Multiply the iteration limit by `factor`, switching to the manual
+    /// mode.
+    pub fn scale_max_round(&mut self, factor: f64) {
+        self.set_max_round((self.max_round as f64 * factor).round() as usize);
+    }
+
+    /// Set the iteration limit, switching to the manual mode.
+    pub fn set_max_round(&mut self, max_round: usize) {
+        self.max_round = max_round.clamp(MIN_ITERATIONS, MAX_ITERATIONS);
+        self.auto_iterations = false;
+        self.drawn = false;
+        info!("max_round {} (manual)", self.max_round);
+    }
+
+    pub fn toggle_auto_iterations(&mut self) {
+        self.auto_iterations = !self.auto_iterations;
+        self.iteration_boost = 1;
+        self.max_round = self.max_round_for(self.scale);
+        self.drawn = false;
+        info!(
+            "max_round {} (auto: {})",
+            self.max_round, self.auto_iterations
+        );
+    }
+
+    /// Smallest scale at which f64 coordinates around the current center
+    /// still resolve every pixel.
+    pub fn precision_limit(&self) -> f64 {
+        let magnitude = self.center_x.abs().max(self.center_y.abs()).max(1.0);
+        magnitude * f64::EPSILON * PRECISION_ULPS
+    }
+
+    /// How much further the view can be zoomed in before the precision of
+    /// f64 degrades the image.
+    pub fn remaining_zoom(&self) -> f64 {
+        (self.scale / self.precision_limit()).max(1.0)
+    }
+
+    pub fn remaining_zoom_message(&self) -> String {
+        let remaining = self.remaining_zoom();
+        let f64_message = if remaining <= 1.0 {
+            String::from("zoom left: none (f64 limit)")
+        } else {
+            format!("zoom left: ~1e{}x in f64", remaining.log10().floor())
+        };
+        if !self.is_quadratic() {
+            return f64_message;
+        }
+        let remaining = (self.scale / DOUBLE_DOUBLE_MIN_SCALE).max(1.0);
+        if remaining <= 1.0 {
+            String::from("zoom left: none (double-double limit)")
+        } else {
+            format!(
+                "{}, ~1e{}x in double-double",
+                f64_message,
+                remaining.log10().floor()
+            )
+        }
+    }
+
+    /// Command line that opens the window at the current view, with the
+    /// options that differ from the defaults.
+    pub fn command_line(&self) -> String {
+        fn value_name<T: ValueEnum>(value: T) -> String {
+            value
+                .to_possible_value()
+                .map_or_else(String::new, |value| value.get_name().to_string())
+        }
+
+        let options = &self.options;
+        let defaults = RenderOptions::default();
+        let mut args = vec![
+            String::from("mandelbrot"),
+            format!("--x {}", self.center_x),
+            format!("--y {}", self.center_y),
+            format!("--scale {}", self.scale),
+            format!("--iterations {}", self.max_round),
+        ];
+        if options.fractal != defaults.fractal {
+            args.push(format!("--fractal {}", value_name(options.fractal)));
+        }
+        if options.exponent != defaults.exponent {
+            args.push(format!("--exponent {}", options.exponent));
+        }
+        if let Some(formula) = &options.formula {
+            args.push(format!("--formula \"{}\"", formula.source()));
+        }
+        if options.norm != defaults.norm {
+            args.push(format!("--norm {}", value_name(options.norm)));
+            if options.norm == Norm::Power {
+                args.push(format!("--norm-power {}", options.norm_power));
+            }
+        }
+        if options.coloring != defaults.coloring {
+            args.push(format!("--coloring {}", value_name(options.coloring)));
+        }
+        // palettes loaded from files are only known by their name
+        if options.palette != defaults.palette && Palette::presets().contains(&options.palette) {
+            args.push(format!("--palette {}", options.palette.name));
+        }
+        if options.bands.count != 0 {
+            args.push(format!(
+                "--bands {} --band-offset {}",
+                options.bands.count, options.bands.offset
+            ));
+        }
+        if options.antialias != defaults.antialias {
+            args.push(format!("--antialias {}", value_name(options.antialias)));
+        }
+        if options.blend != defaults.blend {
+            args.push(format!("--blend {}", value_name(options.blend)));
+        }
+        let variation = options.variation;
+        let plain = Variation::default();
+        if variation.bailout != plain.bailout {
+            args.push(format!("--bailout {}", variation.bailout));
+        }
+        if (variation.z0_x, variation.z0_y) != (plain.z0_x, plain.z0_y) {
+            args.push(format!(
+                "--z0-x {} --z0-y {}",
+                variation.z0_x, variation.z0_y
+            ));
+        }
+        if variation.relaxation != plain.relaxation {
+            args.push(format!("--relaxation {}", variation.relaxation));
+        }
+        args.join(" ")
+    }
+
+    pub fn zoom(&mut self, in_out: f64) -> bool {
+        self.scale *= 1.07_f64.powf(-in_out);
+        self.max_round = self.max_round_for(self.scale);
+        info!("scale {}, max_round {}", self.scale, self.max_round);
+
+        if self.scale > self.max_scale {
+            self.scale = self.max_scale;
+            return false;
+        }
+        if self.scale < self.min_scale() {
+            info!("scale is smaller than the precision allows: {}", self.scale);
+            self.scale = self.min_scale();
+            return false;
+        }
+        true
+    }
+
+    pub fn reset(&mut self) {
+        let view = self.home;
+        self.drawn = false;
+        self.center_x = view.center_x;
+        self.center_y = view.center_y;
+        self.scale = view.scale;
+        self.auto_iterations = true;
+        self.iteration_boost = 1;
+        self.max_round = self.iteration_formula.max_round(view.scale);
+        self.info = true;
+        self.legend = false;
+        self.rendering_time = Duration::ZERO;
+        self.max_scale = 0.1;
+        self.tile_stats.clear();
+    }
+
+    /// Escape iteration of the point (x, y) within max_round, None for the
+    /// points of the set: the scalar kernel on its own, for `--verify`.
+    pub fn escape_time(&self, x: f64, y: f64, periodicity: bool) -> Option<usize> {
+        self.check_divergence_f64(x, y, self.max_round, periodicity)
+    }
+
+    // Escape iteration of the pixel at (x, y), which is the point itself,
+    // or its offset from the center in double-double mode.
+    fn check_divergence(
+        &self,
+        x: f64,
+        y: f64,
+        max_round: usize,
+        periodicity: bool,
+    ) -> Option<usize> {
+        if self.double_double() {
+            self.check_divergence_dd(x, y, max_round, periodicity)
+        } else {
+            self.check_divergence_f64(x, y, max_round, periodicity)
+        }
+    }
+
+    fn check_divergence_f64(
+        &self,
+        pos_x: f64,
+        pos_y: f64,
+        max_round: usize,
+        periodicity: bool,
+    ) -> Option<usize> {
+        if self.is_newton() {
+            return self
+                .check_newton(pos_x, pos_y, max_round)
+                .map(|(round, _)| round);
+        }
+        if self.options.formula.is_some() {
+            return self.check_divergence_generic(pos_x, pos_y, max_round, periodicity);
+        }
+        if (pos_x >= 2.0 || pos_y >= 2.0) && self.options.variation.is_default() {
+            return Some(1);
+        };
+        if !self.is_quadratic() {
+            return self.check_divergence_generic(pos_x, pos_y, max_round, periodicity);
+        }
+
+        let mut xn: f64 = 0.0;
+        let mut yn: f64 = 0.0;
+        let mut xn_1_power: f64 = 0.0;
+        let mut yn_1_power: f64 = 0.0;
+        let mut cycle = Cycle::new((0.0, 0.0));
+
+        let mut round: usize = 1;
+        while round < max_round {
+            let xn_1 = xn;
+            let yn_1 = yn;
+
+            xn = xn_1_power - yn_1_power + pos_x;
+            yn = match self.options.fractal {
+                Fractal::Mandelbrot => 2.0 * xn_1 * yn_1 + pos_y,
+                Fractal::BurningShip => (2.0 * xn_1 * yn_1).abs() + pos_y,
+                Fractal::Tricorn => -2.0 * xn_1 * yn_1 + pos_y,
+                Fractal::Newton => unreachable!("Newton's method is iterated by check_newton()"),
+            };
+
+            // faster than xn.powf(2.0) or nx.powi(2)
+            xn_1_power = xn * xn;
+            yn_1_power = yn * yn;
+
+            if (xn_1_power + yn_1_power) >= 4.0 {
+                return Some(round);
+            }
+
+            if periodicity && cycle.returns((xn, yn), near).is_some() {
+                return None;
+            }
+            round += 1
+        }
+        None
+    }
+
+    /// Whether the formula is one the fast kernels handle: z^2 with the
+    /// circular bailout, without variation.
+    pub fn is_quadratic(&self) -> bool {
+        self.options.fractal != Fractal::Newton
+            && self.options.formula.is_none()
+            && self.options.exponent == 2.0
+            && self.options.norm == Norm::Euclidean
+            && self.options.variation.is_default()
+    }
+
+    // Smallest scale the view can be zoomed to: as deep as double-double
+    // goes for the formulas it handles, down to machine epsilon otherwise.
+    fn min_scale(&self) -> f64 {
+        if self.is_quadratic() {
+            DOUBLE_DOUBLE_MIN_SCALE
+        } else {
+            f64::EPSILON
+        }
+    }
+
+    /// Whether the pixels are iterated in double-double, past the precision
+    /// of f64 with the formulas that support it.
+    pub fn double_double(&self) -> bool {
+        self.is_quadratic() && self.scale < self.precision_limit()
+    }
+
+    // Point of the top left pixel of the frame: its offset from the center
+    // in double-double mode, so that the pixels keep the bits f64 would
+    // lose, or else the point itself.
+    fn frame_origin(&self) -> (f64, f64) {
+        let half_width = (self.scale * self.width as f64) / 2.0;
+        let half_height = (self.scale * self.height as f64) / 2.0;
+        if self.double_double() {
+            (-half_width, half_height)
+        } else {
+            (self.center_x - half_width, self.center_y + half_height)
+        }
+    }
+
+    // check_divergence() in double-double, for the pixel at the offset
+    // (dx, dy) from the center. The same iteration as the f64 kernel at
+    // about a dozen times its cost.
+    fn check_divergence_dd(
+        &self,
+        dx: f64,
+        dy: f64,
+        max_round: usize,
+        periodicity: bool,
+    ) -> Option<usize> {
+        let (center_x, center_y) = self.center();
+        let pos_x = center_x + dx;
+        let pos_y = center_y + dy;
+        if pos_x.hi >= 2.0 || pos_y.hi >= 2.0 {
+            return Some(1);
+        }
+        // the cycles are told apart at the size of a pixel
+        let epsilon = PERIODICITY_EPSILON.min(self.scale * 1e-3);
+
+        let mut xn = DoubleDouble::ZERO;
+        let mut yn = DoubleDouble::ZERO;
+        let mut xn_1_power = DoubleDouble::ZERO;
+        let mut yn_1_power = DoubleDouble::ZERO;
+        let mut cycle = Cycle::new((DoubleDouble::ZERO, DoubleDouble::ZERO));
+        let near = |(x, y): (DoubleDouble, DoubleDouble), (other_x, other_y): (DoubleDouble, _)| {
+            (x - other_x).hi.abs() < epsilon && (y - other_y).hi.abs() < epsilon
+        };
+
+        let mut round: usize = 1;
+        while round < max_round {
+            let product = xn * yn * 2.0;
+            xn = xn_1_power - yn_1_power + pos_x;
+            yn = match self.options.fractal {
+                Fractal::Mandelbrot => product + pos_y,
+                Fractal::BurningShip => product.abs() + pos_y,
+                Fractal::Tricorn => -product + pos_y,
+                Fractal::Newton => unreachable!("Newton's method is iterated in f64"),
+            };
+
+            xn_1_power = xn.square();
+            yn_1_power = yn.square();
+
+            if xn_1_power.hi + yn_1_power.hi >= 4.0 {
+                return Some(round);
+            }
+
+            if periodicity && cycle.returns((xn, yn), near).is_some() {
+                return None;
+            }
+            round += 1
+        }
+        None
+    }
+
+    // Newton's method rather than an escape-time formula, unless the user
+    // typed a formula of their own.
+    pub fn is_newton(&self) -> bool {
+        self.options.fractal == Fractal::Newton && self.options.formula.is_none()
+    }
+
+    // Point of the orbit after (x, y) for the pixel at (pos_x, pos_y): z^d + c
+    // after the fractal's folding of z, or the formula typed by the user.
+    fn next_point(&self, x: f64, y: f64, pos_x: f64, pos_y: f64) -> (f64, f64) {
+        match &self.options.formula {
+            Some(formula) => formula.iterate((x, y), (pos_x, pos_y)),
+            None => {
+                let (px, py) = power(self.options.fractal, x, y, self.options.exponent);
+                (px + pos_x, py + pos_y)
+            }
+        }
+    }
+
+    // Point of the orbit after z for the point c: next_point() relaxed by
+    // the variation.
+    #[inline(always)]
+    fn step(&self, (x, y): (f64, f64), (pos_x, pos_y): (f64, f64)) -> (f64, f64) {
+        let relaxation = self.options.variation.relaxation;
+        let (next_x, next_y) = self.next_point(x, y, pos_x, pos_y);
+        (x + relaxation * (next_x - x), y + relaxation * (next_y - y))
+    }
+
+    /// The orbit of the point (x, y) in f64 as the escape-time formulas
+    /// iterate it, from z0 of the variation.
+    pub fn orbit(&self, x: f64, y: f64) -> Orbit<'_> {
+        let variation = self.options.variation;
+        Orbit {
+            mandelbrot: self,
+            c: (x, y),
+            z: (variation.z0_x, variation.z0_y),
+        }
+    }
+
+    /// Whether a point z of an orbit is past the bailout of the norm and
+    /// the variation.
+    pub fn escape_test(&self) -> impl Fn((f64, f64)) -> bool {
+        let norm = self.options.norm;
+        let norm_power = self.options.norm_power;
+        let radius = norm.radius(norm_power) * self.options.variation.bailout / 2.0;
+        move |(x, y)| norm.value(x, y, norm_power) >= radius
+    }
+
+    // check_divergence() for other exponents, bailout norms, variations and
+    // formulas
+    fn check_divergence_generic(
+        &self,
+        pos_x: f64,
+        pos_y: f64,
+        max_round: usize,
+        periodicity: bool,
+    ) -> Option<usize> {
+        let variation = self.options.variation;
+        let escaped = self.escape_test();
+        let mut cycle = Cycle::new((variation.z0_x, variation.z0_y));
+        for (round, z) in (1..max_round).zip(self.orbit(pos_x, pos_y)) {
+            if escaped(z) {
+                return Some(round);
+            }
+            if periodicity && cycle.returns(z, near).is_some() {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Estimated distance from the point (x, y) to the set, None for points
+    /// that do not escape. Only meaningful for the Mandelbrot formula.
+    pub fn distance_at(&self, x: f64, y: f64) -> Option<f64> {
+        self.check_distance(x, y, self.max_round, true)
+            .map(|(_, distance)| distance)
+    }
+
+    fn uses_distance(&self) -> bool {
+        self.options.coloring == Coloring::Distance
+            && self.options.fractal == Fractal::Mandelbrot
+            && self.options.formula.is_none()
+            && self.options.variation.is_default()
+    }
+
+    // Whether the pixels are colored one by one by point_color() from
+    // their whole orbit rather than by their escape iteration.
+    fn colors_orbits(&self) -> bool {
+        self.is_newton()
+            || ((self.uses_distance()
+                || self.options.coloring.is_trap()
+                || matches!(
+                    self.options.coloring,
+                    Coloring::Stripe | Coloring::Decomposition
+                ))
+                && !self.double_double())
+    }
+
+    // Color of the Newton, distance, trap, stripe or decomposition coloring
+    // at (x, y).
+    fn point_color(&self, x: f64, y: f64, plan: TilePlan, stats: &mut TileStats) -> [u8; 4] {
+        if self.is_newton() {
+            self.newton_color(x, y, plan, stats)
+        } else if self.options.coloring.is_trap() {
+            self.trap_color(x, y, plan, stats)
+        } else if self.options.coloring == Coloring::Stripe {
+            self.stripe_color(x, y, plan, stats)
+        } else if self.options.coloring == Coloring::Decomposition {
+            self.decomposition_color(x, y, plan, stats)
+        } else {
+            self.distance_color(x, y, plan, stats)
+        }
+    }
+
+    // Escape iteration of the point and the smallest distance of its orbit
+    // to the trap of the coloring. The interior is iterated up to the limit
+    // or until its cycle is found, by which time the orbit has gone around
+    // the cycle once.
+    fn check_trap(
+        &self,
+        pos_x: f64,
+        pos_y: f64,
+        max_round: usize,
+        periodicity: bool,
+    ) -> (Option<usize>, f64) {
+        let coloring = self.options.coloring;
+        let variation = self.options.variation;
+        let escaped = self.escape_test();
+        let mut cycle = Cycle::new((variation.z0_x, variation.z0_y));
+        let mut trap = f64::INFINITY;
+
+        for (round, z) in (1..max_round).zip(self.orbit(pos_x, pos_y)) {
+            trap = trap.min(coloring.trap_distance(z.0, z.1));
+            if escaped(z) {
+                return (Some(round), trap);
+            }
+            if periodicity && cycle.returns(z, near).is_some() {
+                break;
+            }
+        }
+        (None, trap)
+    }
+
+    // Color of the trap colorings at (x, y): the palette advances with the
+    // square root of the trap distance, inside the set as well.
+    fn trap_color(&self, x: f64, y: f64, plan: TilePlan, stats: &mut TileStats) -> [u8; 4] {
+        let (round, trap) = self.check_trap(x, y, plan.max_round, plan.periodicity);
+        stats.add(round);
+        let period = self.options.palette.period();
+        let position = (trap.sqrt() * TRAP_CYCLES * period as f64).min(u32::MAX as f64);
+        self.round_to_color(position as usize)
+    }
+
+    // Escape iteration of the point and its stripe average: the mean of
+    // (1 + sin(STRIPE_DENSITY arg z)) / 2 over the orbit, blended between
+    // the means with and without the last point by the fraction of the
+    // smooth escape time, from 0 to 1. None for the points of the set.
+    fn check_stripe(
+        &self,
+        pos_x: f64,
+        pos_y: f64,
+        max_round: usize,
+        periodicity: bool,
+    ) -> Option<(usize, f64)> {
+        let variation = self.options.variation;
+        let mut cycle = Cycle::new((variation.z0_x, variation.z0_y));
+        let mut sum = 0.0_f64;
+
+        for (round, (xn, yn)) in (1..max_round).zip(self.orbit(pos_x, pos_y)) {
+            let last = 0.5 + 0.5 * (STRIPE_DENSITY * yn.atan2(xn)).sin();
+            sum += last;
+            let abs = xn.hypot(yn);
+            if abs >= STRIPE_RADIUS {
+                let average = sum / round as f64;
+                let previous = if round > 1 {
+                    (sum - last) / (round - 1) as f64
+                } else {
+                    average
+                };
+                // 1 right past the bailout, 0 a whole iteration past it
+                let degree = self.options.exponent.max(1.5);
+                let fraction =
+                    (1.0 - (abs.ln() / STRIPE_RADIUS.ln()).ln() / degree.ln()).clamp(0.0, 1.0);
+                return Some((round, previous + fraction * (average - previous)));
+            }
+            if periodicity && cycle.returns((xn, yn), near).is_some() {
+                return None;
+            }
+        }
+        None
+    }
+
+    // Color of the stripe coloring at (x, y): one palette cycle over the
+    // range of the stripe average.
+    fn stripe_color(&self, x: f64, y: f64, plan: TilePlan, stats: &mut TileStats) -> [u8; 4] {
+        let stripe = self.check_stripe(x, y, plan.max_round, plan.periodicity);
+        stats.add(stripe.map(|(round, _)| round));
+        match stripe {
+            Some((_, average)) => {
+                let period = self.options.palette.period();
+                self.round_to_color((average * period as f64) as usize)
+            }
+            None => self.inside_color(x, y, plan.max_round),
+        }
+    }
+
+    // Escape iteration of the point and whether z escapes below the real
+    // axis. None for the points of the set.
+    fn check_decomposition(
+        &self,
+        pos_x: f64,
+        pos_y: f64,
+        max_round: usize,
+        periodicity: bool,
+    ) -> Option<(usize, bool)> {
+        let variation = self.options.variation;
+        let mut cycle = Cycle::new((variation.z0_x, variation.z0_y));
+        for (round, (xn, yn)) in (1..max_round).zip(self.orbit(pos_x, pos_y)) {
+            if xn.hypot(yn) >= DECOMPOSITION_RADIUS {
+                return Some((round, yn < 0.0));
+            }
+            if periodicity && cycle.returns((xn, yn), near).is_some() {
+                return None;
+            }
+        }
+        None
+    }
+
+    // Color of the binary decomposition at (x, y).
+    fn decomposition_color(
+        &self,
+        x: f64,
+        y: f64,
+        plan: TilePlan,
+        stats: &mut TileStats,
+    ) -> [u8; 4] {
+        let escape = self.check_decomposition(x, y, plan.max_round, plan.periodicity);
+        stats.add(escape.map(|(round, _)| round));
+        match escape {
+            Some((round, true)) => self.darken(self.round_to_color(round), DECOMPOSITION_SHADE),
+            Some((round, false)) => self.round_to_color(round),
+            None => self.inside_color(x, y, plan.max_round),
+        }
+    }
+
+    // Whether the points of the set are colored by check_interior(). The
+    // points where Newton's method does not converge have no orbit to
+    // follow.
+    fn colors_interior(&self) -> bool {
+        self.interior() != Interior::Black && !self.is_newton()
+    }
+
+    // The interior coloring of the frame.
+    fn interior(&self) -> Interior {
+        if self.period_analysis {
+            Interior::Period
+        } else {
+            self.options.interior
+        }
+    }
+
+    // |z| at the end of the orbit of a point of the set, and the period of
+    // the cycle it settles on with the iteration at which it is found (from
+    // which the orbit repeats, for the convergence coloring), None when it
+    // is not found by max_round. The magnitude coloring follows the orbit
+    // up to max_round, the others stop at the cycle. None for a point that
+    // escapes after all.
+    fn check_interior(
+        &self,
+        pos_x: f64,
+        pos_y: f64,
+        max_round: usize,
+        interior: Interior,
+    ) -> Option<(f64, Option<(usize, usize)>)> {
+        let variation = self.options.variation;
+        let stops = interior != Interior::Magnitude;
+        let escaped = self.escape_test();
+        let step = |x: f64, y: f64| self.step((x, y), (pos_x, pos_y));
+        let (mut xn, mut yn) = (variation.z0_x, variation.z0_y);
+        let mut checks = Cycle::new((xn, yn));
+        let mut cycle = None;
+
+        for (round, z) in (1..max_round).zip(self.orbit(pos_x, pos_y)) {
+            (xn, yn) = z;
+            if escaped(z) {
+                return None;
+            }
+
+            // the magnitude coloring follows the whole orbit
+            let returned = checks.returns(z, |z, saved| stops && near(z, saved));
+            if let Some(steps) = returned {
+                // the first return close to the point, at most the steps
+                // since the checkpoint
+                let (mut x, mut y) = (xn, yn);
+                let period = (1..=steps)
+                    .find(|_| {
+                        (x, y) = step(x, y);
+                        (x - xn).abs() < INTERIOR_EPSILON && (y - yn).abs() < INTERIOR_EPSILON
+                    })
+                    .unwrap_or(steps);
+                // the checkpoints fall every power of two, so the iteration
+                // from which the orbit repeats within INTERIOR_EPSILON
+                // after a period is found again
+                let settled = if interior == Interior::Convergence {
+                    let start = (variation.z0_x, variation.z0_y);
+                    let mut ahead = (0..period).fold(start, |(x, y), _| step(x, y));
+                    let mut behind = start;
+                    (0..round)
+                        .find(|_| {
+                            let close = (ahead.0 - behind.0).abs() < INTERIOR_EPSILON
+                                && (ahead.1 - behind.1).abs() < INTERIOR_EPSILON;
+                            ahead = step(ahead.0, ahead.1);
+                            behind = step(behind.0, behind.1);
+                            close
+                        })
+                        .unwrap_or(round)
+                } else {
+                    round
+                };
+                cycle = Some((period, settled));
+                break;
+            }
+        }
+        Some((xn.hypot(yn), cycle))
+    }
+
+    // Color of the point (x, y) of the set by the interior coloring, black
+    // without one.
+    fn inside_color(&self, x: f64, y: f64, max_round: usize) -> [u8; 4] {
+        if !self.colors_interior() {
+            return self.color_of(None);
+        }
+        // the pixels of double-double mode give their offset from the
+        // center, and the interior of the set is followed from the nearest
+        // f64 point: its cycle does not hang on the bits f64 loses
+        let (x, y) = if self.double_double() {
+            let (center_x, center_y) = self.center();
+            ((center_x + x).hi, (center_y + y).hi)
+        } else {
+            (x, y)
+        };
+        let interior = self.interior();
+        let period = self.options.palette.period();
+        let position = match (interior, self.check_interior(x, y, max_round, interior)) {
+            (Interior::Magnitude, Some((magnitude, _))) => {
+                let bailout = self.options.variation.bailout;
+                ((magnitude / bailout).min(1.0) * period as f64) as usize
+            }
+            (Interior::Period, Some((_, Some((cycle, _))))) => return self.period_color(cycle),
+            (Interior::Convergence, Some((_, Some((_, round))))) => round,
+            _ => return [0x00, 0x00, 0x00, 0xff],
+        };
+        self.options.palette.color(
+            self.options.bands.quantize(position, period),
+            self.options.blend,
+        )
+    }
+
+    /// Color of the points of the set whose orbit settles on a cycle of
+    /// `cycle` points: a color of the palette per period.
+    pub fn period_color(&self, cycle: usize) -> [u8; 4] {
+        let palette = &self.options.palette;
+        let period = palette.period();
+        let position = cycle * (period / palette.colors.len().max(1));
+        palette.color(
+            self.options.bands.quantize(position, period),
+            self.options.blend,
+        )
+    }
+
+    /// Whether the orbit of the point (x, y) escapes, or else the period of
+    /// the cycle it settles on, within the iteration limit; for the Newton
+    /// fractal, the root it converges to.
+    pub fn probe_period(&self, x: f64, y: f64) -> PeriodProbe {
+        if self.is_newton() {
+            return match self.check_newton(x, y, self.max_round) {
+                Some((round, root)) => PeriodProbe::Root(root, round),
+                None => PeriodProbe::Undecided(self.max_round),
+            };
+        }
+        if let Some(round) = self.check_divergence_f64(x, y, self.max_round, false) {
+            return PeriodProbe::Escapes(round);
+        }
+        match self.check_interior(x, y, self.max_round, Interior::Period) {
+            Some((_, Some((cycle, _)))) => PeriodProbe::Period(cycle),
+            _ => PeriodProbe::Undecided(self.max_round),
+        }
+    }
+
+    // Color of a pixel or sample at (x, y) from its escape iteration.
+    fn sample_color(&self, round: Option<usize>, x: f64, y: f64, max_round: usize) -> [u8; 4] {
+        match round {
+            None if self.colors_interior() => self.inside_color(x, y, max_round),
+            _ => self.color_of(round),
+        }
+    }
+
+    // Escape iteration and exterior distance estimate |z| ln|z| / |dz/dc| of
+    // the point, iterating the derivative along with z.
+    fn check_distance(
+        &self,
+        pos_x: f64,
+        pos_y: f64,
+        max_round: usize,
+        periodicity: bool,
+    ) -> Option<(usize, f64)> {
+        let exponent = self.options.exponent;
+        let (mut xn, mut yn) = (0.0_f64, 0.0_f64);
+        let (mut dx, mut dy) = (0.0_f64, 0.0_f64);
+        let mut cycle = Cycle::new((xn, yn));
+
+        for round in 1..max_round {
+            // dz' = d z^(d-1) dz + 1
+            let (px, py) = power(Fractal::Mandelbrot, xn, yn, exponent - 1.0);
+            let (px, py) = (exponent * px, exponent * py);
+            (dx, dy) = (px * dx - py * dy + 1.0, px * dy + py * dx);
+            let (px, py) = power(Fractal::Mandelbrot, xn, yn, exponent);
+            xn = px + pos_x;
+            yn = py + pos_y;
+
+            let abs = xn.hypot(yn);
+            if abs >= DISTANCE_RADIUS {
+                return Some((round, abs * abs.ln() / dx.hypot(dy)));
+            }
+            if periodicity && cycle.returns((xn, yn), near).is_some() {
+                return None;
+            }
+        }
+        None
+    }
+
+    // Color of the distance coloring at (x, y).
+    fn distance_color(&self, x: f64, y: f64, plan: TilePlan, stats: &mut TileStats) -> [u8; 4] {
+        match self.check_distance(x, y, plan.max_round, plan.periodicity) {
+            Some((round, distance)) => {
+                stats.add(Some(round));
+                let shade = (distance / (DISTANCE_WIDTH * self.scale * self.preview as f64))
+                    .clamp(0.0, 1.0)
+                    .sqrt();
+                self.darken(self.round_to_color(round), shade)
+            }
+            None => {
+                stats.add(None);
+                self.inside_color(x, y, plan.max_round)
+            }
+        }
+    }
+
+    // `color` with its light multiplied by `shade`, from 0 to 1.
+    fn darken(&self, [r, g, b, a]: [u8; 4], shade: f64) -> [u8; 4] {
+        let darken = |channel: u8| match self.options.blend {
+            Blend::Srgb => (channel as f64 * shade) as u8,
+            Blend::Linear => palette::to_srgb(palette::to_linear(channel) * shade as f32),
+        };
+        [darken(r), darken(g), darken(b), a]
+    }
+
+    // Degree of the Newton polynomial: the exponent, from 3 on, since the
+    // two basins of z^2 - 1 are split by a straight line.
+    fn newton_degree(&self) -> f64 {
+        self.options.exponent.round().max(3.0)
+    }
+
+    /// d of the formula iterated: the degree of the Newton polynomial, or
+    /// the exponent of z^d + c.
+    pub fn degree(&self) -> f64 {
+        if self.is_newton() {
+            self.newton_degree()
+        } else {
+            self.options.exponent
+        }
+    }
+
+    /// The point after z of Newton's method on z^d - 1 and whether the
+    /// step to it was small enough to have converged, or None where the
+    /// derivative vanishes.
+    pub fn newton_step(&self, (xn, yn): (f64, f64)) -> Option<((f64, f64), bool)> {
+        let degree = self.newton_degree();
+        // z^(d-1), then f(z) = z^d - 1 and f'(z) = d z^(d-1)
+        let (px, py) = power(Fractal::Newton, xn, yn, degree - 1.0);
+        let (fx, fy) = (xn * px - yn * py - 1.0, xn * py + yn * px);
+        let denominator = degree * (px * px + py * py);
+        if denominator == 0.0 {
+            return None;
+        }
+        // f(z) / f'(z)
+        let step_x = (fx * px + fy * py) / denominator;
+        let step_y = (fy * px - fx * py) / denominator;
+        let converged = step_x * step_x + step_y * step_y < NEWTON_TOLERANCE;
+        Some(((xn - step_x, yn - step_y), converged))
+    }
+
+    /// Index of the root of z^d - 1 nearest to z, counted counterclockwise
+    /// from 0 for the root 1.
+    pub fn newton_root(&self, (x, y): (f64, f64)) -> usize {
+        let degree = self.newton_degree();
+        let turn = y.atan2(x) / std::f64::consts::TAU;
+        (turn * degree).round().rem_euclid(degree) as usize
+    }
+
+    // Iteration at which Newton's method on z^d - 1, started at the point,
+    // converges and the index of the root it reaches; None when it has not
+    // converged by max_round.
+    fn check_newton(&self, pos_x: f64, pos_y: f64, max_round: usize) -> Option<(usize, usize)> {
+        let mut z = (pos_x, pos_y);
+        for round in 1..max_round {
+            let converged;
+            (z, converged) = self.newton_step(z)?;
+            if converged {
+                return Some((round, self.newton_root(z)));
+            }
+        }
+        None
+    }
+
+    // Color of the Newton fractal at (x, y): the roots are spread evenly
+    // over a cycle of the palette, and the slower a point converges, the
+    // darker it is.
+    fn newton_color(&self, x: f64, y: f64, plan: TilePlan, stats: &mut TileStats) -> [u8; 4] {
+        match self.check_newton(x, y, plan.max_round) {
+            Some((round, root)) => {
+                stats.add(Some(round));
+                let period = self.options.palette.period() as f64;
+                let position = root as f64 * period / self.newton_degree();
+                let color = self
+                    .options
+                    .palette
+                    .color(position as usize, self.options.blend);
+                let shade = NEWTON_FADE.powi(round as i32 - 1).max(NEWTON_MIN_SHADE);
+                self.darken(color, shade)
+            }
+            None => {
+                stats.add(None);
+                self.color_of(None)
+            }
+        }
+    }
+
+    // Four points at once, with the SIMD kernel when it supports the formula.
+    fn check_divergence_x4(
+        &self,
+        xs: [f64; LANES],
+        ys: [f64; LANES],
+        plan: TilePlan,
+    ) -> [Option<usize>; LANES] {
+        if !self.is_quadratic() || self.double_double() {
+            return [0, 1, 2, 3].map(|lane| {
+                self.check_divergence(xs[lane], ys[lane], plan.max_round, plan.periodicity)
+            });
+        }
+        simd::check_divergence_x4(
+            xs,
+            ys,
+            plan.max_round,
+            plan.periodicity,
+            self.options.fractal,
+        )
+    }
+
+    pub fn round_to_color(&self, round: usize) -> [u8; 4] {
+        let palette = &self.options.palette;
+        let period = palette.period();
+        let round = match (self.options.coloring, &self.histogram, self.exposure) {
+            (Coloring::Histogram, Some((_, histogram)), _) => histogram.spread(round, period),
+            (_, _, Some(exposure)) if self.exposes() => exposure.spread(round, period),
+            _ => round,
+        };
+        palette.color(
+            self.options.bands.quantize(round, period),
+            self.options.blend,
+        )
+    }
+
+    fn color_of(&self, round: Option<usize>) -> [u8; 4] {
+        if (self.gpu_coloring || self.color_cycling)
+            && self.options.antialias == Antialias::Off
+            && self.options.coloring == Coloring::Iterations
+            && !self.cost_map
+        {
+            let [r, g, b, _] = (round.unwrap_or(0).min(0xff_ffff) as u32).to_le_bytes();
+            return [r, g, b, 0x00];
+        }
+        match round {
+            Some(round) => self.round_to_color(round),
+            None => [0x00, 0x00, 0x00, 0xff],
+        }
+    }
+
+    /// Average color of `grid` x `grid` samples spread over the pixel whose
+    /// center is (x, y).
+    fn supersample(
+        &self,
+        x: f64,
+        y: f64,
+        grid: usize,
+        plan: TilePlan,
+        stats: &mut TileStats,
+    ) -> [u8; 4] {
+        let step = self.scale / grid as f64;
+        let offset = |i: usize| (i as f64 + 0.5) * step - self.scale / 2.0;
+
+        let count = grid * grid;
+        let sample = |i: usize| (x + offset(i % grid), y - offset(i / grid));
+        // sums of the channels, or of their light with the linear blend
+        let mut sum = [0_u32; 3];
+        let mut light = [0_f32; 3];
+        let linear = self.options.blend == Blend::Linear;
+        let mut add = |rgba: [u8; 4]| {
+            for channel in 0..3 {
+                if linear {
+                    light[channel] += palette::to_linear(rgba[channel]);
+                } else {
+                    sum[channel] += rgba[channel] as u32;
+                }
+            }
+        };
+        if self.colors_orbits() {
+            for (sample_x, sample_y) in (0..count).map(sample) {
+                add(self.point_color(sample_x, sample_y, plan, stats));
+            }
+        } else if !count.is_multiple_of(LANES) {
+            for (sample_x, sample_y) in (0..count).map(sample) {
+                let round =
+                    self.check_divergence(sample_x, sample_y, plan.max_round, plan.periodicity);
+                stats.add(round);
+                add(self.sample_color(round, sample_x, sample_y, plan.max_round));
+            }
+        } else {
+            for first in (0..count).step_by(LANES) {
+                let mut xs = [0.0; LANES];
+                let mut ys = [0.0; LANES];
+                for lane in 0..LANES {
+                    (xs[lane], ys[lane]) = sample(first + lane);
+                }
+                let rounds = self.check_divergence_x4(xs, ys, plan);
+                for (round, (sample_x, sample_y)) in rounds.into_iter().zip(xs.into_iter().zip(ys))
+                {
+                    stats.add(round);
+                    add(self.sample_color(round, sample_x, sample_y, plan.max_round));
+                }
+            }
+        }
+
+        let count = count as u32;
+        if linear {
+            let average = |channel: usize| palette::to_srgb(light[channel] / count as f32);
+            return [average(0), average(1), average(2), 0xff];
+        }
+        [
+            (sum[0] / count) as u8,
+            (sum[1] / count) as u8,
+            (sum[2] / count) as u8,
+            0xff,
+        ]
+    }
+
+    // Pixels whose color differs strongly from the right or lower neighbor.
+    fn high_contrast_pixels(&self, frame: &[u8]) -> Vec<usize> {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let difference = |a: usize, b: usize| -> u32 {
+            (0..3)
+                .map(|channel| {
+                    (frame[4 * a + channel] as i32 - frame[4 * b + channel] as i32).unsigned_abs()
+                })
+                .sum()
+        };
+        (0..width * height)
+            .into_par_iter()
+            .filter(|&i| {
+                let right = i % width + 1 < width && difference(i, i + 1) > ADAPTIVE_THRESHOLD;
+                let below = i / width + 1 < height && difference(i, i + width) > ADAPTIVE_THRESHOLD;
+                right || below
+            })
+            .collect()
+    }
+
+    fn adaptive_antialias(&self, frame: &mut [u8], min_x: f64, max_y: f64) {
+        let width = self.width as usize;
+        let mut targets = self.high_contrast_pixels(frame);
+        // the lower/right neighbor of an edge is on the edge too
+        let neighbors: Vec<usize> = targets
+            .iter()
+            .flat_map(|&i| [i + 1, i + width])
+            .filter(|&i| i < width * self.height as usize)
+            .collect();
+        targets.extend(neighbors);
+        targets.par_sort_unstable();
+        targets.dedup();
+
+        let plan = TilePlan {
+            max_round: self.max_round,
+            periodicity: true,
+        };
+        let colors: Vec<[u8; 4]> = targets
+            .par_iter()
+            .map(|&i| {
+                let x = min_x + ((i % width) as f64) * self.scale;
+                let y = max_y - ((self.first_row + i / width) as f64) * self.scale;
+                self.supersample(x, y, ADAPTIVE_GRID, plan, &mut TileStats::default())
+            })
+            .collect();
+        for (i, rgba) in targets.iter().zip(colors) {
+            frame[(4 * i)..(4 * i + 4)].copy_from_slice(&rgba);
+        }
+        info!("adaptive antialias: {} pixels", targets.len());
+    }
+
+    pub fn rendering_time_message(&self) -> String {
+        format!(
+            "rendering time: {}.{:04}[sec]",
+            self.rendering_time.as_secs(),
+            self.rendering_time.subsec_millis()
+        )
+    }
+
+    fn render_tile(&self, tile: &Tile, plan: TilePlan, min_x: f64, max_y: f64) -> TileResult {
+        let start_time = Instant::now();
+        let mut stats = TileStats::default();
+        let grid = self.options.antialias.grid();
+        let (colors, iterations) = if grid > 1 || self.colors_orbits() {
+            let mut colors = Vec::with_capacity(tile.pixels());
+            for tile_y in 0..tile.height {
+                let y = max_y - ((self.first_row + tile.y + tile_y) as f64) * self.scale;
+                for tile_x in 0..tile.width {
+                    let x = min_x + ((tile.x + tile_x) as f64) * self.scale;
+                    colors.push(if grid > 1 {
+                        self.supersample(x, y, grid, plan, &mut stats)
+                    } else {
+                        self.point_color(x, y, plan, &mut stats)
+                    });
+                }
+            }
+            (colors, Vec::new())
+        } else {
+            let rounds = if self.subdivides() {
+                self.subdivide(tile, plan, min_x, max_y, &mut stats)
+            } else {
+                self.escape_rounds(tile, plan, min_x, max_y, &mut stats)
+            };
+            // colored as a separate pass over the iterations, which the
+            // frame keeps to be colored again
+            let colors = rounds
+                .iter()
+                .enumerate()
+                .map(|(index, round)| {
+                    let x = min_x + ((tile.x + index % tile.width) as f64) * self.scale;
+                    let y = max_y
+                        - ((self.first_row + tile.y + index / tile.width) as f64) * self.scale;
+                    self.sample_color(*round, x, y, plan.max_round)
+                })
+                .collect();
+            let iterations = if self.keeps_iterations() {
+                rounds.into_iter().map(iteration_value).collect()
+            } else {
+                Vec::new()
+            };
+            (colors, iterations)
+        };
+        stats.time = start_time.elapsed();
+        (colors, iterations, stats)
+    }
+
+    // Escape iterations of the pixels of the tile, computed one by one.
+    fn escape_rounds(
+        &self,
+        tile: &Tile,
+        plan: TilePlan,
+        min_x: f64,
+        max_y: f64,
+        stats: &mut TileStats,
+    ) -> Vec<Option<usize>> {
+        let mut rounds = Vec::with_capacity(tile.pixels());
+        for tile_y in 0..tile.height {
+            let y = max_y - ((self.first_row + tile.y + tile_y) as f64) * self.scale;
+            let mut tile_x = 0;
+            while tile_x < tile.width {
+                if tile_x + LANES <= tile.width {
+                    let mut xs = [0.0; LANES];
+                    for (lane, x) in xs.iter_mut().enumerate() {
+                        *x = min_x + ((tile.x + tile_x + lane) as f64) * self.scale;
+                    }
+                    rounds.extend(self.check_divergence_x4(xs, [y; LANES], plan));
+                    tile_x += LANES;
+                } else {
+                    let x = min_x + ((tile.x + tile_x) as f64) * self.scale;
+                    rounds.push(self.check_divergence(x, y, plan.max_round, plan.periodicity));
+                    tile_x += 1;
+                }
+            }
+        }
+        for round in &rounds {
+            stats.add(*round);
+        }
+        rounds
+    }
+
+    // Whether full frames keep the escape iteration of every pixel, from
+    // which they are colored again when only the palette, the bands or the
+    // blend change: the colorings of the iterations alone, when there is
+    // memory for a copy of the frame.
+    fn keeps_iterations(&self) -> bool {
+        !self.low_memory && self.options.antialias == Antialias::Off && !self.colors_orbits()
+    }
+
+    /// Escape iterations of the pixels of the last full frame, row by row,
+    /// 0 for the points of the set, or None when the coloring of the frame
+    /// needs more than the iterations.
+    pub fn iterations(&self) -> Option<&[u32]> {
+        let rendered = self.rendered.as_ref()?;
+        let pixels = rendered.width as usize * rendered.height as usize;
+        (self.iterations.len() == pixels).then_some(&self.iterations[..])
+    }
+
+    /// `iterations` when the last full frame shows the view.
+    pub fn view_iterations(&self) -> Option<&[u32]> {
+        self.iterations()
+            .filter(|_| self.pan_shift() == Some((0, 0)))
+    }
+
+    /// Escape iterations of the pixels of the view, row by row, 0 for the
+    /// points of the set: those kept with the last full frame when it shows
+    /// the view, or else computed one by one.
+    pub fn escape_iterations(&self) -> Vec<u32> {
+        if let Some(iterations) = self.view_iterations() {
+            return iterations.to_vec();
+        }
+        let (min_x, max_y) = self.frame_origin();
+        self.sample_rounds(min_x, max_y, 1)
+            .into_iter()
+            .map(iteration_value)
+            .collect()
+    }
+
+    // Color the last full frame again from its escape iterations when the
+    // options it was rendered with differ from the current ones only in
+    // how the iterations are colored, so that it is kept instead of being
+    // rendered again.
+    fn recolor_rendered(&mut self) {
+        let Some(rendered) = &self.rendered else {
+            return;
+        };
+        let mut recolored = rendered.options.clone();
+        recolored.palette = self.options.palette.clone();
+        recolored.bands = self.options.bands;
+        recolored.blend = self.options.blend;
+        if rendered.options == self.options
+            || recolored != self.options
+            // the colors of the points of the set are not kept
+            || self.colors_interior()
+            || !self.keeps_iterations()
+            || self.iterations().is_none()
+        {
+            return;
+        }
+
+        let mut frame = Arc::unwrap_or_clone(std::mem::take(&mut self.rendered).unwrap());
+        frame
+            .frame
+            .par_chunks_exact_mut(4)
+            .zip(self.iterations.par_iter())
+            .for_each(|(pixel, &value)| {
+                pixel.copy_from_slice(&self.color_of((value != 0).then_some(value as usize)));
+            });
+        frame.options = recolored;
+        self.rendered = Some(Arc::new(frame));
+        info!("recolored from the iterations of the frame");
+    }
+
+    // Whether tiles are rendered by subdivide(). It relies on the bands of
+    // equal escape iterations of z^2 + c being annuli around the connected,
+    // full Mandelbrot set: a rectangle whose border is one band (or the set)
+    // cannot hold anything else, unless it surrounds the whole set.
+    fn subdivides(&self) -> bool {
+        self.subdivision
+            && self.options.fractal == Fractal::Mandelbrot
+            && self.is_quadratic()
+            && self.options.antialias.grid() == 1
+            && !self.colors_orbits()
+    }
+
+    // Escape iterations of the pixels of the tile by Mariani–Silver
+    // subdivision: compute the border of a rectangle, fill the inside with
+    // the border's iteration count when the whole border has the same one,
+    // and split the rectangle in four otherwise.
+    fn subdivide(
+        &self,
+        tile: &Tile,
+        plan: TilePlan,
+        min_x: f64,
+        max_y: f64,
+        stats: &mut TileStats,
+    ) -> Vec<Option<usize>> {
+        // None for the pixels not computed or filled yet
+        let mut rounds: Vec<Option<Option<usize>>> = vec![None; tile.pixels()];
+        let index = |x: usize, y: usize| y * tile.width + x;
+        // the pixels not computed yet of those given to compute(), kept
+        // from one call to the next
+        let mut pending: Vec<(usize, usize)> = Vec::new();
+        let mut compute = |pixels: &[(usize, usize)],
+                           rounds: &mut [Option<Option<usize>>],
+                           stats: &mut TileStats| {
+            pending.clear();
+            pending.extend(
+                pixels
+                    .iter()
+                    .copied()
+                    .filter(|(x, y)| rounds[index(*x, *y)].is_none()),
+            );
+            let point = |(x, y): (usize, usize)| {
+                (
+                    min_x + ((tile.x + x) as f64) * self.scale,
+                    max_y - ((self.first_row + tile.y + y) as f64) * self.scale,
+                )
+            };
+            for chunk in pending.chunks(LANES) {
+                let mut results = [None; LANES];
+                if chunk.len() == LANES {
+                    let mut xs = [0.0; LANES];
+                    let mut ys = [0.0; LANES];
+                    for (lane, pixel) in chunk.iter().enumerate() {
+                        (xs[lane], ys[lane]) = point(*pixel);
+                    }
+                    results = self.check_divergence_x4(xs, ys, plan);
+                } else {
+                    for (result, pixel) in results.iter_mut().zip(chunk) {
+                        let (x, y) = point(*pixel);
+                        *result = self.check_divergence(x, y, plan.max_round, plan.periodicity);
+                    }
+                }
+                for ((x, y), round) in chunk.iter().zip(results) {
+                    stats.add(round);
+                    rounds[index(*x, *y)] = Some(round);
+                }
+            }
+        };
+
+        // rectangles as inclusive pixel ranges (x0, y0, x1, y1)
+        let mut rectangles = vec![(0, 0, tile.width - 1, tile.height - 1)];
+        // the pixels of one rectangle at a time
+        let mut border: Vec<(usize, usize)> = Vec::new();
+        while let Some((x0, y0, x1, y1)) = rectangles.pop() {
+            border.clear();
+            border.extend((x0..=x1).map(|x| (x, y0)));
+            if y1 > y0 {
+                border.extend((x0..=x1).map(|x| (x, y1)));
+            }
+            for y in y0 + 1..y1 {
+                border.push((x0, y));
+                if x1 > x0 {
+                    border.push((x1, y));
+                }
+            }
+            compute(&border, &mut rounds, stats);
+            if x1 - x0 < 2 || y1 - y0 < 2 {
+                continue;
+            }
+
+            let first = rounds[index(x0, y0)];
+            let inside = || (y0 + 1..y1).flat_map(|y| (x0 + 1..x1).map(move |x| (x, y)));
+            if border.iter().all(|(x, y)| rounds[index(*x, *y)] == first) {
+                for (x, y) in inside() {
+                    if rounds[index(x, y)].is_none() {
+                        // computed by compute(), so never None here
+                        let round = first.unwrap();
+                        stats.add(round);
+                        stats.filled += 1;
+                        rounds[index(x, y)] = Some(round);
+                    }
+                }
+            } else if x1 - x0 <= MIN_SUBDIVISION || y1 - y0 <= MIN_SUBDIVISION {
+                border.clear();
+                border.extend(inside());
+                compute(&border, &mut rounds, stats);
+            } else {
+                let (mid_x, mid_y) = ((x0 + x1) / 2, (y0 + y1) / 2);
+                rectangles.extend([
+                    (x0, y0, mid_x, mid_y),
+                    (mid_x, y0, x1, mid_y),
+                    (x0, mid_y, mid_x, y1),
+                    (mid_x, mid_y, x1, y1),
+                ]);
+            }
+        }
+        // every pixel is on the border of a rectangle or inside one
+        rounds.into_iter().flatten().collect()
+    }
+
+    // Whether the iteration limit of each tile is planned from samples of
+    // it. Newton's method has no escape to sample.
+    fn adapts_iterations(&self) -> bool {
+        self.adaptive_iterations && !self.is_newton()
+    }
+
+    // Raise the iteration limit of the auto mode when many of the pixels
+    // next to the set on the frame just drawn escaped late, a sign that much
+    // of its black would escape at a higher limit, and tell whether to draw
+    // the frame again. A frame that would not have been raised at half the
+    // limit lowers the raise for the next views.
+    fn tune_iterations(&mut self, min_x: f64, max_y: f64) -> bool {
+        if !self.iteration_tuning
+            || !self.auto_iterations
+            || self.is_newton()
+            || self.cancel.is_cancelled()
+        {
+            return false;
+        }
+        let width = self.width as usize;
+        // the iterations of the frame, or a sparser grid of them
+        let sampled;
+        let (values, columns) = if self.iterations.len() == width * self.height as usize {
+            (&self.iterations[..], width)
+        } else {
+            sampled = self
+                .sample_rounds(min_x, max_y, TUNING_STEP)
+                .into_iter()
+                .map(iteration_value)
+                .collect::<Vec<_>>();
+            (&sampled[..], width.div_ceil(TUNING_STEP))
+        };
+        let limit = self.max_round.min(u32::MAX as usize) as u32;
+        let raise = late_share(values, columns, limit).is_some_and(|share| share > LATE_SHARE);
+        let lower = self.iteration_boost > 1
+            && late_share(values, columns, limit / 2).is_none_or(|share| share < LATE_SHARE / 2.0);
+        if raise && self.iteration_boost < MAX_ITERATION_BOOST && self.max_round < MAX_ITERATIONS {
+            self.iteration_boost *= 2;
+            self.max_round = self.max_round_for(self.scale);
+            info!(
+                "pixels around the set escape late: max_round {}",
+                self.max_round
+            );
+            return true;
+        }
+        if lower {
+            self.iteration_boost /= 2;
+        }
+        false
+    }
+
+    // Escape statistics of a sparse grid of the pixels of the tile at the
+    // full iteration limit, up to the first point of the set, after which
+    // the tile gets the full limit anyway.
+    fn sample_tile(&self, tile: &Tile, min_x: f64, max_y: f64) -> TileStats {
+        let along = |length: usize| {
+            let mut pixels: Vec<usize> = (0..length).step_by(LIMIT_SAMPLE_STEP).collect();
+            if pixels.last() != Some(&(length - 1)) {
+                pixels.push(length - 1);
+            }
+            pixels
+        };
+        let mut stats = TileStats::default();
+        if self.cancel.is_cancelled() {
+            return stats;
+        }
+        let columns = along(tile.width);
+        for tile_y in along(tile.height) {
+            let y = max_y - ((self.first_row + tile.y + tile_y) as f64) * self.scale;
+            for tile_x in &columns {
+                let x = min_x + ((tile.x + tile_x) as f64) * self.scale;
+                stats.add(self.check_divergence(x, y, self.max_round, true));
+                if stats.interior > 0 {
+                    return stats;
+                }
+            }
+        }
+        stats
+    }
+
+    // Sample the escape iterations over a grid of `step` pixels for the
+    // colorings that follow their distribution: the histogram, and the auto
+    // exposure. After a raise of the iteration limit, by hand or by the
+    // auto mode, of a frame whose samples are otherwise the same, the
+    // pixels escaped before keep their histogram colors. A frame of the
+    // same samples, such as one only colored differently, reuses them.
+    fn sample_distribution(&mut self, min_x: f64, max_y: f64, step: usize) {
+        let histogram = self.options.coloring == Coloring::Histogram;
+        let exposes = self.exposes();
+        if !histogram && !exposes && !self.iteration_histogram {
+            return;
+        }
+        let key = self.sample_key();
+        let sampled = Some((key.clone(), self.max_round, step));
+        if self.sampled == sampled
+            && (!histogram
+                || matches!(&self.histogram, Some((histogram_key, histogram))
+                    if *histogram_key == key && histogram.max_round == self.max_round))
+            && (!exposes || self.exposure.is_some())
+            && (!self.iteration_histogram || self.iteration_counts.is_some())
+        {
+            return;
+        }
+
+        let samples = self.sample_rounds(min_x, max_y, step);
+        self.iteration_counts = self
+            .iteration_histogram
+            .then(|| IterationCounts::new(&samples, self.max_round));
+        let mut rounds: Vec<usize> = samples.into_iter().flatten().collect();
+        self.exposure = if exposes {
+            Exposure::new(&mut rounds)
+        } else {
+            None
+        };
+        if histogram {
+            let mut histogram = Histogram::new(rounds, self.max_round);
+            if let Some((histogram_key, previous)) = &self.histogram {
+                if *histogram_key == key {
+                    histogram.keep_colors(previous);
+                }
+            }
+            self.histogram = Some((key, histogram));
+        }
+        self.sampled = sampled;
+    }
+
+    fn sample_key(&self) -> SampleKey {
+        let options = RenderOptions {
+            antialias: Antialias::default(),
+            coloring: Coloring::default(),
+            interior: Interior::default(),
+            blend: Blend::default(),
+            bands: Bands::default(),
+            palette: Palette::default(),
+            ..self.options.clone()
+        };
+        (
+            self.view(),
+            self.center(),
+            (self.width, self.height),
+            options,
+        )
+    }
+
+    // Escape iterations over a grid of `step` pixels, None inside the set.
+    fn sample_rounds(&self, min_x: f64, max_y: f64, step: usize) -> Vec<Option<usize>> {
+        let plan = TilePlan {
+            max_round: self.max_round,
+            periodicity: true,
+        };
+        let xs: Vec<f64> = (0..self.width as usize)
+            .step_by(step)
+            .map(|pixel_x| min_x + pixel_x as f64 * self.scale)
+            .collect();
+        (0..self.height as usize)
+            .into_par_iter()
+            .step_by(step)
+            .flat_map_iter(|pixel_y| {
+                let y = max_y - pixel_y as f64 * self.scale;
+                let mut rounds = Vec::with_capacity(xs.len());
+                for chunk in xs.chunks(LANES) {
+                    if let Ok(lanes) = <[f64; LANES]>::try_from(chunk) {
+                        rounds.extend(self.check_divergence_x4(lanes, [y; LANES], plan));
+                    } else {
+                        rounds.extend(chunk.iter().map(|x| {
+                            self.check_divergence(*x, y, plan.max_round, plan.periodicity)
+                        }));
+                    }
+                }
+                rounds
+            })
+            .collect()
+    }
+
+    // Sample the center of every block of `preview` x `preview` pixels and
+    // fill the block with its color. Antialiasing is skipped, and the
+    // histogram of the last full frame is reused.
+    fn draw_preview(&self, frame: &mut [u8], min_x: f64, max_y: f64) {
+        let step = self.preview;
+        let width = self.width as usize;
+        let plan = TilePlan {
+            max_round: self.max_round,
+            periodicity: true,
+        };
+        let xs: Vec<f64> = (0..width)
+            .step_by(step)
+            .map(|pixel_x| min_x + (pixel_x + step / 2) as f64 * self.scale)
+            .collect();
+        frame
+            .par_chunks_mut(4 * width * step)
+            .enumerate()
+            .for_each(|(block_row, rows)| {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                let y = max_y - (block_row * step + step / 2) as f64 * self.scale;
+                let mut stats = TileStats::default();
+                let colors: Vec<[u8; 4]> = if self.colors_orbits() {
+                    xs.iter()
+                        .map(|x| self.point_color(*x, y, plan, &mut stats))
+                        .collect()
+                } else {
+                    let mut rounds = Vec::with_capacity(xs.len());
+                    for chunk in xs.chunks(LANES) {
+                        if let Ok(lanes) = <[f64; LANES]>::try_from(chunk) {
+                            rounds.extend(self.check_divergence_x4(lanes, [y; LANES], plan));
+                        } else {
+                            rounds.extend(chunk.iter().map(|x| {
+                                self.check_divergence(*x, y, plan.max_round, plan.periodicity)
+                            }));
+                        }
+                    }
+                    rounds
+                        .into_iter()
+                        .zip(&xs)
+                        .map(|(round, x)| self.sample_color(round, *x, y, plan.max_round))
+                        .collect()
+                };
+                for row in rows.chunks_exact_mut(4 * width) {
+                    for (pixel_x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                        pixel.copy_from_slice(&colors[pixel_x / step]);
+                    }
+                }
+            });
+    }
+
+    fn blit(&self, frame: &mut [u8], tile: &Tile, colors: &[[u8; 4]]) {
+        tile::blit(frame, self.width as usize, tile, colors);
+    }
+
+    fn draw_tiles(&mut self, frame: &mut [u8], min_x: f64, max_y: f64) {
+        let tiles = tile::tiles(self.width as usize, self.height as usize);
+        let schedule = self
+            .tile_order
+            .schedule(&tiles, self.width as usize, self.height as usize);
+        let prev_stats = std::mem::take(&mut self.tile_stats);
+        let prev_stats = (prev_stats.len() == tiles.len()).then_some(prev_stats);
+        // the adaptive iteration limits go by samples of this frame, the
+        // others by the tiles of the previous one
+        let samples = self
+            .adapts_iterations()
+            .then(|| map_in_order(&tiles, |tile| self.sample_tile(tile, min_x, max_y)));
+        let plans: Vec<TilePlan> = (0..tiles.len())
+            .map(|index| {
+                let prev = match &samples {
+                    Some(samples) => Some(&samples[index]),
+                    None => prev_stats.as_ref().map(|stats| &stats[index]),
+                };
+                TilePlan::new(prev, self.max_round)
+            })
+            .collect();
+        let tasks = tile::tasks(&tiles, &schedule, prev_stats.as_deref(), threads());
+        // the low-memory mode stages a row of tiles' worth of pixels at a
+        // time instead of the whole frame
+        let strip = if self.low_memory {
+            self.width as usize * TILE_SIZE
+        } else {
+            usize::MAX
+        };
+
+        let width = self.width as usize;
+        let pixels = if self.keeps_iterations() {
+            width * self.height as usize
+        } else {
+            0
+        };
+        self.iterations = Arc::new(vec![0; pixels]);
+
+        // in the order of `tiles`, which the cost map and the next frame
+        // look them up by
+        let mut tile_stats = vec![TileStats::default(); tiles.len()];
+        let mut start = 0;
+        while start < tasks.len() {
+            let mut end = start;
+            let mut pixels = 0;
+            while end < tasks.len() && pixels < strip {
+                pixels += tasks[end]
+                    .iter()
+                    .map(|(_, tile)| tile.pixels())
+                    .sum::<usize>();
+                end += 1;
+            }
+            let strip_tasks = &tasks[start..end];
+            start = end;
+            let results: Vec<Option<TaskResult>> = map_in_order(strip_tasks, |task| {
+                let mut results = Vec::with_capacity(task.len());
+                for (index, tile) in task {
+                    if self.cancel.is_cancelled() {
+                        return None;
+                    }
+                    let (colors, iterations, stats) =
+                        self.render_tile(tile, plans[*index], min_x, max_y);
+                    if let Some(sender) = &self.tile_sender {
+                        // the receiver goes away with a newer frame
+                        let _ = sender.send((*tile, colors.clone()));
+                    }
+                    results.push((colors, iterations, stats));
+                }
+                Some(results)
+            });
+            if self.cancel.is_cancelled() {
+                return;
+            }
+
+            for (task, results) in strip_tasks.iter().zip(results.into_iter().flatten()) {
+                for ((index, tile), (colors, iterations, stats)) in task.iter().zip(results) {
+                    self.blit(frame, tile, &colors);
+                    if !iterations.is_empty() {
+                        let values = Arc::make_mut(&mut self.iterations);
+                        tile::blit_values(values, width, tile, &iterations);
+                    }
+                    self.session_cost.add_tile(&stats);
+                    tile_stats[*index].merge(&stats);
+                }
+            }
+        }
+        let specialized = plans
+            .iter()
+            .filter(|plan| !plan.periodicity || plan.max_round < self.max_round)
+            .count();
+        info!(
+            "specialized tiles: {}/{}, tasks: {}",
+            specialized,
+            tiles.len(),
+            tasks.len()
+        );
+        info!(
+            "pixels filled by subdivision: {}/{}",
+            tile_stats.iter().map(|stats| stats.filled).sum::<usize>(),
+            self.width * self.height
+        );
+        self.tile_stats = tile_stats;
+    }
+
+    // Whole-pixel offset (columns, rows) of the current view from the last
+    // rendered one, when the frame only has to be shifted.
+    fn pan_shift(&self) -> Option<(isize, isize)> {
+        let rendered = self.rendered.as_ref()?;
+        if rendered.width != self.width
+            || rendered.height != self.height
+            || rendered.scale != self.scale
+            || rendered.max_round != self.max_round
+            || rendered.options != self.options
+            // the adaptive pass depends on the neighbors across the seams
+            || self.options.antialias == Antialias::Adaptive
+            // the cost map needs the statistics of every tile
+            || self.cost_map
+        {
+            return None;
+        }
+
+        let (center_x, center_y) = self.center();
+        let shift_x = (center_x - rendered.center.0).hi / self.scale;
+        let shift_y = (rendered.center.1 - center_y).hi / self.scale;
+        let whole = |shift: f64, size: u32| {
+            ((shift - shift.round()).abs() < PAN_TOLERANCE && shift.abs() < size as f64)
+                .then_some(shift.round() as isize)
+        };
+        Some((whole(shift_x, self.width)?, whole(shift_y, self.height)?))
+    }
+
+    // Move the last rendered frame by (shift_x, shift_y) pixels and render
+    // only the strips it no longer covers.
+    fn draw_panned(
+        &mut self,
+        frame: &mut [u8],
+        shift_x: isize,
+        shift_y: isize,
+        min_x: f64,
+        max_y: f64,
+    ) {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let rendered = match &self.rendered {
+            Some(rendered) => &rendered.frame,
+            None => return,
+        };
+
+        // the iterations move with the pixels when the frame keeps them
+        let keeps_iterations = self.iterations().is_some();
+        let mut iterations = vec![0_u32; if keeps_iterations { width * height } else { 0 }];
+
+        // columns of the new frame that are still in the old one
+        let kept_start = (-shift_x).max(0) as usize;
+        let kept_end = (width as isize - shift_x).min(width as isize) as usize;
+        for row in 0..height {
+            let old_row = row as isize + shift_y;
+            if old_row < 0 || old_row >= height as isize {
+                continue;
+            }
+            let new_start = row * width + kept_start;
+            let old_start = old_row as usize * width + (kept_start as isize + shift_x) as usize;
+            let length = kept_end - kept_start;
+            frame[4 * new_start..4 * (new_start + length)]
+                .copy_from_slice(&rendered[4 * old_start..4 * (old_start + length)]);
+            if keeps_iterations {
+                iterations[new_start..new_start + length]
+                    .copy_from_slice(&self.iterations[old_start..old_start + length]);
+            }
+        }
+
+        let mut exposed = Vec::new();
+        if shift_x != 0 {
+            exposed.push(Tile {
+                x: if shift_x > 0 { kept_end } else { 0 },
+                y: 0,
+                width: shift_x.unsigned_abs(),
+                height,
+            });
+        }
+        if shift_y != 0 {
+            exposed.push(Tile {
+                x: kept_start,
+                y: if shift_y > 0 {
+                    height - shift_y as usize
+                } else {
+                    0
+                },
+                width: kept_end - kept_start,
+                height: shift_y.unsigned_abs(),
+            });
+        }
+        let tiles: Vec<Tile> = exposed.into_iter().flat_map(tile::tiles_in).collect();
+
+        // the tile statistics describe the old positions, so the new strips
+        // get the full budget
+        let plan = TilePlan {
+            max_round: self.max_round,
+            periodicity: true,
+        };
+        let results: Vec<Option<TileResult>> = tiles
+            .par_iter()
+            .map(|tile| {
+                (!self.cancel.is_cancelled()).then(|| self.render_tile(tile, plan, min_x, max_y))
+            })
+            .collect();
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        for (tile, (colors, tile_iterations, stats)) in tiles.iter().zip(results.iter().flatten()) {
+            self.blit(frame, tile, colors);
+            if keeps_iterations {
+                tile::blit_values(&mut iterations, width, tile, tile_iterations);
+            }
+            self.session_cost.add_tile(stats);
+        }
+        self.iterations = Arc::new(iterations);
+        if shift_x != 0 || shift_y != 0 {
+            self.tile_stats.clear();
+        }
+        info!(
+            "panned by ({}, {}): {} pixels rendered",
+            shift_x,
+            shift_y,
+            tiles.iter().map(Tile::pixels).sum::<usize>()
+        );
+    }
+
+    /// Sample the escape iterations of the whole view for the histogram
+    /// coloring and the auto exposure of the strips of `draw_strip`, more
+    /// sparsely than for a frame rendered at once. The samples are the same
+    /// wherever the strips are rendered.
+    pub fn sample_strips(&mut self) {
+        let (min_x, max_y) = self.frame_origin();
+        let pixels = self.width as usize * self.height as usize;
+        let step =
+            HISTOGRAM_STEP.max((pixels as f64 / STRIP_HISTOGRAM_SAMPLES).sqrt().ceil() as usize);
+        install(|| self.sample_distribution(min_x, max_y, step));
+    }
+
+    /// Render the rows `top..top + rows` of the view into `strip`, for
+    /// images too large to render at once. The strips put together make
+    /// the image `draw` would without overlays, but for the histogram
+    /// coloring and the auto exposure, which go by the samples of
+    /// `sample_strips`.
+    pub fn draw_strip(&mut self, strip: &mut [u8], top: usize, rows: usize) {
+        install(|| self.draw_strip_rows(strip, top, rows));
+    }
+
+    fn draw_strip_rows(&mut self, strip: &mut [u8], top: usize, rows: usize) {
+        let width = self.width as usize;
+        let (min_x, max_y) = self.frame_origin();
+
+        // the adaptive antialiasing compares every pixel with the ones below
+        // and to its right, so the strip is rendered with a row of tiles
+        // around it
+        let margin = if self.options.antialias == Antialias::Adaptive {
+            TILE_SIZE
+        } else {
+            0
+        };
+        let first = top.saturating_sub(margin);
+        let last = (top + rows + margin).min(self.height as usize);
+        let mut job = self.clone();
+        job.height = (last - first) as u32;
+        job.first_row = first;
+        job.tile_stats.clear();
+        job.rendered = None;
+        let mut rendered = vec![0_u8; 4 * width * (last - first)];
+        job.draw_tiles(&mut rendered, min_x, max_y);
+        if self.options.antialias == Antialias::Adaptive {
+            job.adaptive_antialias(&mut rendered, min_x, max_y);
+        }
+        let start = 4 * width * (top - first);
+        strip.copy_from_slice(&rendered[start..start + 4 * width * rows]);
+        self.session_cost = job.session_cost;
+    }
+
+    pub fn draw(&mut self, frame: &mut [u8]) {
+        install(|| self.draw_frame(frame));
+    }
+
+    fn draw_frame(&mut self, frame: &mut [u8]) {
+        if self.drawn {
+            return;
+        }
+
+        let start_time = Instant::now();
+        let width = self.width as usize;
+        let (min_x, max_y) = self.frame_origin();
+
+        if self.preview > 1 {
+            // the last full frame stays for the pan that ends the movement
+            self.draw_preview(frame, min_x, max_y);
+        } else {
+            self.recolor_rendered();
+            match self.pan_shift() {
+                // a pan keeps the histogram, so that the kept pixels match
+                // the new ones
+                Some((shift_x, shift_y)) => {
+                    self.draw_panned(frame, shift_x, shift_y, min_x, max_y);
+                }
+                None => {
+                    let step = if self.low_memory {
+                        2 * HISTOGRAM_STEP
+                    } else {
+                        HISTOGRAM_STEP
+                    };
+                    self.sample_distribution(min_x, max_y, step);
+                    self.draw_tiles(frame, min_x, max_y);
+                    while self.tune_iterations(min_x, max_y) {
+                        self.sample_distribution(min_x, max_y, step);
+                        self.draw_tiles(frame, min_x, max_y);
+                    }
+                    if self.options.antialias == Antialias::Adaptive && !self.cancel.is_cancelled()
+                    {
+                        self.adaptive_antialias(frame, min_x, max_y);
+                    }
+                }
+            }
+            // the frame is left half drawn for a newer one
+            if self.cancel.is_cancelled() {
+                return;
+            }
+            self.rendered = (!self.low_memory).then(|| {
+                Arc::new(Rendered {
+                    width: self.width,
+                    height: self.height,
+                    center: self.center(),
+                    scale: self.scale,
+                    max_round: self.max_round,
+                    options: self.options.clone(),
+                    frame: frame.to_vec(),
+                })
+            });
+        }
+        if self.cancel.is_cancelled() {
+            return;
+        }
+
+        self.rendering_time = start_time.elapsed();
+        self.session_cost.add_frame();
+        let mut rendering_time_msg = self.rendering_time_message();
+        if self.preview > 1 {
+            rendering_time_msg += &format!(" (preview 1/{})", self.preview);
+        }
+        info!("{}", rendering_time_msg);
+        // the canvas clips the overlays to windows of any size
+        let mut canvas = Canvas::new(frame, width, self.height as usize);
+        canvas.theme = self.hud;
+        if self.gpu_coloring || self.color_cycling {
+            let options = &self.options;
+            canvas.iteration_colors = Some((
+                IterationColors::new(&options.palette, options.bands, options.blend),
+                0,
+            ));
+        }
+        // the tile statistics belong to the last full frame
+        if self.cost_map && self.preview == 1 {
+            info!("{}", self.session_cost.message());
+            let tiles = tile::tiles(width, self.height as usize);
+            cost::draw_map(&mut canvas, &tiles, &self.tile_stats);
+        }
+        if self.info {
+            let mut lines = vec![
+                format!("x: {}", self.center_x),
+                format!("y: {}", self.center_y),
+                format!("scale: {}", self.scale),
+                format!(
+                    "iterations: {} ({})",
+                    self.max_round,
+                    if self.auto_iterations {
+                        "auto"
+                    } else {
+                        "manual"
+                    }
+                ),
+                rendering_time_msg,
+                format!(
+                    "kernel: {}, {} threads",
+                    if self.double_double() {
+                        String::from("double-double (scalar)")
+                    } else {
+                        simd::kernel_name()
+                    },
+                    threads()
+                ),
+            ];
+            if let Some(status) = &self.gpu_status {
+                lines.push(format!("gpu: {}", status));
+            }
+            match &self.options.formula {
+                Some(formula) => lines.push(format!("formula: {}", formula.source())),
+                None => lines.push(format!(
+                    "fractal: {} (d = {})",
+                    self.options.fractal.name(),
+                    self.degree()
+                )),
+            }
+            if self.options.norm != Norm::Euclidean {
+                lines.push(format!("norm: {}", self.options.norm.name()));
+            }
+            if !self.options.variation.is_default() {
+                lines.push(format!("variation: {}", self.options.variation.message()));
+            }
+            if self.options.antialias != Antialias::Off {
+                lines.push(format!("antialias: {}", self.options.antialias.name()));
+            }
+            lines.push(format!("palette: {}", self.options.palette.name));
+            if self.options.coloring != Coloring::Iterations || self.gpu_coloring {
+                lines.push(format!(
+                    "coloring: {}{}",
+                    self.options.coloring.name(),
+                    if self.gpu_coloring { " (gpu)" } else { "" }
+                ));
+            }
+            if self.period_analysis {
+                lines.push(String::from("interior: period analysis"));
+            } else if self.options.interior != Interior::Black {
+                lines.push(format!("interior: {}", self.options.interior.name()));
+            }
+            if let Some(((x, y), probe)) = self.probe {
+                lines.push(format!("probe ({:.6}, {:.6}): {}", x, y, probe.message()));
+            }
+            if self.external_rays {
+                let angles: Vec<_> = self.rays.angles().iter().map(Angle::to_string).collect();
+                lines.push(if self.shows_external_rays() {
+                    format!("external rays: {}", angles.join(" "))
+                } else {
+                    String::from("external rays: z^2 + c only")
+                });
+            }
+            if let (true, Some(exposure)) = (self.exposes(), self.exposure) {
+                lines.push(format!(
+                    "exposure: {:.1}-{:.1}",
+                    exposure.low, exposure.high
+                ));
+            }
+            let bands = self.options.bands;
+            if bands.count != 0 {
+                lines.push(format!("bands: {} (offset {})", bands.count, bands.offset));
+            }
+            lines.push(self.remaining_zoom_message());
+            canvas.draw_text_block(&lines, HUD_MARGIN);
+        }
+        if self.legend {
+            annotate::legend(&mut canvas, self, 1);
+        }
+        if self.iteration_histogram {
+            // turned on over a frame that was not sampled
+            if self.iteration_counts.is_none() {
+                let samples = self.sample_rounds(min_x, max_y, HISTOGRAM_STEP);
+                self.iteration_counts = Some(IterationCounts::new(&samples, self.max_round));
+            }
+            if let Some(counts) = &self.iteration_counts {
+                annotate::iteration_histogram(&mut canvas, self, counts, 1);
+            }
+        }
+        if self.minimap {
+            if !matches!(&self.overview, Some(overview) if overview.shows(&self.options)) {
+                self.overview = Some(Minimap::new(&self.options));
+            }
+            if let Some(overview) = &self.overview {
+                overview.draw(&mut canvas, self.view(), (self.width, self.height));
+            }
+        }
+        if self.external_rays && self.shows_external_rays() {
+            // too slow to find the equipotentials again for every preview
+            // and lost in the rounding of double-double views
+            let rays_only = self.preview > 1 || self.double_double();
+            let mut rays = std::mem::take(&mut self.rays);
+            rays.draw(&mut canvas, self, rays_only);
+            self.rays = rays;
+        }
+        if self.period_analysis {
+            annotate::period_key(&mut canvas, self, 1);
+        }
+        if let Some(((x, y), _)) = self.probe {
+            let (pixel_x, pixel_y) = self.pixel_of(x, y);
+            annotate::probe_marker(&mut canvas, pixel_x, pixel_y - self.first_row as f64);
+        }
+
+        self.drawn = true;
+    }
+}
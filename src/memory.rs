@@ -0,0 +1,19 @@
+use std::fs;
+
+// Available memory below which the low-memory mode is selected on its own.
+pub const LOW_MEMORY_THRESHOLD: u64 = 512 << 20;
+
+/// Bytes of memory available for new allocations according to
+/// /proc/meminfo, or None where it cannot be read.
+pub fn available() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+pub fn is_low() -> bool {
+    available().is_some_and(|bytes| bytes < LOW_MEMORY_THRESHOLD)
+}
@@ -0,0 +1,102 @@
+use crate::canvas::{Canvas, Corner, SHADOW};
+use crate::mandelbrot::{Antialias, Mandelbrot, RenderOptions, View};
+
+// size of the overview in frame pixels
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 96;
+const MARGIN: usize = 5;
+// area of the plane the overview shows, wide enough for the whole set of
+// each fractal
+const OVERVIEW: View = View {
+    center_x: -0.5,
+    center_y: 0.0,
+    scale: 4.5 / WIDTH as f64,
+};
+// views narrower than this many pixels of the overview are marked with a
+// crosshair instead of their outline
+const MIN_OUTLINE: f64 = 4.0;
+const CROSSHAIR: usize = 6;
+
+/// Small image of the whole set drawn in a corner of the window with the
+/// area of the view marked on it, to keep track of where a deep zoom is.
+/// It is rendered again whenever the options of the view change.
+#[derive(Clone)]
+pub struct Minimap {
+    options: RenderOptions,
+    image: Vec<u8>,
+}
+
+impl Minimap {
+    pub fn new(options: &RenderOptions) -> Self {
+        let mut overview = Mandelbrot::new(WIDTH, HEIGHT);
+        let max_round = Mandelbrot::auto_max_round(OVERVIEW.scale);
+        overview.set_view(
+            OVERVIEW.center_x,
+            OVERVIEW.center_y,
+            OVERVIEW.scale,
+            max_round,
+        );
+        overview.options = options.clone();
+        // too small to tell the difference
+        overview.options.antialias = Antialias::Off;
+        overview.info = false;
+        let mut image = vec![0_u8; 4 * WIDTH as usize * HEIGHT as usize];
+        overview.draw(&mut image);
+        Self {
+            options: options.clone(),
+            image,
+        }
+    }
+
+    /// Whether the overview was rendered with `options`.
+    pub fn shows(&self, options: &RenderOptions) -> bool {
+        self.options == *options
+    }
+
+    /// Draw the overview in the top corner the information display of the
+    /// theme leaves free, with the outline of `view` of a frame of `size`
+    /// pixels, or a crosshair on it when it is too small to outline. A view
+    /// off the overview is marked at its edge.
+    pub fn draw(&self, canvas: &mut Canvas, view: View, size: (u32, u32)) {
+        let (width, height) = (WIDTH as usize, HEIGHT as usize);
+        let x = match canvas.theme.corner {
+            Corner::TopRight => MARGIN,
+            _ => canvas.width.saturating_sub(MARGIN + width),
+        };
+        let y = MARGIN;
+        let [r, g, b] = canvas.theme.foreground;
+        let foreground = [r, g, b, 0xff];
+        canvas.fill_rect(x.saturating_sub(1), y - 1, width + 2, height + 2, SHADOW);
+        canvas.draw_image(x, y, width, height, &self.image);
+
+        // the view in pixels of the overview, kept on it
+        let ratio = view.scale / OVERVIEW.scale;
+        let center_x = (view.center_x - OVERVIEW.center_x) / OVERVIEW.scale + WIDTH as f64 / 2.0;
+        let center_y = (OVERVIEW.center_y - view.center_y) / OVERVIEW.scale + HEIGHT as f64 / 2.0;
+        let half_width = size.0 as f64 * ratio / 2.0;
+        let half_height = size.1 as f64 * ratio / 2.0;
+        let clamp_x = |value: f64| value.clamp(0.0, (width - 1) as f64) as usize;
+        let clamp_y = |value: f64| value.clamp(0.0, (height - 1) as f64) as usize;
+        if 2.0 * half_width < MIN_OUTLINE {
+            let (cx, cy) = (x + clamp_x(center_x), y + clamp_y(center_y));
+            let left = cx.saturating_sub(CROSSHAIR).max(x);
+            let top = cy.saturating_sub(CROSSHAIR).max(y);
+            let right = (cx + CROSSHAIR).min(x + width - 1);
+            let bottom = (cy + CROSSHAIR).min(y + height - 1);
+            canvas.fill_rect(left, cy, right - left + 1, 1, foreground);
+            canvas.fill_rect(cx, top, 1, bottom - top + 1, foreground);
+        } else {
+            let left = clamp_x(center_x - half_width);
+            let top = clamp_y(center_y - half_height);
+            let right = clamp_x(center_x + half_width);
+            let bottom = clamp_y(center_y + half_height);
+            canvas.stroke_rect(
+                x + left,
+                y + top,
+                right - left + 1,
+                bottom - top + 1,
+                foreground,
+            );
+        }
+    }
+}
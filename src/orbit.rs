@@ -0,0 +1,230 @@
+use crate::canvas::{Canvas, LINE_HEIGHT, SHADOW};
+use crate::double_double::DoubleDouble;
+use crate::mandelbrot::{self, Cycle, Fractal, Mandelbrot};
+
+const MARGIN: usize = 5;
+pub const PANEL_COLUMNS: usize = 28;
+const PLOT_SIZE: usize = 120;
+// the plot shows this square around the origin, where every orbit stays
+// until it escapes
+const PLOT_RANGE: f64 = 2.0;
+const PLOT_POINTS: usize = 2000;
+const ORBIT_COLOR: [u8; 4] = [0x00, 0xff, 0x00, 0xff];
+const POINT_COLOR: [u8; 4] = [0xff, 0x40, 0x40, 0xff];
+const AXIS_COLOR: [u8; 4] = [0x40, 0x40, 0x40, 0xff];
+
+/// Statistics of the orbit of a single point, for the inspector panel.
+#[derive(Clone, Debug)]
+pub struct OrbitStats {
+    pub x: f64,
+    pub y: f64,
+    pub max_round: usize,
+    /// iteration at which z passed the bailout, or converged for Newton's
+    /// method, None for interior points
+    pub escape: Option<usize>,
+    /// root of z^d - 1 that Newton's method converged to
+    pub root: Option<usize>,
+    /// continuous escape time
+    pub smooth: Option<f64>,
+    pub last_abs: f64,
+    pub min_abs: f64,
+    pub max_abs: f64,
+    /// length of the cycle the orbit fell into
+    pub period: Option<usize>,
+    /// estimated distance to the set, for the Mandelbrot formula only
+    pub distance: Option<f64>,
+    /// the first points of the orbit
+    pub orbit: Vec<(f64, f64)>,
+}
+
+impl OrbitStats {
+    /// Iterate the point (x, y) of the view of `mandelbrot` like its
+    /// renderer does, with the variation, keeping track of the orbit and of
+    /// its derivative. z^2 is iterated in double-double, like the deep
+    /// views are drawn. The Newton fractal follows Newton's method instead.
+    pub fn compute(mandelbrot: &Mandelbrot, x: DoubleDouble, y: DoubleDouble) -> Self {
+        if mandelbrot.is_newton() {
+            return Self::newton(mandelbrot, x.hi, y.hi);
+        }
+        let options = &mandelbrot.options;
+        let max_round = mandelbrot.max_round;
+        let exponent = options.exponent;
+        let variation = options.variation;
+        let escaped = mandelbrot.escape_test();
+        // dz/dc of z^d + c from z = 0
+        let derivative = options.fractal == Fractal::Mandelbrot
+            && options.formula.is_none()
+            && variation.is_default();
+        let mut stats = Self {
+            x: x.hi,
+            y: y.hi,
+            max_round,
+            escape: None,
+            root: None,
+            smooth: None,
+            last_abs: 0.0,
+            min_abs: f64::INFINITY,
+            max_abs: 0.0,
+            period: None,
+            distance: None,
+            orbit: Vec::new(),
+        };
+
+        let mut precise = mandelbrot
+            .is_quadratic()
+            .then_some((DoubleDouble::ZERO, DoubleDouble::ZERO));
+        let mut orbit = mandelbrot.orbit(x.hi, y.hi);
+        let (mut zx, mut zy) = (variation.z0_x, variation.z0_y);
+        let (mut dx, mut dy) = (0.0_f64, 0.0_f64);
+        let mut cycle = Cycle::new((zx, zy));
+
+        for round in 1..max_round {
+            if derivative {
+                // dz' = d z^(d-1) dz + 1
+                let (px, py) = mandelbrot::power(Fractal::Mandelbrot, zx, zy, exponent - 1.0);
+                let (px, py) = (exponent * px, exponent * py);
+                (dx, dy) = (px * dx - py * dy + 1.0, px * dy + py * dx);
+            }
+            (zx, zy) = match &mut precise {
+                Some(z) => {
+                    *z = mandelbrot::quadratic_step(options.fractal, *z, (x, y));
+                    (z.0.hi, z.1.hi)
+                }
+                None => orbit.next().unwrap_or_default(),
+            };
+
+            let abs = zx.hypot(zy);
+            stats.last_abs = abs;
+            stats.min_abs = stats.min_abs.min(abs);
+            stats.max_abs = stats.max_abs.max(abs);
+            if stats.orbit.len() < PLOT_POINTS {
+                stats.orbit.push((zx, zy));
+            }
+
+            if escaped((zx, zy)) {
+                stats.escape = Some(round);
+                stats.smooth = Some(round as f64 + 1.0 - abs.ln().ln() / exponent.ln());
+                if derivative {
+                    stats.distance = Some(abs * abs.ln() / dx.hypot(dy));
+                }
+                break;
+            }
+            if let Some(period) = cycle.returns((zx, zy), mandelbrot::near) {
+                stats.period = Some(period);
+                break;
+            }
+        }
+        stats
+    }
+
+    // The points of Newton's method on z^d - 1 from (x, y) up to the root
+    // it converges to.
+    fn newton(mandelbrot: &Mandelbrot, x: f64, y: f64) -> Self {
+        let mut stats = Self {
+            x,
+            y,
+            max_round: mandelbrot.max_round,
+            escape: None,
+            root: None,
+            smooth: None,
+            last_abs: 0.0,
+            min_abs: f64::INFINITY,
+            max_abs: 0.0,
+            period: None,
+            distance: None,
+            orbit: Vec::new(),
+        };
+        let mut z = (x, y);
+        for round in 1..mandelbrot.max_round {
+            let Some((next, converged)) = mandelbrot.newton_step(z) else {
+                break;
+            };
+            z = next;
+            let abs = z.0.hypot(z.1);
+            stats.last_abs = abs;
+            stats.min_abs = stats.min_abs.min(abs);
+            stats.max_abs = stats.max_abs.max(abs);
+            if stats.orbit.len() < PLOT_POINTS {
+                stats.orbit.push(z);
+            }
+            if converged {
+                stats.escape = Some(round);
+                stats.root = Some(mandelbrot.newton_root(z));
+                break;
+            }
+        }
+        stats
+    }
+
+    fn lines(&self, close_key: &str) -> Vec<String> {
+        let optional = |value: Option<f64>| match value {
+            Some(value) => format!("{:.6e}", value),
+            None => String::from("-"),
+        };
+        vec![
+            String::from("orbit of"),
+            format!(" x: {:.12}", self.x),
+            format!(" y: {:.12}", self.y),
+            match (self.escape, self.root) {
+                (Some(round), Some(root)) => format!("root {} at: {}", root, round),
+                (Some(round), None) => format!("escape: {}", round),
+                (None, _) => format!("escape: none in {}", self.max_round),
+            },
+            format!("smooth: {}", optional(self.smooth)),
+            format!("last |z|: {:.6}", self.last_abs),
+            format!("min |z|: {:.6}", self.min_abs),
+            format!("max |z|: {:.6}", self.max_abs),
+            match self.period {
+                Some(period) => format!("period: {}", period),
+                None => String::from("period: -"),
+            },
+            format!("distance: {}", optional(self.distance)),
+            format!("({} to close)", close_key),
+        ]
+    }
+
+    /// Draw the panel at the top-right corner: the statistics followed by a
+    /// plot of the orbit.
+    pub fn draw(&self, canvas: &mut Canvas, close_key: &str) {
+        self.draw_panel(canvas, &self.lines(close_key));
+    }
+
+    /// Draw `lines` at the top-right corner followed by a plot of the orbit.
+    pub fn draw_panel(&self, canvas: &mut Canvas, lines: &[String]) {
+        let left = canvas
+            .width
+            .saturating_sub(MARGIN + PANEL_COLUMNS * Canvas::text_width(" ", 1));
+        for (i, line) in lines.iter().enumerate() {
+            canvas.draw_text(left, MARGIN + i * LINE_HEIGHT, line, 1);
+        }
+
+        let top = MARGIN + lines.len() * LINE_HEIGHT + MARGIN;
+        canvas.fill_rect(left, top, PLOT_SIZE, PLOT_SIZE, SHADOW);
+        canvas.fill_rect(left, top + PLOT_SIZE / 2, PLOT_SIZE, 1, AXIS_COLOR);
+        canvas.fill_rect(left + PLOT_SIZE / 2, top, 1, PLOT_SIZE, AXIS_COLOR);
+        let to_plot = |x: f64, y: f64| -> Option<(usize, usize)> {
+            let px = (x / PLOT_RANGE + 1.0) / 2.0 * PLOT_SIZE as f64;
+            let py = (1.0 - y / PLOT_RANGE) / 2.0 * PLOT_SIZE as f64;
+            let inside = 0.0..(PLOT_SIZE - 1) as f64;
+            if inside.contains(&px) && inside.contains(&py) {
+                Some((left + px as usize, top + py as usize))
+            } else {
+                None
+            }
+        };
+        for (x, y) in &self.orbit {
+            if let Some((px, py)) = to_plot(*x, *y) {
+                canvas.fill_rect(px, py, 1, 1, ORBIT_COLOR);
+            }
+        }
+        if let Some((px, py)) = to_plot(self.x, self.y) {
+            canvas.fill_rect(
+                px.saturating_sub(1),
+                py.saturating_sub(1),
+                3,
+                3,
+                POINT_COLOR,
+            );
+        }
+    }
+}
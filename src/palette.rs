@@ -0,0 +1,383 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn default_section_size() -> usize {
+    256
+}
+
+// Longest cycle of a blend of two palettes, which otherwise takes the
+// cycle of the longer one.
+const MAX_BLEND_PERIOD: usize = 1 << 16;
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Color gradient through `colors`, advancing to the next color every
+/// `section_size` iterations and wrapping back to the first one after the
+/// last. With `positions`, the colors are stops of a cycle of
+/// `section_size` iterations per color instead, each at its position in the
+/// cycle from 0 to 1.
+///
+/// A palette file is TOML:
+///
+/// ```toml
+/// name = "sunset"
+/// section_size = 64
+/// colors = [[0, 0, 64], [255, 128, 0], [255, 255, 192]]
+/// positions = [0.0, 0.2, 0.5]
+/// ```
+///
+/// or a Fractint map (`.map`) of lines of red, green and blue, one color
+/// per iteration.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    #[serde(default = "default_section_size")]
+    pub section_size: usize,
+    pub colors: Vec<[u8; 3]>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub positions: Vec<f64>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::presets().remove(0)
+    }
+}
+
+impl Palette {
+    pub fn presets() -> Vec<Self> {
+        let preset = |name: &str, section_size, colors: &[[u8; 3]]| Self {
+            name: String::from(name),
+            section_size,
+            colors: colors.to_vec(),
+            positions: Vec::new(),
+        };
+        vec![
+            preset(
+                "classic",
+                256,
+                &[
+                    [0x00, 0x00, 0x80],
+                    [0x00, 0xff, 0x00],
+                    [0xff, 0xff, 0x00],
+                    [0x00, 0xff, 0xff],
+                    [0x00, 0x00, 0xff],
+                ],
+            ),
+            preset(
+                "fire",
+                64,
+                &[
+                    [0x00, 0x00, 0x00],
+                    [0x80, 0x00, 0x00],
+                    [0xff, 0x40, 0x00],
+                    [0xff, 0xc0, 0x00],
+                    [0xff, 0xff, 0xc0],
+                ],
+            ),
+            preset(
+                "ocean",
+                64,
+                &[
+                    [0x00, 0x08, 0x20],
+                    [0x00, 0x40, 0x80],
+                    [0x00, 0xa0, 0xc0],
+                    [0xc0, 0xf0, 0xff],
+                    [0x00, 0x60, 0xa0],
+                ],
+            ),
+            preset(
+                "rainbow",
+                48,
+                &[
+                    [0xff, 0x00, 0x00],
+                    [0xff, 0xff, 0x00],
+                    [0x00, 0xff, 0x00],
+                    [0x00, 0xff, 0xff],
+                    [0x00, 0x00, 0xff],
+                    [0xff, 0x00, 0xff],
+                ],
+            ),
+            preset("grayscale", 128, &[[0x10, 0x10, 0x10], [0xff, 0xff, 0xff]]),
+        ]
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        Self::presets()
+            .into_iter()
+            .find(|palette| palette.name == name)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let palette: Self = if path.extension().is_some_and(|extension| extension == "map") {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            Self::from_map(&name, &contents)?
+        } else {
+            toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        };
+        if palette.colors.is_empty() || palette.section_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "a palette needs at least one color and a positive section_size",
+            ));
+        }
+        let sorted = palette.positions.windows(2).all(|pair| pair[0] <= pair[1]);
+        let in_cycle = palette
+            .positions
+            .iter()
+            .all(|position| (0.0..1.0).contains(position));
+        if !palette.positions.is_empty()
+            && (palette.positions.len() != palette.colors.len() || !sorted || !in_cycle)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "positions need one value from 0 to 1 per color, in increasing order",
+            ));
+        }
+        Ok(palette)
+    }
+
+    /// The palette of a Fractint map: a color of three numbers from 0 to 255
+    /// per line, with anything after them ignored, one per iteration.
+    pub fn from_map(name: &str, contents: &str) -> io::Result<Self> {
+        let colors = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(number, line)| {
+                let channels: Vec<u8> = line
+                    .split_whitespace()
+                    .take(3)
+                    .map_while(|channel| channel.parse().ok())
+                    .collect();
+                <[u8; 3]>::try_from(channels).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line {}: expected red, green and blue", number + 1),
+                    )
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self {
+            name: String::from(name),
+            section_size: 1,
+            colors,
+            positions: Vec::new(),
+        })
+    }
+
+    /// Write the palette as a palette file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents =
+            toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents)
+    }
+
+    /// A preset of this name, or else the palette file at this path.
+    pub fn from_name_or_path(name: &str) -> io::Result<Self> {
+        match Self::preset(name) {
+            Some(palette) => Ok(palette),
+            None => Self::load(Path::new(name)).map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} is neither a preset nor a palette file", name),
+                ),
+                _ => e,
+            }),
+        }
+    }
+
+    /// The colors of the cycles of both palettes mixed, `t` of the way from
+    /// this one to `other`, one color per iteration.
+    pub fn blend(&self, other: &Self, t: f64) -> Self {
+        let (a, b) = (self.period(), other.period());
+        let common = a / gcd(a, b) * b;
+        let period = if common <= MAX_BLEND_PERIOD {
+            common
+        } else {
+            a.max(b)
+        };
+        let t = t as f32;
+        let colors = (0..period)
+            .map(|round| {
+                let [r0, g0, b0, _] = self.color(round, Blend::Srgb);
+                let [r1, g1, b1, _] = other.color(round, Blend::Srgb);
+                let mix =
+                    |from: u8, to: u8| (from as f32 * (1.0 - t) + to as f32 * t).round() as u8;
+                [mix(r0, r1), mix(g0, g1), mix(b0, b1)]
+            })
+            .collect();
+        Self {
+            name: format!("{} to {}", self.name, other.name),
+            section_size: 1,
+            colors,
+            positions: Vec::new(),
+        }
+    }
+
+    /// Iterations of one full cycle through the colors.
+    pub fn period(&self) -> usize {
+        self.section_size * self.colors.len()
+    }
+
+    pub fn color(&self, round: usize, blend: Blend) -> [u8; 4] {
+        if !self.positions.is_empty() {
+            return self.stop_color(round, blend);
+        }
+        let section_size = self.section_size;
+        let table_number = (round / section_size) % self.colors.len();
+        let color_index = round % section_size;
+
+        let [r0, g0, b0] = self.colors[table_number];
+        let [r1, g1, b1] = self.colors[(table_number + 1) % self.colors.len()];
+        let interporation = |a: u8, b: u8| match blend {
+            Blend::Srgb => {
+                (((a as usize * (section_size - color_index) + b as usize * color_index)
+                    / section_size)
+                    & 0xff) as u8
+            }
+            Blend::Linear => {
+                let t = color_index as f32 / section_size as f32;
+                to_srgb(to_linear(a) * (1.0 - t) + to_linear(b) * t)
+            }
+        };
+
+        let r = interporation(r0, r1);
+        let g = interporation(g0, g1);
+        let b = interporation(b0, b1);
+
+        [r, g, b, 0xff]
+    }
+
+    // color() between the stops around the position of `round` in the
+    // cycle, from the last stop around to the first.
+    fn stop_color(&self, round: usize, blend: Blend) -> [u8; 4] {
+        let period = self.period();
+        let count = self.colors.len();
+        let mut position = (round % period) as f64 / period as f64;
+        let from = self
+            .positions
+            .iter()
+            .rposition(|stop| *stop <= position)
+            .unwrap_or(count - 1);
+        let to = (from + 1) % count;
+        let start = self.positions[from];
+        let mut end = self.positions[to];
+        if to <= from {
+            end += 1.0;
+        }
+        if position < start {
+            position += 1.0;
+        }
+        let t = if end > start {
+            ((position - start) / (end - start)) as f32
+        } else {
+            0.0
+        };
+        let [r0, g0, b0] = self.colors[from];
+        let [r1, g1, b1] = self.colors[to];
+        let interpolation = |a: u8, b: u8| match blend {
+            Blend::Srgb => (a as f32 * (1.0 - t) + b as f32 * t).round() as u8,
+            Blend::Linear => to_srgb(to_linear(a) * (1.0 - t) + to_linear(b) * t),
+        };
+        [
+            interpolation(r0, r1),
+            interpolation(g0, g1),
+            interpolation(b0, b1),
+            0xff,
+        ]
+    }
+}
+
+/// Where the colors of a gradient are mixed, and the shading and the
+/// supersamples of the pixels averaged.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Blend {
+    /// on the 8-bit sRGB values, which darkens the middle of gradients
+    /// between contrasting colors
+    #[default]
+    Srgb,
+    /// in linear light, converted back to sRGB for the output
+    Linear,
+}
+
+/// Light intensity (0 to 1) of an 8-bit sRGB channel value.
+pub fn to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// 8-bit sRGB channel value of a light intensity (0 to 1).
+pub fn to_srgb(linear: f32) -> u8 {
+    let linear = linear.clamp(0.0, 1.0);
+    let value = if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (value * 255.0).round() as u8
+}
+
+pub const BAND_COUNTS: [usize; 6] = [0, 4, 8, 16, 32, 64];
+
+/// Contour-map look: the iterations of a palette cycle are divided into
+/// `count` bands of a single color each, with the band boundaries shifted
+/// by `offset` iterations. A count of 0 leaves the gradient smooth.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bands {
+    pub count: usize,
+    pub offset: usize,
+}
+
+impl Bands {
+    /// The next band count of BAND_COUNTS.
+    pub fn next_count(self) -> Self {
+        let index = BAND_COUNTS
+            .iter()
+            .position(|count| *count == self.count)
+            .map_or(0, |index| (index + 1) % BAND_COUNTS.len());
+        Self {
+            count: BAND_COUNTS[index],
+            offset: self.offset,
+        }
+    }
+
+    /// Shift the boundaries by a quarter of a band.
+    pub fn shift(self, period: usize) -> Self {
+        if self.count == 0 {
+            return self;
+        }
+        let width = self.width(period);
+        Self {
+            count: self.count,
+            offset: (self.offset + (width / 4).max(1)) % width,
+        }
+    }
+
+    fn width(self, period: usize) -> usize {
+        (period / self.count).max(1)
+    }
+
+    /// First iteration of the band that `round` falls into.
+    pub fn quantize(self, round: usize, period: usize) -> usize {
+        if self.count == 0 {
+            return round;
+        }
+        let width = self.width(period);
+        ((round + self.offset) / width * width).saturating_sub(self.offset)
+    }
+}
@@ -0,0 +1,223 @@
+use egui::{Color32, Sense};
+use log::{error, info};
+use std::path::Path;
+
+use crate::palette::{Blend, Palette};
+
+// size of the preview of the gradient, in frame pixels
+const PREVIEW_WIDTH: f32 = 256.0;
+const PREVIEW_HEIGHT: f32 = 16.0;
+// height of the list of stops, beyond which it scrolls
+const STOPS_HEIGHT: f32 = 200.0;
+// range of the iterations from one stop to the next
+const MAX_SECTION_SIZE: usize = 4096;
+const DEFAULT_FILE: &str = "palette.toml";
+
+/// Gradient stops edited in the settings panel: the colors of a palette at
+/// their positions in its cycle, turned into the palette of the view on
+/// every change, saved as a palette file or imported from one.
+pub struct PaletteEditor {
+    name: String,
+    section_size: usize,
+    // position in the cycle from 0 to 1 and color of each stop
+    stops: Vec<(f64, [u8; 3])>,
+    // path of the file to save to or import from
+    file: String,
+    // outcome of the last save or import
+    status: Option<String>,
+}
+
+impl Default for PaletteEditor {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            section_size: 0,
+            stops: Vec::new(),
+            file: String::from(DEFAULT_FILE),
+            status: None,
+        }
+    }
+}
+
+impl PaletteEditor {
+    // Take the stops of `palette`, its colors spread evenly over the cycle
+    // unless they have positions.
+    fn edit(&mut self, palette: &Palette) {
+        let count = palette.colors.len();
+        self.name = palette.name.clone();
+        self.section_size = palette.section_size;
+        self.stops = palette
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(index, color)| {
+                let position = palette
+                    .positions
+                    .get(index)
+                    .copied()
+                    .unwrap_or(index as f64 / count as f64);
+                (position, *color)
+            })
+            .collect();
+    }
+
+    /// The palette of the stops, in the order of their positions.
+    pub fn palette(&self) -> Palette {
+        let mut stops = self.stops.clone();
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Palette {
+            name: self.name.clone(),
+            section_size: self.section_size.max(1),
+            colors: stops.iter().map(|(_, color)| *color).collect(),
+            positions: stops.iter().map(|(position, _)| *position).collect(),
+        }
+    }
+
+    /// Show the editor, starting from `current` the first time. Returns the
+    /// palette of the stops when they were edited or imported.
+    pub fn show(&mut self, ui: &mut egui::Ui, current: &Palette) -> Option<Palette> {
+        if self.stops.is_empty() {
+            self.edit(current);
+        }
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("name");
+            changed |= ui.text_edit_singleline(&mut self.name).changed();
+        });
+        changed |= ui
+            .add(
+                egui::Slider::new(&mut self.section_size, 1..=MAX_SECTION_SIZE)
+                    .logarithmic(true)
+                    .text("iterations per stop"),
+            )
+            .changed();
+
+        let palette = self.palette();
+        let (rect, _) =
+            ui.allocate_exact_size(egui::vec2(PREVIEW_WIDTH, PREVIEW_HEIGHT), Sense::hover());
+        let period = palette.period();
+        for column in 0..PREVIEW_WIDTH as usize {
+            let round = column * period / PREVIEW_WIDTH as usize;
+            let [r, g, b, _] = palette.color(round, Blend::Srgb);
+            let left = rect.min.x + column as f32;
+            let strip = egui::Rect::from_min_max(
+                egui::pos2(left, rect.min.y),
+                egui::pos2(left + 1.0, rect.max.y),
+            );
+            ui.painter()
+                .rect_filled(strip, 0.0, Color32::from_rgb(r, g, b));
+        }
+
+        let mut removed = None;
+        let removable = self.stops.len() > 1;
+        // a Fractint map has a stop per color
+        egui::ScrollArea::vertical()
+            .max_height(STOPS_HEIGHT)
+            .show(ui, |ui| {
+                for (index, (position, color)) in self.stops.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(position)
+                                    .speed(0.002)
+                                    .range(0.0..=0.999),
+                            )
+                            .changed();
+                        changed |= ui.color_edit_button_srgb(color).changed();
+                        if removable && ui.button("remove").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                }
+            });
+        if let Some(index) = removed {
+            self.stops.remove(index);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Add stop").clicked() {
+                self.add_stop();
+                changed = true;
+            }
+            if ui.button("Edit current").clicked() {
+                self.edit(current);
+                self.status = None;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("file");
+            ui.text_edit_singleline(&mut self.file);
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                self.save();
+            }
+            if ui.button("Import").clicked() {
+                changed |= self.import();
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+        changed.then(|| self.palette())
+    }
+
+    // Add a stop in the middle of the widest gap between two stops, in the
+    // color of the gradient there.
+    fn add_stop(&mut self) {
+        let palette = self.palette();
+        let positions = &palette.positions;
+        let count = positions.len();
+        let (start, width) = (0..count)
+            .map(|index| {
+                let next = if index + 1 < count {
+                    positions[index + 1]
+                } else {
+                    positions[0] + 1.0
+                };
+                (positions[index], next - positions[index])
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or((0.0, 1.0));
+        let position = (start + width / 2.0).fract();
+        let round = (position * palette.period() as f64) as usize;
+        let [r, g, b, _] = palette.color(round, Blend::Srgb);
+        self.stops.push((position, [r, g, b]));
+        self.stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    fn save(&mut self) {
+        let path = Path::new(&self.file);
+        self.status = Some(match self.palette().save(path) {
+            Ok(()) => {
+                info!("saved {}", path.display());
+                format!("saved {}", path.display())
+            }
+            Err(e) => {
+                error!("failed to save {}: {}", path.display(), e);
+                format!("failed to save: {}", e)
+            }
+        });
+    }
+
+    // Take the stops of the palette file, or Fractint map, at the path of
+    // the file field. Returns whether it loaded.
+    fn import(&mut self) -> bool {
+        let file = self.file.clone();
+        let path = Path::new(&file);
+        match Palette::load(path) {
+            Ok(palette) => {
+                info!("imported {}", path.display());
+                self.edit(&palette);
+                self.status = Some(format!("imported {}", path.display()));
+                true
+            }
+            Err(e) => {
+                error!("failed to import {}: {}", path.display(), e);
+                self.status = Some(format!("failed to import: {}", e));
+                false
+            }
+        }
+    }
+}
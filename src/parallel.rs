@@ -0,0 +1,173 @@
+// The parallel iterators the renderer uses: rayon's with the `parallel`
+// feature, and otherwise plain iterators behind the same method names, for
+// targets without threads such as the browser. The renderer can be limited
+// to a pool of its own, smaller than rayon's global one of a thread per core.
+
+#[cfg(feature = "parallel")]
+pub use rayon::prelude::*;
+
+#[cfg(not(feature = "parallel"))]
+pub use sequential::*;
+
+/// Map `f` over `items` on every thread, starting the items in their order
+/// as the threads free up (`par_iter` splits them into ranges instead), and
+/// return the results in the order of `items`.
+#[cfg(feature = "parallel")]
+pub fn map_in_order<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<R>>> = items.iter().map(|_| Mutex::new(None)).collect();
+    let (next, results_ref, f) = (&next, &results, &f);
+    rayon::scope(|scope| {
+        for _ in 0..rayon::current_num_threads().min(items.len()) {
+            scope.spawn(move |_| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                *results_ref[index].lock().unwrap() = Some(result);
+            });
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap().unwrap())
+        .collect()
+}
+
+// the pool of set_threads(), or None for the global one
+#[cfg(feature = "parallel")]
+static POOL: std::sync::Mutex<Option<std::sync::Arc<rayon::ThreadPool>>> =
+    std::sync::Mutex::new(None);
+
+/// Limit the renderer to `threads` threads on a pool of its own, or give it
+/// every core again with None. A render in progress finishes on the threads
+/// it started on.
+#[cfg(feature = "parallel")]
+pub fn set_threads(threads: Option<usize>) -> Result<(), String> {
+    let pool = match threads {
+        Some(threads) => Some(std::sync::Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|e| e.to_string())?,
+        )),
+        None => None,
+    };
+    *POOL.lock().unwrap() = pool;
+    Ok(())
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn set_threads(_threads: Option<usize>) -> Result<(), String> {
+    Ok(())
+}
+
+/// Run `f` with its parallel iterators on the threads of set_threads().
+#[cfg(feature = "parallel")]
+pub fn install<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    let pool = POOL.lock().unwrap().clone();
+    match pool {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn install<R>(f: impl FnOnce() -> R) -> R {
+    f()
+}
+
+/// Number of threads `map_in_order` runs on.
+#[cfg(feature = "parallel")]
+pub fn threads() -> usize {
+    match &*POOL.lock().unwrap() {
+        Some(pool) => pool.current_num_threads(),
+        None => rayon::current_num_threads(),
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn threads() -> usize {
+    1
+}
+
+/// Number of threads the renderer runs on without a limit, one per core.
+#[cfg(feature = "parallel")]
+pub fn max_threads() -> usize {
+    rayon::current_num_threads()
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn max_threads() -> usize {
+    1
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn map_in_order<T, R>(items: &[T], f: impl Fn(&T) -> R) -> Vec<R> {
+    items.iter().map(f).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+mod sequential {
+    use std::iter::FlatMap;
+    use std::slice::{ChunksExactMut, ChunksMut, Iter};
+
+    pub trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {}
+
+    pub trait ParallelIterator: Iterator + Sized {
+        fn flat_map_iter<U, F>(self, f: F) -> FlatMap<Self, U, F>
+        where
+            U: IntoIterator,
+            F: FnMut(Self::Item) -> U,
+        {
+            self.flat_map(f)
+        }
+    }
+
+    impl<I: Iterator> ParallelIterator for I {}
+
+    pub trait ParallelSlice<T> {
+        fn par_iter(&self) -> Iter<'_, T>;
+    }
+
+    impl<T> ParallelSlice<T> for [T] {
+        fn par_iter(&self) -> Iter<'_, T> {
+            self.iter()
+        }
+    }
+
+    pub trait ParallelSliceMut<T> {
+        fn par_chunks_mut(&mut self, size: usize) -> ChunksMut<'_, T>;
+        fn par_chunks_exact_mut(&mut self, size: usize) -> ChunksExactMut<'_, T>;
+        fn par_sort_unstable(&mut self)
+        where
+            T: Ord;
+    }
+
+    impl<T> ParallelSliceMut<T> for [T] {
+        fn par_chunks_mut(&mut self, size: usize) -> ChunksMut<'_, T> {
+            self.chunks_mut(size)
+        }
+
+        fn par_chunks_exact_mut(&mut self, size: usize) -> ChunksExactMut<'_, T> {
+            self.chunks_exact_mut(size)
+        }
+
+        fn par_sort_unstable(&mut self)
+        where
+            T: Ord,
+        {
+            self.sort_unstable()
+        }
+    }
+}
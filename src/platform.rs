@@ -0,0 +1,117 @@
+// What the native build and the browser build (wasm32) do differently: the
+// clock, threads, logging, the clipboard and the window.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::{Instant, SystemTime, UNIX_EPOCH};
+#[cfg(target_arch = "wasm32")]
+pub use web_time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Run `f` on a new thread, or right away in the browser, which has none.
+pub fn spawn<F: FnOnce() + Send + 'static>(f: F) {
+    #[cfg(not(target_arch = "wasm32"))]
+    std::thread::spawn(f);
+    #[cfg(target_arch = "wasm32")]
+    f();
+}
+
+/// Run `f` like spawn() unless the last `f` given with `busy` is still
+/// running, and tell whether it was started.
+pub fn spawn_unless_busy<F: FnOnce() + Send + 'static>(busy: &Arc<AtomicBool>, f: F) -> bool {
+    if busy.swap(true, Ordering::AcqRel) {
+        return false;
+    }
+    let busy = Arc::clone(busy);
+    spawn(move || {
+        f();
+        busy.store(false, Ordering::Release);
+    });
+    true
+}
+
+/// Send the log to stderr (RUST_LOG filters it), or to the console of the
+/// browser along with panics.
+pub fn init_logger() {
+    #[cfg(not(target_arch = "wasm32"))]
+    env_logger::init();
+    #[cfg(target_arch = "wasm32")]
+    {
+        if log::set_logger(&ConsoleLogger).is_ok() {
+            log::set_max_level(log::LevelFilter::Info);
+        }
+        std::panic::set_hook(Box::new(|panic| {
+            web_sys::console::error_1(&panic.to_string().into());
+        }));
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+struct ConsoleLogger;
+
+#[cfg(target_arch = "wasm32")]
+impl log::Log for ConsoleLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = format!("{} {}", record.level(), record.args()).into();
+        match record.level() {
+            log::Level::Error => web_sys::console::error_1(&message),
+            log::Level::Warn => web_sys::console::warn_1(&message),
+            _ => web_sys::console::log_1(&message),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// The system clipboard, opened on the first copy. It stays open once
+/// used: on X11 the contents are served by the program that set them, for
+/// as long as it keeps the clipboard. Browsers only allow asynchronous
+/// copies from a page, which are not supported.
+#[derive(Default)]
+pub struct Clipboard {
+    #[cfg(not(target_arch = "wasm32"))]
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    pub fn set_text(&mut self, text: String) -> Result<(), String> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if self.clipboard.is_none() {
+                self.clipboard = Some(arboard::Clipboard::new().map_err(|e| e.to_string())?);
+            }
+            if let Some(clipboard) = &mut self.clipboard {
+                clipboard.set_text(text).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = text;
+            Err(String::from("no clipboard in the browser build"))
+        }
+    }
+}
+
+/// Add the canvas of the window to the body of the page.
+#[cfg(target_arch = "wasm32")]
+pub fn attach_canvas(window: &winit::window::Window) {
+    use winit::platform::web::WindowExtWebSys;
+
+    let canvas = window.canvas();
+    let attached = web_sys::window()
+        .and_then(|page| page.document())
+        .and_then(|document| document.body())
+        .and_then(|body| body.append_child(&canvas).ok());
+    if attached.is_none() {
+        log::error!("failed to add the canvas to the page");
+    }
+}
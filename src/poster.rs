@@ -0,0 +1,47 @@
+use log::info;
+use std::io::Write;
+use std::path::Path;
+
+use crate::canvas::Canvas;
+use crate::caption::Caption;
+use crate::export;
+use crate::framing::{self, Fit};
+use crate::mandelbrot::Mandelbrot;
+use crate::tile::TILE_SIZE;
+
+// pixels of the strips a poster is rendered in (16 MiB of RGBA)
+const STRIP_PIXELS: usize = 1 << 22;
+
+/// Render `mandelbrot`, framed at its own size, into a PNG image of `size`
+/// pixels at `path`, a strip of rows at a time written out as soon as it is
+/// rendered, so that the memory used stays the same at any size. The
+/// `caption` is drawn into the image when given.
+pub fn save(
+    mandelbrot: &Mandelbrot,
+    size: (u32, u32),
+    fit: Fit,
+    caption: Option<&Caption>,
+    path: &Path,
+    note: &str,
+) -> Result<(), png::EncodingError> {
+    let (mut job, framing) = framing::job(mandelbrot, size, fit);
+    let (width, height) = (size.0 as usize, size.1 as usize);
+    // whole rows of tiles, as in a frame rendered at once
+    let strip_rows = (STRIP_PIXELS / width / TILE_SIZE).max(1) * TILE_SIZE;
+    let mut writer = export::png_stream(path, size.0, size.1, note)?;
+    let mut strip = vec![0_u8; 4 * width * strip_rows.min(height)];
+    job.sample_strips();
+    for top in (0..height).step_by(strip_rows) {
+        let rows = strip_rows.min(height - top);
+        let strip = &mut strip[..4 * width * rows];
+        job.draw_strip(strip, top, rows);
+        let mut canvas = Canvas::new(strip, width, rows);
+        framing.letterbox(&mut canvas, top);
+        if let Some(caption) = caption {
+            caption.draw(&mut canvas, job.view(), size, top);
+        }
+        writer.write_all(strip)?;
+        info!("poster: {}/{} rows", top + rows, height);
+    }
+    writer.finish()
+}
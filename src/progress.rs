@@ -0,0 +1,137 @@
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const PROGRESS_FILE: &str = "progress.toml";
+// starts each chunk appended to the progress file
+const CHUNK_HEADER: &str = "\n[[completed]]\n";
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct Chunk {
+    pub number: usize,
+    pub crc32: u32,
+}
+
+impl Chunk {
+    // the chunk as appended to the progress file
+    fn entry(&self) -> String {
+        format!(
+            "{}number = {}\ncrc32 = {}\n",
+            CHUNK_HEADER, self.number, self.crc32
+        )
+    }
+}
+
+/// Record of the chunks (frames or tiles) of a long export that are already
+/// written, stored next to the output as `progress.toml` so that an
+/// interrupted export can be resumed. The job is written once, and each
+/// chunk appended to it.
+#[derive(Deserialize, Debug)]
+pub struct Progress<J> {
+    job: J,
+    #[serde(default)]
+    completed: Vec<Chunk>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl<J: Serialize + DeserializeOwned + PartialEq> Progress<J> {
+    /// Start recording the export of `job` in `dir`.
+    ///
+    /// With `resume`, the chunks recorded by an earlier run of the same job
+    /// are kept when `chunk_path` still holds data with the recorded
+    /// checksum. An earlier run of a different job is an error then, so that
+    /// its output is not mixed up with this one.
+    pub fn open<F>(dir: &Path, job: J, resume: bool, chunk_path: F) -> io::Result<Self>
+    where
+        F: Fn(usize) -> PathBuf,
+    {
+        let path = dir.join(PROGRESS_FILE);
+        let mut progress = Self {
+            job,
+            completed: Vec::new(),
+            path: path.clone(),
+        };
+        if !resume {
+            progress.save()?;
+            return Ok(progress);
+        }
+
+        let previous = match fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents).map_err(invalid_data)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                progress.save()?;
+                return Ok(progress);
+            }
+            Err(e) => return Err(e),
+        };
+        if previous.job != progress.job {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} belongs to a different export", path.display()),
+            ));
+        }
+
+        for chunk in previous.completed {
+            match fs::read(chunk_path(chunk.number)) {
+                Ok(data) if crc32fast::hash(&data) == chunk.crc32 => progress.completed.push(chunk),
+                _ => info!(
+                    "chunk {} is missing or broken, rendering again",
+                    chunk.number
+                ),
+            }
+        }
+        info!(
+            "resuming with {} completed chunks",
+            progress.completed.len()
+        );
+        progress.save()?;
+        Ok(progress)
+    }
+
+    // An interruption may cut the last chunk appended short: it is dropped
+    // like a chunk whose file is missing.
+    fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents).or_else(|e| match contents.rfind(CHUNK_HEADER) {
+            Some(end) => toml::from_str(&contents[..end]).map_err(|_| e),
+            None => Err(e),
+        })
+    }
+
+    pub fn is_completed(&self, number: usize) -> bool {
+        self.completed.iter().any(|chunk| chunk.number == number)
+    }
+
+    /// Record that chunk `number` has been written with `data`.
+    pub fn complete(&mut self, number: usize, data: &[u8]) -> io::Result<()> {
+        let chunk = Chunk {
+            number,
+            crc32: crc32fast::hash(data),
+        };
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(chunk.entry().as_bytes())?;
+        self.completed.push(chunk);
+        Ok(())
+    }
+
+    fn save(&self) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct Job<'a, J> {
+            job: &'a J,
+        }
+
+        // write and rename so that an interruption never leaves a truncated file
+        let mut contents = toml::to_string(&Job { job: &self.job }).map_err(invalid_data)?;
+        contents.extend(self.completed.iter().map(Chunk::entry));
+        let temporary = self.path.with_extension("toml.tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, &self.path)
+    }
+}
@@ -0,0 +1,27 @@
+/// xorshift64*: fast and reproducible from a seed, not for cryptography.
+pub struct Random(u64);
+
+impl Random {
+    // the seed goes through splitmix64, so that neighboring seeds start
+    // far apart
+    pub fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Self((z ^ (z >> 31)) | 1)
+    }
+
+    /// Uniform in [-1, 1).
+    pub fn next(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let bits = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        bits as f64 / (1_u64 << 52) as f64 - 1.0
+    }
+
+    /// Uniform index below `len`, which must be positive.
+    pub fn index(&mut self, len: usize) -> usize {
+        (((self.next() + 1.0) / 2.0 * len as f64) as usize).min(len - 1)
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::canvas::{Canvas, GLYPH_SIZE};
+use crate::mandelbrot::{self, Cycle, Mandelbrot, View};
+use crate::parallel::*;
+
+// |z| the potential and the rays are measured from, large enough for the
+// potential to be smooth
+const ESCAPE_RADIUS: f64 = 65536.0;
+// points of a ray per halving of the potential: Newton's method follows the
+// ray from each to the next as long as they are close
+const SHARPNESS: usize = 8;
+// halvings of the potential a ray is traced through at most
+const MAX_DEPTH: usize = 256;
+const NEWTON_STEPS: usize = 64;
+// a ray ends where its points stop moving in f64
+const MIN_STEP: f64 = 1e-14;
+// neighboring pixels further apart in potential levels than this are too
+// close to the set for its equipotentials to be told apart
+const MAX_LEVEL_STEP: f64 = 0.5;
+// weight of the equipotentials over the image
+const EQUIPOTENTIAL_ALPHA: f64 = 0.5;
+// distance of the angle of a ray from its first point in the view
+const LABEL_OFFSET: f64 = 4.0;
+
+// the view, the frame size and the iteration limit of a frame
+type FrameKey = (View, (u32, u32), usize);
+
+/// Angle of an external ray in turns, a fraction p/q that doubles exactly,
+/// since the rays that land together have angles of the same denominator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Angle {
+    num: u64,
+    den: u64,
+}
+
+impl Angle {
+    fn turns(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    // the angle of the ray through the squares of the points of this one
+    fn doubled(self) -> Self {
+        Self {
+            num: (2 * self.num as u128 % self.den as u128) as u64,
+            den: self.den,
+        }
+    }
+}
+
+impl FromStr for Angle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (num, den) = value
+            .split_once('/')
+            .ok_or_else(|| format!("{} is not a fraction p/q", value))?;
+        let (Ok(num), Ok(den)) = (num.trim().parse::<u64>(), den.trim().parse::<u64>()) else {
+            return Err(format!("{} is not a fraction p/q", value));
+        };
+        if den == 0 {
+            return Err(format!("{} has a zero denominator", value));
+        }
+        Ok(Self {
+            num: num % den,
+            den,
+        })
+    }
+}
+
+impl fmt::Display for Angle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+/// Angles shown until others are given: the rays landing at the roots of
+/// the period 2 bulb and of the period 3 bulb at the top.
+pub fn default_angles() -> Vec<Angle> {
+    [(1, 3), (2, 3), (1, 7), (2, 7)]
+        .into_iter()
+        .map(|(num, den)| Angle { num, den })
+        .collect()
+}
+
+/// Equipotentials and external rays of the quadratic Mandelbrot set. The
+/// equipotentials are the curves where the potential log|z_n| / 2^n halves,
+/// found again for every view; the rays are traced once, in the complex
+/// plane, from far away to where they land on the set.
+#[derive(Clone)]
+pub struct ExternalRays {
+    angles: Vec<Angle>,
+    // points of the rays in the order of the angles, None until drawn
+    rays: Option<Vec<Vec<(f64, f64)>>>,
+    // pixels of the equipotentials, with the frame they were found for
+    equipotentials: Option<(FrameKey, Vec<usize>)>,
+}
+
+impl Default for ExternalRays {
+    fn default() -> Self {
+        Self::new(default_angles())
+    }
+}
+
+impl ExternalRays {
+    pub fn new(angles: Vec<Angle>) -> Self {
+        Self {
+            angles,
+            rays: None,
+            equipotentials: None,
+        }
+    }
+
+    pub fn angles(&self) -> &[Angle] {
+        &self.angles
+    }
+
+    /// Draw the equipotentials of the view of `mandelbrot`, unless
+    /// `rays_only`, and the rays over them, each with its angle where it
+    /// enters the view.
+    pub fn draw(&mut self, canvas: &mut Canvas, mandelbrot: &Mandelbrot, rays_only: bool) {
+        if !rays_only {
+            let key: FrameKey = (
+                mandelbrot.view(),
+                (mandelbrot.width, mandelbrot.height),
+                mandelbrot.max_round,
+            );
+            let pixels = match &self.equipotentials {
+                Some((found, pixels)) if *found == key => pixels,
+                _ => {
+                    &self
+                        .equipotentials
+                        .insert((key, equipotentials(mandelbrot)))
+                        .1
+                }
+            };
+            let [r, g, b] = canvas.theme.foreground;
+            let foreground = [r as f64, g as f64, b as f64];
+            for pixel in pixels {
+                let (x, y) = (pixel % canvas.width, pixel / canvas.width);
+                canvas.blend_rect(x, y, 1, 1, foreground, EQUIPOTENTIAL_ALPHA);
+            }
+        }
+
+        let angles = &self.angles;
+        let rays = self
+            .rays
+            .get_or_insert_with(|| angles.iter().map(|angle| trace(*angle)).collect());
+        let [r, g, b] = canvas.theme.foreground;
+        let foreground = [r, g, b, 0xff];
+        for (angle, ray) in angles.iter().zip(rays.iter()) {
+            let pixels: Vec<_> = ray
+                .iter()
+                .map(|(x, y)| mandelbrot.pixel_of(*x, *y))
+                .collect();
+            for segment in pixels.windows(2) {
+                canvas.draw_line(segment[0], segment[1], foreground);
+            }
+            let (width, height) = (canvas.width as f64, canvas.height as f64);
+            if let Some((x, y)) = pixels
+                .iter()
+                .find(|(x, y)| *x >= 0.0 && *y >= 0.0 && *x < width && *y < height)
+            {
+                let label = angle.to_string();
+                // kept inside the canvas on the right and at the bottom
+                let x = (x + LABEL_OFFSET)
+                    .min(width - Canvas::text_width(&label, 1) as f64)
+                    .max(0.0);
+                let y = (y + LABEL_OFFSET).min(height - GLYPH_SIZE as f64).max(0.0);
+                canvas.draw_text(x as usize, y as usize, &label, 1);
+            }
+        }
+    }
+}
+
+// z and dz/dc after `rounds` iterations of z^2 + c from z = 0.
+fn iterate(c: (f64, f64), rounds: usize) -> ((f64, f64), (f64, f64)) {
+    let (mut z, mut dz) = ((0.0, 0.0), (0.0, 0.0));
+    for _ in 0..rounds {
+        dz = (
+            2.0 * (z.0 * dz.0 - z.1 * dz.1) + 1.0,
+            2.0 * (z.0 * dz.1 + z.1 * dz.0),
+        );
+        z = (z.0 * z.0 - z.1 * z.1 + c.0, 2.0 * z.0 * z.1 + c.1);
+    }
+    (z, dz)
+}
+
+// The point near `c` whose z after `rounds` iterations is `target`, by
+// Newton's method, or None when it does not converge.
+fn newton(mut c: (f64, f64), target: (f64, f64), rounds: usize) -> Option<(f64, f64)> {
+    for _ in 0..NEWTON_STEPS {
+        let (z, dz) = iterate(c, rounds);
+        let (num_x, num_y) = (z.0 - target.0, z.1 - target.1);
+        let norm = dz.0 * dz.0 + dz.1 * dz.1;
+        let step = (
+            (num_x * dz.0 + num_y * dz.1) / norm,
+            (num_y * dz.0 - num_x * dz.1) / norm,
+        );
+        c = (c.0 - step.0, c.1 - step.1);
+        if !(c.0.is_finite() && c.1.is_finite()) {
+            return None;
+        }
+        if step.0.hypot(step.1) <= MIN_STEP * c.0.hypot(c.1).max(1.0) {
+            return Some(c);
+        }
+    }
+    Some(c)
+}
+
+/// Points of the external ray of `angle`, from the escape radius to where
+/// f64 no longer tells them apart: the points whose z after n iterations is
+/// at the angle doubled n - 1 times, with |z| going down from the escape
+/// radius to its square root before n goes up by one.
+pub fn trace(angle: Angle) -> Vec<(f64, f64)> {
+    let turns = angle.turns() * std::f64::consts::TAU;
+    let mut c = (ESCAPE_RADIUS * turns.cos(), ESCAPE_RADIUS * turns.sin());
+    let mut points = vec![c];
+    let mut angle = angle;
+    for depth in 1..=MAX_DEPTH {
+        let turns = angle.turns() * std::f64::consts::TAU;
+        for step in 0..SHARPNESS {
+            let radius = ESCAPE_RADIUS.powf(0.5_f64.powf((step + 1) as f64 / SHARPNESS as f64));
+            let target = (radius * turns.cos(), radius * turns.sin());
+            let Some(next) = newton(c, target, depth) else {
+                return points;
+            };
+            if (next.0 - c.0).hypot(next.1 - c.1) <= MIN_STEP {
+                return points;
+            }
+            c = next;
+            points.push(c);
+        }
+        angle = angle.doubled();
+    }
+    points
+}
+
+// Indices of the pixels of the frame of `mandelbrot` where the level of
+// the potential changes from the pixel to its right or below, as long as
+// the levels are far enough apart to be drawn as separate curves. The
+// points of the set are skipped when the rendered frame tells them, or
+// else stopped at their cycle like the renderer does.
+fn equipotentials(mandelbrot: &Mandelbrot) -> Vec<usize> {
+    let (width, height) = (mandelbrot.width as usize, mandelbrot.height as usize);
+    let log_radius = ESCAPE_RADIUS.ln();
+    let inside = mandelbrot.view_iterations();
+    let mut levels = vec![f64::NAN; width * height];
+    levels
+        .par_chunks_mut(width.max(1))
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, level) in row.iter_mut().enumerate() {
+                if inside.is_some_and(|iterations| iterations[y * width + x] == 0) {
+                    continue;
+                }
+                let (c_x, c_y) = mandelbrot.point_at(x as f64, y as f64);
+                let (mut z_x, mut z_y) = (0.0_f64, 0.0_f64);
+                let mut cycle = Cycle::new((z_x, z_y));
+                for round in 1..=mandelbrot.max_round {
+                    (z_x, z_y) = (z_x * z_x - z_y * z_y + c_x, 2.0 * z_x * z_y + c_y);
+                    let norm = z_x * z_x + z_y * z_y;
+                    if norm > ESCAPE_RADIUS * ESCAPE_RADIUS {
+                        // continuous in c: the potential is log_radius / 2^level
+                        *level = round as f64 - (0.5 * norm.ln() / log_radius).log2();
+                        break;
+                    }
+                    if cycle.returns((z_x, z_y), mandelbrot::near).is_some() {
+                        break;
+                    }
+                }
+            }
+        });
+    let crosses = |a: f64, b: f64| (a - b).abs() < MAX_LEVEL_STEP && a.floor() != b.floor();
+    (0..width * height)
+        .filter(|&pixel| {
+            let (x, y) = (pixel % width, pixel / width);
+            let level = levels[pixel];
+            (x + 1 < width && crosses(level, levels[pixel + 1]))
+                || (y + 1 < height && crosses(level, levels[pixel + width]))
+        })
+        .collect()
+}
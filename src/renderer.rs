@@ -0,0 +1,155 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use crate::mandelbrot::Mandelbrot;
+use crate::platform::Instant;
+use crate::tile::{CancelToken, Tile};
+
+// a job to draw, numbered by start()
+type Job = (u64, Mandelbrot);
+// a drawn job and its frame
+type Finished = (u64, Mandelbrot, Vec<u8>);
+// a tile of the frame in progress, with its colors
+type DrawnTile = (Tile, Vec<[u8; 4]>);
+// frames drawn quicker than this show no progress
+const PROGRESS_DELAY: Duration = Duration::from_millis(300);
+
+/// Draws frames on a worker thread that lives as long as the renderer, so
+/// that the event loop keeps handling input while the tiles are rendered
+/// (the browser build, without threads, draws them on the event loop).
+/// Starting a frame cancels the one in progress, which stops at its next
+/// tile, and the worker goes on with the latest frame started. The tiles of a full frame come
+/// out one by one as they are drawn, in the tile order of the view, which
+/// also tells how far the frame has come.
+pub struct Renderer {
+    #[cfg(not(target_arch = "wasm32"))]
+    jobs: Sender<Job>,
+    #[cfg(target_arch = "wasm32")]
+    sender: Sender<Finished>,
+    receiver: Receiver<Finished>,
+    cancel: CancelToken,
+    generation: u64,
+    tiles: Option<Receiver<DrawnTile>>,
+    // when the frame in progress started, its pixels and how many of them
+    // the tiles drawn so far cover
+    started: Option<Instant>,
+    pixels: usize,
+    drawn: usize,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        #[cfg(not(target_arch = "wasm32"))]
+        let jobs = {
+            let (jobs, pending) = mpsc::channel();
+            std::thread::spawn(move || work(pending, sender));
+            jobs
+        };
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            jobs,
+            #[cfg(target_arch = "wasm32")]
+            sender,
+            receiver,
+            cancel: CancelToken::default(),
+            generation: 0,
+            tiles: None,
+            started: None,
+            pixels: 0,
+            drawn: 0,
+        }
+    }
+}
+
+impl Renderer {
+    /// Start drawing `mandelbrot` unless its last frame is up to date.
+    pub fn start(&mut self, mandelbrot: &mut Mandelbrot) {
+        let mut job = match mandelbrot.take_job() {
+            Some(job) => job,
+            None => return,
+        };
+        self.cancel.cancel();
+        self.cancel = CancelToken::default();
+        self.generation += 1;
+        job.cancel = self.cancel.clone();
+        let (tile_sender, tiles) = mpsc::channel();
+        job.tile_sender = Some(tile_sender);
+        self.tiles = Some(tiles);
+        self.started = Some(Instant::now());
+        self.pixels = job.width as usize * job.height as usize;
+        self.drawn = 0;
+
+        // the worker and the receiver only go away with the renderer
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = self.jobs.send((self.generation, job));
+        #[cfg(target_arch = "wasm32")]
+        if let Some(finished) = draw((self.generation, job)) {
+            let _ = self.sender.send(finished);
+        }
+    }
+
+    /// The frame of the last start() once it is drawn, with the job that
+    /// drew it for `Mandelbrot::finish`.
+    pub fn finished(&mut self) -> Option<(Mandelbrot, Vec<u8>)> {
+        let finished = self
+            .receiver
+            .try_iter()
+            .filter(|(generation, _, _)| *generation == self.generation)
+            .last()
+            .map(|(_, job, frame)| (job, frame));
+        if finished.is_some() {
+            self.started = None;
+        }
+        finished
+    }
+
+    /// The tiles of the frame of the last start() drawn since the last call.
+    pub fn drawn_tiles(&mut self) -> Vec<DrawnTile> {
+        let tiles: Vec<DrawnTile> = self
+            .tiles
+            .iter()
+            .flat_map(|tiles| tiles.try_iter())
+            .collect();
+        self.drawn += tiles.iter().map(|(tile, _)| tile.pixels()).sum::<usize>();
+        tiles
+    }
+
+    /// The part of the frame in progress drawn so far, from 0 to 1, once it
+    /// has taken long enough to show. A frame whose iteration limit is
+    /// raised is drawn again from the start, so the part is that of the
+    /// latest pass.
+    pub fn progress(&self) -> Option<f64> {
+        let started = self.started?;
+        if started.elapsed() < PROGRESS_DELAY || self.pixels == 0 {
+            return None;
+        }
+        let pass = self.drawn % self.pixels;
+        Some(if pass == 0 && self.drawn > 0 {
+            1.0
+        } else {
+            pass as f64 / self.pixels as f64
+        })
+    }
+}
+
+// Draw the jobs as they come, skipping the ones a newer job has already
+// replaced, until the renderer goes away.
+#[cfg(not(target_arch = "wasm32"))]
+fn work(jobs: Receiver<Job>, finished: Sender<Finished>) {
+    while let Ok(job) = jobs.recv() {
+        let job = jobs.try_iter().last().unwrap_or(job);
+        if let Some(frame) = draw(job) {
+            if finished.send(frame).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// The frame of `job`, unless a newer one cancelled it.
+fn draw((generation, mut job): Job) -> Option<Finished> {
+    let mut frame = vec![0_u8; 4 * job.width as usize * job.height as usize];
+    job.draw(&mut frame);
+    (!job.cancel.is_cancelled()).then_some((generation, job, frame))
+}
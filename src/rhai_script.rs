@@ -0,0 +1,204 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use clap::ValueEnum;
+use log::info;
+use rhai::{Engine, EvalAltResult};
+
+use crate::animation;
+use crate::export;
+use crate::mandelbrot::{
+    check_exponent, check_iterations, check_scale, Coloring, Fractal, Mandelbrot, View,
+};
+use crate::palette::Palette;
+
+type Fallible = Result<(), Box<EvalAltResult>>;
+
+// The view the script moves and the frame it renders.
+struct Camera {
+    mandelbrot: Mandelbrot,
+    frame: Vec<u8>,
+    // whether the frame shows the current view
+    rendered: bool,
+}
+
+impl Camera {
+    fn set_view(&mut self, view: View) {
+        let max_round = self.mandelbrot.max_round_for(view.scale);
+        self.mandelbrot
+            .set_view(view.center_x, view.center_y, view.scale, max_round);
+        self.rendered = false;
+    }
+
+    fn render(&mut self) {
+        if !self.rendered {
+            self.mandelbrot.request_redraw();
+            self.mandelbrot.draw(&mut self.frame);
+            self.rendered = true;
+        }
+    }
+
+    fn save(&mut self, path: &Path) -> Fallible {
+        self.render();
+        let (width, height) = (self.mandelbrot.width, self.mandelbrot.height);
+        export::save_png(path, width, height, &self.frame)
+            .map_err(|e| format!("failed to save {}: {}", path.display(), e))?;
+        info!("saved {}", path.display());
+        Ok(())
+    }
+}
+
+fn value<T: ValueEnum>(kind: &str, name: &str) -> Result<T, Box<EvalAltResult>> {
+    T::from_str(name, true).map_err(|_| format!("unknown {}: {}", kind, name).into())
+}
+
+/// Run the Rhai script in the file at `path` on `mandelbrot`, the view of
+/// the window without the window. The script moves the camera and renders
+/// and saves frames with these functions, beside those of Rhai itself:
+///
+/// ```text
+/// center_x() center_y() scale() iterations()    the view
+/// set_center(X, Y)  set_scale(S)  zoom(FACTOR)  move it, FACTOR > 1 in
+/// set_iterations(N)  auto_iterations()          limit of the iterations
+/// set_palette(NAME)  set_fractal(NAME)          presets or palette files,
+/// set_coloring(NAME)  set_exponent(D)           --fractal, --coloring
+/// render()                                      draw the frame of the view
+/// save(FILE)                                    save it as PNG
+/// animate(X, Y, S, FRAMES, DIR)                 zoom to the view (X, Y, S)
+///                                               in FRAMES frames saved to
+///                                               DIR/frame_NNNNN.png
+/// ```
+///
+/// The numbers of the view are floats: `set_center(0.0, 0.0)`, not
+/// `set_center(0, 0)`.
+pub fn run_file(path: &Path, mut mandelbrot: Mandelbrot) -> Result<(), String> {
+    let script = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    mandelbrot.info = false;
+    let frame = vec![0_u8; 4 * mandelbrot.width as usize * mandelbrot.height as usize];
+    let camera = Rc::new(RefCell::new(Camera {
+        mandelbrot,
+        frame,
+        rendered: false,
+    }));
+
+    let mut engine = Engine::new();
+    let shared = camera.clone();
+    engine.register_fn("center_x", move || shared.borrow().mandelbrot.center_x);
+    let shared = camera.clone();
+    engine.register_fn("center_y", move || shared.borrow().mandelbrot.center_y);
+    let shared = camera.clone();
+    engine.register_fn("scale", move || shared.borrow().mandelbrot.scale);
+    let shared = camera.clone();
+    engine.register_fn("iterations", move || {
+        shared.borrow().mandelbrot.max_round as i64
+    });
+    let shared = camera.clone();
+    engine.register_fn("set_center", move |x: f64, y: f64| {
+        let mut camera = shared.borrow_mut();
+        let scale = camera.mandelbrot.scale;
+        camera.set_view(View {
+            center_x: x,
+            center_y: y,
+            scale,
+        });
+    });
+    let shared = camera.clone();
+    engine.register_fn("set_scale", move |scale: f64| -> Fallible {
+        let scale = check_scale(scale)?;
+        let mut camera = shared.borrow_mut();
+        let view = camera.mandelbrot.view();
+        camera.set_view(View { scale, ..view });
+        Ok(())
+    });
+    let shared = camera.clone();
+    engine.register_fn("zoom", move |factor: f64| -> Fallible {
+        let mut camera = shared.borrow_mut();
+        let view = camera.mandelbrot.view();
+        let scale = check_scale(view.scale / factor)?;
+        camera.set_view(View { scale, ..view });
+        Ok(())
+    });
+    let shared = camera.clone();
+    engine.register_fn("set_iterations", move |iterations: i64| -> Fallible {
+        let iterations = check_iterations(iterations.max(0) as usize)?;
+        let mut camera = shared.borrow_mut();
+        camera.mandelbrot.set_max_round(iterations);
+        camera.rendered = false;
+        Ok(())
+    });
+    let shared = camera.clone();
+    engine.register_fn("auto_iterations", move || {
+        let mut camera = shared.borrow_mut();
+        if !camera.mandelbrot.auto_iterations {
+            camera.mandelbrot.toggle_auto_iterations();
+        }
+        camera.rendered = false;
+    });
+    let shared = camera.clone();
+    engine.register_fn("set_palette", move |name: &str| -> Fallible {
+        let palette = Palette::from_name_or_path(name)
+            .map_err(|e| format!("failed to load the palette {}: {}", name, e))?;
+        let mut camera = shared.borrow_mut();
+        camera.mandelbrot.options.palette = palette;
+        camera.rendered = false;
+        Ok(())
+    });
+    let shared = camera.clone();
+    engine.register_fn("set_fractal", move |name: &str| -> Fallible {
+        let fractal: Fractal = value("fractal", name)?;
+        let mut camera = shared.borrow_mut();
+        camera.mandelbrot.options.fractal = fractal;
+        camera.rendered = false;
+        Ok(())
+    });
+    let shared = camera.clone();
+    engine.register_fn("set_coloring", move |name: &str| -> Fallible {
+        let coloring: Coloring = value("coloring", name)?;
+        let mut camera = shared.borrow_mut();
+        camera.mandelbrot.options.coloring = coloring;
+        camera.rendered = false;
+        Ok(())
+    });
+    let shared = camera.clone();
+    engine.register_fn("set_exponent", move |exponent: f64| -> Fallible {
+        let exponent = check_exponent(exponent)?;
+        let mut camera = shared.borrow_mut();
+        camera.mandelbrot.options.exponent = exponent;
+        camera.rendered = false;
+        Ok(())
+    });
+    let shared = camera.clone();
+    engine.register_fn("render", move || shared.borrow_mut().render());
+    let shared = camera.clone();
+    engine.register_fn("save", move |file: &str| -> Fallible {
+        shared.borrow_mut().save(Path::new(file))
+    });
+    let shared = camera.clone();
+    engine.register_fn(
+        "animate",
+        move |x: f64, y: f64, scale: f64, frames: i64, dir: &str| -> Fallible {
+            let scale = check_scale(scale)?;
+            let dir = Path::new(dir);
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+            let mut camera = shared.borrow_mut();
+            let end = View {
+                center_x: x,
+                center_y: y,
+                scale,
+            };
+            let path = animation::zoom_path(camera.mandelbrot.view(), end, frames.max(1) as usize);
+            for (number, view) in path.into_iter().enumerate() {
+                camera.set_view(view);
+                camera.save(&animation::frame_path(dir, number))?;
+            }
+            Ok(())
+        },
+    );
+
+    engine
+        .run(&script)
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,108 @@
+use crate::canvas::{Canvas, FOREGROUND, LINE_HEIGHT};
+use crate::mandelbrot::Mandelbrot;
+
+const MARGIN: usize = 5;
+// half the side of the squares marking the ends
+const MARKER: usize = 2;
+
+/// A segment between two points of the complex plane, measured in the
+/// units of the plane. The first click sets its start, the second its end,
+/// which follows the pointer in between; a third click starts a new one.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ruler {
+    start: Option<(f64, f64)>,
+    end: Option<(f64, f64)>,
+    fixed: bool,
+}
+
+impl Ruler {
+    pub fn click(&mut self, point: (f64, f64)) {
+        if self.start.is_none() || self.fixed {
+            *self = Self {
+                start: Some(point),
+                end: Some(point),
+                fixed: false,
+            };
+        } else {
+            self.end = Some(point);
+            self.fixed = true;
+        }
+    }
+
+    /// The start and the end of the segment once both are clicked.
+    pub fn segment(&self) -> Option<((f64, f64), (f64, f64))> {
+        match (self.start, self.end, self.fixed) {
+            (Some(start), Some(end), true) => Some((start, end)),
+            _ => None,
+        }
+    }
+
+    /// Move the end that is not fixed yet to the pointer. Returns whether it
+    /// moved.
+    pub fn follow(&mut self, point: (f64, f64)) -> bool {
+        if self.start.is_none() || self.fixed || self.end == Some(point) {
+            return false;
+        }
+        self.end = Some(point);
+        true
+    }
+
+    fn lines(&self, close_key: &str, export_key: &str) -> Vec<String> {
+        let (Some((x0, y0)), Some((x1, y1))) = (self.start, self.end) else {
+            return vec![
+                String::from("ruler: click the start"),
+                format!("({} to close)", close_key),
+            ];
+        };
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let mut lines = vec![
+            String::from("ruler"),
+            format!("from: {} {}", x0, y0),
+            format!("  to: {} {}", x1, y1),
+            format!("  dx: {}", dx),
+            format!("  dy: {}", dy),
+            format!("distance: {}", dx.hypot(dy)),
+            format!("angle: {:.4} deg", dy.atan2(dx).to_degrees()),
+        ];
+        if self.fixed {
+            lines.push(format!("({} to export the transect)", export_key));
+        }
+        lines.push(format!("({} to close)", close_key));
+        lines
+    }
+
+    /// Draw the segment over the view of `mandelbrot`, with the
+    /// measurements at the bottom-left corner.
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas,
+        mandelbrot: &Mandelbrot,
+        close_key: &str,
+        export_key: &str,
+    ) {
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            let start = mandelbrot.pixel_of(start.0, start.1);
+            let end = mandelbrot.pixel_of(end.0, end.1);
+            canvas.draw_line(start, end, FOREGROUND);
+            for (x, y) in [start, end] {
+                if x >= 0.0 && y >= 0.0 {
+                    let (x, y) = (x as usize, y as usize);
+                    canvas.stroke_rect(
+                        x.saturating_sub(MARKER),
+                        y.saturating_sub(MARKER),
+                        2 * MARKER + 1,
+                        2 * MARKER + 1,
+                        FOREGROUND,
+                    );
+                }
+            }
+        }
+        let lines = self.lines(close_key, export_key);
+        let top = canvas
+            .height
+            .saturating_sub(MARGIN + lines.len() * LINE_HEIGHT);
+        for (i, line) in lines.iter().enumerate() {
+            canvas.draw_text(MARGIN, top + i * LINE_HEIGHT, line, 1);
+        }
+    }
+}
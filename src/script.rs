@@ -0,0 +1,402 @@
+use std::path::Path;
+use std::time::Duration;
+
+use log::info;
+use winit::event::{TouchPhase, VirtualKeyCode};
+
+use crate::action::{Action, Bindings};
+use crate::click::LeftButton;
+use crate::controls::{self, Controls};
+use crate::data::{self, DataFormat};
+use crate::double_double::DoubleDouble;
+use crate::export;
+use crate::glide::ZoomGlide;
+use crate::mandelbrot::Mandelbrot;
+use crate::platform::Instant;
+use crate::touch::Touches;
+
+// relative difference of the numbers an `expect` takes as equal
+const TOLERANCE: f64 = 1e-9;
+// difference in pixels of the centers `expect precise-center` takes as equal
+const PIXEL_TOLERANCE: f64 = 1e-3;
+
+/// A window session driven by a script instead of the user: the keys and
+/// the left button and wheel of the mouse do what they do in the window,
+/// on a clock that only moves with `wait`. One command per line:
+///
+/// ```text
+/// key [shift+|alt+]KEY      press a key (names as in `bind` of the config)
+/// press|move|release X Y    left button at the frame pixel (X, Y)
+/// click X Y                 press and release
+/// touch ID PHASE X Y        finger ID of a touch screen at (X, Y), PHASE
+///                           start, move or end
+/// wheel STEPS [X Y]         scroll, positive to zoom in about (X, Y) or
+///                           the center; the zoom glides while time passes
+/// wait MS                   let MS milliseconds pass
+/// go X Y SCALE              go to the view, X and Y read to the precision
+///                           of double-double
+/// render                    draw the frame of the view if it changed
+/// export FILE               save the frame as shown in the window as PNG
+/// export-iterations FILE    save the escape iterations like the window, in
+///                           the format of --data
+/// print                     log the view
+/// expect center X Y | precise-center X Y | scale S | iterations N
+///        palette NAME | fractal NAME | coloring NAME | offset X Y
+///        pixel X Y R G B
+/// ```
+///
+/// Blank lines and lines starting with `#` are skipped.
+pub struct Session {
+    pub mandelbrot: Mandelbrot,
+    pub controls: Controls,
+    bindings: Bindings,
+    left_button: LeftButton,
+    touches: Touches,
+    zoom_glide: ZoomGlide,
+    held: bool,
+    now: Instant,
+    // the last frame drawn, before the drag offset
+    frame: Vec<u8>,
+    /// format of `export-iterations`
+    pub data_format: DataFormat,
+}
+
+impl Session {
+    pub fn new(
+        mandelbrot: Mandelbrot,
+        controls: Controls,
+        bindings: Bindings,
+        left_button: LeftButton,
+        touches: Touches,
+    ) -> Self {
+        let frame = vec![0_u8; 4 * mandelbrot.width as usize * mandelbrot.height as usize];
+        Self {
+            mandelbrot,
+            controls,
+            bindings,
+            left_button,
+            touches,
+            zoom_glide: ZoomGlide::default(),
+            held: false,
+            now: Instant::now(),
+            frame,
+            data_format: DataFormat::default(),
+        }
+    }
+
+    /// Run the script in the file at `path`, stopping at the first command
+    /// that fails.
+    pub fn run_file(&mut self, path: &Path) -> Result<(), String> {
+        let script = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        for (number, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.run_line(line)
+                .map_err(|e| format!("{}:{}: {}", path.display(), number + 1, e))?;
+        }
+        Ok(())
+    }
+
+    /// Run one command, as one step of the event loop.
+    pub fn run_line(&mut self, line: &str) -> Result<(), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = words.split_first().ok_or("empty command")?;
+        let mut actions = Vec::new();
+        let (mut shift, mut alt) = (false, false);
+        match *command {
+            "key" => {
+                let [name] = args else {
+                    return Err(String::from("usage: key [shift+|alt+]KEY"));
+                };
+                let mut name = *name;
+                loop {
+                    if let Some(rest) = name.strip_prefix("shift+") {
+                        shift = true;
+                        name = rest;
+                    } else if let Some(rest) = name.strip_prefix("alt+") {
+                        alt = true;
+                        name = rest;
+                    } else {
+                        break;
+                    }
+                }
+                let key: VirtualKeyCode = toml::Value::String(name.to_string())
+                    .try_into()
+                    .map_err(|_| format!("unknown key {}", name))?;
+                actions = self.bindings.actions_of(key);
+                if !actions
+                    .iter()
+                    .any(|action| controls::ACTIONS.contains(action))
+                {
+                    return Err(format!("{} does nothing in a script", name));
+                }
+            }
+            "press" => {
+                let position = position(args)?;
+                self.press(position);
+            }
+            "move" => {
+                let position = position(args)?;
+                if self.held {
+                    self.drag(position);
+                }
+            }
+            "release" => {
+                let position = position(args)?;
+                self.release(position);
+            }
+            "click" => {
+                let position = position(args)?;
+                self.press(position);
+                self.release(position);
+            }
+            "touch" => {
+                let [id, phase, x, y] = args else {
+                    return Err(String::from("usage: touch ID start|move|end X Y"));
+                };
+                let id = id.parse().map_err(|_| format!("not a finger id: {}", id))?;
+                let phase = match *phase {
+                    "start" => TouchPhase::Started,
+                    "move" => TouchPhase::Moved,
+                    "end" => TouchPhase::Ended,
+                    _ => return Err(format!("unknown touch phase {}", phase)),
+                };
+                let position = position(&[x, y])?;
+                if let Some(gesture) = self.touches.touch(id, phase, position, self.now) {
+                    actions.push(gesture.apply(&mut self.mandelbrot));
+                }
+            }
+            "wheel" => {
+                let (steps, anchor) = match args {
+                    [steps] => (
+                        steps,
+                        (
+                            self.mandelbrot.width as f64 / 2.0,
+                            self.mandelbrot.height as f64 / 2.0,
+                        ),
+                    ),
+                    [steps, x, y] => (steps, position(&[x, y])?),
+                    _ => return Err(String::from("usage: wheel STEPS [X Y]")),
+                };
+                self.zoom_glide.add(number(steps)?, anchor, self.now);
+                actions.push(Action::Scroll);
+            }
+            "wait" => {
+                let [ms] = args else {
+                    return Err(String::from("usage: wait MS"));
+                };
+                let ms = ms
+                    .parse()
+                    .map_err(|_| format!("not milliseconds: {}", ms))?;
+                self.now += Duration::from_millis(ms);
+                if self.zoom_glide.step(&mut self.mandelbrot, self.now) {
+                    self.mandelbrot.request_redraw();
+                }
+            }
+            "go" => {
+                let [x, y, scale] = args else {
+                    return Err(String::from("usage: go X Y SCALE"));
+                };
+                let scale = number(scale)?;
+                self.mandelbrot.go_to(precise(x)?, precise(y)?, scale)?;
+                self.mandelbrot.request_redraw();
+            }
+            "render" => self.render(),
+            "export" => {
+                let [file] = args else {
+                    return Err(String::from("usage: export FILE"));
+                };
+                self.render();
+                export::save_png(
+                    Path::new(file),
+                    self.mandelbrot.width,
+                    self.mandelbrot.height,
+                    &self.shown(),
+                )
+                .map_err(|e| format!("failed to save {}: {}", file, e))?;
+            }
+            "export-iterations" => {
+                let [file] = args else {
+                    return Err(String::from("usage: export-iterations FILE"));
+                };
+                self.render();
+                data::save(&self.mandelbrot, self.data_format, Path::new(file))
+                    .map_err(|e| format!("failed to save {}: {}", file, e))?;
+            }
+            "print" => {
+                let view = self.mandelbrot.view();
+                info!(
+                    "center ({}, {}), scale {}, iterations {}",
+                    view.center_x, view.center_y, view.scale, self.mandelbrot.max_round
+                );
+            }
+            "expect" => self.expect(args)?,
+            _ => return Err(format!("unknown command {}", command)),
+        }
+        self.controls
+            .apply(&mut self.mandelbrot, &actions, shift, alt);
+        Ok(())
+    }
+
+    fn press(&mut self, position: (f64, f64)) {
+        self.held = true;
+        if self.left_button.press(position, self.now) {
+            info!("double clicked");
+            self.mandelbrot.set_center(position.0, position.1);
+            self.mandelbrot.request_redraw();
+        }
+    }
+
+    fn drag(&mut self, position: (f64, f64)) {
+        let follow = self.mandelbrot.low_memory;
+        if let Some(step) = self.left_button.drag(position, follow) {
+            if follow {
+                self.mandelbrot.move_center(-step.0, step.1);
+                self.mandelbrot.request_redraw();
+            }
+        }
+    }
+
+    fn release(&mut self, position: (f64, f64)) {
+        self.held = false;
+        let follow = self.mandelbrot.low_memory;
+        if let Some(offset) = self.left_button.release(Some(position), follow) {
+            info!("drag: ({}, {})", -offset.0, offset.1);
+            self.mandelbrot.move_center(-offset.0, offset.1);
+            self.mandelbrot.request_redraw();
+        }
+    }
+
+    /// Draw the frame like the window, where the dragged image stays until
+    /// the frame of the new view replaces it.
+    fn render(&mut self) {
+        if let Some(mut job) = self.mandelbrot.take_job() {
+            job.draw(&mut self.frame);
+            self.mandelbrot.finish(job);
+            if !self.held {
+                self.left_button.frame_arrived();
+            }
+        }
+    }
+
+    // the frame with the image moved by the drag
+    fn shown(&self) -> Vec<u8> {
+        let offset = self.left_button.offset;
+        if offset == (0.0, 0.0) {
+            return self.frame.clone();
+        }
+        let mut shown = vec![0_u8; self.frame.len()];
+        crate::blit_shifted(
+            &mut shown,
+            &self.frame,
+            self.mandelbrot.width as usize,
+            self.mandelbrot.height as usize,
+            (offset.0 as isize, offset.1 as isize),
+        );
+        shown
+    }
+
+    fn expect(&self, args: &[&str]) -> Result<(), String> {
+        let (what, values) = args.split_first().ok_or("usage: expect WHAT VALUE...")?;
+        let mandelbrot = &self.mandelbrot;
+        let names = |expected: &str, actual: &str| {
+            if expected == actual {
+                Ok(())
+            } else {
+                Err(format!("expected {} {}, got {}", what, expected, actual))
+            }
+        };
+        match (*what, values) {
+            ("center", [x, y]) => numbers(
+                what,
+                &[number(x)?, number(y)?],
+                &[mandelbrot.center_x, mandelbrot.center_y],
+            ),
+            ("precise-center", [x, y]) => {
+                let (center_x, center_y) = mandelbrot.center();
+                let pixels = |expected: &str, actual: DoubleDouble| {
+                    precise(expected).map(|expected| (actual - expected).hi / mandelbrot.scale)
+                };
+                let (dx, dy) = (pixels(x, center_x)?, pixels(y, center_y)?);
+                if dx.abs() <= PIXEL_TOLERANCE && dy.abs() <= PIXEL_TOLERANCE {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "expected {} {} {}, got {} pixels off",
+                        what,
+                        x,
+                        y,
+                        dx.hypot(dy)
+                    ))
+                }
+            }
+            ("scale", [scale]) => numbers(what, &[number(scale)?], &[mandelbrot.scale]),
+            ("iterations", [iterations]) => {
+                numbers(what, &[number(iterations)?], &[mandelbrot.max_round as f64])
+            }
+            ("offset", [x, y]) => {
+                let offset = self.left_button.offset;
+                numbers(what, &[number(x)?, number(y)?], &[offset.0, offset.1])
+            }
+            ("pixel", [x, y, r, g, b]) => {
+                let (x, y) = (number(x)? as usize, number(y)? as usize);
+                if x >= mandelbrot.width as usize || y >= mandelbrot.height as usize {
+                    return Err(format!("pixel ({}, {}) is outside the frame", x, y));
+                }
+                let i = 4 * (y * mandelbrot.width as usize + x);
+                let color = &self.shown()[i..i + 3];
+                numbers(
+                    what,
+                    &[number(r)?, number(g)?, number(b)?],
+                    &color.iter().map(|&c| c as f64).collect::<Vec<_>>(),
+                )
+            }
+            ("palette", [name]) => names(name, &mandelbrot.options.palette.name),
+            ("fractal", [name]) => names(name, mandelbrot.options.fractal.name()),
+            ("coloring", [name]) => names(name, mandelbrot.options.coloring.name()),
+            _ => Err(format!("cannot expect {}", args.join(" "))),
+        }
+    }
+}
+
+fn number(word: &str) -> Result<f64, String> {
+    word.parse().map_err(|_| format!("not a number: {}", word))
+}
+
+fn precise(word: &str) -> Result<DoubleDouble, String> {
+    DoubleDouble::parse(word).ok_or_else(|| format!("not a number: {}", word))
+}
+
+fn position(args: &[&str]) -> Result<(f64, f64), String> {
+    match args {
+        [x, y] => Ok((number(x)?, number(y)?)),
+        _ => Err(String::from("expected a position X Y")),
+    }
+}
+
+fn numbers(what: &str, expected: &[f64], actual: &[f64]) -> Result<(), String> {
+    let close = expected
+        .iter()
+        .zip(actual)
+        .all(|(e, a)| (e - a).abs() <= TOLERANCE * e.abs().max(a.abs()).max(1.0));
+    if close {
+        Ok(())
+    } else {
+        let join = |values: &[f64]| {
+            values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        Err(format!(
+            "expected {} {}, got {}",
+            what,
+            join(expected),
+            join(actual)
+        ))
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use crate::export;
+use crate::mandelbrot::{Fractal, Mandelbrot};
+use crate::parallel::*;
+
+pub const SDF_FILE: &str = "sdf.png";
+// distance in pixels that maps to the ends of the 16-bit range
+const RANGE_PIXELS: f64 = 64.0;
+// "infinite" squared distance of the distance transform, kept finite so
+// that the parabola intersections stay finite too
+const FAR: f64 = 1e20;
+
+// Squared distance transform of one line (Felzenszwalb and Huttenlocher):
+// d[q] = min over p of (q - p)^2 + f[p].
+fn transform_line(f: &[f64]) -> Vec<f64> {
+    let n = f.len();
+    let mut d = vec![0.0; n];
+    // parabolas of the lower envelope and the boundaries between them
+    let mut v = vec![0_usize; n];
+    let mut z = vec![0.0; n + 1];
+    let intersection = |q: usize, p: usize| {
+        ((f[q] + (q * q) as f64) - (f[p] + (p * p) as f64)) / (2.0 * (q as f64 - p as f64))
+    };
+    let mut k = 0;
+    z[0] = f64::NEG_INFINITY;
+    z[1] = f64::INFINITY;
+    for q in 1..n {
+        let mut s = intersection(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersection(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = f64::INFINITY;
+    }
+    k = 0;
+    for (q, distance) in d.iter_mut().enumerate() {
+        while z[k + 1] < q as f64 {
+            k += 1;
+        }
+        let offset = q as f64 - v[k] as f64;
+        *distance = offset * offset + f[v[k]];
+    }
+    d
+}
+
+// Distance in pixels from every pixel to the nearest pixel where `seed` is
+// true.
+fn distance_transform(seed: &[bool], width: usize, height: usize) -> Vec<f64> {
+    let mut squared: Vec<f64> = seed.iter().map(|&s| if s { 0.0 } else { FAR }).collect();
+    for x in 0..width {
+        let column: Vec<f64> = (0..height).map(|y| squared[y * width + x]).collect();
+        for (y, value) in transform_line(&column).into_iter().enumerate() {
+            squared[y * width + x] = value;
+        }
+    }
+    squared
+        .par_chunks_mut(width)
+        .for_each(|row| row.copy_from_slice(&transform_line(row)));
+    squared.into_iter().map(f64::sqrt).collect()
+}
+
+/// Signed distance from every pixel of the view to the boundary of the
+/// set, in complex-plane units: positive outside, from the distance
+/// estimator, and negative inside, from the distance to the nearest
+/// exterior pixel.
+pub fn distance_field(mandelbrot: &Mandelbrot) -> Vec<f64> {
+    let width = mandelbrot.width as usize;
+    let height = mandelbrot.height as usize;
+    let (min_x, max_y) = mandelbrot.point_at(0.0, 0.0);
+    let exterior: Vec<Option<f64>> = install(|| {
+        (0..height)
+            .into_par_iter()
+            .flat_map_iter(|pixel_y| {
+                let y = max_y - pixel_y as f64 * mandelbrot.scale;
+                (0..width).map(move |pixel_x| {
+                    mandelbrot.distance_at(min_x + pixel_x as f64 * mandelbrot.scale, y)
+                })
+            })
+            .collect()
+    });
+
+    let seed: Vec<bool> = exterior.iter().map(Option::is_some).collect();
+    let inside = distance_transform(&seed, width, height);
+    exterior
+        .iter()
+        .zip(inside)
+        .map(|(distance, pixels)| match distance {
+            Some(distance) => *distance,
+            // the boundary lies halfway to the nearest exterior pixel
+            None => -(pixels - 0.5).max(0.0) * mandelbrot.scale,
+        })
+        .collect()
+}
+
+/// Write the signed distance field of the view as a 16-bit grayscale PNG:
+/// 32768 on the boundary, 0 and 65535 at RANGE_PIXELS pixels inside and
+/// outside. Text chunks give the view, the distance units and `note`
+/// unless it is empty.
+pub fn save(mandelbrot: &Mandelbrot, path: &Path, note: &str) -> Result<(), png::EncodingError> {
+    if mandelbrot.options.fractal != Fractal::Mandelbrot {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the distance estimator supports the mandelbrot formula only",
+        )
+        .into());
+    }
+    let range = RANGE_PIXELS * mandelbrot.scale;
+    let data: Vec<u8> = distance_field(mandelbrot)
+        .into_iter()
+        .flat_map(|distance| {
+            let value = (32768.0 + 32767.0 * distance / range).clamp(0.0, 65535.0);
+            (value.round() as u16).to_be_bytes()
+        })
+        .collect();
+
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), mandelbrot.width, mandelbrot.height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Sixteen);
+    let metadata = [
+        (
+            "Description",
+            String::from(
+                "signed distance to the boundary of the Mandelbrot set, \
+                 positive outside: distance = (value - 32768) / 32767 * distance_range",
+            ),
+        ),
+        ("distance_range", range.to_string()),
+        ("distance_range_pixels", RANGE_PIXELS.to_string()),
+        ("center_x", mandelbrot.center_x.to_string()),
+        ("center_y", mandelbrot.center_y.to_string()),
+        ("pixel_size", mandelbrot.scale.to_string()),
+        ("iterations", mandelbrot.max_round.to_string()),
+        ("exponent", mandelbrot.options.exponent.to_string()),
+    ];
+    for (keyword, text) in metadata {
+        encoder.add_text_chunk(keyword.to_string(), text)?;
+    }
+    if !note.is_empty() {
+        encoder.add_itxt_chunk(export::NOTE_KEYWORD.to_string(), note.to_string())?;
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    Ok(())
+}
@@ -0,0 +1,344 @@
+use log::{error, info};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::io::Cursor;
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::export;
+use crate::mandelbrot::{self, Fractal, Mandelbrot, Norm, View};
+use crate::palette::Palette;
+
+pub const MAX_IMAGE_SIZE: u32 = 4096;
+
+pub struct ServeOptions {
+    pub listen: String,
+    pub jobs: usize,
+    pub queue: usize,
+    pub tile_cache: usize,
+}
+
+// keeps a single request from occupying a worker for too long
+pub const MAX_ITERATIONS: usize = 1 << 20;
+
+/// Parameters of
+/// `GET /render?x=&y=&scale=&width=&height=&iterations=&palette=&fractal=&exponent=&norm=`.
+/// Omitted parameters take the same defaults as the command line; the
+/// palette is one of the presets.
+#[derive(Debug, PartialEq)]
+pub struct RenderRequest {
+    pub view: View,
+    pub width: u32,
+    pub height: u32,
+    pub max_round: Option<usize>,
+    pub palette: Palette,
+    pub fractal: Fractal,
+    pub exponent: f64,
+    pub norm: Norm,
+}
+
+impl Default for RenderRequest {
+    fn default() -> Self {
+        Self {
+            view: View::default(),
+            width: 640,
+            height: 480,
+            max_round: None,
+            palette: Palette::default(),
+            fractal: Fractal::default(),
+            exponent: mandelbrot::default_exponent(),
+            norm: Norm::default(),
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value of {}: {}", key, value))
+}
+
+impl RenderRequest {
+    pub fn from_query(query: &str) -> Result<Self, String> {
+        let mut request = Self::default();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key {
+                "x" => request.view.center_x = parse(key, value)?,
+                "y" => request.view.center_y = parse(key, value)?,
+                "scale" => request.view.scale = parse(key, value)?,
+                "width" => request.width = parse(key, value)?,
+                "height" => request.height = parse(key, value)?,
+                "iterations" => request.max_round = Some(parse(key, value)?),
+                "palette" => {
+                    request.palette = Palette::preset(value)
+                        .ok_or_else(|| format!("unknown palette: {}", value))?
+                }
+                "fractal" => {
+                    request.fractal = clap::ValueEnum::from_str(value, false)
+                        .map_err(|_| format!("unknown fractal: {}", value))?
+                }
+                "exponent" => request.exponent = parse(key, value)?,
+                "norm" => {
+                    request.norm = clap::ValueEnum::from_str(value, false)
+                        .map_err(|_| format!("unknown norm: {}", value))?
+                }
+                _ => return Err(format!("unknown parameter: {}", key)),
+            }
+        }
+
+        if !(request.view.center_x.is_finite() && request.view.center_y.is_finite()) {
+            return Err(format!(
+                "center must be finite: {}, {}",
+                request.view.center_x, request.view.center_y
+            ));
+        }
+        if !(request.view.scale.is_finite() && request.view.scale > 0.0) {
+            return Err(format!("scale must be positive: {}", request.view.scale));
+        }
+        for size in [request.width, request.height] {
+            if !(1..=MAX_IMAGE_SIZE).contains(&size) {
+                return Err(format!("image size must be 1 to {}", MAX_IMAGE_SIZE));
+            }
+        }
+        mandelbrot::check_exponent(request.exponent)?;
+        if let Some(max_round) = request.max_round {
+            if !(2..=MAX_ITERATIONS).contains(&max_round) {
+                return Err(format!("iterations must be 2 to {}", MAX_ITERATIONS));
+            }
+        }
+        Ok(request)
+    }
+
+    pub fn render(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut mandelbrot = Mandelbrot::new(self.width, self.height);
+        let max_round = self
+            .max_round
+            .unwrap_or_else(|| Mandelbrot::auto_max_round(self.view.scale));
+        mandelbrot.set_view(
+            self.view.center_x,
+            self.view.center_y,
+            self.view.scale,
+            max_round,
+        );
+        mandelbrot.options.palette = self.palette.clone();
+        mandelbrot.options.fractal = self.fractal;
+        mandelbrot.options.exponent = self.exponent;
+        mandelbrot.options.norm = self.norm;
+        mandelbrot.info = false;
+
+        let mut frame = vec![0_u8; 4 * self.width as usize * self.height as usize];
+        mandelbrot.draw(&mut frame);
+
+        let mut png = Vec::new();
+        export::write_png(&mut png, self.width, self.height, &frame)?;
+        Ok(png)
+    }
+}
+
+pub const TILE_SIZE: u32 = 256;
+pub const MAX_TILE_ZOOM: u32 = 40;
+// zoom level 0 is a single tile covering this square of the complex plane
+const TILE_ORIGIN_X: f64 = -2.75;
+const TILE_ORIGIN_Y: f64 = 2.0;
+const TILE_SPAN: f64 = 4.0;
+
+/// Web map tile address (`/{z}/{x}/{y}.png`), with y growing downward as in
+/// Leaflet and OpenLayers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TileKey {
+    pub z: u32,
+    pub x: u64,
+    pub y: u64,
+}
+
+impl TileKey {
+    /// Parse a tile path. Returns None for paths that are not tile requests.
+    pub fn from_path(path: &str) -> Option<Result<Self, String>> {
+        let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        let (z, x, y) = match parts.as_slice() {
+            [z, x, y] => (*z, *x, y.strip_suffix(".png")?),
+            _ => return None,
+        };
+        let key = (|| {
+            let key = Self {
+                z: parse("z", z)?,
+                x: parse("x", x)?,
+                y: parse("y", y)?,
+            };
+            if key.z > MAX_TILE_ZOOM {
+                return Err(format!("zoom level must be 0 to {}", MAX_TILE_ZOOM));
+            }
+            if key.x >= 1 << key.z || key.y >= 1 << key.z {
+                return Err(format!("tile out of range at zoom level {}", key.z));
+            }
+            Ok(key)
+        })();
+        Some(key)
+    }
+
+    pub fn render_request(&self) -> RenderRequest {
+        let span = TILE_SPAN / (1_u64 << self.z) as f64;
+        RenderRequest {
+            view: View {
+                center_x: TILE_ORIGIN_X + (self.x as f64 + 0.5) * span,
+                center_y: TILE_ORIGIN_Y - (self.y as f64 + 0.5) * span,
+                scale: span / TILE_SIZE as f64,
+            },
+            width: TILE_SIZE,
+            height: TILE_SIZE,
+            max_round: None,
+            palette: Palette::default(),
+            fractal: Fractal::default(),
+            exponent: mandelbrot::default_exponent(),
+            norm: Norm::default(),
+        }
+    }
+}
+
+/// Rendered tiles, dropping the oldest one when full.
+struct TileCache {
+    capacity: usize,
+    tiles: HashMap<TileKey, Arc<Vec<u8>>>,
+    order: VecDeque<TileKey>,
+}
+
+impl TileCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tiles: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn get(&self, key: &TileKey) -> Option<Arc<Vec<u8>>> {
+        self.tiles.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: TileKey, png: Arc<Vec<u8>>) {
+        if self.capacity == 0 || self.tiles.contains_key(&key) {
+            return;
+        }
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.tiles.remove(&oldest);
+            }
+        }
+        self.order.push_back(key);
+        self.tiles.insert(key, png);
+    }
+}
+
+fn png_response(png: &[u8]) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(png).with_header("Content-Type: image/png".parse::<Header>().unwrap())
+}
+
+fn text_response(status: u16, text: &str) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(text).with_status_code(status)
+}
+
+fn render_response(render_request: &RenderRequest) -> Result<Vec<u8>, Response<Cursor<Vec<u8>>>> {
+    render_request.render().map_err(|e| {
+        error!("render failed: {}", e);
+        text_response(500, "render failed")
+    })
+}
+
+fn tile_response(key: TileKey, cache: &Mutex<TileCache>) -> Response<Cursor<Vec<u8>>> {
+    // copy the tile into the response only after the lock is released
+    let cached = cache.lock().unwrap().get(&key);
+    if let Some(png) = cached {
+        return png_response(&png);
+    }
+    match render_response(&key.render_request()) {
+        Ok(png) => {
+            let png = Arc::new(png);
+            cache.lock().unwrap().insert(key, Arc::clone(&png));
+            png_response(&png)
+        }
+        Err(response) => response,
+    }
+}
+
+fn respond(request: Request, cache: &Mutex<TileCache>) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    let response = if path == "/render" {
+        match RenderRequest::from_query(query) {
+            Ok(render_request) => match render_response(&render_request) {
+                Ok(png) => png_response(&png),
+                Err(response) => response,
+            },
+            Err(message) => text_response(400, &message),
+        }
+    } else {
+        match TileKey::from_path(path) {
+            Some(Ok(key)) => tile_response(key, cache),
+            Some(Err(message)) => text_response(400, &message),
+            None => text_response(404, "not found"),
+        }
+    };
+
+    send(request, response);
+}
+
+fn send(request: Request, response: Response<Cursor<Vec<u8>>>) {
+    info!(
+        "{} {} {}",
+        request.method(),
+        request.url(),
+        response.status_code().0
+    );
+    if let Err(e) = request.respond(response) {
+        error!("failed to send the response: {}", e);
+    }
+}
+
+// Answer requests for cached tiles without waiting for a worker.
+fn cached_tile(request: &Request, cache: &Mutex<TileCache>) -> Option<Arc<Vec<u8>>> {
+    match TileKey::from_path(request.url()) {
+        Some(Ok(key)) => cache.lock().unwrap().get(&key),
+        _ => None,
+    }
+}
+
+/// Serve rendered PNGs over HTTP.
+///
+/// Up to `jobs` requests are rendered at the same time and up to `queue`
+/// more wait for a free worker; further requests are refused with 503.
+/// Map tiles are kept in a cache of `tile_cache` entries.
+pub fn run(options: &ServeOptions) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let server = Server::http(&options.listen)?;
+    println!("listening on http://{}", options.listen);
+
+    let (sender, receiver) = mpsc::sync_channel::<Request>(options.queue);
+    let receiver = Arc::new(Mutex::new(receiver));
+    let cache = Arc::new(Mutex::new(TileCache::new(options.tile_cache)));
+    for _ in 0..options.jobs {
+        let receiver = Arc::clone(&receiver);
+        let cache = Arc::clone(&cache);
+        thread::spawn(move || loop {
+            let request = match receiver.lock().unwrap().recv() {
+                Ok(request) => request,
+                Err(_) => break,
+            };
+            respond(request, &cache);
+        });
+    }
+
+    for request in server.incoming_requests() {
+        if let Some(png) = cached_tile(&request, &cache) {
+            send(request, png_response(&png));
+            continue;
+        }
+        if let Err(TrySendError::Full(request)) = sender.try_send(request) {
+            info!("queue is full, refusing {}", request.url());
+            send(request, text_response(503, "server busy"));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::double_double::{self, DoubleDouble};
+use crate::mandelbrot::{Fractal, Mandelbrot};
+
+/// What the window showed when it quit, restored when it starts again
+/// (unless `--fresh` is given):
+///
+/// ```toml
+/// x = -0.745
+/// y = 0.105
+/// scale = 0.00005
+/// iterations = 1024
+/// palette = "fire"
+/// fractal = "burning-ship"
+/// window_width = 1280
+/// window_height = 960
+/// ```
+///
+/// Views deeper than f64 also keep the low parts of their center in
+/// double-double, `x_lo` and `y_lo`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SavedSession {
+    pub x: f64,
+    pub y: f64,
+    #[serde(default, skip_serializing_if = "double_double::is_exact")]
+    pub x_lo: f64,
+    #[serde(default, skip_serializing_if = "double_double::is_exact")]
+    pub y_lo: f64,
+    pub scale: f64,
+    /// iteration limit of the manual mode, none in the automatic mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<usize>,
+    /// preset name or palette file
+    pub palette: String,
+    #[serde(default)]
+    pub fractal: Fractal,
+    /// logical size of the window, which may be larger than its frame
+    pub window_width: u32,
+    pub window_height: u32,
+}
+
+impl SavedSession {
+    /// `~/.config/mandelbrot/session.toml` or the equivalent of the
+    /// platform, next to the configuration file.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("mandelbrot").join("session.toml"))
+    }
+
+    pub fn of(mandelbrot: &Mandelbrot, palette: String, window_size: (u32, u32)) -> Self {
+        let (x, y) = mandelbrot.center();
+        Self {
+            x: x.hi,
+            y: y.hi,
+            x_lo: x.lo,
+            y_lo: y.lo,
+            scale: mandelbrot.scale,
+            iterations: (!mandelbrot.auto_iterations).then_some(mandelbrot.max_round),
+            palette,
+            fractal: mandelbrot.options.fractal,
+            window_width: window_size.0,
+            window_height: window_size.1,
+        }
+    }
+
+    /// Read the session file. A missing file is no session.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let session: Self =
+            toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if !(session.scale.is_finite() && session.scale > 0.0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "scale must be positive",
+            ));
+        }
+        if ![session.x, session.y, session.x_lo, session.y_lo]
+            .iter()
+            .all(|value| value.is_finite())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the center must be finite",
+            ));
+        }
+        Ok(Some(session))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents =
+            toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents)
+    }
+
+    /// Move `mandelbrot` to the view of the session, keeping its home.
+    pub fn apply_view(&self, mandelbrot: &mut Mandelbrot) {
+        let x = DoubleDouble::from(self.x) + self.x_lo;
+        let y = DoubleDouble::from(self.y) + self.y_lo;
+        match self.iterations {
+            Some(iterations) => {
+                mandelbrot.set_precise_view(x, y, self.scale, mandelbrot.max_round);
+                mandelbrot.set_max_round(iterations);
+            }
+            None => {
+                mandelbrot.auto_iterations = true;
+                let max_round = mandelbrot.max_round_for(self.scale);
+                mandelbrot.set_precise_view(x, y, self.scale, max_round);
+            }
+        }
+    }
+}
@@ -0,0 +1,646 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use egui::epaint::{ClippedShape, ImageData, Primitive, Vertex};
+use egui::{Color32, ComboBox, Context, Pos2, RawInput, Rect, TextureId};
+use pixels::Pixels;
+use winit::event::VirtualKeyCode;
+use winit_input_helper::{TextChar, WinitInputHelper};
+
+use crate::action::Action;
+use crate::bookmark::Bookmarks;
+use crate::canvas::Canvas;
+use crate::coords;
+use crate::double_double::DoubleDouble;
+use crate::mandelbrot::{self, check_scale, Antialias, Coloring, Fractal, Interior, Mandelbrot};
+use crate::palette::{Blend, Palette};
+use crate::palette_editor::PaletteEditor;
+use crate::platform::Instant;
+
+// where the panel first opens, in frame pixels
+const PANEL_POSITION: [f32; 2] = [10.0, 10.0];
+// bookmarks listed at most under the search field
+const MAX_SEARCH_RESULTS: usize = 8;
+
+// Keys passed to the text fields besides the typed characters.
+const EDITING_KEYS: [(VirtualKeyCode, egui::Key); 10] = [
+    (VirtualKeyCode::Back, egui::Key::Backspace),
+    (VirtualKeyCode::Delete, egui::Key::Delete),
+    (VirtualKeyCode::Left, egui::Key::ArrowLeft),
+    (VirtualKeyCode::Right, egui::Key::ArrowRight),
+    (VirtualKeyCode::Up, egui::Key::ArrowUp),
+    (VirtualKeyCode::Down, egui::Key::ArrowDown),
+    (VirtualKeyCode::Home, egui::Key::Home),
+    (VirtualKeyCode::End, egui::Key::End),
+    (VirtualKeyCode::Return, egui::Key::Enter),
+    (VirtualKeyCode::Escape, egui::Key::Escape),
+];
+
+// Buttons of the panel and the actions they trigger.
+const EXPORT_BUTTONS: [(&str, Action); 10] = [
+    ("Print view", Action::Dump),
+    ("Copy view", Action::CopyView),
+    ("Save bookmark", Action::SaveBookmark),
+    ("Export animation", Action::ExportAnimation),
+    ("Add keyframe", Action::AddKeyframe),
+    ("Export keyframes", Action::ExportKeyframes),
+    ("Export distance field", Action::ExportDistanceField),
+    ("Export iterations", Action::ExportIterations),
+    ("Export transect", Action::ExportTransect),
+    ("Export image", Action::ExportImage),
+];
+
+/// The values the panel edits, taken from the view before each step and
+/// applied back when they changed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub max_round: usize,
+    pub auto_iterations: bool,
+    pub palette: usize,
+    pub fractal: Fractal,
+    pub antialias: Antialias,
+    pub coloring: Coloring,
+    pub interior: Interior,
+    pub auto_exposure: bool,
+    pub blend: Blend,
+    pub subdivision: bool,
+    pub adaptive_iterations: bool,
+    pub iteration_tuning: bool,
+}
+
+impl Settings {
+    pub fn of(mandelbrot: &Mandelbrot, palette: usize) -> Self {
+        Self {
+            max_round: mandelbrot.max_round,
+            auto_iterations: mandelbrot.auto_iterations,
+            palette,
+            fractal: mandelbrot.options.fractal,
+            antialias: mandelbrot.options.antialias,
+            coloring: mandelbrot.options.coloring,
+            interior: mandelbrot.options.interior,
+            auto_exposure: mandelbrot.auto_exposure(),
+            blend: mandelbrot.options.blend,
+            subdivision: mandelbrot.subdivision,
+            adaptive_iterations: mandelbrot.adaptive_iterations,
+            iteration_tuning: mandelbrot.iteration_tuning,
+        }
+    }
+
+    pub fn apply(&self, mandelbrot: &mut Mandelbrot, palettes: &[Palette]) {
+        if self.auto_iterations != mandelbrot.auto_iterations {
+            mandelbrot.toggle_auto_iterations();
+        } else if self.max_round != mandelbrot.max_round {
+            mandelbrot.set_max_round(self.max_round);
+        }
+        mandelbrot.options.palette = palettes[self.palette].clone();
+        mandelbrot.options.fractal = self.fractal;
+        mandelbrot.options.antialias = self.antialias;
+        mandelbrot.options.coloring = self.coloring;
+        mandelbrot.options.interior = self.interior;
+        if self.auto_exposure != mandelbrot.auto_exposure() {
+            mandelbrot.set_auto_exposure(self.auto_exposure);
+        }
+        mandelbrot.options.blend = self.blend;
+        mandelbrot.subdivision = self.subdivision;
+        mandelbrot.adaptive_iterations = self.adaptive_iterations;
+        mandelbrot.iteration_tuning = self.iteration_tuning;
+    }
+}
+
+// An egui texture in the memory of the CPU, premultiplied like the colors
+// of the vertices.
+struct Texture {
+    width: usize,
+    height: usize,
+    texels: Vec<Color32>,
+}
+
+impl Texture {
+    fn sample(&self, uv: Pos2) -> Color32 {
+        let x = ((uv.x * self.width as f32) as usize).min(self.width - 1);
+        let y = ((uv.y * self.height as f32) as usize).min(self.height - 1);
+        self.texels[x + y * self.width]
+    }
+}
+
+/// Settings panel drawn with egui over the image. The frame has no GPU
+/// pipeline of its own for egui, so the triangles egui produces are filled
+/// into the frame on the CPU, like the rest of the overlays. One point of
+/// egui is one frame pixel.
+pub struct SettingsPanel {
+    pub visible: bool,
+    /// note of the view, given to new bookmarks and exported images
+    pub note: String,
+    context: Context,
+    textures: HashMap<TextureId, Texture>,
+    shapes: Vec<ClippedShape>,
+    pointer: Option<Pos2>,
+    start: Instant,
+    // text of the bookmark search field
+    search: String,
+    go_to: GoToFields,
+    palette_editor: PaletteEditor,
+}
+
+// Text of the fields of a view to go to, and why the last one typed could
+// not be gone to.
+#[derive(Default)]
+struct GoToFields {
+    x: String,
+    y: String,
+    scale: String,
+    error: Option<String>,
+}
+
+/// A view typed into the panel, with its center to the precision of
+/// double-double so that published coordinates of deep zooms come out
+/// right, and its scale unless the field was left empty.
+#[derive(Clone, Copy, Debug)]
+pub struct Destination {
+    pub x: DoubleDouble,
+    pub y: DoubleDouble,
+    pub scale: Option<f64>,
+}
+
+impl GoToFields {
+    fn destination(&self) -> Result<Destination, String> {
+        let coordinate = |name: &str, text: &str| {
+            DoubleDouble::parse(text).ok_or_else(|| format!("{} is not a number", name))
+        };
+        let scale = match self.scale.trim() {
+            "" => None,
+            text => match text.parse::<f64>().map(check_scale) {
+                Ok(Ok(scale)) => Some(scale),
+                _ => return Err(String::from("scale is not a positive number")),
+            },
+        };
+        Ok(Destination {
+            x: coordinate("x", &self.x)?,
+            y: coordinate("y", &self.y)?,
+            scale,
+        })
+    }
+}
+
+/// What a step of the panel did besides editing the settings.
+pub struct PanelOutput {
+    /// actions of the buttons clicked
+    pub actions: Vec<Action>,
+    /// whether the panel looks different from the last step
+    pub changed: bool,
+    /// whether the note of a bookmark was edited
+    pub bookmarks_edited: bool,
+    /// view typed into the go-to fields
+    pub destination: Option<Destination>,
+    /// palette of the edited gradient stops, when they changed
+    pub palette: Option<Palette>,
+}
+
+impl Default for SettingsPanel {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            note: String::new(),
+            context: Context::default(),
+            textures: HashMap::new(),
+            shapes: Vec::new(),
+            pointer: None,
+            start: Instant::now(),
+            search: String::new(),
+            go_to: GoToFields::default(),
+            palette_editor: PaletteEditor::default(),
+        }
+    }
+}
+
+impl SettingsPanel {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Whether the pointer is over the panel or dragging one of its
+    /// widgets, so that the view must not react to it.
+    pub fn has_pointer(&self) -> bool {
+        self.visible && self.context.wants_pointer_input()
+    }
+
+    /// Whether a text field of the panel has the keyboard, so that the
+    /// keys must not trigger their actions.
+    pub fn has_keyboard(&self) -> bool {
+        self.visible && self.context.wants_keyboard_input()
+    }
+
+    /// Run the panel on the input of the step, editing `settings` and the
+    /// notes of `bookmarks`.
+    pub fn update(
+        &mut self,
+        pixels: &Pixels,
+        input: &WinitInputHelper,
+        settings: &mut Settings,
+        palettes: &[Palette],
+        mandelbrot: &Mandelbrot,
+        bookmarks: &mut Bookmarks,
+    ) -> PanelOutput {
+        if !self.visible {
+            let hidden = !self.shapes.is_empty();
+            self.shapes.clear();
+            return PanelOutput {
+                actions: Vec::new(),
+                changed: hidden,
+                bookmarks_edited: false,
+                destination: None,
+                palette: None,
+            };
+        }
+
+        let texture = pixels.context().texture_extent;
+        let screen = Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(texture.width as f32, texture.height as f32),
+        );
+        let modifiers = egui::Modifiers {
+            shift: input.held_shift(),
+            ctrl: input.held_control(),
+            command: input.held_control(),
+            ..egui::Modifiers::default()
+        };
+        let mut raw_input = RawInput {
+            screen_rect: Some(screen),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            modifiers,
+            ..RawInput::default()
+        };
+        let pointer = coords::pointer(pixels, input).map(|(x, y)| Pos2::new(x as f32, y as f32));
+        match pointer {
+            Some(pos) if self.pointer != pointer => {
+                raw_input.events.push(egui::Event::PointerMoved(pos))
+            }
+            None if self.pointer.is_some() => raw_input.events.push(egui::Event::PointerGone),
+            _ => {}
+        }
+        self.pointer = pointer;
+        if let Some(pos) = pointer {
+            for (button, index) in [
+                (egui::PointerButton::Primary, 0),
+                (egui::PointerButton::Secondary, 1),
+            ] {
+                for pressed in [true, false] {
+                    let changed = if pressed {
+                        input.mouse_pressed(index)
+                    } else {
+                        input.mouse_released(index)
+                    };
+                    if changed {
+                        raw_input.events.push(egui::Event::PointerButton {
+                            pos,
+                            button,
+                            pressed,
+                            modifiers,
+                        });
+                    }
+                }
+            }
+        }
+        let text: String = input
+            .text()
+            .into_iter()
+            .filter_map(|text| match text {
+                TextChar::Char(c) if !c.is_control() => Some(c),
+                _ => None,
+            })
+            .collect();
+        if !text.is_empty() {
+            raw_input.events.push(egui::Event::Text(text));
+        }
+        for (key, egui_key) in EDITING_KEYS {
+            if input.key_pressed(key) {
+                raw_input.events.push(egui::Event::Key {
+                    key: egui_key,
+                    physical_key: None,
+                    pressed: true,
+                    repeat: false,
+                    modifiers,
+                });
+            }
+        }
+
+        let mut actions = Vec::new();
+        let mut bookmarks_edited = false;
+        let mut visible = self.visible;
+        let mut destination = None;
+        let mut palette = None;
+        let search = &mut self.search;
+        let note = &mut self.note;
+        let go_to = &mut self.go_to;
+        let palette_editor = &mut self.palette_editor;
+        let output = self.context.run(raw_input, |context| {
+            egui::Window::new("Settings")
+                .open(&mut visible)
+                .default_pos(PANEL_POSITION)
+                .resizable(false)
+                .show(context, |ui| {
+                    show_settings(ui, settings, palettes, mandelbrot);
+                    ui.collapsing("palette editor", |ui| {
+                        palette = palette_editor.show(ui, &mandelbrot.options.palette);
+                    });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("note");
+                        ui.text_edit_singleline(note);
+                    });
+                    ui.separator();
+                    for (label, action) in EXPORT_BUTTONS {
+                        if ui.button(label).clicked() {
+                            actions.push(action);
+                        }
+                    }
+                    ui.separator();
+                    destination = show_go_to(ui, go_to, mandelbrot);
+                    ui.separator();
+                    bookmarks_edited = show_bookmarks(ui, search, bookmarks, &mut actions);
+                });
+        });
+        self.visible = visible;
+
+        for (id, delta) in output.textures_delta.set {
+            let ImageData::Color(image) = delta.image;
+            let [width, height] = image.size;
+            match delta.pos {
+                None => {
+                    self.textures.insert(
+                        id,
+                        Texture {
+                            width,
+                            height,
+                            texels: image.pixels.clone(),
+                        },
+                    );
+                }
+                Some([x, y]) => {
+                    let Some(texture) = self.textures.get_mut(&id) else {
+                        continue;
+                    };
+                    for (row, texels) in image.pixels.chunks_exact(width).enumerate() {
+                        let start = x + (y + row) * texture.width;
+                        texture.texels[start..start + width].copy_from_slice(texels);
+                    }
+                }
+            }
+        }
+        for id in output.textures_delta.free {
+            self.textures.remove(&id);
+        }
+
+        let changed = output.shapes != self.shapes;
+        self.shapes = output.shapes;
+        PanelOutput {
+            actions,
+            changed,
+            bookmarks_edited,
+            destination,
+            palette,
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas) {
+        if self.shapes.is_empty() {
+            return;
+        }
+        let primitives = self.context.tessellate(self.shapes.clone(), 1.0);
+        for primitive in primitives {
+            let Primitive::Mesh(mesh) = primitive.primitive else {
+                continue;
+            };
+            let Some(texture) = self.textures.get(&mesh.texture_id) else {
+                continue;
+            };
+            let clip = primitive.clip_rect;
+            for triangle in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+                fill_triangle(canvas, texture, clip, a, b, c);
+            }
+        }
+    }
+}
+
+fn show_settings(
+    ui: &mut egui::Ui,
+    settings: &mut Settings,
+    palettes: &[Palette],
+    mandelbrot: &Mandelbrot,
+) {
+    ui.add(
+        egui::Slider::new(
+            &mut settings.max_round,
+            mandelbrot::MIN_ITERATIONS..=mandelbrot::MAX_ITERATIONS,
+        )
+        .logarithmic(true)
+        .text("iterations"),
+    );
+    ui.checkbox(&mut settings.auto_iterations, "automatic iterations");
+
+    ComboBox::from_label("palette")
+        .selected_text(&palettes[settings.palette].name)
+        .show_ui(ui, |ui| {
+            for (index, palette) in palettes.iter().enumerate() {
+                ui.selectable_value(&mut settings.palette, index, &palette.name);
+            }
+        });
+    ComboBox::from_label("fractal")
+        .selected_text(settings.fractal.name())
+        .show_ui(ui, |ui| {
+            for fractal in Fractal::value_variants() {
+                ui.selectable_value(&mut settings.fractal, *fractal, fractal.name());
+            }
+        });
+    ComboBox::from_label("coloring")
+        .selected_text(settings.coloring.name())
+        .show_ui(ui, |ui| {
+            for coloring in Coloring::value_variants() {
+                ui.selectable_value(&mut settings.coloring, *coloring, coloring.name());
+            }
+        });
+    ComboBox::from_label("interior")
+        .selected_text(settings.interior.name())
+        .show_ui(ui, |ui| {
+            for interior in Interior::value_variants() {
+                ui.selectable_value(&mut settings.interior, *interior, interior.name());
+            }
+        });
+    ui.checkbox(&mut settings.auto_exposure, "auto exposure");
+    ComboBox::from_label("antialias")
+        .selected_text(settings.antialias.name())
+        .show_ui(ui, |ui| {
+            for antialias in Antialias::value_variants() {
+                ui.selectable_value(&mut settings.antialias, *antialias, antialias.name());
+            }
+        });
+    let mut linear = settings.blend == Blend::Linear;
+    if ui.checkbox(&mut linear, "blend in linear light").changed() {
+        settings.blend = if linear { Blend::Linear } else { Blend::Srgb };
+    }
+
+    // the coordinates are f64 throughout: the panel tells how far that goes
+    ui.separator();
+    ui.label(mandelbrot.remaining_zoom_message());
+    ui.checkbox(&mut settings.subdivision, "rectangle subdivision");
+    ui.checkbox(
+        &mut settings.adaptive_iterations,
+        "adaptive iteration limits",
+    );
+    ui.checkbox(
+        &mut settings.iteration_tuning,
+        "raise the auto limit for late escapes",
+    );
+}
+
+// Fields for the center and the scale of a view to go to, with the current
+// ones as hints. Returns the view once "Go to" is clicked or Enter pressed
+// in a field, unless it does not parse.
+fn show_go_to(
+    ui: &mut egui::Ui,
+    fields: &mut GoToFields,
+    mandelbrot: &Mandelbrot,
+) -> Option<Destination> {
+    let mut submitted = false;
+    let view = mandelbrot.view();
+    for (label, text, hint) in [
+        ("x", &mut fields.x, view.center_x),
+        ("y", &mut fields.y, view.center_y),
+        ("scale", &mut fields.scale, view.scale),
+    ] {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            let field = egui::TextEdit::singleline(text).hint_text(hint.to_string());
+            let response = ui.add(field);
+            submitted |=
+                response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+        });
+    }
+    submitted |= ui.button("Go to").clicked();
+    if let Some(error) = &fields.error {
+        ui.label(error);
+    }
+    if !submitted {
+        return None;
+    }
+    match fields.destination() {
+        Ok(destination) => {
+            fields.error = None;
+            Some(destination)
+        }
+        Err(error) => {
+            fields.error = Some(error);
+            None
+        }
+    }
+}
+
+// The bookmarks whose name or note contains the search text, with a button
+// to go to each and its note to edit. Returns whether a note was edited.
+fn show_bookmarks(
+    ui: &mut egui::Ui,
+    search: &mut String,
+    bookmarks: &mut Bookmarks,
+    actions: &mut Vec<Action>,
+) -> bool {
+    let mut edited = false;
+    ui.horizontal(|ui| {
+        ui.label("search bookmarks");
+        ui.text_edit_singleline(search);
+    });
+    for index in bookmarks
+        .search(search)
+        .into_iter()
+        .take(MAX_SEARCH_RESULTS)
+    {
+        let bookmark = &mut bookmarks.bookmark[index];
+        ui.horizontal(|ui| {
+            if ui.button(&bookmark.name).clicked() {
+                actions.push(Action::JumpBookmark(index));
+            }
+            edited |= ui.text_edit_singleline(&mut bookmark.note).changed();
+        });
+    }
+    edited
+}
+
+// Twice the signed area of (a, b, p), positive when p is on the left of
+// a -> b with y pointing down.
+fn edge(a: Pos2, b: Pos2, p: Pos2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// Pixels whose center lies exactly on an edge belong to only one of the
+// two triangles sharing the edge, which run along it in opposite
+// directions, so that translucent shapes are not blended twice there.
+fn owns_edge(a: Pos2, b: Pos2) -> bool {
+    b.y > a.y || (b.y == a.y && b.x < a.x)
+}
+
+// Fill the triangle into the canvas, interpolating the colors and texture
+// coordinates of the vertices at the center of each pixel and blending the
+// premultiplied result over the image.
+fn fill_triangle(
+    canvas: &mut Canvas,
+    texture: &Texture,
+    clip: Rect,
+    a: Vertex,
+    b: Vertex,
+    c: Vertex,
+) {
+    let (b, c) = if edge(a.pos, b.pos, c.pos) < 0.0 {
+        (c, b)
+    } else {
+        (b, c)
+    };
+    let area = edge(a.pos, b.pos, c.pos);
+    if area <= 0.0 {
+        return;
+    }
+    let min_x = a.pos.x.min(b.pos.x).min(c.pos.x).max(clip.min.x).max(0.0);
+    let min_y = a.pos.y.min(b.pos.y).min(c.pos.y).max(clip.min.y).max(0.0);
+    let max_x = a
+        .pos
+        .x
+        .max(b.pos.x)
+        .max(c.pos.x)
+        .min(clip.max.x)
+        .min(canvas.width as f32);
+    let max_y = a
+        .pos
+        .y
+        .max(b.pos.y)
+        .max(c.pos.y)
+        .min(clip.max.y)
+        .min(canvas.height as f32);
+    if min_x >= max_x || min_y >= max_y {
+        return;
+    }
+
+    let edges = [(b.pos, c.pos), (c.pos, a.pos), (a.pos, b.pos)];
+    for py in min_y as usize..max_y.ceil() as usize {
+        for px in min_x as usize..max_x.ceil() as usize {
+            let p = Pos2::new(px as f32 + 0.5, py as f32 + 0.5);
+            let weights = edges.map(|(from, to)| edge(from, to, p));
+            let inside = weights.iter().zip(edges).all(|(weight, (from, to))| {
+                *weight > 0.0 || (*weight == 0.0 && owns_edge(from, to))
+            });
+            if !inside || !clip.contains(p) {
+                continue;
+            }
+            let [wa, wb, wc] = weights.map(|weight| weight / area);
+            let uv = Pos2::new(
+                wa * a.uv.x + wb * b.uv.x + wc * c.uv.x,
+                wa * a.uv.y + wb * b.uv.y + wc * c.uv.y,
+            );
+            let texel = texture.sample(uv).to_array();
+            let [ca, cb, cc] = [a, b, c].map(|vertex| vertex.color.to_array());
+            let rgba = [0, 1, 2, 3].map(|i| {
+                let color = wa * ca[i] as f32 + wb * cb[i] as f32 + wc * cc[i] as f32;
+                color / 255.0 * texel[i] as f32 / 255.0
+            });
+            canvas.blend_premultiplied(px, py, rgba);
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use log::info;
+use std::sync::OnceLock;
+use std::time::Duration;
+use wide::{f64x4, CmpGe, CmpLt};
+
+use crate::mandelbrot::{Fractal, PERIODICITY_EPSILON};
+use crate::platform::Instant;
+
+pub const LANES: usize = 4;
+
+// Iterations between the escape checks of the kernels the autotuner picks
+// from.
+const UNROLL_FACTORS: [usize; 4] = [1, 2, 4, 8];
+// Timing rounds of each factor in the autotuner.
+const TUNING_ROUNDS: usize = 3;
+
+/// Name of the kernels, the instruction set they run with on this CPU and
+/// their unroll factor, for the information display. The unroll factor is
+/// only known once a frame has needed it.
+pub fn kernel_name() -> String {
+    match UNROLL_FACTOR.get() {
+        Some(factor) => format!("simd x{} {} (unroll {})", LANES, isa().name(), factor),
+        None => format!("simd x{} {}", LANES, isa().name()),
+    }
+}
+
+/// Instruction sets the kernels are compiled for, beyond the one of the
+/// build target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Isa {
+    Baseline,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    #[cfg(target_arch = "x86_64")]
+    Avx512,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+}
+
+impl Isa {
+    fn name(self) -> &'static str {
+        match self {
+            Isa::Baseline if cfg!(target_arch = "x86_64") => "sse2",
+            Isa::Baseline => "generic",
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => "avx2",
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx512 => "avx512f",
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => "neon",
+        }
+    }
+
+    fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if std::arch::is_x86_feature_detected!("avx512f") {
+                return Isa::Avx512;
+            }
+            if std::arch::is_x86_feature_detected!("avx2") {
+                return Isa::Avx2;
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                return Isa::Neon;
+            }
+        }
+        Isa::Baseline
+    }
+}
+
+static ISA: OnceLock<Isa> = OnceLock::new();
+
+// Best instruction set of this CPU that the kernels are compiled for,
+// detected on first use.
+fn isa() -> Isa {
+    *ISA.get_or_init(|| {
+        let isa = Isa::detect();
+        info!("kernel instruction set: {}", isa.name());
+        isa
+    })
+}
+
+// z^2 + c of four points after the fractal's folding, also returning the
+// squares of the new coordinates for the escape test.
+#[inline(always)]
+fn step(
+    fractal: Fractal,
+    (x, y, x_power, y_power): (f64x4, f64x4, f64x4, f64x4),
+    cx: f64x4,
+    cy: f64x4,
+) -> (f64x4, f64x4, f64x4, f64x4) {
+    let two = f64x4::splat(2.0);
+    let xn = x_power - y_power + cx;
+    let yn = match fractal {
+        Fractal::Mandelbrot => two * x * y + cy,
+        Fractal::BurningShip => (two * x * y).abs() + cy,
+        Fractal::Tricorn => -two * x * y + cy,
+        Fractal::Newton => unreachable!("Newton's method has no escape kernel"),
+    };
+    (xn, yn, xn * xn, yn * yn)
+}
+
+// Lanes that start at or beyond 2 escape at the first iteration.
+#[inline(always)]
+fn initial_result(pos_x: [f64; LANES], pos_y: [f64; LANES]) -> ([Option<usize>; LANES], i32) {
+    let mut result = [None; LANES];
+    let mut active: i32 = 0;
+    for lane in 0..LANES {
+        if pos_x[lane] >= 2.0 || pos_y[lane] >= 2.0 {
+            result[lane] = Some(1);
+        } else {
+            active |= 1 << lane;
+        }
+    }
+    (result, active)
+}
+
+/// `Mandelbrot::check_divergence` for four points at once.
+///
+/// The lanes keep iterating until all of them have escaped or become
+/// periodic, so the arithmetic is done in the same order as the scalar
+/// version and both produce the same iteration counts. Without the
+/// periodicity check, the unrolled kernel chosen by the autotuner runs
+/// instead.
+pub fn check_divergence_x4(
+    pos_x: [f64; LANES],
+    pos_y: [f64; LANES],
+    max_round: usize,
+    periodicity: bool,
+    fractal: Fractal,
+) -> [Option<usize>; LANES] {
+    if !periodicity {
+        return match unroll_factor() {
+            8 => check_divergence_x4_unrolled::<8>(pos_x, pos_y, max_round, fractal),
+            4 => check_divergence_x4_unrolled::<4>(pos_x, pos_y, max_round, fractal),
+            2 => check_divergence_x4_unrolled::<2>(pos_x, pos_y, max_round, fractal),
+            _ => check_divergence_x4_unrolled::<1>(pos_x, pos_y, max_round, fractal),
+        };
+    }
+    // SAFETY: isa() only picks an instruction set the CPU supports
+    match isa() {
+        Isa::Baseline => periodic(pos_x, pos_y, max_round, fractal),
+        #[cfg(target_arch = "x86_64")]
+        Isa::Avx2 => unsafe { periodic_avx2(pos_x, pos_y, max_round, fractal) },
+        #[cfg(target_arch = "x86_64")]
+        Isa::Avx512 => unsafe { periodic_avx512(pos_x, pos_y, max_round, fractal) },
+        #[cfg(target_arch = "aarch64")]
+        Isa::Neon => unsafe { periodic_neon(pos_x, pos_y, max_round, fractal) },
+    }
+}
+
+/// `check_divergence_x4` without the periodicity check, testing for escape
+/// only every `K` iterations. A block in which some lane escaped is replayed one
+/// iteration at a time, so the iteration counts stay exact: once |z|
+/// reaches 2 it keeps growing, and the overflow to NaN also fails the
+/// `< 4` test at the end of the block.
+pub fn check_divergence_x4_unrolled<const K: usize>(
+    pos_x: [f64; LANES],
+    pos_y: [f64; LANES],
+    max_round: usize,
+    fractal: Fractal,
+) -> [Option<usize>; LANES] {
+    // SAFETY: isa() only picks an instruction set the CPU supports
+    match isa() {
+        Isa::Baseline => unrolled::<K>(pos_x, pos_y, max_round, fractal),
+        #[cfg(target_arch = "x86_64")]
+        Isa::Avx2 => unsafe { unrolled_avx2::<K>(pos_x, pos_y, max_round, fractal) },
+        #[cfg(target_arch = "x86_64")]
+        Isa::Avx512 => unsafe { unrolled_avx512::<K>(pos_x, pos_y, max_round, fractal) },
+        #[cfg(target_arch = "aarch64")]
+        Isa::Neon => unsafe { unrolled_neon::<K>(pos_x, pos_y, max_round, fractal) },
+    }
+}
+
+// The kernels inlined into functions compiled for each instruction set, so
+// that the `wide` operations use its wider registers and instructions.
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn periodic_avx2(
+    pos_x: [f64; LANES],
+    pos_y: [f64; LANES],
+    max_round: usize,
+    fractal: Fractal,
+) -> [Option<usize>; LANES] {
+    periodic(pos_x, pos_y, max_round, fractal)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn unrolled_avx2<const K: usize>(
+    pos_x: [f64; LANES],
+    pos_y: [f64; LANES],
+    max_round: usize,
+    fractal: Fractal,
+) -> [Option<usize>; LANES] {
+    unrolled::<K>(pos_x, pos_y, max_round, fractal)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+fn periodic_avx512(
+    pos_x: [f64; LANES],
+    pos_y: [f64; LANES],
+    max_round: usize,
+    fractal: Fractal,
+) -> [Option<usize>; LANES] {
+    periodic(pos_x, pos_y, max_round, fractal)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+fn unrolled_avx512<const K: usize>(
+    pos_x: [f64; LANES],
+    pos_y: [f64; LANES],
+    max_round: usize,
+    fractal: Fractal,
+) -> [Option<usize>; LANES] {
+    unrolled::<K>(pos_x, pos_y, max_round, fractal)
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+fn periodic_neon(
+    pos_x: [f64; LANES],
+    pos_y: [f64; LANES],
+    max_round: usize,
+    fractal: Fractal,
+) -> [Option<usize>; LANES] {
+    periodic(pos_x, pos_y, max_round, fractal)
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+fn unrolled_neon<const K: usize>(
+    pos_x: [f64; LANES],
+    pos_y: [f64; LANES],
+    max_round: usize,
+    fractal: Fractal,
+) -> [Option<usize>; LANES] {
+    unrolled::<K>(pos_x, pos_y, max_round, fractal)
+}
+
+#[inline(always)]
+fn periodic(
+    pos_x: [f64; LANES],
+    pos_y: [f64; LANES],
+    max_round: usize,
+    fractal: Fractal,
+) -> [Option<usize>; LANES] {
+    let (mut result, mut active) = initial_result(pos_x, pos_y);
+
+    let cx = f64x4::new(pos_x);
+    let cy = f64x4::new(pos_y);
+    let four = f64x4::splat(4.0);
+    let epsilon = f64x4::splat(PERIODICITY_EPSILON);
+
+    let mut state = (f64x4::ZERO, f64x4::ZERO, f64x4::ZERO, f64x4::ZERO);
+    let mut saved_x = f64x4::ZERO;
+    let mut saved_y = f64x4::ZERO;
+    let mut period: usize = 0;
+    let mut check_interval: usize = 1;
+
+    let mut round: usize = 1;
+    while round < max_round && active != 0 {
+        state = step(fractal, state, cx, cy);
+        let (xn, yn, x_power, y_power) = state;
+
+        let escaped = (x_power + y_power).cmp_ge(four).move_mask() & active;
+        if escaped != 0 {
+            for (lane, lane_result) in result.iter_mut().enumerate() {
+                if escaped & (1 << lane) != 0 {
+                    *lane_result = Some(round);
+                }
+            }
+            active &= !escaped;
+        }
+
+        // periodic lanes are inside the set and keep the None result
+        let periodic = (xn - saved_x).abs().cmp_lt(epsilon) & (yn - saved_y).abs().cmp_lt(epsilon);
+        active &= !periodic.move_mask();
+        period += 1;
+        if period == check_interval {
+            saved_x = xn;
+            saved_y = yn;
+            period = 0;
+            check_interval *= 2;
+        }
+        round += 1
+    }
+    result
+}
+
+#[inline(always)]
+fn unrolled<const K: usize>(
+    pos_x: [f64; LANES],
+    pos_y: [f64; LANES],
+    max_round: usize,
+    fractal: Fractal,
+) -> [Option<usize>; LANES] {
+    let (mut result, mut active) = initial_result(pos_x, pos_y);
+    let cx = f64x4::new(pos_x);
+    let cy = f64x4::new(pos_y);
+    let four = f64x4::splat(4.0);
+    let mut state = (f64x4::ZERO, f64x4::ZERO, f64x4::ZERO, f64x4::ZERO);
+
+    let mut round: usize = 1;
+    while round + K <= max_round && active != 0 {
+        let start = state;
+        for _ in 0..K {
+            state = step(fractal, state, cx, cy);
+        }
+        let (_, _, x_power, y_power) = state;
+        let escaped = !(x_power + y_power).cmp_lt(four).move_mask() & active;
+        if escaped != 0 {
+            let mut replay = start;
+            let mut pending = escaped;
+            for offset in 0..K {
+                replay = step(fractal, replay, cx, cy);
+                let (_, _, x_power, y_power) = replay;
+                let now = (x_power + y_power).cmp_ge(four).move_mask() & pending;
+                for (lane, lane_result) in result.iter_mut().enumerate() {
+                    if now & (1 << lane) != 0 {
+                        *lane_result = Some(round + offset);
+                    }
+                }
+                pending &= !now;
+            }
+            active &= !escaped;
+        }
+        round += K;
+    }
+
+    // the iterations left over by the blocks
+    while round < max_round && active != 0 {
+        state = step(fractal, state, cx, cy);
+        let (_, _, x_power, y_power) = state;
+        let escaped = (x_power + y_power).cmp_ge(four).move_mask() & active;
+        for (lane, lane_result) in result.iter_mut().enumerate() {
+            if escaped & (1 << lane) != 0 {
+                *lane_result = Some(round);
+            }
+        }
+        active &= !escaped;
+        round += 1;
+    }
+    result
+}
+
+// Time the unrolled kernel with `K` on slowly escaping points just above
+// the neck between the main cardioid and the period-2 bulb.
+fn time_unrolled<const K: usize>() -> Duration {
+    let start = Instant::now();
+    for quad in 0..16 {
+        let ys = [0, 1, 2, 3].map(|lane| 1e-3 * (1.0 + (quad * LANES + lane) as f64 / 64.0));
+        std::hint::black_box(check_divergence_x4_unrolled::<K>(
+            [-0.75; LANES],
+            ys,
+            1 << 16,
+            Fractal::Mandelbrot,
+        ));
+    }
+    start.elapsed()
+}
+
+static UNROLL_FACTOR: OnceLock<usize> = OnceLock::new();
+
+// Escape-check interval of the kernel that ran fastest on this machine,
+// measured on first use.
+fn unroll_factor() -> usize {
+    *UNROLL_FACTOR.get_or_init(|| {
+        let mut best = (Duration::MAX, 1);
+        for _ in 0..TUNING_ROUNDS {
+            for factor in UNROLL_FACTORS {
+                let time = match factor {
+                    8 => time_unrolled::<8>(),
+                    4 => time_unrolled::<4>(),
+                    2 => time_unrolled::<2>(),
+                    _ => time_unrolled::<1>(),
+                };
+                best = best.min((time, factor));
+            }
+        }
+        info!("kernel unroll factor: {} ({:?})", best.1, best.0);
+        best.1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mandelbrot::Mandelbrot;
+
+    type Kernel = fn([f64; LANES], [f64; LANES], usize, Fractal) -> [Option<usize>; LANES];
+
+    // Compare a kernel with the scalar one on a grid of points around each
+    // fractal, with a limit that leaves iterations over after the blocks.
+    fn assert_matches_scalar(kernel: Kernel, periodicity: bool) {
+        let max_round = 301;
+        for fractal in [Fractal::Mandelbrot, Fractal::BurningShip, Fractal::Tricorn] {
+            let mut mandelbrot = Mandelbrot::new(4, 4);
+            mandelbrot.options.fractal = fractal;
+            mandelbrot.max_round = max_round;
+            for row in 0..40 {
+                let y = -1.25 + row as f64 / 16.0;
+                for column in 0..16 {
+                    let xs = [0, 1, 2, 3].map(|lane| -2.25 + (column * LANES + lane) as f64 / 24.0);
+                    let ys = [y, y + 0.01, -y, y * 0.5];
+                    let results = kernel(xs, ys, max_round, fractal);
+                    for lane in 0..LANES {
+                        assert_eq!(
+                            results[lane],
+                            mandelbrot.escape_time(xs[lane], ys[lane], periodicity),
+                            "{:?} at ({}, {})",
+                            fractal,
+                            xs[lane],
+                            ys[lane]
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unrolled_kernels_match_the_scalar_one() {
+        assert_matches_scalar(unrolled::<1>, false);
+        assert_matches_scalar(unrolled::<2>, false);
+        assert_matches_scalar(unrolled::<4>, false);
+        assert_matches_scalar(unrolled::<8>, false);
+    }
+
+    #[test]
+    fn periodic_kernel_matches_the_scalar_one() {
+        assert_matches_scalar(periodic, true);
+    }
+
+    #[test]
+    fn dispatched_kernels_match_the_scalar_one() {
+        assert_matches_scalar(
+            |x, y, max_round, fractal| check_divergence_x4(x, y, max_round, true, fractal),
+            true,
+        );
+        assert_matches_scalar(check_divergence_x4_unrolled::<8>, false);
+    }
+}
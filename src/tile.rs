@@ -0,0 +1,258 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const TILE_SIZE: usize = 64;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    pub fn pixels(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+pub fn tiles(width: usize, height: usize) -> Vec<Tile> {
+    tiles_in(Tile {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    })
+}
+
+/// Split `area` into tiles of at most TILE_SIZE x TILE_SIZE pixels.
+pub fn tiles_in(area: Tile) -> Vec<Tile> {
+    split(area, TILE_SIZE)
+}
+
+// Split `area` into tiles of at most `size` x `size` pixels.
+fn split(area: Tile, size: usize) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (area.y..area.y + area.height).step_by(size) {
+        for x in (area.x..area.x + area.width).step_by(size) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: size.min(area.x + area.width - x),
+                height: size.min(area.y + area.height - y),
+            });
+        }
+    }
+    tiles
+}
+
+// tasks per thread a frame is cut into when the cost of its tiles is known
+const TASKS_PER_THREAD: usize = 8;
+// smallest side of the pieces an expensive tile is split into
+const MIN_PIECE: usize = TILE_SIZE / 4;
+
+/// Tiles rendered one after the other by one task, each with the index of
+/// the tile of the frame whose statistics it goes into.
+pub type Task = Vec<(usize, Tile)>;
+
+/// Cut the `tiles` of a frame, in the order of `schedule`, into tasks of
+/// about the same cost for `threads` threads, from the time every tile took
+/// in the previous frame (`prev`): a tile that took much longer than its
+/// share is split into smaller pieces, so that no thread is left with a
+/// long tile at the end of the frame, and runs of cheap tiles are joined
+/// into one task. Without the previous times, every tile is a task.
+pub fn tasks(
+    tiles: &[Tile],
+    schedule: &[usize],
+    prev: Option<&[TileStats]>,
+    threads: usize,
+) -> Vec<Task> {
+    let total: Duration = prev.map_or(Duration::ZERO, |stats| {
+        stats.iter().map(|stats| stats.time).sum()
+    });
+    let (Some(prev), false) = (prev, total.is_zero()) else {
+        return schedule
+            .iter()
+            .map(|&index| vec![(index, tiles[index])])
+            .collect();
+    };
+    let share = total / (threads * TASKS_PER_THREAD).max(1) as u32;
+
+    let mut tasks = Vec::new();
+    let mut joined = Vec::new();
+    let mut joined_time = Duration::ZERO;
+    for &index in schedule {
+        let tile = tiles[index];
+        let time = prev[index].time;
+        if time > 2 * share {
+            // quarters of quarters until the pieces are about one share
+            let mut size = TILE_SIZE;
+            let mut pieces = 1_u32;
+            while size > MIN_PIECE && time > share * pieces {
+                size /= 2;
+                pieces *= 4;
+            }
+            tasks.extend(
+                split(tile, size)
+                    .into_iter()
+                    .map(|piece| vec![(index, piece)]),
+            );
+            continue;
+        }
+        joined.push((index, tile));
+        joined_time += time;
+        if joined_time >= share {
+            tasks.push(std::mem::take(&mut joined));
+            joined_time = Duration::ZERO;
+        }
+    }
+    if !joined.is_empty() {
+        tasks.push(joined);
+    }
+    tasks
+}
+
+/// Copy the colors of `tile` into a frame `width` pixels wide.
+pub fn blit(frame: &mut [u8], width: usize, tile: &Tile, colors: &[[u8; 4]]) {
+    for (row, row_colors) in colors.chunks_exact(tile.width).enumerate() {
+        let start = 4 * ((tile.y + row) * width + tile.x);
+        let pixels = &mut frame[start..(start + 4 * tile.width)];
+        for (pixel, rgba) in pixels.chunks_exact_mut(4).zip(row_colors) {
+            pixel.copy_from_slice(rgba);
+        }
+    }
+}
+
+/// Copy the values of the pixels of `tile` into a buffer of one value per
+/// pixel of a frame `width` pixels wide.
+pub fn blit_values<T: Copy>(buffer: &mut [T], width: usize, tile: &Tile, values: &[T]) {
+    for (row, row_values) in values.chunks_exact(tile.width).enumerate() {
+        let start = (tile.y + row) * width + tile.x;
+        buffer[start..start + tile.width].copy_from_slice(row_values);
+    }
+}
+
+/// Order in which the tiles of a frame are rendered, and shown in the
+/// window as they finish.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum TileOrder {
+    /// row by row from the top left
+    Rows,
+    /// in rings around the center of the frame, where the eye is and where
+    /// the zoom goes
+    #[default]
+    CenterOut,
+}
+
+impl TileOrder {
+    /// Indices of the `tiles` of a frame of `width` by `height` pixels in
+    /// the order to render them.
+    pub fn schedule(self, tiles: &[Tile], width: usize, height: usize) -> Vec<usize> {
+        let mut schedule: Vec<usize> = (0..tiles.len()).collect();
+        if self == Self::CenterOut {
+            // the ring of the tile in tiles from the center, then clockwise
+            // around it from the left (y grows downward): a spiral of rings
+            let key = |tile: &Tile| {
+                let dx = (tile.x as f64 + tile.width as f64 / 2.0 - width as f64 / 2.0)
+                    / TILE_SIZE as f64;
+                let dy = (tile.y as f64 + tile.height as f64 / 2.0 - height as f64 / 2.0)
+                    / TILE_SIZE as f64;
+                ((dx.abs().max(dy.abs()) - 0.5).max(0.0).ceil(), dy.atan2(dx))
+            };
+            schedule.sort_by(|&a, &b| {
+                let (ring_a, angle_a) = key(&tiles[a]);
+                let (ring_b, angle_b) = key(&tiles[b]);
+                ring_a.total_cmp(&ring_b).then(angle_a.total_cmp(&angle_b))
+            });
+        }
+        schedule
+    }
+}
+
+/// Flag shared with a render in progress, which checks it before every
+/// tile and gives up once it is set.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TileStats {
+    pub interior: usize,
+    pub escaped: usize,
+    pub max_escape: usize,
+    /// pixels filled from the border of their rectangle by the subdivision
+    pub filled: usize,
+    /// time spent rendering the tile
+    pub time: Duration,
+}
+
+impl TileStats {
+    /// Add the statistics of a piece of the tile.
+    pub fn merge(&mut self, piece: &TileStats) {
+        self.interior += piece.interior;
+        self.escaped += piece.escaped;
+        self.max_escape = self.max_escape.max(piece.max_escape);
+        self.filled += piece.filled;
+        self.time += piece.time;
+    }
+
+    pub fn add(&mut self, round: Option<usize>) {
+        match round {
+            Some(round) => {
+                self.escaped += 1;
+                self.max_escape = self.max_escape.max(round);
+            }
+            None => self.interior += 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TilePlan {
+    pub max_round: usize,
+    pub periodicity: bool,
+}
+
+// Iterations granted beyond the slowest escape seen in the previous frame,
+// so that the cap of an exterior tile still tolerates small view changes.
+const ESCAPE_HEADROOM: usize = 64;
+
+impl TilePlan {
+    // Decide the iteration budget of a tile from what it looked like in the
+    // previous frame, or in samples of it in this one:
+    //  * tiles without interior points are capped near their slowest escape
+    //    and skip the periodicity check, which only costs time there.
+    //  * the other tiles (and tiles without history) get the full max_round
+    //    with periodicity checks, so that points which fall into a cycle
+    //    stop early.
+    pub fn new(prev: Option<&TileStats>, max_round: usize) -> Self {
+        let full = Self {
+            max_round,
+            periodicity: true,
+        };
+        let stats = match prev {
+            Some(stats) => stats,
+            None => return full,
+        };
+
+        if stats.interior == 0 && stats.escaped > 0 {
+            Self {
+                max_round: (stats.max_escape * 2 + ESCAPE_HEADROOM).min(max_round),
+                periodicity: false,
+            }
+        } else {
+            full
+        }
+    }
+}
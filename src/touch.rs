@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use log::info;
+use winit::event::TouchPhase;
+
+use crate::action::Action;
+use crate::click::Clicks;
+use crate::mandelbrot::Mandelbrot;
+use crate::platform::Instant;
+
+/// What the fingers on the view did.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    /// one finger moved by (x, y) frame pixels
+    Pan(f64, f64),
+    /// two fingers moved their midpoint by `pan` frame pixels and
+    /// spread `factor` times further apart around it
+    Pinch {
+        center: (f64, f64),
+        factor: f64,
+        pan: (f64, f64),
+    },
+    /// one finger tapped twice at the same place
+    DoubleTap(f64, f64),
+}
+
+/// The fingers on a touch screen: one finger drags the view, two pinch to
+/// zoom about their midpoint and pan with it, and a double tap recenters.
+/// A third finger is followed but ignored.
+pub struct Touches {
+    taps: Clicks,
+    // ids and frame pixels of the fingers, in the order they touched
+    fingers: Vec<(u64, (f64, f64))>,
+}
+
+impl Touches {
+    pub fn new(double_tap_interval: Duration) -> Self {
+        Self {
+            taps: Clicks::taps(double_tap_interval),
+            fingers: Vec::new(),
+        }
+    }
+
+    /// Follow the finger `id` at `position` (in frame pixels) at `now`.
+    pub fn touch(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        position: (f64, f64),
+        now: Instant,
+    ) -> Option<Gesture> {
+        let index = self.fingers.iter().position(|(finger, _)| *finger == id);
+        match (phase, index) {
+            (TouchPhase::Started, None) => {
+                let first = self.fingers.is_empty();
+                self.fingers.push((id, position));
+                (first && self.taps.press(position, now))
+                    .then_some(Gesture::DoubleTap(position.0, position.1))
+            }
+            (TouchPhase::Moved, Some(index)) => {
+                let before = self.fingers[index].1;
+                if before == position {
+                    return None;
+                }
+                self.taps.moved(position);
+                let gesture = match self.fingers.as_slice() {
+                    [_] => Some(Gesture::Pan(position.0 - before.0, position.1 - before.1)),
+                    [(_, a), (_, b), ..] if index < 2 => {
+                        let other = if index == 0 { *b } else { *a };
+                        pinch(before, position, other)
+                    }
+                    _ => None,
+                };
+                self.fingers[index].1 = position;
+                gesture
+            }
+            (TouchPhase::Ended | TouchPhase::Cancelled, Some(index)) => {
+                self.fingers.remove(index);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+// One finger of a pinch moved from `before` to `after` while the other
+// stayed at `other`.
+fn pinch(before: (f64, f64), after: (f64, f64), other: (f64, f64)) -> Option<Gesture> {
+    let spread = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).hypot(a.1 - b.1);
+    let midpoint = |a: (f64, f64), b: (f64, f64)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    let (spread_before, spread_after) = (spread(before, other), spread(after, other));
+    if spread_before == 0.0 || spread_after == 0.0 {
+        return None;
+    }
+    let (center_before, center) = (midpoint(before, other), midpoint(after, other));
+    Some(Gesture::Pinch {
+        center,
+        factor: spread_after / spread_before,
+        pan: (center.0 - center_before.0, center.1 - center_before.1),
+    })
+}
+
+impl Gesture {
+    /// Move the view of `mandelbrot` with the gesture: the plane follows
+    /// the fingers. Returns the action it stands for.
+    pub fn apply(self, mandelbrot: &mut Mandelbrot) -> Action {
+        mandelbrot.request_redraw();
+        match self {
+            Gesture::Pan(x, y) => {
+                mandelbrot.move_center(-x, y);
+                Action::Drag
+            }
+            Gesture::Pinch {
+                center,
+                factor,
+                pan,
+            } => {
+                // zoom about where the midpoint was, then move it along
+                mandelbrot.zoom_at(center.0 - pan.0, center.1 - pan.1, factor);
+                mandelbrot.move_center(-pan.0, pan.1);
+                Action::Scroll
+            }
+            Gesture::DoubleTap(x, y) => {
+                info!("double tapped");
+                mandelbrot.set_center(x, y);
+                Action::Recenter
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::mandelbrot::Mandelbrot;
+use crate::orbit::OrbitStats;
+
+pub const TRANSECT_FILE: &str = "transect.csv";
+
+/// Sample the escape time at `samples` points evenly spaced from `start` to
+/// `end` of the complex plane, both included, and write a line of CSV per
+/// point as it is computed: its position, its distance from `start`, and
+/// its escape iteration, continuous escape time and distance estimate, left
+/// empty for the points that do not escape. The points are iterated like
+/// the pixels of `mandelbrot`, with its variation.
+pub fn save(
+    mandelbrot: &Mandelbrot,
+    start: (f64, f64),
+    end: (f64, f64),
+    samples: usize,
+    path: &Path,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "sample,x,y,along,iterations,smooth,distance")?;
+    let length = (end.0 - start.0).hypot(end.1 - start.1);
+    for sample in 0..samples {
+        let t = if samples > 1 {
+            sample as f64 / (samples - 1) as f64
+        } else {
+            0.0
+        };
+        let x = start.0 + (end.0 - start.0) * t;
+        let y = start.1 + (end.1 - start.1) * t;
+        let stats = OrbitStats::compute(mandelbrot, x.into(), y.into());
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{}",
+            sample,
+            x,
+            y,
+            length * t,
+            optional(stats.escape),
+            optional(stats.smooth),
+            optional(stats.distance)
+        )?;
+    }
+    writer.flush()
+}
+
+// The value, or an empty field.
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(String::new, |value| value.to_string())
+}
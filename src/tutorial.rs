@@ -0,0 +1,142 @@
+use crate::action::{Action, Bindings};
+use crate::canvas::{Canvas, LINE_HEIGHT};
+
+struct Step {
+    // "{}" is replaced with the key bound to the next action of `keys`
+    prompt: &'static [&'static str],
+    keys: &'static [Action],
+    done_by: &'static [Action],
+}
+
+const STEPS: [Step; 6] = [
+    Step {
+        prompt: &[
+            "Drag with the left mouse button",
+            "or press {}/{}/{}/{} to pan.",
+        ],
+        keys: &[
+            Action::MoveUp,
+            Action::MoveDown,
+            Action::MoveLeft,
+            Action::MoveRight,
+        ],
+        done_by: &[
+            Action::Drag,
+            Action::MoveUp,
+            Action::MoveDown,
+            Action::MoveLeft,
+            Action::MoveRight,
+        ],
+    },
+    Step {
+        prompt: &["Scroll the mouse wheel", "or press {}/{} to zoom."],
+        keys: &[Action::ZoomIn, Action::ZoomOut],
+        done_by: &[Action::Scroll, Action::ZoomIn, Action::ZoomOut],
+    },
+    Step {
+        prompt: &["Double-click a point", "to move it to the center."],
+        keys: &[],
+        done_by: &[Action::Recenter],
+    },
+    Step {
+        prompt: &[
+            "Press {} to show which color",
+            "belongs to which iteration count.",
+        ],
+        keys: &[Action::ToggleLegend],
+        done_by: &[Action::ToggleLegend],
+    },
+    Step {
+        prompt: &[
+            "Press {} to bookmark this view,",
+            "then {} to come back to it.",
+        ],
+        keys: &[Action::SaveBookmark, Action::JumpBookmark(0)],
+        done_by: &[Action::SaveBookmark],
+    },
+    Step {
+        prompt: &[
+            "Press {} to export a zoom animation",
+            "toward the current view.",
+        ],
+        keys: &[Action::ExportAnimation],
+        done_by: &[Action::ExportAnimation],
+    },
+];
+
+const MARGIN: usize = 5;
+
+/// Step-by-step guide through the basic operations, advancing when the user
+/// performs the action the current step asks for.
+#[derive(Default)]
+pub struct Tutorial {
+    step: Option<usize>,
+}
+
+impl Tutorial {
+    pub fn toggle(&mut self) {
+        self.step = match self.step {
+            Some(_) => None,
+            None => Some(0),
+        };
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.step.is_some()
+    }
+
+    /// Advance to the next step if `action` completes the current one.
+    /// Returns true when the prompt has changed.
+    pub fn observe(&mut self, action: Action) -> bool {
+        let step = match self.step {
+            Some(step) => step,
+            None => return false,
+        };
+        if !STEPS[step].done_by.contains(&action) {
+            return false;
+        }
+        self.step = if step + 1 < STEPS.len() {
+            Some(step + 1)
+        } else {
+            None
+        };
+        true
+    }
+
+    fn lines(&self, step: usize, bindings: &Bindings) -> Vec<String> {
+        let mut keys = STEPS[step]
+            .keys
+            .iter()
+            .map(|action| bindings.key_name(*action));
+        let mut lines = vec![format!(
+            "Tutorial {}/{} ({} to quit)",
+            step + 1,
+            STEPS.len(),
+            bindings.key_name(Action::Tutorial)
+        )];
+        for prompt in STEPS[step].prompt {
+            let mut parts = prompt.split("{}");
+            let mut line = String::from(parts.next().unwrap_or_default());
+            for part in parts {
+                line.push_str(&keys.next().unwrap_or_default());
+                line.push_str(part);
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, bindings: &Bindings) {
+        let step = match self.step {
+            Some(step) => step,
+            None => return,
+        };
+        let lines = self.lines(step, bindings);
+        let top = canvas
+            .height
+            .saturating_sub(MARGIN + lines.len() * LINE_HEIGHT);
+        for (i, line) in lines.iter().enumerate() {
+            canvas.draw_text(MARGIN, top + i * LINE_HEIGHT, line, 1);
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use clap::ValueEnum;
+
+use crate::info_card::InfoCard;
+use crate::mandelbrot::{Fractal, Mandelbrot, View};
+use crate::random::Random;
+use crate::simd;
+
+// points sampled in each viewport
+const SAMPLES: usize = 4096;
+// iteration limit of the sampled points, low enough to keep the run short
+const MAX_ROUND: usize = 2000;
+// iteration limit of the known interior points
+const INTERIOR_MAX_ROUND: usize = 100_000;
+// size of the rendered viewports
+const RENDER_WIDTH: u32 = 160;
+const RENDER_HEIGHT: u32 = 120;
+// the starting points of the info cards are framed for this window
+const CARD_WIDTH: f64 = 640.0;
+const CARD_HEIGHT: f64 = 480.0;
+
+// Escape iterations of z^2 + c worked out by hand.
+const KNOWN_ESCAPES: [(f64, f64, usize); 6] = [
+    (2.0, 0.0, 1),
+    (-2.1, 0.0, 1),
+    (1.0, 0.0, 2),
+    (0.5, 0.0, 5),
+    (0.0, 1.5, 2),
+    (-1.0, 1.0, 3),
+];
+
+// Points of the Mandelbrot set: the centers of the main cardioid, of the
+// period-2 bulb and of period-3 components, a point of the cardioid and
+// i, whose orbit 0, i, -1 + i, -i, -1 + i... ends in a cycle.
+const KNOWN_INTERIOR: [(f64, f64); 6] = [
+    (0.0, 0.0),
+    (-1.0, 0.0),
+    (-1.754_877_666_246_693, 0.0),
+    (-0.122_561_166_876_654, 0.744_861_766_619_744),
+    (-0.5, 0.5),
+    (0.0, 1.0),
+];
+
+// Escape iteration of c = (cx, cy) by the textbook loop, one step at a time
+// and without periodicity checks: the reference of the kernels, which must
+// produce the same iteration counts.
+fn reference(fractal: Fractal, cx: f64, cy: f64, max_round: usize) -> Option<usize> {
+    if cx >= 2.0 || cy >= 2.0 {
+        return Some(1);
+    }
+    let (mut x, mut y) = (0.0_f64, 0.0_f64);
+    for round in 1..max_round {
+        let xy = match fractal {
+            Fractal::Mandelbrot => 2.0 * x * y,
+            Fractal::BurningShip => (2.0 * x * y).abs(),
+            Fractal::Tricorn => -2.0 * x * y,
+            Fractal::Newton => unreachable!("Newton's method has no escape reference"),
+        };
+        (x, y) = (x * x - y * y + cx, xy + cy);
+        if x * x + y * y >= 4.0 {
+            return Some(round);
+        }
+    }
+    None
+}
+
+// Every kernel on the point: the scalar one and the SIMD ones (in lane 0
+// of points that differ in each lane), with and without the periodicity
+// check.
+fn kernels(mandelbrot: &Mandelbrot, x: f64, y: f64) -> Vec<(String, Option<usize>)> {
+    let fractal = mandelbrot.options.fractal;
+    let max_round = mandelbrot.max_round;
+    let xs = [x, x + 0.25, x - 0.5, 0.0];
+    let ys = [y, -y, y + 0.125, 2.5];
+    let mut results = vec![
+        (String::from("scalar"), mandelbrot.escape_time(x, y, false)),
+        (
+            String::from("scalar periodic"),
+            mandelbrot.escape_time(x, y, true),
+        ),
+    ];
+    results.extend([
+        (
+            String::from("simd periodic"),
+            simd::check_divergence_x4(xs, ys, max_round, true, fractal)[0],
+        ),
+        (
+            String::from("simd x1"),
+            simd::check_divergence_x4_unrolled::<1>(xs, ys, max_round, fractal)[0],
+        ),
+        (
+            String::from("simd x2"),
+            simd::check_divergence_x4_unrolled::<2>(xs, ys, max_round, fractal)[0],
+        ),
+        (
+            String::from("simd x4"),
+            simd::check_divergence_x4_unrolled::<4>(xs, ys, max_round, fractal)[0],
+        ),
+        (
+            String::from("simd x8"),
+            simd::check_divergence_x4_unrolled::<8>(xs, ys, max_round, fractal)[0],
+        ),
+    ]);
+    results
+}
+
+fn mandelbrot_for(fractal: Fractal, max_round: usize) -> Mandelbrot {
+    let mut mandelbrot = Mandelbrot::new(RENDER_WIDTH, RENDER_HEIGHT);
+    mandelbrot.options.fractal = fractal;
+    mandelbrot.max_round = max_round;
+    mandelbrot.info = false;
+    mandelbrot
+}
+
+/// Outcome of one check: how many comparisons it made and the first few
+/// that failed.
+struct Check {
+    name: String,
+    count: usize,
+    failures: Vec<String>,
+    failed: usize,
+    // accepted differences, see `rendered`
+    tolerated: usize,
+}
+
+impl Check {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            count: 0,
+            failures: Vec::new(),
+            failed: 0,
+            tolerated: 0,
+        }
+    }
+
+    fn expect<T: PartialEq + std::fmt::Debug>(&mut self, what: &str, expected: T, actual: T) {
+        self.count += 1;
+        if expected != actual {
+            self.failed += 1;
+            if self.failures.len() < 3 {
+                self.failures.push(format!(
+                    "{}: expected {:?}, got {:?}",
+                    what, expected, actual
+                ));
+            }
+        }
+    }
+
+    fn report(&self) -> bool {
+        if self.failed == 0 && self.tolerated > 0 {
+            println!(
+                "ok    {} ({} comparisons, {} periodicity false positives)",
+                self.name, self.count, self.tolerated
+            );
+        } else if self.failed == 0 {
+            println!("ok    {} ({} comparisons)", self.name, self.count);
+        } else {
+            println!(
+                "FAIL  {} ({} of {} comparisons)",
+                self.name, self.failed, self.count
+            );
+            for failure in &self.failures {
+                println!("        {}", failure);
+            }
+        }
+        self.failed == 0
+    }
+}
+
+fn known_escapes() -> Check {
+    let mut check = Check::new(String::from("known escape iterations"));
+    let mandelbrot = mandelbrot_for(Fractal::Mandelbrot, MAX_ROUND);
+    for (x, y, round) in KNOWN_ESCAPES {
+        let point = format!("({}, {})", x, y);
+        check.expect(
+            &format!("reference {}", point),
+            Some(round),
+            reference(Fractal::Mandelbrot, x, y, MAX_ROUND),
+        );
+        for (kernel, result) in kernels(&mandelbrot, x, y) {
+            check.expect(&format!("{} {}", kernel, point), Some(round), result);
+        }
+    }
+    check
+}
+
+fn known_interior() -> Check {
+    let mut check = Check::new(String::from("known interior points"));
+    let mandelbrot = mandelbrot_for(Fractal::Mandelbrot, INTERIOR_MAX_ROUND);
+    for (x, y) in KNOWN_INTERIOR {
+        for (kernel, result) in kernels(&mandelbrot, x, y) {
+            check.expect(&format!("{} ({}, {})", kernel, x, y), None, result);
+        }
+    }
+    check
+}
+
+// Random points of the view of a 640x480 window around `view`.
+fn sample_points(view: View, random: &mut Random) -> Vec<(f64, f64)> {
+    (0..SAMPLES)
+        .map(|_| {
+            (
+                view.center_x + random.next() * view.scale * CARD_WIDTH / 2.0,
+                view.center_y + random.next() * view.scale * CARD_HEIGHT / 2.0,
+            )
+        })
+        .collect()
+}
+
+fn against_reference(fractal: Fractal, name: &str, view: View, random: &mut Random) -> Check {
+    let mut check = Check::new(format!("kernels = reference, {} {}", fractal.name(), name));
+    let max_round = Mandelbrot::auto_max_round(view.scale).min(MAX_ROUND);
+    let mandelbrot = mandelbrot_for(fractal, max_round);
+    for (x, y) in sample_points(view, random) {
+        let expected = reference(fractal, x, y, max_round);
+        for (kernel, result) in kernels(&mandelbrot, x, y) {
+            check.expect(&format!("{} ({}, {})", kernel, x, y), expected, result);
+        }
+    }
+    check
+}
+
+// z^2 + c and conj(z)^2 + c commute with the conjugation of c, so their
+// sets are symmetric across the real axis, exactly in floating point too.
+fn symmetry(fractal: Fractal, name: &str, view: View, random: &mut Random) -> Check {
+    let mut check = Check::new(format!("real-axis symmetry, {} {}", fractal.name(), name));
+    let max_round = Mandelbrot::auto_max_round(view.scale).min(MAX_ROUND);
+    let mandelbrot = mandelbrot_for(fractal, max_round);
+    for (x, y) in sample_points(view, random) {
+        let above = kernels(&mandelbrot, x, y);
+        let below = kernels(&mandelbrot, x, -y);
+        for ((kernel, above), (_, below)) in above.into_iter().zip(below) {
+            check.expect(&format!("{} ({}, {})", kernel, x, y), above, below);
+        }
+    }
+    check
+}
+
+// The tiled renderer against the reference, pixel by pixel. Orbits that
+// escape only after following a cycle within PERIODICITY_EPSILON are taken
+// for members of the set by the periodicity check the renderer relies on;
+// such pixels (near Misiurewicz points like i) are tolerated when the
+// scalar kernel makes the same call.
+fn rendered(fractal: Fractal, name: &str, view: View) -> Check {
+    let mut check = Check::new(format!("rendered frame, {} {}", fractal.name(), name));
+    // the same area as the 640x480 window of the info card
+    let scale = view.scale * CARD_WIDTH / RENDER_WIDTH as f64;
+    let max_round = Mandelbrot::auto_max_round(view.scale).min(MAX_ROUND);
+    let mut mandelbrot = mandelbrot_for(fractal, max_round);
+    // the subdivision may skip pixels that the reference computes
+    mandelbrot.subdivision = false;
+    mandelbrot.center_x = view.center_x;
+    mandelbrot.center_y = view.center_y;
+    mandelbrot.scale = scale;
+    let mut frame = vec![0_u8; 4 * (RENDER_WIDTH * RENDER_HEIGHT) as usize];
+    mandelbrot.draw(&mut frame);
+
+    let (min_x, max_y) = mandelbrot.point_at(0.0, 0.0);
+    for (i, rgba) in frame.chunks_exact(4).enumerate() {
+        let pixel_x = i % RENDER_WIDTH as usize;
+        let pixel_y = i / RENDER_WIDTH as usize;
+        let x = min_x + pixel_x as f64 * scale;
+        let y = max_y - pixel_y as f64 * scale;
+        let expected = match reference(fractal, x, y, max_round) {
+            Some(round) => mandelbrot.round_to_color(round),
+            None => [0x00, 0x00, 0x00, 0xff],
+        };
+        let actual = [rgba[0], rgba[1], rgba[2], rgba[3]];
+        if actual != expected
+            && actual == [0x00, 0x00, 0x00, 0xff]
+            && mandelbrot.escape_time(x, y, true).is_none()
+        {
+            check.count += 1;
+            check.tolerated += 1;
+            continue;
+        }
+        check.expect(
+            &format!("pixel ({}, {})", pixel_x, pixel_y),
+            expected,
+            actual,
+        );
+    }
+    check
+}
+
+/// Compare the kernels and the renderer with the reference iteration on
+/// known points and on the starting points of the info cards, and check
+/// the symmetry of the formulas. Prints one line per check and returns
+/// whether all of them passed.
+pub fn run() -> bool {
+    let mut random = Random::new(0);
+    let mut checks = vec![known_escapes(), known_interior()];
+    // Newton's method converges rather than escapes, with no kernel of its
+    // own to check
+    for fractal in Fractal::value_variants()
+        .iter()
+        .filter(|fractal| **fractal != Fractal::Newton)
+    {
+        for start in InfoCard::builtin(*fractal).start {
+            let view = start.view();
+            checks.push(against_reference(*fractal, &start.name, view, &mut random));
+            if *fractal != Fractal::BurningShip {
+                checks.push(symmetry(*fractal, &start.name, view, &mut random));
+            }
+            checks.push(rendered(*fractal, &start.name, view));
+        }
+    }
+
+    let failed = checks.iter().filter(|check| !check.report()).count();
+    println!("{} checks, {} failed", checks.len(), failed);
+    failed == 0
+}
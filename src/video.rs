@@ -0,0 +1,96 @@
+use log::info;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+/// How ffmpeg encodes the frames of a video export.
+#[derive(Clone, Debug)]
+pub struct VideoOptions {
+    pub fps: u32,
+    /// encoder, as ffmpeg names it
+    pub codec: String,
+    /// bit rate like `8M`, or None for the default quality of the encoder
+    pub bitrate: Option<String>,
+}
+
+/// An ffmpeg process encoding the RGBA frames written to it into a video
+/// file, so that an animation needs no numbered PNGs in between.
+pub struct Video {
+    child: Child,
+    // None once ffmpeg has stopped reading the frames
+    stdin: Option<ChildStdin>,
+}
+
+impl Video {
+    /// Start ffmpeg writing `path` from frames of `width`x`height`, which
+    /// have to be even for the chroma of yuv420p.
+    pub fn start(path: &Path, width: u32, height: u32, options: &VideoOptions) -> io::Result<Self> {
+        if !width.is_multiple_of(2) || !height.is_multiple_of(2) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "a video needs an even width and height, not {}x{}",
+                    width, height
+                ),
+            ));
+        }
+        let mut command = Command::new("ffmpeg");
+        command
+            .args([
+                "-y",
+                "-loglevel",
+                "error",
+                "-f",
+                "rawvideo",
+                "-pix_fmt",
+                "rgba",
+            ])
+            .args(["-s", &format!("{}x{}", width, height)])
+            .args(["-r", &options.fps.to_string()])
+            .args(["-i", "-", "-c:v", &options.codec])
+            // the pixel format players take, instead of the RGB of the input
+            .args(["-pix_fmt", "yuv420p"]);
+        if let Some(bitrate) = &options.bitrate {
+            command.args(["-b:v", bitrate]);
+        }
+        let mut child = command
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("failed to start ffmpeg: {}", e)))?;
+        let stdin = child.stdin.take().expect("stdin of ffmpeg is piped");
+        info!("encoding {} with {}", path.display(), options.codec);
+        Ok(Self {
+            child,
+            stdin: Some(stdin),
+        })
+    }
+
+    /// Encode the next frame, RGBA row by row. When ffmpeg no longer takes
+    /// it, the error says how ffmpeg ended.
+    pub fn write(&mut self, frame: &[u8]) -> io::Result<()> {
+        let Some(stdin) = &mut self.stdin else {
+            return Err(io::Error::other("ffmpeg has stopped"));
+        };
+        let Err(e) = stdin.write_all(frame) else {
+            return Ok(());
+        };
+        self.stdin = None;
+        let status = self.child.wait()?;
+        Err(io::Error::new(
+            e.kind(),
+            format!("ffmpeg stopped reading the frames ({}): {}", status, e),
+        ))
+    }
+
+    /// Close the input and wait for ffmpeg to write the rest of the file.
+    pub fn finish(mut self) -> io::Result<()> {
+        drop(self.stdin.take());
+        let status = self.child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("ffmpeg failed: {}", status)))
+        }
+    }
+}
@@ -0,0 +1,696 @@
+// Sessions of the window driven by `mandelbrot --script`: key presses,
+// drags and clicks, and what they do to the view; `--rhai` scripts; and
+// the data of headless renders.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+// a small frame keeps the debug builds fast
+const SIZE: [&str; 4] = ["--width", "160", "--height", "120"];
+
+fn script_path(name: &str, script: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.txt", name));
+    fs::write(&path, script).unwrap();
+    path
+}
+
+/// Run `script` and return whether it passed, with its log.
+fn run_with(name: &str, script: &str, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+        .arg("--script")
+        .arg(script_path(name, script))
+        .args(SIZE)
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+fn run(name: &str, script: &str) {
+    let (passed, log) = run_with(name, script, &[]);
+    assert!(passed, "{}", log);
+}
+
+#[test]
+fn keys_move_the_view_and_cycle_options() {
+    run(
+        "keys",
+        "
+        expect center -0.7 0
+        expect scale 0.005
+        key Right
+        key K
+        expect center -0.65 0.05
+        key P
+        expect palette fire
+        key P
+        expect palette ocean
+        key X
+        expect coloring histogram
+        key Space
+        expect center -0.7 0
+        ",
+    );
+}
+
+#[test]
+fn reset_goes_through_its_stages() {
+    run(
+        "reset",
+        "
+        key P
+        key X
+        key RBracket
+        key Right
+        key Space
+        expect center -0.7 0
+        expect palette fire
+        expect coloring histogram
+        key Space
+        expect palette classic
+        expect coloring iterations
+        expect iterations 512
+        # anything in between starts over from the view
+        key Right
+        key Space
+        expect center -0.7 0
+        expect palette classic
+        ",
+    );
+}
+
+#[test]
+fn wheel_and_page_keys_zoom() {
+    run(
+        "zoom",
+        "
+        wheel 1
+        wait 1000
+        expect scale 0.004672897196261682
+        wheel -1
+        wait 1000
+        expect scale 0.005
+        key PageDown
+        key PageUp
+        expect scale 0.005
+        key shift+PageUp
+        expect scale 0.004966284860416485
+        ",
+    );
+}
+
+#[test]
+fn wheel_zoom_glides_toward_the_pointer() {
+    run(
+        "zoom-glide",
+        "
+        wheel 1 40 30
+        expect scale 0.005
+        # the point under the pointer stays there
+        wait 1000
+        expect scale 0.004672897196261682
+        expect center -0.7130841121495327 0.009813084112149535
+        ",
+    );
+}
+
+#[test]
+fn drag_moves_the_view_on_release() {
+    run(
+        "drag",
+        "
+        render
+        press 80 60
+        move 100 50
+        expect offset 20 -10
+        expect center -0.7 0
+        release 100 50
+        expect center -0.8 -0.05
+        expect offset 20 -10
+        render
+        expect offset 0 0
+        # the button pressed elsewhere, such as on the panel, drags nothing
+        release 120 80
+        expect center -0.8 -0.05
+        ",
+    );
+}
+
+#[test]
+fn drag_follows_the_pointer_in_low_memory_mode() {
+    let (passed, log) = run_with(
+        "drag-low-memory",
+        "
+        press 80 60
+        move 100 60
+        expect center -0.8 0
+        expect offset 0 0
+        move 100 70
+        expect center -0.8 0.05
+        release 100 70
+        expect center -0.8 0.05
+        ",
+        &["--low-memory"],
+    );
+    assert!(passed, "{}", log);
+}
+
+#[test]
+fn double_click_recenters() {
+    run(
+        "double-click",
+        "
+        click 40 30
+        wait 200
+        click 40 30
+        expect center -0.9 0.15
+        # the release ending a double click does not drag
+        press 60 60
+        wait 100
+        press 60 60
+        move 70 70
+        release 70 70
+        expect center -1 0.15
+        ",
+    );
+}
+
+#[test]
+fn slow_clicks_are_not_a_double_click() {
+    run(
+        "slow-clicks",
+        "
+        click 40 30
+        wait 800
+        click 40 30
+        expect center -0.7 0
+        ",
+    );
+}
+
+#[test]
+fn fingers_pan_pinch_and_double_tap() {
+    run(
+        "touch",
+        "
+        touch 1 start 80 60
+        touch 1 move 100 60
+        expect center -0.8 0
+        touch 1 end 100 60
+        # the midpoint moves 20 pixels right as the fingers spread twice apart
+        touch 1 start 60 60
+        touch 2 start 100 60
+        touch 2 move 140 60
+        expect scale 0.0025
+        expect center -0.85 0
+        touch 2 end 140 60
+        touch 1 end 60 60
+        touch 1 start 40 30
+        touch 1 end 40 30
+        wait 100
+        touch 1 start 42 30
+        touch 1 end 42 30
+        expect center -0.945 0.075
+        ",
+    );
+}
+
+#[test]
+fn export_writes_the_frame_as_shown() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("export.png");
+    let _ = fs::remove_file(&path);
+    run(
+        "export",
+        &format!(
+            "
+            render
+            press 80 60
+            move 100 60
+            # the dragged image leaves black behind until it is rendered
+            expect pixel 0 0 0 0 0
+            export {}
+            ",
+            path.display()
+        ),
+    );
+    let png = fs::read(&path).unwrap();
+    assert!(png.starts_with(b"\x89PNG"));
+}
+
+#[test]
+fn failed_expectations_stop_the_script() {
+    let (passed, log) = run_with(
+        "fail",
+        "
+        key P
+        expect palette classic
+        key P
+        ",
+        &[],
+    );
+    assert!(!passed);
+    assert!(
+        log.contains("fail.txt:3: expected palette classic, got fire"),
+        "{}",
+        log
+    );
+}
+
+#[cfg(feature = "rhai")]
+#[test]
+fn rhai_script_animates_a_zoom() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("rhai_frames");
+    let _ = fs::remove_dir_all(&dir);
+    let script = format!(
+        r#"
+        set_palette("fire");
+        zoom(2.0);
+        if scale() != 0.0025 {{ throw "zoom did not halve the scale"; }}
+        animate(-0.75, 0.1, 1e-4, 3, "{}");
+        if center_x() != -0.75 || center_y() != 0.1 {{ throw "the zoom did not end at its view"; }}
+        set_fractal("no-such-fractal");
+        "#,
+        dir.display()
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+        .arg("--rhai")
+        .arg(script_path("animate", &script))
+        .args(SIZE)
+        .output()
+        .unwrap();
+    let log = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(log.contains("unknown fractal: no-such-fractal"), "{}", log);
+    for number in 0..3 {
+        let png = fs::read(dir.join(format!("frame_{:05}.png", number))).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+}
+
+// Escape iterations of the view rendered with `args`, as CSV.
+fn render_data(name: &str, args: &[&str]) -> Result<String, String> {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.csv", name));
+    let _ = fs::remove_file(&path);
+    let output = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+        .args(["--render", "--data", "csv", "--output"])
+        .arg(&path)
+        .args(SIZE)
+        .args(args)
+        .output()
+        .unwrap();
+    if output.status.success() {
+        Ok(fs::read_to_string(&path).unwrap())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+#[test]
+fn formula_powers_of_zero_and_below() {
+    let data = |formula: &str| render_data("formula", &["--formula", formula]).unwrap();
+    assert_eq!(data("z^0 + c"), data("1 + c"));
+    assert_eq!(data("z^-1 + c"), data("1/z + c"));
+    assert_eq!(data("z^-2 + c"), data("1/(z*z) + c"));
+    assert_ne!(data("z^-1 + c"), data("z + c"));
+    for (formula, error) in [
+        (
+            "z^1000000000 + c",
+            "exponent 1000000000 at 3 is not within -64 to 64",
+        ),
+        ("z^ + c", "expected a number, z, c, i or a function at 4"),
+        ("z^2 + q", "unknown name `q` at 7"),
+        (
+            &format!("{}z{}", "(".repeat(30000), ")".repeat(30000)),
+            "formula nested too deeply at 257",
+        ),
+    ] {
+        let log = render_data("bad-formula", &["--formula", formula]).unwrap_err();
+        assert!(log.contains(error), "{}", log);
+    }
+}
+
+#[cfg(feature = "rhai")]
+#[test]
+fn rhai_script_checks_the_values_like_the_command_line() {
+    for (script, error) in [
+        ("set_exponent(1e9);", "exponent must be 1.5 to 16"),
+        ("set_iterations(0);", "iterations must be 2 to 16777216"),
+        (
+            "set_iterations(100000000);",
+            "iterations must be 2 to 16777216",
+        ),
+        ("zoom(0.0);", "scale must be positive"),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+            .arg("--rhai")
+            .arg(script_path("checks", script))
+            .args(SIZE)
+            .output()
+            .unwrap();
+        let log = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(log.contains(error), "{}", log);
+    }
+}
+
+#[test]
+fn deep_views_keep_the_center_in_double_double() {
+    run(
+        "double-double",
+        "
+        go -1.7490000000000000000012 0.0000000000000000000034 1e-21
+        expect precise-center -1.7490000000000000000012 0.0000000000000000000034
+        expect center -1.749 0
+        # ten pixels of 1e-21 each, below the last bit of -1.749 in f64
+        key Right
+        expect precise-center -1.7489999999999999999912 0.0000000000000000000034
+        key Up
+        key Up
+        expect precise-center -1.7489999999999999999912 0.0000000000000000000234
+        render
+        ",
+    );
+    let (passed, log) = run_with(
+        "double-double-off",
+        "
+        go -1.7490000000000000000012 0 1e-21
+        expect precise-center -1.749 0
+        ",
+        &[],
+    );
+    assert!(!passed);
+    assert!(
+        log.contains("expected precise-center -1.749 0, got 1.2"),
+        "{}",
+        log
+    );
+}
+
+#[test]
+fn deep_views_reject_what_is_beyond_f64() {
+    for (name, go, error) in [
+        ("go-huge", "go 1e400 0 1e-21", "not a number: 1e400"),
+        (
+            "go-tiny",
+            "go 1e-400 0 1e-21",
+            "expected precise-center 1 0",
+        ),
+        (
+            "go-endless",
+            "go 1e999999999 0 1e-21",
+            "not a number: 1e999999999",
+        ),
+        ("go-inf-scale", "go 0 0 inf", "scale must be positive"),
+    ] {
+        let (passed, log) = run_with(name, &format!("{}\nexpect precise-center 1 0", go), &[]);
+        assert!(!passed);
+        assert!(log.contains(error), "{}", log);
+    }
+}
+
+#[test]
+fn deep_views_color_the_interior_like_shallow_ones() {
+    // the period-2 bulb fills the view at both scales, in f64 and in
+    // double-double
+    let render = |scale: &str| {
+        let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("bulb{}.png", scale));
+        let output = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+            .args([
+                "--render",
+                "--x=-1.0",
+                "--interior",
+                "period",
+                "--scale",
+                scale,
+            ])
+            .args(SIZE)
+            .arg("--output")
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        fs::read(&path).unwrap()
+    };
+    assert!(render("1e-13") == render("1e-17"));
+}
+
+#[test]
+fn frames_export_resumes_from_its_progress_file() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("resume_frames");
+    let _ = fs::remove_dir_all(&dir);
+    let export = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+            .args(["--render", "--frames", "2", "--coloring", "histogram"])
+            .env("RUST_LOG", "info")
+            .arg("--output")
+            .arg(&dir)
+            .args(SIZE)
+            .args(args)
+            .output()
+            .unwrap();
+        let log = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert!(output.status.success(), "{}", log);
+        log
+    };
+    export(&[]);
+    // the options go before their tables, or TOML cannot write the job
+    let progress = fs::read_to_string(dir.join("progress.toml")).unwrap();
+    assert!(
+        progress.contains("coloring = \"histogram\""),
+        "{}",
+        progress
+    );
+    // an interruption while a frame was recorded leaves it cut short
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .open(dir.join("progress.toml"))
+        .unwrap();
+    file.write_all(b"\n[[completed]]\nnumber = 2\ncr").unwrap();
+    let log = export(&["--resume"]);
+    assert!(log.contains("resuming with 2 completed chunks"), "{}", log);
+}
+
+#[test]
+fn rays_run_along_the_real_axis_and_check_their_angles() {
+    // 0/1 lands on the cusp at 0.25, 1/2 on the tip at -2
+    for (name, angle, x, pixel) in [
+        ("ray-0", "0/1", "0.3", "150"),
+        ("ray-1-2", "1/2", "-2.2", "40"),
+    ] {
+        let (passed, log) = run_with(
+            name,
+            &format!(
+                "
+                render
+                expect pixel {} 60 176 176 176
+                ",
+                pixel
+            ),
+            &["--rays", angle, "--x", x, "--y", "0", "--scale", "0.001"],
+        );
+        assert!(passed, "{}", log);
+    }
+    // inside the main cardioid, short of the landing point
+    let (passed, log) = run_with(
+        "ray-end",
+        "
+        render
+        expect pixel 20 60 0 0 0
+        ",
+        &[
+            "--rays", "0/1", "--x", "0.3", "--y", "0", "--scale", "0.001",
+        ],
+    );
+    assert!(passed, "{}", log);
+
+    for (angle, error) in [
+        ("1/0", "1/0 has a zero denominator"),
+        ("x", "x is not a fraction p/q"),
+    ] {
+        let (passed, log) = run_with("ray-angle", "render\n", &["--rays", angle]);
+        assert!(!passed);
+        assert!(log.contains(error), "{}", log);
+    }
+}
+
+#[test]
+fn keyframes_reject_what_the_command_line_would() {
+    let keyframe = |scale: &str, frames: &str| {
+        format!(
+            "[[keyframe]]\nx = -0.7\ny = 0.0\ny_lo = 1e-20\nscale = {}\nframes = {}\n\
+             [keyframe.palette]\nname = \"gray\"\ncolors = [[0, 0, 0], [255, 255, 255]]\n",
+            scale, frames
+        )
+    };
+    let export = |name: &str, contents: String| {
+        let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+        let path = tmp.join(format!("{}.toml", name));
+        fs::write(&path, contents).unwrap();
+        let dir = tmp.join(name);
+        let _ = fs::remove_dir_all(&dir);
+        let output = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+            .args(["--render", "--keyframes"])
+            .arg(&path)
+            .arg("--output")
+            .arg(&dir)
+            .args(SIZE)
+            .output()
+            .unwrap();
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+            dir,
+        )
+    };
+
+    let (passed, log, dir) = export(
+        "keyframes",
+        keyframe("0.005", "2") + &keyframe("0.001", "2"),
+    );
+    assert!(passed, "{}", log);
+    assert_eq!(fs::read_dir(dir).unwrap().count(), 3);
+
+    for (name, contents, error) in [
+        (
+            "keyframes-scale",
+            keyframe("0.005", "2") + &keyframe("0", "2"),
+            "keyframe 2: scale must be positive",
+        ),
+        (
+            "keyframes-frames",
+            keyframe("0.005", "1000000000") + &keyframe("0.001", "2"),
+            "keyframe 1: frames must be at most 100000",
+        ),
+    ] {
+        let (passed, log, _) = export(name, contents);
+        assert!(!passed);
+        assert!(log.contains(error), "{}", log);
+    }
+}
+
+#[test]
+fn video_options_need_a_video_of_even_size() {
+    let output = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("odd.mp4");
+    for (args, error) in [
+        (
+            &["--video", "--width", "161"][..],
+            "a video needs an even width and height, not 161x480",
+        ),
+        (&["--fps", "10"][..], "--video"),
+        (&["--codec", "libx265"][..], "--video"),
+    ] {
+        let result = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+            .args(["--render", "--frames", "2", "--output"])
+            .arg(&output)
+            .args(args)
+            .output()
+            .unwrap();
+        let log = String::from_utf8_lossy(&result.stderr);
+        assert!(!result.status.success());
+        assert!(log.contains(error), "{}", log);
+    }
+}
+
+#[test]
+fn bindings_list_the_question_mark_for_the_help_next_to_f1() {
+    let output = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+        .arg("--list-bindings")
+        .output()
+        .unwrap();
+    let listing = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(listing.contains("toggle-help: F1, ?\n"), "{}", listing);
+}
+
+#[test]
+fn listed_action_names_bind_in_the_configuration() {
+    let list = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+            .arg("--list-bindings")
+            .args(args)
+            .output()
+            .unwrap();
+        let log = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert!(output.status.success(), "{}", log);
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let mut config = String::new();
+    for line in list(&[]).lines() {
+        let (name, _) = line.rsplit_once(": ").unwrap();
+        let action = if name.starts_with('{') {
+            name.to_string()
+        } else {
+            format!("\"{}\"", name)
+        };
+        config += &format!("[[bind]]\nkey = \"F12\"\naction = {}\n", action);
+    }
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("bind-all.toml");
+    fs::write(&path, config).unwrap();
+    let listing = list(&["--config", path.to_str().unwrap()]);
+    assert!(listing.contains("toggle-help: F1, F12, ?\n"), "{}", listing);
+}
+
+#[test]
+fn iterations_export_in_the_chosen_format() {
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let view = ["--x", "-0.745", "--y", "0.11", "--scale", "0.0002"];
+    let render = |format: &str| {
+        let path = tmp.join(format!("render-iterations.{}", format));
+        let output = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+            .args(["--render", "--data", format, "--output"])
+            .arg(&path)
+            .args(SIZE)
+            .args(view)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        fs::read(&path).unwrap()
+    };
+
+    let csv = String::from_utf8(render("csv")).unwrap();
+    let rows: Vec<Vec<u32>> = csv
+        .lines()
+        .map(|line| {
+            line.split(',')
+                .map(|value| value.parse().unwrap())
+                .collect()
+        })
+        .collect();
+    assert_eq!(rows.len(), 120);
+    assert!(rows.iter().all(|row| row.len() == 160));
+    // the right edge is inside the set, the left edge escapes
+    assert_eq!(rows[60][159], 0);
+    assert!(rows[60][0] > 0);
+
+    let npy = render("npy");
+    assert!(npy.starts_with(b"\x93NUMPY\x01\x00"));
+    assert!(String::from_utf8_lossy(&npy).contains("'shape': (120, 160)"));
+    assert_eq!(npy.len() % 64, 4 * 160 * 120 % 64);
+
+    let raw = render("raw");
+    assert!(raw.starts_with(b"MBITER01"));
+    assert_eq!(&raw[8..16], &[160, 0, 0, 0, 120, 0, 0, 0]);
+    assert_eq!(raw.len(), 48 + 4 * 160 * 120);
+    let value = |index: usize| u32::from_le_bytes(raw[48 + 4 * index..][..4].try_into().unwrap());
+    assert_eq!(value(60 * 160), rows[60][0]);
+
+    // the window writes the same iterations, from its frame, in the format
+    // it was started with
+    let path = tmp.join("window-iterations.csv");
+    let (passed, log) = run_with(
+        "iterations",
+        &format!("render\nexport-iterations {}", path.display()),
+        &[&["--data", "csv"][..], &view[..]].concat(),
+    );
+    assert!(passed, "{}", log);
+    assert_eq!(fs::read_to_string(&path).unwrap(), csv);
+}